@@ -0,0 +1,88 @@
+//! Vazão da conversão, com textos de 1 KB, 100 KB e 10 MB.
+//!
+//! `cargo bench` mostra quanto tempo cada etapa leva. Com `cargo bench -- --save-baseline
+//! <nome>` os tempos são guardados, e com `cargo bench -- --baseline <nome>` o criterion
+//! compara com os guardados e aponta as etapas que ficaram mais lentas.
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use tcp::{
+    midi_action::MidiAction,
+    text_to_midi::{Sheet, SheetBuilder},
+};
+
+/// Os tamanhos dos textos medidos.
+const SIZES: [(&str, usize); 3] = [("1KB", 1 << 10), ("100KB", 100 << 10), ("10MB", 10 << 20)];
+
+/// Um trecho com notas, repetições, pausas e comandos, repetido até o tamanho pedido.
+const PHRASE: &str = "CDE FGA Bo R+ Ci D- DRONE+ P+ Ee P- DRONE- R- BPM+ X{F0 7E 7F 09 01 F7} ";
+
+/// As configurações com o texto de cada tamanho.
+fn builders() -> impl Iterator<Item = (&'static str, usize, SheetBuilder)> {
+    SIZES.into_iter().map(|(label, size)| {
+        let builder = Sheet::builder()
+            .text(PHRASE.repeat(size / PHRASE.len() + 1))
+            .seed(0);
+        (label, size, builder)
+    })
+}
+
+fn map_substring_to_char(c: &mut Criterion) {
+    let mut group = c.benchmark_group("map_substring_to_char");
+    // Os textos de 10 MB levam segundos por execução
+    group.sample_size(10);
+    for (label, size, builder) in builders() {
+        let mut sheet = builder.build().expect("the options are the defaults");
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_function(BenchmarkId::from_parameter(label), |b| {
+            b.iter(|| black_box(sheet.map_substring_to_char()))
+        });
+    }
+    group.finish();
+}
+
+/// O `process_text` só é alcançado pelo `process`, que também prepara o texto antes,
+/// como na interface.
+fn process_text(c: &mut Criterion) {
+    let mut group = c.benchmark_group("process_text");
+    group.sample_size(10);
+    for (label, size, builder) in builders() {
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(label),
+            &builder,
+            |b, builder| {
+                b.iter(|| {
+                    builder
+                        .clone()
+                        .build()
+                        .expect("the options are the defaults")
+                        .process()
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn as_track(c: &mut Criterion) {
+    let mut group = c.benchmark_group("as_track");
+    group.sample_size(10);
+    for (label, size, builder) in builders() {
+        let actions = builder
+            .build()
+            .expect("the options are the defaults")
+            .process();
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(label),
+            &actions,
+            |b, actions| b.iter(|| MidiAction::as_track(actions)),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, map_substring_to_char, process_text, as_track);
+criterion_main!(benches);
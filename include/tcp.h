@@ -0,0 +1,82 @@
+/* Gerado pelo cbindgen a partir de src/ffi.rs. Não edite à mão. */
+
+#ifndef TCP_H
+#define TCP_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// O resultado de uma chamada.
+typedef enum TcpStatus {
+  // A conversão deu certo.
+  TCP_STATUS_OK = 0,
+  // Algum ponteiro obrigatório era nulo.
+  TCP_STATUS_NULL_ARGUMENT,
+  // O texto não é UTF-8 válido.
+  TCP_STATUS_INVALID_UTF8,
+  // O BPM está fora de 4..=360.
+  TCP_STATUS_INVALID_BPM,
+  // O volume está fora de 0..=127.
+  TCP_STATUS_INVALID_VOLUME,
+  // A oitava está fora de 0..=12.
+  TCP_STATUS_INVALID_OCTAVE,
+  // O instrumento está fora de 0..=127.
+  TCP_STATUS_INVALID_INSTRUMENT,
+} TcpStatus;
+
+// As configurações iniciais da conversão. Comece de `tcp_default_options`.
+typedef struct TcpOptions {
+  // O BPM inicial.
+  uint16_t bpm;
+  // O volume inicial.
+  uint16_t volume;
+  // A oitava inicial.
+  uint8_t octave;
+  // O instrumento inicial, do General MIDI.
+  uint8_t instrument;
+  // Se os caracteres do texto são emitidos como letras (karaokê).
+  bool lyrics;
+  // Se `seed` deve ser usada. Sem ela, cada conversão sai diferente.
+  bool seeded;
+  // A semente dos comandos aleatórios.
+  uint64_t seed;
+} TcpOptions;
+
+// Bytes alocados pela biblioteca, que devem ser liberados com `tcp_bytes_free`.
+typedef struct TcpBytes {
+  // O início dos bytes, ou nulo se não há nenhum.
+  uint8_t *data;
+  // Quantos bytes há.
+  size_t len;
+} TcpBytes;
+
+// As configurações padrão, as mesmas da interface.
+struct TcpOptions tcp_default_options(void);
+
+// Converte o texto em um arquivo MIDI (SMF), escrito em `out`.
+//
+// Se `options` for nulo, usa as configurações padrão. Se a conversão falhar,
+// `out` fica vazio e não precisa ser liberado.
+//
+// # Safety
+//
+// `text` deve ser uma string terminada em nulo, `options` deve ser nulo ou
+// apontar para um `TcpOptions`, e `out` deve apontar para um `TcpBytes` gravável.
+enum TcpStatus tcp_text_to_midi(const char *text,
+                                const struct TcpOptions *options,
+                                struct TcpBytes *out);
+
+// Libera os bytes devolvidos por `tcp_text_to_midi`.
+//
+// # Safety
+//
+// `bytes` deve ter vindo desta biblioteca e não pode ser liberado duas vezes.
+void tcp_bytes_free(struct TcpBytes bytes);
+
+// Uma descrição em inglês do resultado, que não deve ser liberada.
+const char *tcp_status_message(enum TcpStatus status);
+
+#endif  /* TCP_H */
@@ -0,0 +1,345 @@
+use std::fmt;
+
+use midly::{MetaMessage, MidiMessage, Smf, Timing, TrackEventKind};
+
+use crate::{
+    midi_action::MidiAction,
+    play::{self, MergedEvent},
+    time_state::TimeSignature,
+};
+
+/// Uma operação da diferença entre duas sequências de ações.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DiffOp {
+    /// A ação está nas duas sequências.
+    Equal(MidiAction),
+    /// A ação só está na segunda sequência.
+    Insert(MidiAction),
+    /// A ação só está na primeira sequência.
+    Delete(MidiAction),
+}
+
+impl DiffOp {
+    /// A ação da operação.
+    pub fn action(&self) -> &MidiAction {
+        match self {
+            Self::Equal(action) | Self::Insert(action) | Self::Delete(action) => action,
+        }
+    }
+
+    /// Se a operação é uma mudança, ou seja, não é `Equal`.
+    pub fn is_change(&self) -> bool {
+        !matches!(self, Self::Equal(_))
+    }
+}
+
+impl fmt::Display for DiffOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mark = match self {
+            Self::Equal(_) => ' ',
+            Self::Insert(_) => '+',
+            Self::Delete(_) => '-',
+        };
+        write!(f, "{mark} {}", self.action())
+    }
+}
+
+/// A diferença entre duas sequências de ações, pela maior subsequência comum.
+///
+/// Quando há uma troca, a remoção vem antes da inserção.
+pub fn diff(a: &[MidiAction], b: &[MidiAction]) -> Vec<DiffOp> {
+    // lcs[i][j] é o tamanho da maior subsequência comum de a[i..] e b[j..]
+    let mut lcs = vec![vec![0_usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut ops = Vec::with_capacity(a.len().max(b.len()));
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            ops.push(DiffOp::Equal(a[i].clone()));
+            i += 1;
+            j += 1;
+        } else if j == b.len() || (i < a.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            ops.push(DiffOp::Delete(a[i].clone()));
+            i += 1;
+        } else {
+            ops.push(DiffOp::Insert(b[j].clone()));
+            j += 1;
+        }
+    }
+
+    ops
+}
+
+/// Mostra a diferença uma operação por linha, como um diff de texto.
+///
+/// Com `context`, só as mudanças e as ações iguais a até essa distância delas são mostradas.
+pub fn render(ops: &[DiffOp], context: Option<usize>) -> String {
+    let near_change = |index: usize| match context {
+        None => true,
+        Some(context) => {
+            let start = index.saturating_sub(context);
+            let end = (index + context + 1).min(ops.len());
+            ops[start..end].iter().any(DiffOp::is_change)
+        }
+    };
+
+    ops.iter()
+        .enumerate()
+        .filter(|&(index, _)| near_change(index))
+        .map(|(_, op)| format!("{op}\n"))
+        .collect()
+}
+
+/// Uma nota de um arquivo, do NoteOn até o NoteOff.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NoteSpan {
+    pub channel: u8,
+    pub key: u8,
+    pub velocity: u8,
+    /// O tick do NoteOn.
+    pub start: u64,
+    /// O tick do NoteOff, ou o fim do arquivo se ele não vier.
+    pub end: u64,
+}
+
+/// Uma mudança de volume (CC7) de um arquivo.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VolumeChange {
+    pub channel: u8,
+    pub tick: u64,
+    pub volume: u8,
+}
+
+/// As notas de um arquivo ao longo do tempo, para desenhá-las em um piano roll.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Timeline {
+    /// Ticks por semimínima.
+    pub tpqn: u64,
+    /// O compasso do início do arquivo, que marca os compassos da régua.
+    pub time_signature: TimeSignature,
+    /// As notas, em ordem de início.
+    pub notes: Vec<NoteSpan>,
+    /// As mudanças de volume, em ordem.
+    pub volumes: Vec<VolumeChange>,
+    /// Os marcadores de seção (`==Refrão==`), com os seus ticks, em ordem.
+    pub markers: Vec<(u64, String)>,
+    /// O tick do último evento.
+    pub ticks: u64,
+}
+
+impl Timeline {
+    /// As notas, os volumes e os marcadores de todas as trilhas do arquivo.
+    ///
+    /// Arquivos em SMPTE usam os ticks por semimínima padrão, já que a régua só os aproxima.
+    pub fn from_smf(smf: &Smf<'_>) -> Self {
+        let tpqn = match smf.header.timing {
+            Timing::Metrical(tpqn) => tpqn.as_int(),
+            Timing::Timecode(..) => MidiAction::D_TPQN.as_int(),
+        };
+        let events = play::merge_tracks(&smf.tracks);
+        let ticks = events.last().map_or(0, |event| event.tick);
+
+        // As notas ligadas, por canal e tecla, com o índice em `notes`
+        let mut open: Vec<(u8, u8, usize)> = Vec::new();
+        let mut notes: Vec<NoteSpan> = Vec::new();
+        let mut volumes = Vec::new();
+        let mut markers = Vec::new();
+        for MergedEvent { tick, kind, .. } in &events {
+            let (channel, message) = match kind {
+                TrackEventKind::Midi { channel, message } => (channel.as_int(), message),
+                TrackEventKind::Meta(MetaMessage::Marker(name)) => {
+                    markers.push((*tick, String::from_utf8_lossy(name).into_owned()));
+                    continue;
+                }
+                _ => continue,
+            };
+            let (key, vel) = match message {
+                MidiMessage::NoteOn { key, vel } => (key.as_int(), vel.as_int()),
+                MidiMessage::NoteOff { key, .. } => (key.as_int(), 0),
+                MidiMessage::Controller { controller, value } if controller.as_int() == 7 => {
+                    volumes.push(VolumeChange {
+                        channel,
+                        tick: *tick,
+                        volume: value.as_int(),
+                    });
+                    continue;
+                }
+                _ => continue,
+            };
+            if let Some(position) = open.iter().position(|&(c, k, _)| (c, k) == (channel, key)) {
+                let (_, _, index) = open.remove(position);
+                notes[index].end = *tick;
+            }
+            if vel > 0 {
+                open.push((channel, key, notes.len()));
+                notes.push(NoteSpan {
+                    channel,
+                    key,
+                    velocity: vel,
+                    start: *tick,
+                    end: ticks,
+                });
+            }
+        }
+
+        Self {
+            tpqn: tpqn as u64,
+            time_signature: play::initial_time_signature(&events),
+            notes,
+            volumes,
+            markers,
+            ticks,
+        }
+    }
+
+    /// Os canais usados pelas notas e pelos volumes, em ordem.
+    pub fn channels(&self) -> Vec<u8> {
+        let mut channels: Vec<u8> = self
+            .notes
+            .iter()
+            .map(|note| note.channel)
+            .chain(self.volumes.iter().map(|change| change.channel))
+            .collect();
+        channels.sort_unstable();
+        channels.dedup();
+
+        channels
+    }
+
+    /// Ticks por tempo do compasso.
+    pub fn beat_ticks(&self) -> u64 {
+        let unit = self.tpqn * 4 / self.time_signature.denominator.max(1) as u64;
+        unit * self.time_signature.units_per_beat()
+    }
+
+    /// Ticks por compasso.
+    pub fn bar_ticks(&self) -> u64 {
+        self.tpqn * 4 * self.time_signature.numerator as u64
+            / self.time_signature.denominator.max(1) as u64
+    }
+
+    /// Os números das notas, para resumir o que o arquivo toca.
+    pub fn statistics(&self) -> Statistics {
+        let keys = self.notes.iter().map(|note| note.key);
+        Statistics {
+            notes: self.notes.len(),
+            channels: self.channels().len(),
+            range: keys.clone().min().zip(keys.max()),
+            bars: self.ticks.div_ceil(self.bar_ticks().max(1)),
+        }
+    }
+}
+
+/// Os números das notas de um arquivo.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Statistics {
+    /// Quantas notas tocam.
+    pub notes: usize,
+    /// Quantos canais são usados.
+    pub channels: usize,
+    /// A nota mais grave e a mais aguda, se houver notas.
+    pub range: Option<(u8, u8)>,
+    /// Quantos compassos, completos ou não, o arquivo ocupa.
+    pub bars: u64,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::text_to_midi::{Sheet, State};
+
+    #[test]
+    fn edit_is_a_delete_and_insert() {
+        let a = [MidiAction::PlayNote(60), MidiAction::PlayNote(62)];
+        let b = [MidiAction::PlayNote(60), MidiAction::PlayNote(64)];
+
+        assert_eq!(
+            diff(&a, &b),
+            [
+                DiffOp::Equal(MidiAction::PlayNote(60)),
+                DiffOp::Delete(MidiAction::PlayNote(62)),
+                DiffOp::Insert(MidiAction::PlayNote(64)),
+            ]
+        );
+    }
+
+    #[test]
+    fn rendering_with_context() {
+        let a = Sheet::builder().text("CDEFG").build().unwrap().process();
+        let b = Sheet::builder().text("CDEAG").build().unwrap().process();
+
+        let rendered = render(&diff(&a, &b), Some(1));
+
+        assert_eq!(rendered, "  note 64\n- note 65\n+ note 69\n  note 67\n");
+    }
+
+    #[test]
+    fn volume_change_is_semantic() {
+        // Same notes, only the volume differs
+        let a = Sheet::builder().text("CD").build().unwrap().process();
+        let b = Sheet::builder().text("C+D").build().unwrap().process();
+
+        let changes: Vec<DiffOp> = diff(&a, &b).into_iter().filter(DiffOp::is_change).collect();
+
+        assert_eq!(changes, [DiffOp::Insert(MidiAction::ChangeVolume(100))]);
+    }
+
+    #[test]
+    fn timeline_pairs_notes() {
+        let actions = Sheet::builder().text("C D").build().unwrap().process();
+        let smf = MidiAction::as_track(&actions);
+
+        let timeline = Timeline::from_smf(&smf);
+
+        let spans: Vec<(u8, u64, u64)> = timeline
+            .notes
+            .iter()
+            .map(|note| (note.key, note.start, note.end))
+            .collect();
+        assert_eq!(spans, [(60, 0, 480), (62, 960, 1440)]);
+        assert_eq!((timeline.beat_ticks(), timeline.bar_ticks()), (480, 1920));
+        assert_eq!(
+            timeline.volumes,
+            [VolumeChange {
+                channel: 0,
+                tick: 0,
+                volume: State::D_VOLUME as u8,
+            }]
+        );
+        assert_eq!(timeline.channels(), [0]);
+        assert_eq!(
+            timeline.statistics(),
+            Statistics {
+                notes: 2,
+                channels: 1,
+                range: Some((60, 62)),
+                bars: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn timeline_keeps_the_markers() {
+        let actions = Sheet::builder()
+            .text("==Verse==CD==Chorus==E")
+            .build()
+            .unwrap()
+            .process();
+
+        let timeline = Timeline::from_smf(&MidiAction::as_track(&actions));
+
+        assert_eq!(
+            timeline.markers,
+            [(0, "Verse".to_string()), (960, "Chorus".to_string())]
+        );
+    }
+}
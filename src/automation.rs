@@ -0,0 +1,279 @@
+//! Automação: curvas de volume, pan e andamento ao longo da música, que viram
+//! eventos de controle e de tempo interpolados.
+//!
+//! As curvas ficam no cabeçalho do texto, uma por linha, com cada ponto escrito como
+//! `tempo:valor`, o tempo em semínimas a partir do começo:
+//!
+//! ```text
+//! @lane.volume=0:100, 8:40, 16:100
+//! @lane.tempo=0:120, 31.5:90
+//! ```
+
+use std::{fmt, ops::RangeInclusive, time::Duration};
+
+use crate::{
+    midi_action::{MidiAction, TimedAction},
+    tempo_map::TempoMap,
+    text_to_midi::State,
+};
+
+/// O que uma curva controla.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Parameter {
+    /// O volume do canal da melodia (CC 7).
+    Volume,
+    /// O pan do canal da melodia (CC 10), com o centro em 64.
+    Pan,
+    /// O andamento, em BPM.
+    Tempo,
+}
+
+impl Parameter {
+    /// Todos os parâmetros, na ordem mostrada na interface.
+    pub const ALL: [Self; 3] = [Self::Volume, Self::Pan, Self::Tempo];
+
+    /// O nome usado no cabeçalho, depois de `lane.`.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Volume => "volume",
+            Self::Pan => "pan",
+            Self::Tempo => "tempo",
+        }
+    }
+
+    /// O parâmetro com o nome dado.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|parameter| parameter.name() == name)
+    }
+
+    /// Os valores aceitos.
+    pub fn range(self) -> RangeInclusive<u16> {
+        match self {
+            Self::Volume => State::LIMITS.volume,
+            Self::Pan => 0..=127,
+            Self::Tempo => State::LIMITS.bpm,
+        }
+    }
+
+    /// A ação que leva o parâmetro ao valor dado.
+    pub fn action(self, value: u16) -> MidiAction {
+        match self {
+            Self::Volume => MidiAction::ChangeVolume(value),
+            Self::Pan => MidiAction::Pan(value as u8),
+            Self::Tempo => MidiAction::ChangeBPM(value),
+        }
+    }
+}
+
+/// Um ponto da curva.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Point {
+    /// O tick, com o `MidiAction::D_TPQN`.
+    pub tick: u64,
+    /// O valor do parâmetro.
+    pub value: u16,
+}
+
+/// A curva de um parâmetro: retas entre os pontos, em ordem de tick.
+///
+/// Antes do primeiro ponto, o parâmetro fica como o texto deixou; depois do último,
+/// fica com o valor dele.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Lane {
+    /// O parâmetro controlado.
+    pub parameter: Parameter,
+    points: Vec<Point>,
+}
+
+impl Lane {
+    /// A distância padrão, em ticks, entre os eventos de uma reta: uma fusa.
+    pub const D_RESOLUTION: u64 = 60;
+
+    /// Uma curva sem pontos.
+    pub const fn new(parameter: Parameter) -> Self {
+        Self {
+            parameter,
+            points: Vec::new(),
+        }
+    }
+
+    /// Os pontos, em ordem de tick.
+    pub fn points(&self) -> &[Point] {
+        &self.points
+    }
+
+    /// Acrescenta um ponto, trocando o que estiver no mesmo tick, e retorna a posição dele.
+    ///
+    /// O valor é limitado à faixa do parâmetro.
+    pub fn insert(&mut self, tick: u64, value: u16) -> usize {
+        let (min, max) = self.parameter.range().into_inner();
+        let point = Point {
+            tick,
+            value: value.clamp(min, max),
+        };
+        match self.points.binary_search_by_key(&tick, |point| point.tick) {
+            Ok(index) => {
+                self.points[index] = point;
+                index
+            }
+            Err(index) => {
+                self.points.insert(index, point);
+                index
+            }
+        }
+    }
+
+    /// Tira o ponto da posição dada.
+    pub fn remove(&mut self, index: usize) {
+        if index < self.points.len() {
+            self.points.remove(index);
+        }
+    }
+
+    /// Tira todos os pontos.
+    pub fn clear(&mut self) {
+        self.points.clear();
+    }
+
+    /// O valor da curva no tick dado, se ele não estiver antes do primeiro ponto.
+    pub fn value_at(&self, tick: u64) -> Option<u16> {
+        let next = self.points.partition_point(|point| point.tick <= tick);
+        let before = self.points.get(next.checked_sub(1)?)?;
+        let Some(after) = self.points.get(next) else {
+            return Some(before.value);
+        };
+
+        let (from, to) = (before.value as i64, after.value as i64);
+        let span = (after.tick - before.tick) as i64;
+        let within = (tick - before.tick) as i64;
+        Some((from + (to - from) * within / span) as u16)
+    }
+
+    /// Os valores da curva a cada `resolution` ticks, do primeiro ponto ao último,
+    /// só quando mudam.
+    pub fn events(&self, resolution: u64) -> Vec<(u64, u16)> {
+        let resolution = resolution.max(1);
+        let mut events: Vec<(u64, u16)> = Vec::new();
+        let mut push = |tick: u64, value: u16| {
+            if events.last().is_none_or(|&(_, last)| last != value) {
+                events.push((tick, value));
+            }
+        };
+        for pair in self.points.windows(2) {
+            let (from, to) = (pair[0], pair[1]);
+            for tick in (from.tick..to.tick).step_by(resolution as usize) {
+                push(tick, self.value_at(tick).unwrap_or(from.value));
+            }
+        }
+        if let Some(last) = self.points.last() {
+            push(last.tick, last.value);
+        }
+
+        events
+    }
+
+    /// Lê os pontos de uma curva do cabeçalho, como `0:100, 8:40`.
+    pub fn parse(parameter: Parameter, text: &str) -> Result<Self, &'static str> {
+        let mut lane = Self::new(parameter);
+        for point in text.split(',').filter(|point| !point.trim().is_empty()) {
+            let (beat, value) = point.split_once(':').ok_or("expected beat:value")?;
+            let beat: f64 = beat.trim().parse().map_err(|_| "not a number")?;
+            let value: u16 = value.trim().parse().map_err(|_| "not a number")?;
+            if !beat.is_finite() || beat < 0.0 || !parameter.range().contains(&value) {
+                return Err("out of range");
+            }
+            let tick = (beat * MidiAction::D_TPQN.as_int() as f64).round() as u64;
+            lane.insert(tick, value);
+        }
+
+        Ok(lane)
+    }
+}
+
+/// Os pontos como no cabeçalho, com os tempos em semínimas.
+impl fmt::Display for Lane {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tpqn = MidiAction::D_TPQN.as_int() as u64;
+        for (index, point) in self.points.iter().enumerate() {
+            if index > 0 {
+                write!(f, ", ")?;
+            }
+            if point.tick % tpqn == 0 {
+                write!(f, "{}", point.tick / tpqn)?;
+            } else {
+                let beat = format!("{:.3}", point.tick as f64 / tpqn as f64);
+                write!(f, "{}", beat.trim_end_matches('0'))?;
+            }
+            write!(f, ":{}", point.value)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Acrescenta os eventos das curvas às ações, a cada `resolution` ticks, e refaz os
+/// tempos de todas, já que a curva do andamento muda quando cada uma acontece.
+///
+/// No mesmo tick, os eventos das curvas vêm depois das ações do texto e valem no lugar
+/// delas.
+pub fn apply(lanes: &[Lane], mut timed: Vec<TimedAction>, resolution: u64) -> Vec<TimedAction> {
+    if lanes.iter().all(|lane| lane.points.is_empty()) {
+        return timed;
+    }
+
+    for lane in lanes {
+        timed.extend(
+            lane.events(resolution)
+                .into_iter()
+                .map(|(tick, value)| TimedAction {
+                    tick,
+                    time: Duration::ZERO,
+                    channel: 0,
+                    action: lane.parameter.action(value),
+                    source_range: 0..0,
+                }),
+        );
+    }
+    timed.sort_by_key(|timed| timed.tick);
+    let map = TempoMap::from_timed(&timed);
+    for timed in &mut timed {
+        timed.time = map.time_at(timed.tick);
+    }
+
+    timed
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lanes_are_interpolated() {
+        let lane = Lane::parse(Parameter::Volume, "0:100, 1:40, 2.5:40").unwrap();
+
+        assert_eq!(lane.value_at(240), Some(70));
+        assert_eq!(lane.value_at(5000), Some(40));
+        assert_eq!(
+            lane.events(120),
+            [(0, 100), (120, 85), (240, 70), (360, 55), (480, 40)]
+        );
+        assert_eq!(lane.to_string(), "0:100, 1:40, 2.5:40");
+        assert!(Lane::parse(Parameter::Tempo, "0:0").is_err());
+        assert!(Lane::parse(Parameter::Pan, "x:3").is_err());
+    }
+
+    #[test]
+    fn tempo_lanes_retime_the_actions() {
+        let timed = TimedAction::from_actions(&vec![MidiAction::PlayNote(60); 3]);
+        let tempo = Lane::parse(Parameter::Tempo, "1:60").unwrap();
+
+        let automated = apply(&[tempo], timed, Lane::D_RESOLUTION);
+
+        let times: Vec<_> = automated.iter().map(|timed| timed.time).collect();
+        // Half a second for the first beat at the default 120 BPM, then a second per beat
+        assert_eq!(times, [0, 500, 500, 1500].map(Duration::from_millis));
+        assert_eq!(automated[2].action, MidiAction::ChangeBPM(60));
+    }
+}
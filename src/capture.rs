@@ -0,0 +1,103 @@
+//! Gravação do que o player realmente enviou, com os instantes reais de cada mensagem,
+//! para conferir a fidelidade do tempo contra o arquivo tocado.
+
+use std::{sync::Mutex, time::Duration};
+
+use midly::{
+    live::{LiveEvent, SystemCommon},
+    num::{u15, u24, u28},
+    Format, Header, MetaMessage, Smf, Timing, Track, TrackEvent, TrackEventKind,
+};
+
+/// As mensagens enviadas por um player, com os instantes do relógio dele.
+#[derive(Debug, Default)]
+pub struct Capture {
+    messages: Mutex<Vec<(Duration, Vec<u8>)>>,
+}
+
+impl Capture {
+    /// Ticks por semimínima do arquivo salvo: com o andamento de 60 BPM, cada tick
+    /// é um milissegundo.
+    const TPQN: u16 = 1000;
+    /// Microssegundos por semimínima do arquivo salvo.
+    const MSPQN: u32 = 1_000_000;
+
+    /// Guarda uma mensagem enviada no instante dado.
+    pub fn record(&self, time: Duration, message: &[u8]) {
+        self.messages.lock().unwrap().push((time, message.to_vec()));
+    }
+
+    /// Quantas mensagens foram guardadas.
+    pub fn len(&self) -> usize {
+        self.messages.lock().unwrap().len()
+    }
+
+    /// Um arquivo MIDI com as mensagens guardadas, a primeira no tick 0.
+    ///
+    /// As mensagens de tempo real, como os pulsos de Clock, não cabem em um arquivo
+    /// e ficam de fora, mas o tempo entre as outras é mantido.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let messages = self.messages.lock().unwrap();
+        let start = messages.first().map_or(Duration::ZERO, |&(time, _)| time);
+
+        let mut track = Track::new();
+        track.push(TrackEvent {
+            delta: 0.into(),
+            kind: TrackEventKind::Meta(MetaMessage::Tempo(u24::new(Self::MSPQN))),
+        });
+        let mut last = 0;
+        for (time, message) in messages.iter() {
+            let kind = match LiveEvent::parse(message) {
+                Ok(LiveEvent::Midi { channel, message }) => {
+                    TrackEventKind::Midi { channel, message }
+                }
+                // No arquivo, a SysEx vai sem o F0, que já é o tipo do evento
+                Ok(LiveEvent::Common(SystemCommon::SysEx(_))) => {
+                    TrackEventKind::SysEx(&message[1..])
+                }
+                _ => continue,
+            };
+            let tick = (time.saturating_sub(start).as_millis() as u64).max(last);
+            track.push(TrackEvent {
+                delta: u28::try_from((tick - last) as u32).unwrap_or(u28::max_value()),
+                kind,
+            });
+            last = tick;
+        }
+        track.push(TrackEvent {
+            delta: 0.into(),
+            kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+        });
+
+        let smf = Smf {
+            header: Header::new(Format::SingleTrack, Timing::Metrical(u15::new(Self::TPQN))),
+            tracks: vec![track],
+        };
+        let mut bytes = Vec::new();
+        smf.write_std(&mut bytes)
+            .expect("writing to memory does not fail");
+
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn saved_capture_keeps_real_times() {
+        let capture = Capture::default();
+        capture.record(Duration::from_millis(100), &[0x90, 60, 100]);
+        capture.record(Duration::from_millis(105), &[0xF8]);
+        capture.record(Duration::from_millis(612), &[0x80, 60, 0]);
+
+        let bytes = capture.to_bytes();
+
+        let smf = Smf::parse(&bytes).unwrap();
+        let deltas: Vec<u32> = smf.tracks[0].iter().map(|e| e.delta.as_int()).collect();
+        // Tempo, note on, note off 512 ms later without the clock pulse, end of track
+        assert_eq!(deltas, [0, 0, 512, 0]);
+        assert_eq!(capture.len(), 3);
+    }
+}
@@ -0,0 +1,161 @@
+//! Troca de canais na saída, para tocar arquivos de vários canais em aparelhos que
+//! só escutam um, ou para levar cada canal para onde o aparelho espera.
+
+use std::{error::Error, fmt, str::FromStr};
+
+use midly::{num::u4, Track, TrackEventKind};
+
+/// Para qual canal vai cada um dos 16 canais do MIDI, contados a partir de 0.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ChannelMap([u8; 16]);
+
+impl Default for ChannelMap {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl ChannelMap {
+    /// Cada canal vai para ele mesmo.
+    pub const fn identity() -> Self {
+        let mut map = [0; 16];
+        let mut channel = 0;
+        while channel < 16 {
+            map[channel] = channel as u8;
+            channel += 1;
+        }
+        Self(map)
+    }
+
+    /// Todos os canais vão para o canal dado.
+    pub fn all_to(channel: u8) -> Self {
+        Self([channel & 0x0F; 16])
+    }
+
+    /// O canal para onde vai o canal dado.
+    pub fn get(&self, from: u8) -> u8 {
+        self.0[(from & 0x0F) as usize]
+    }
+
+    /// Manda o canal `from` para o canal `to`.
+    pub fn set(&mut self, from: u8, to: u8) {
+        self.0[(from & 0x0F) as usize] = to & 0x0F;
+    }
+
+    /// Se nenhum canal muda.
+    pub fn is_identity(&self) -> bool {
+        *self == Self::identity()
+    }
+
+    /// Troca o canal dos eventos de canal da trilha.
+    pub fn apply(&self, track: &mut Track<'_>) {
+        if self.is_identity() {
+            return;
+        }
+        for event in track.iter_mut() {
+            if let TrackEventKind::Midi { channel, .. } = &mut event.kind {
+                *channel = u4::new(self.get(channel.as_int()));
+            }
+        }
+    }
+
+    /// Troca o canal de uma mensagem já serializada, se for de canal (de 0x80 a 0xEF).
+    pub fn apply_to_message(&self, message: &mut [u8]) {
+        if let Some(status) = message.first_mut() {
+            if (0x80..0xF0).contains(status) {
+                *status = (*status & 0xF0) | self.get(*status & 0x0F);
+            }
+        }
+    }
+}
+
+/// Lê uma troca escrita como `1=10,2=10`, ou `all=1` para mandar tudo para um canal.
+///
+/// Os canais são contados a partir de 1, como nos aparelhos; os não citados não mudam.
+impl FromStr for ChannelMap {
+    type Err = InvalidChannelMap;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let channel = |text: &str| match text.trim().parse::<u8>() {
+            Ok(channel @ 1..=16) => Ok(channel - 1),
+            _ => Err(InvalidChannelMap(text.trim().to_string())),
+        };
+
+        let mut map = Self::identity();
+        for pair in spec.split(',').filter(|pair| !pair.trim().is_empty()) {
+            let (from, to) = pair
+                .split_once('=')
+                .ok_or_else(|| InvalidChannelMap(pair.trim().to_string()))?;
+            let to = channel(to)?;
+            if from.trim().eq_ignore_ascii_case("all") {
+                map = Self::all_to(to);
+            } else {
+                map.set(channel(from)?, to);
+            }
+        }
+
+        Ok(map)
+    }
+}
+
+/// Erro para uma troca de canais mal escrita, com o trecho que não foi entendido.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidChannelMap(pub String);
+
+impl fmt::Display for InvalidChannelMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid channel remap \"{}\", expected something like 1=10,2=10 or all=1",
+            self.0
+        )
+    }
+}
+
+impl Error for InvalidChannelMap {}
+
+#[cfg(test)]
+mod test {
+    use midly::{num::u7, MidiMessage, TrackEvent};
+
+    use super::*;
+
+    #[test]
+    fn spec_moves_only_the_named_channels() {
+        let map: ChannelMap = "1=10, 3=10".parse().unwrap();
+        assert_eq!((map.get(0), map.get(1), map.get(2)), (9, 1, 9));
+
+        let map: ChannelMap = "all=2,16=16".parse().unwrap();
+        assert_eq!((map.get(0), map.get(9), map.get(15)), (1, 1, 15));
+
+        assert!("".parse::<ChannelMap>().unwrap().is_identity());
+        assert_eq!(
+            "1=17".parse::<ChannelMap>(),
+            Err(InvalidChannelMap("17".to_string()))
+        );
+    }
+
+    #[test]
+    fn only_channel_events_move() {
+        let map = ChannelMap::all_to(4);
+        let mut track = vec![TrackEvent {
+            delta: 0.into(),
+            kind: TrackEventKind::Midi {
+                channel: 9.into(),
+                message: MidiMessage::NoteOn {
+                    key: u7::new(36),
+                    vel: u7::new(100),
+                },
+            },
+        }];
+        map.apply(&mut track);
+        assert!(matches!(track[0].kind, TrackEventKind::Midi { channel, .. } if channel == 4));
+
+        let mut sysex = [0xF0, 0x7E, 0xF7];
+        map.apply_to_message(&mut sysex);
+        assert_eq!(sysex, [0xF0, 0x7E, 0xF7]);
+        let mut program = [0xC2, 5];
+        map.apply_to_message(&mut program);
+        assert_eq!(program, [0xC4, 5]);
+    }
+}
@@ -0,0 +1,155 @@
+//! O estado de som de cada canal: o instrumento, os controladores e o pitch bend,
+//! acumulados das mensagens enviadas.
+//!
+//! Um aparelho reaberto no meio da música, ou a música que pula para outro ponto,
+//! precisa desse estado de novo, e reenviá-lo custa poucas mensagens por canal, em vez
+//! de repetir todas as que vieram antes.
+
+use midly::{
+    live::LiveEvent,
+    num::{u4, u7},
+    MidiMessage, PitchBend,
+};
+
+use crate::mixer;
+
+/// Controlador que volta os controladores do canal ao padrão (CC 121).
+pub const RESET_ALL_CONTROLLERS: u8 = 121;
+
+/// O primeiro dos controladores de modo do canal, como o que desliga as notas, que
+/// não fazem parte do estado.
+const CHANNEL_MODE: u8 = 120;
+
+/// Os controladores que o `RESET_ALL_CONTROLLERS` volta ao padrão, segundo a RP-015:
+/// modulação, expressão e os pedais. O volume, o pan, o banco e os efeitos ficam.
+const RESET_BY_RESET_ALL: [u8; 6] = [1, 11, 64, 65, 66, 67];
+
+/// O estado de um canal.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct Channel {
+    program: Option<u7>,
+    /// Os controladores, na ordem da última mudança de cada um, para que sequências
+    /// como a de RPN (CC 101, 100 e 6) sejam reenviadas na mesma ordem.
+    controllers: Vec<(u7, u7)>,
+    pitch_bend: Option<PitchBend>,
+}
+
+/// O estado de som dos 16 canais, que começa no padrão de um aparelho recém-ligado.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChannelState {
+    channels: [Channel; mixer::CHANNELS],
+}
+
+impl ChannelState {
+    /// O estado depois das mensagens dadas, como as de um trecho da música.
+    pub fn from_events<'a>(events: impl IntoIterator<Item = LiveEvent<'a>>) -> Self {
+        let mut state = Self::default();
+        for event in events {
+            state.update(&event);
+        }
+
+        state
+    }
+
+    /// Acumula o efeito da mensagem. As notas e as mensagens de sistema não mudam nada.
+    pub fn update(&mut self, event: &LiveEvent<'_>) {
+        let LiveEvent::Midi { channel, message } = event else {
+            return;
+        };
+        let state = &mut self.channels[channel.as_int() as usize];
+        match *message {
+            MidiMessage::ProgramChange { program } => state.program = Some(program),
+            MidiMessage::PitchBend { bend } => state.pitch_bend = Some(bend),
+            MidiMessage::Controller { controller, .. }
+                if controller.as_int() == RESET_ALL_CONTROLLERS =>
+            {
+                state
+                    .controllers
+                    .retain(|(controller, _)| !RESET_BY_RESET_ALL.contains(&controller.as_int()));
+                state.pitch_bend = None;
+            }
+            MidiMessage::Controller { controller, value } if controller.as_int() < CHANNEL_MODE => {
+                state.controllers.retain(|&(other, _)| other != controller);
+                state.controllers.push((controller, value));
+            }
+            _ => (),
+        }
+    }
+
+    /// As mensagens que levam um aparelho recém-ligado a este estado: por canal, os
+    /// controladores, o instrumento depois do banco escolhido e o pitch bend.
+    pub fn messages(&self) -> Vec<LiveEvent<'static>> {
+        let mut messages = Vec::new();
+        for (state, channel) in self.channels.iter().zip(0..) {
+            let channel = u4::new(channel);
+            let controllers = state
+                .controllers
+                .iter()
+                .map(|&(controller, value)| MidiMessage::Controller { controller, value });
+            let program = state
+                .program
+                .map(|program| MidiMessage::ProgramChange { program });
+            let bend = state.pitch_bend.map(|bend| MidiMessage::PitchBend { bend });
+            messages.extend(
+                controllers
+                    .chain(program)
+                    .chain(bend)
+                    .map(|message| LiveEvent::Midi { channel, message }),
+            );
+        }
+
+        messages
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn state_keeps_the_last_value_in_order() {
+        let event = |channel: u8, message| LiveEvent::Midi {
+            channel: u4::new(channel),
+            message,
+        };
+        let controller = |controller: u8, value: u8| MidiMessage::Controller {
+            controller: u7::new(controller),
+            value: u7::new(value),
+        };
+        let bend = MidiMessage::PitchBend {
+            bend: PitchBend::from_f32(0.5),
+        };
+        let state = ChannelState::from_events([
+            event(0, controller(7, 100)),
+            event(0, controller(0, 1)),
+            event(0, MidiMessage::ProgramChange { program: 5.into() }),
+            event(0, controller(1, 64)),
+            event(0, controller(7, 90)),
+            event(
+                0,
+                MidiMessage::NoteOn {
+                    key: 60.into(),
+                    vel: 100.into(),
+                },
+            ),
+            event(0, controller(123, 0)),
+            event(3, bend),
+            event(3, controller(64, 127)),
+            event(3, controller(RESET_ALL_CONTROLLERS, 0)),
+            event(9, MidiMessage::ProgramChange { program: 0.into() }),
+            event(9, MidiMessage::ProgramChange { program: 1.into() }),
+        ]);
+
+        assert_eq!(
+            state.messages(),
+            [
+                event(0, controller(0, 1)),
+                event(0, controller(1, 64)),
+                event(0, controller(7, 90)),
+                event(0, MidiMessage::ProgramChange { program: 5.into() }),
+                event(9, MidiMessage::ProgramChange { program: 1.into() }),
+            ]
+        );
+        assert!(ChannelState::default().messages().is_empty());
+    }
+}
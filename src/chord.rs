@@ -0,0 +1,259 @@
+//! Cifras de acordes, como `Cmaj7`, `Am` ou `G7/B`, e as notas que as tocam.
+//!
+//! No texto, uma cifra entre aspas (`"Am"`) toca o acorde no canal do acompanhamento,
+//! segurado até a próxima cifra, enquanto a melodia continua; `"N.C."` só solta o acorde.
+
+use std::{error::Error, fmt, str::FromStr};
+
+use crate::note::Note;
+
+/// O tipo do acorde, pelos intervalos das notas acima da fundamental.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Quality {
+    Major,
+    Minor,
+    Diminished,
+    Augmented,
+    Sus2,
+    Sus4,
+    Major6,
+    Minor6,
+    Dominant7,
+    Major7,
+    Minor7,
+    MinorMajor7,
+    HalfDiminished7,
+    Diminished7,
+    Dominant9,
+    Major9,
+    Minor9,
+    Add9,
+}
+
+impl Quality {
+    /// Os sufixos das cifras de cada tipo, comparados com todo o resto da cifra,
+    /// fora o baixo.
+    const SUFFIXES: [(&'static str, Self); 29] = [
+        ("mMaj7", Self::MinorMajor7),
+        ("m(maj7)", Self::MinorMajor7),
+        ("maj9", Self::Major9),
+        ("maj7", Self::Major7),
+        ("m7b5", Self::HalfDiminished7),
+        ("dim7", Self::Diminished7),
+        ("sus2", Self::Sus2),
+        ("sus4", Self::Sus4),
+        ("add9", Self::Add9),
+        ("min", Self::Minor),
+        ("dim", Self::Diminished),
+        ("aug", Self::Augmented),
+        ("sus", Self::Sus4),
+        ("M7", Self::Major7),
+        ("Δ7", Self::Major7),
+        ("ø7", Self::HalfDiminished7),
+        ("°7", Self::Diminished7),
+        ("m9", Self::Minor9),
+        ("m7", Self::Minor7),
+        ("m6", Self::Minor6),
+        ("Δ", Self::Major7),
+        ("ø", Self::HalfDiminished7),
+        ("°", Self::Diminished),
+        ("+", Self::Augmented),
+        ("9", Self::Dominant9),
+        ("7", Self::Dominant7),
+        ("6", Self::Major6),
+        ("m", Self::Minor),
+        ("-", Self::Minor),
+    ];
+
+    /// Os semitons de cada nota do acorde a partir da fundamental, em ordem.
+    pub const fn intervals(self) -> &'static [u8] {
+        match self {
+            Self::Major => &[0, 4, 7],
+            Self::Minor => &[0, 3, 7],
+            Self::Diminished => &[0, 3, 6],
+            Self::Augmented => &[0, 4, 8],
+            Self::Sus2 => &[0, 2, 7],
+            Self::Sus4 => &[0, 5, 7],
+            Self::Major6 => &[0, 4, 7, 9],
+            Self::Minor6 => &[0, 3, 7, 9],
+            Self::Dominant7 => &[0, 4, 7, 10],
+            Self::Major7 => &[0, 4, 7, 11],
+            Self::Minor7 => &[0, 3, 7, 10],
+            Self::MinorMajor7 => &[0, 3, 7, 11],
+            Self::HalfDiminished7 => &[0, 3, 6, 10],
+            Self::Diminished7 => &[0, 3, 6, 9],
+            Self::Dominant9 => &[0, 4, 7, 10, 14],
+            Self::Major9 => &[0, 4, 7, 11, 14],
+            Self::Minor9 => &[0, 3, 7, 10, 14],
+            Self::Add9 => &[0, 4, 7, 14],
+        }
+    }
+}
+
+/// Como as notas do acorde são distribuídas.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Voicing {
+    /// As notas empilhadas a partir da fundamental, o mais perto possível.
+    #[default]
+    Close,
+    /// A fundamental uma oitava abaixo das outras, para um som mais aberto.
+    Open,
+    /// A segunda nota mais aguda uma oitava abaixo, como nos arranjos de guitarra e piano.
+    Drop2,
+}
+
+impl FromStr for Voicing {
+    type Err = &'static str;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "close" => Ok(Self::Close),
+            "open" => Ok(Self::Open),
+            "drop2" => Ok(Self::Drop2),
+            _ => Err("expected close, open or drop2"),
+        }
+    }
+}
+
+/// Um acorde lido de uma cifra.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Chord {
+    /// A fundamental, em semitons a partir de dó (de 0 a 11).
+    pub root: u8,
+    /// O tipo do acorde.
+    pub quality: Quality,
+    /// O baixo de uma cifra com barra, como o si de `G/B`, em semitons a partir de dó.
+    pub bass: Option<u8>,
+}
+
+impl Chord {
+    /// A oitava da fundamental do acompanhamento, abaixo da oitava padrão da melodia.
+    pub const OCTAVE: u8 = 3;
+
+    /// As notas do MIDI do acorde, da mais grave à mais aguda, com a fundamental na
+    /// oitava dada. As que não cabem no MIDI ficam de fora.
+    pub fn keys(&self, voicing: Voicing, octave: u8) -> Vec<u8> {
+        let root = (octave as i16 + 1) * Note::SEMITONES as i16 + self.root as i16;
+        let mut keys: Vec<i16> = self
+            .quality
+            .intervals()
+            .iter()
+            .map(|&interval| root + interval as i16)
+            .collect();
+
+        match voicing {
+            Voicing::Close => (),
+            Voicing::Open => keys[0] -= Note::SEMITONES as i16,
+            Voicing::Drop2 => {
+                let second = keys.len() - 2;
+                keys[second] -= Note::SEMITONES as i16;
+            }
+        }
+        keys.sort_unstable();
+        if let Some(bass) = self.bass {
+            // O baixo fica abaixo de todas as outras notas
+            let lowest = keys[0];
+            let mut key = lowest - (lowest - bass as i16).rem_euclid(Note::SEMITONES as i16);
+            if key == lowest {
+                key -= Note::SEMITONES as i16;
+            }
+            keys.insert(0, key);
+        }
+
+        keys.into_iter()
+            .filter_map(|key| u8::try_from(key).ok())
+            .filter(|&key| key <= 127)
+            .collect()
+    }
+
+    /// A nota de uma letra de A a G, maiúscula, seguida ou não de `#` ou `b`, e o resto.
+    fn parse_pitch(text: &str) -> Option<(u8, &str)> {
+        let mut chars = text.chars();
+        let letter = chars.next().filter(char::is_ascii_uppercase)?;
+        let natural = Note::from_char(letter)?.semitone()?;
+        let rest = chars.as_str();
+        let (shift, rest) = match rest.as_bytes().first() {
+            Some(b'#') => (1, &rest[1..]),
+            Some(b'b') => (Note::SEMITONES - 1, &rest[1..]),
+            _ => (0, rest),
+        };
+
+        Some(((natural + shift) % Note::SEMITONES, rest))
+    }
+}
+
+impl FromStr for Chord {
+    type Err = InvalidChord;
+
+    fn from_str(symbol: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidChord(symbol.to_string());
+        let (root, rest) = Self::parse_pitch(symbol.trim()).ok_or_else(invalid)?;
+        let (suffix, bass) = match rest.split_once('/') {
+            Some((suffix, bass)) => match Self::parse_pitch(bass) {
+                Some((bass, "")) => (suffix, Some(bass)),
+                _ => return Err(invalid()),
+            },
+            None => (rest, None),
+        };
+        let quality = if suffix.is_empty() {
+            Quality::Major
+        } else {
+            Quality::SUFFIXES
+                .iter()
+                .find(|&&(written, _)| written == suffix)
+                .map(|&(_, quality)| quality)
+                .ok_or_else(invalid)?
+        };
+
+        Ok(Self {
+            root,
+            quality,
+            bass,
+        })
+    }
+}
+
+/// Erro para uma cifra que não foi entendida.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidChord(pub String);
+
+impl fmt::Display for InvalidChord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown chord symbol \"{}\"", self.0)
+    }
+}
+
+impl Error for InvalidChord {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn keys(symbol: &str, voicing: Voicing) -> Vec<u8> {
+        symbol
+            .parse::<Chord>()
+            .unwrap()
+            .keys(voicing, Chord::OCTAVE)
+    }
+
+    #[test]
+    fn symbols_become_chords() {
+        assert_eq!(keys("C", Voicing::Close), [48, 52, 55]);
+        assert_eq!(keys("Am", Voicing::Close), [57, 60, 64]);
+        assert_eq!(keys("Cmaj7", Voicing::Close), [48, 52, 55, 59]);
+        assert_eq!(keys("F#m7b5", Voicing::Close), [54, 57, 60, 64]);
+        assert_eq!(keys("Bb7", Voicing::Close), [58, 62, 65, 68]);
+        // The slash bass goes under the whole chord
+        assert_eq!(keys("G/B", Voicing::Close), [47, 55, 59, 62]);
+        assert!("Cat".parse::<Chord>().is_err());
+        assert!("H7".parse::<Chord>().is_err());
+        assert!("C/x".parse::<Chord>().is_err());
+    }
+
+    #[test]
+    fn voicings_spread_the_notes() {
+        assert_eq!(keys("C", Voicing::Open), [36, 52, 55]);
+        assert_eq!(keys("Cmaj7", Voicing::Drop2), [43, 48, 52, 59]);
+        assert_eq!("Drop2".parse(), Ok(Voicing::Drop2));
+    }
+}
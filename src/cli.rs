@@ -0,0 +1,445 @@
+use std::{error::Error, fs, path::Path};
+
+use midly::{Smf, Timing};
+
+use crate::{
+    analysis::Timeline,
+    channel_map::ChannelMap,
+    compose::{Arrangement, Part},
+    diagnostics::{self, Diagnostic},
+    export::{
+        self,
+        clips::{self, Clip, ClipLayout},
+        summary::Summary,
+    },
+    grade::Grade,
+    import,
+    midi_action::{MidiAction, TimedAction},
+    note::{NameStyle, Pitch},
+    patch_map::PatchMap,
+    preset::Preset,
+    progress::Progress,
+    quantize::Quantizer,
+    session::{Recorder, Replay},
+    text_to_midi::{ParseError, ParseMode, Sheet, State},
+    transform,
+    validate::{self, Warning},
+};
+
+/// Texto de ajuda da linha de comando.
+const USAGE: &str = "usage:
+  tcp [--record-session <session.jsonl>] [--replay-session <session.jsonl>]
+                                          open the graphical interface, writing what is
+                                          done in it to a session journal, or doing again
+                                          what one recorded, at the same pace
+  tcp convert <input.txt> <output.mid> [--dump-events <events.json|events.csv>]
+                                          convert a text into a MIDI file
+  tcp clips [--patterns] <output.mid> <input.txt>...
+                                          convert several texts into one MIDI file, each
+                                          starting at a marker named after its file
+                                          (--patterns: one independent track each, format 2)
+  tcp compose <output.mid> <input.txt|input.mid>[@bar]...
+                                          put several texts or MIDI files in one file,
+                                          one track each, starting at the given bar
+                                          (without @bar, right after the previous ones;
+                                          parts at the same bar are layered)
+  tcp import <events.json> <output.mid>   convert a JSON event list into a MIDI file
+  tcp midicsv <input.mid> <output.csv>    write a MIDI file as midicsv text, one event
+                                          per line, to edit it in a spreadsheet
+  tcp csvmidi <input.csv> <output.mid>    convert midicsv text back into a MIDI file
+  tcp stems <input.txt|input.mid> <directory>
+                                          write one MIDI file per channel of each track
+  tcp validate <file.mid>                 check a MIDI file for problems
+  tcp check [--strict] <input.txt>        convert a text without writing anything, listing
+                                          its problems, its notes and how long it plays;
+                                          fails if there are problems (--strict: also
+                                          characters that mean nothing)
+  tcp grade <reference.mid> <submission.mid>
+                                          compare a student's file with a reference: the
+                                          order of notes and tempo changes, not their exact
+                                          timing, with a similarity score and a diff
+  tcp quantize <input.mid> <output.mid> [grid] [strength%]
+                                          snap note starts to a grid (1/4..1/32)
+
+Any command also accepts --verbose, which writes what the converter and the player do
+to stderr. convert, clips, compose, import, csvmidi and stems also accept, for the
+written file:
+  --remap <from=to,...>                   move channels (1=10 sends channel 1 to 10,
+                                          all=1 sends every channel to 1)
+  --patch-map <patches.toml>              replace GM programs with a device's program
+                                          and bank (see src/patch_map.rs)
+convert, clips, stems and check also accept, for the texts:
+  --preset <preset.toml>                  convert with the instrument, BPM, note length
+                                          and transforms of a preset (see src/preset.rs)";
+
+/// Executa a linha de comando com os argumentos passados, sem o nome do programa.
+pub fn run(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut args = args.to_vec();
+    let preset = take_option(&mut args, "--preset")?.map_or(Ok(Preset::default()), Preset::load)?;
+    let device = Device {
+        remap: take_option(&mut args, "--remap")?
+            .map_or(Ok(ChannelMap::identity()), |spec| spec.parse())?,
+        patches: take_option(&mut args, "--patch-map")?
+            .map_or(Ok(PatchMap::default()), PatchMap::load)?,
+    };
+    match &args[..] {
+        [command, input, output] if command == "convert" => {
+            convert(input, output, None, &preset, &device)
+        }
+        [command, input, output, flag, events]
+            if command == "convert" && flag == "--dump-events" =>
+        {
+            convert(input, output, Some(events), &preset, &device)
+        }
+        [command, args @ ..] if command == "clips" => convert_clips(args, &preset, &device),
+        [command, output, inputs @ ..] if command == "compose" && !inputs.is_empty() => {
+            compose(output, inputs, &device)
+        }
+        [command, input, output] if command == "import" => import(input, output, &device),
+        [command, input, output] if command == "midicsv" => midicsv(input, output),
+        [command, input, output] if command == "csvmidi" => csvmidi(input, output, &device),
+        [command, input, directory] if command == "stems" => {
+            stems(input, directory, &preset, &device)
+        }
+        [command, file] if command == "validate" => validate(file),
+        [command, args @ ..] if command == "check" => check(args, &preset),
+        [command, reference, submission] if command == "grade" => grade(reference, submission),
+        [command, input, output, options @ ..] if command == "quantize" && options.len() <= 2 => {
+            quantize(input, output, options)
+        }
+        _ => Err(USAGE.into()),
+    }
+}
+
+/// O aparelho para onde vão os arquivos escritos: os canais e os programas que ele espera.
+struct Device {
+    remap: ChannelMap,
+    patches: PatchMap,
+}
+
+impl Device {
+    /// Troca os programas e os canais de todas as trilhas do arquivo.
+    fn apply(&self, smf: &mut Smf<'_>) {
+        for track in &mut smf.tracks {
+            self.patches.apply(track);
+            self.remap.apply(track);
+        }
+    }
+}
+
+/// Tira dos argumentos as opções do diário da sessão da interface: onde gravar o que
+/// for feito e o que reproduzir.
+pub fn take_session(
+    args: &mut Vec<String>,
+) -> Result<(Option<Recorder>, Option<Replay>), Box<dyn Error>> {
+    let recorder = take_option(args, "--record-session")?
+        .map(|file| Recorder::create(Path::new(&file)).map_err(|error| format!("{file}: {error}")))
+        .transpose()?;
+    let replay = take_option(args, "--replay-session")?
+        .map(|file| Replay::load(Path::new(&file)).map_err(|error| format!("{file}: {error}")))
+        .transpose()?;
+
+    Ok((recorder, replay))
+}
+
+/// Tira dos argumentos a opção dada e o seu valor, se houver.
+fn take_option(args: &mut Vec<String>, flag: &str) -> Result<Option<String>, Box<dyn Error>> {
+    let Some(index) = args.iter().position(|arg| arg == flag) else {
+        return Ok(None);
+    };
+    if index + 1 >= args.len() {
+        return Err(USAGE.into());
+    }
+    let value = args.remove(index + 1);
+    args.remove(index);
+
+    Ok(Some(value))
+}
+
+/// Converte um arquivo de texto em um arquivo MIDI, informando os avisos encontrados.
+///
+/// Se `events` for dado, também escreve nele a lista de eventos com seus tempos.
+fn convert(
+    input: &str,
+    output: &str,
+    events: Option<&str>,
+    preset: &Preset,
+    device: &Device,
+) -> Result<(), Box<dyn Error>> {
+    let timed = text_actions(fs::read_to_string(input)?, preset)?;
+    let mut smf = TimedAction::as_track(&timed);
+
+    report(&validate::validate_smf(&smf));
+
+    device.apply(&mut smf);
+    smf.save(output)?;
+    if let Some(events) = events {
+        export::events::save(&timed, Path::new(events))?;
+    }
+    Ok(())
+}
+
+/// As ações de um texto, com as configurações e as transformações do preset,
+/// informando os avisos encontrados.
+fn text_actions(text: String, preset: &Preset) -> Result<Vec<TimedAction>, Box<dyn Error>> {
+    report(&validate::validate_header(&text));
+    let actions = preset.transform(preset.sheet(&text)?.process());
+    report(&validate::validate_actions(&actions));
+
+    Ok(preset.transform_timed(TimedAction::from_actions(&actions)))
+}
+
+/// Converte vários textos em um só arquivo MIDI, com um trecho nomeado para cada um.
+fn convert_clips(args: &[String], preset: &Preset, device: &Device) -> Result<(), Box<dyn Error>> {
+    let (layout, args) = match args {
+        [flag, args @ ..] if flag == "--patterns" => (ClipLayout::Patterns, args),
+        _ => (ClipLayout::Sequential, args),
+    };
+    let [output, inputs @ ..] = args else {
+        return Err(USAGE.into());
+    };
+    if inputs.is_empty() {
+        return Err(USAGE.into());
+    }
+
+    let mut clips = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        let name = Path::new(input).file_stem().unwrap_or_default();
+        clips.push(Clip {
+            name: name.to_string_lossy().into_owned(),
+            actions: text_actions(fs::read_to_string(input)?, preset)?,
+        });
+    }
+    let mut smf = clips::container(&clips, layout);
+    report(&validate::validate_smf(&smf));
+
+    device.apply(&mut smf);
+    smf.save(output)?;
+    Ok(())
+}
+
+/// Junta textos e arquivos MIDI em um arquivo de várias trilhas, cada um no seu compasso.
+fn compose(output: &str, inputs: &[String], device: &Device) -> Result<(), Box<dyn Error>> {
+    let mut arrangement = Arrangement::default();
+    for input in inputs {
+        let (path, bar) = match input.rsplit_once('@') {
+            Some((path, bar)) => (path, Some(bar.parse::<u32>()?)),
+            None => (input.as_str(), None),
+        };
+        let mut part = Part::load(Path::new(path))?;
+        match bar {
+            Some(bar) => {
+                part.start_bar = bar.max(1);
+                arrangement.parts.push(part);
+            }
+            None => arrangement.append(part),
+        }
+    }
+    let mut smf = arrangement.to_smf();
+    report(&validate::validate_smf(&smf));
+
+    device.apply(&mut smf);
+    smf.save(output)?;
+    Ok(())
+}
+
+/// Converte uma lista de eventos em JSON, como a do `--dump-events`, em um arquivo MIDI.
+fn import(input: &str, output: &str, device: &Device) -> Result<(), Box<dyn Error>> {
+    let json = fs::read_to_string(input)?;
+    let actions = import::events::from_json(&json)?;
+    let mut smf = TimedAction::as_track(&actions);
+
+    report(&validate::validate_smf(&smf));
+
+    device.apply(&mut smf);
+    smf.save(output)?;
+    Ok(())
+}
+
+/// Escreve um arquivo MIDI no formato de texto do midicsv.
+fn midicsv(input: &str, output: &str) -> Result<(), Box<dyn Error>> {
+    let bytes = fs::read(input)?;
+    let smf = Smf::parse(&bytes)?;
+    fs::write(output, export::midicsv::to_csv(&smf))?;
+    Ok(())
+}
+
+/// Converte um texto no formato do midicsv, como um editado em uma planilha, em um
+/// arquivo MIDI.
+fn csvmidi(input: &str, output: &str, device: &Device) -> Result<(), Box<dyn Error>> {
+    let csv = fs::read_to_string(input)?;
+    let bytes = import::midicsv::from_csv(&csv)?;
+    let mut smf = Smf::parse(&bytes)?;
+
+    report(&validate::validate_smf(&smf));
+
+    device.apply(&mut smf);
+    smf.save(output)?;
+    Ok(())
+}
+
+/// Salva um arquivo MIDI por canal de um texto, ou de um arquivo MIDI, na pasta dada.
+fn stems(
+    input: &str,
+    directory: &str,
+    preset: &Preset,
+    device: &Device,
+) -> Result<(), Box<dyn Error>> {
+    let bytes;
+    let timed;
+    let mut smf = if Path::new(input)
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("mid"))
+    {
+        bytes = fs::read(input)?;
+        Smf::parse(&bytes)?
+    } else {
+        timed = text_actions(fs::read_to_string(input)?, preset)?;
+        TimedAction::as_track(&timed)
+    };
+
+    device.apply(&mut smf);
+    for file in export::stems::save(&smf, Path::new(directory))? {
+        println!("{}", file.display());
+    }
+    Ok(())
+}
+
+/// Valida um arquivo MIDI já existente.
+fn validate(file: &str) -> Result<(), Box<dyn Error>> {
+    let bytes = fs::read(file)?;
+    let smf = Smf::parse(&bytes)?;
+    let warnings = validate::validate_smf(&smf);
+
+    if warnings.is_empty() {
+        println!("{file}: no problems found");
+    }
+    report(&warnings);
+
+    Ok(())
+}
+
+/// Converte um texto sem escrever nada, imprimindo os problemas, com a linha e a coluna
+/// de cada um, e um resumo das notas. Falha se houver problemas, para servir em scripts.
+///
+/// Os caracteres ignorados também são impressos, mas só são problemas com `--strict`.
+fn check(args: &[String], preset: &Preset) -> Result<(), Box<dyn Error>> {
+    let (mode, input) = match args {
+        [input] => (ParseMode::Lenient, input),
+        [flag, input] | [input, flag] if flag == "--strict" => (ParseMode::Strict, input),
+        _ => return Err(USAGE.into()),
+    };
+    let text = fs::read_to_string(input)?;
+    let parsed = match preset
+        .builder()
+        .mode(mode)
+        .text(&text)
+        .build()?
+        .parse(&Progress::default())
+    {
+        Ok(parsed) => parsed,
+        Err(ParseError::Unrecognized(unrecognized)) => {
+            print_diagnostics(input, &text, &diagnostics::from_unrecognized(&unrecognized));
+            return Err(ParseError::Unrecognized(unrecognized).into());
+        }
+        Err(error) => return Err(error.into()),
+    };
+
+    let actions = preset.transform(parsed.actions.clone());
+    let sources = transform::carry_sources(&parsed.actions, &parsed.sources, &actions);
+    let timed = preset.transform_timed(TimedAction::from_sourced_actions(&actions, &sources));
+    let smf = TimedAction::as_track(&timed);
+
+    let mut diagnostics = diagnostics::from_header(&text);
+    diagnostics.extend(diagnostics::from_warnings(
+        &validate::validate_actions(&actions),
+        &sources,
+    ));
+    let problems = diagnostics.len();
+    diagnostics.extend(diagnostics::from_unrecognized(&parsed.unrecognized));
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+    print_diagnostics(input, &text, &diagnostics);
+    let warnings = validate::validate_smf(&smf);
+    report(&warnings);
+
+    let statistics = Timeline::from_smf(&smf).statistics();
+    let summary = Summary::of(&smf);
+    let range = statistics.range.map_or("-".to_string(), |(low, high)| {
+        let name = |key| {
+            Pitch::from_midi(key).map_or(key.to_string(), |pitch| pitch.name(NameStyle::Letter))
+        };
+        format!("{}..{}", name(low), name(high))
+    });
+    let seconds = summary.duration.as_secs_f64();
+    println!("notes: {} ({range})", statistics.notes);
+    println!("channels: {}", statistics.channels);
+    println!("bars: {}", statistics.bars);
+    println!("tempo changes: {}", summary.tempo_events);
+    println!(
+        "estimated duration: {}:{:04.1}",
+        (seconds / 60.0) as u64,
+        seconds % 60.0
+    );
+
+    let problems = problems + warnings.len();
+    if problems > 0 {
+        return Err(format!("{input}: problems found: {problems}").into());
+    }
+    Ok(())
+}
+
+/// Imprime os diagnósticos de um texto na saída de erro, como os dos compiladores.
+fn print_diagnostics(input: &str, text: &str, diagnostics: &[Diagnostic]) {
+    for diagnostic in diagnostics {
+        let (line, column) = diagnostic.position(text);
+        eprintln!("{input}:{line}:{column}: {}", diagnostic.message);
+    }
+}
+
+/// Compara o arquivo MIDI de um aluno com o de referência, imprimindo as diferenças e a
+/// nota de semelhança.
+fn grade(reference: &str, submission: &str) -> Result<(), Box<dyn Error>> {
+    let (reference_bytes, submission_bytes) = (fs::read(reference)?, fs::read(submission)?);
+    let grade = Grade::compare(
+        &Smf::parse(&reference_bytes)?,
+        &Smf::parse(&submission_bytes)?,
+    );
+
+    print!("{}", grade.report(2));
+    println!(
+        "matched: {} of {} (played {}, octave errors {})",
+        grade.matched, grade.expected, grade.played, grade.octave_errors
+    );
+    println!("score: {:.1}%", grade.score());
+    Ok(())
+}
+
+/// Quantiza as notas de um arquivo MIDI, salvando o resultado em outro.
+fn quantize(input: &str, output: &str, options: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut quantizer = Quantizer::default();
+    if let Some(grid) = options.first() {
+        quantizer.grid = grid.parse()?;
+    }
+    if let Some(strength) = options.get(1) {
+        quantizer.strength = strength.trim_end_matches('%').parse()?;
+    }
+
+    let bytes = fs::read(input)?;
+    let mut smf = Smf::parse(&bytes)?;
+    let Timing::Metrical(tpqn) = smf.header.timing else {
+        return Err("only files with metrical timing can be quantized".into());
+    };
+    for track in &mut smf.tracks {
+        quantizer.quantize(track, tpqn);
+    }
+
+    smf.save(output)?;
+    Ok(())
+}
+
+/// Imprime os avisos na saída de erro.
+fn report(warnings: &[Warning]) {
+    for warning in warnings {
+        eprintln!("warning: {warning}");
+    }
+}
@@ -0,0 +1,106 @@
+use std::{error::Error, fmt};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use midly::Smf;
+
+/// Erro ao ler um MIDI da área de transferência.
+#[derive(Debug)]
+pub enum PasteError {
+    /// O texto não é base64 válido.
+    NotBase64(base64::DecodeError),
+    /// Os bytes não são um arquivo MIDI válido.
+    NotMidi(midly::Error),
+}
+
+impl fmt::Display for PasteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotBase64(error) => write!(f, "the clipboard is not base64: {error}"),
+            Self::NotMidi(error) => write!(f, "the clipboard is not a MIDI file: {error}"),
+        }
+    }
+}
+
+impl Error for PasteError {}
+
+/// Serializa o arquivo MIDI em base64, para ser copiado como texto.
+pub fn encode(smf: &Smf<'_>) -> String {
+    let mut bytes = Vec::new();
+    smf.write_std(&mut bytes)
+        .expect("writing to a vector never fails");
+
+    STANDARD.encode(bytes)
+}
+
+/// Lê os bytes de um arquivo MIDI em base64, verificando se são válidos.
+///
+/// Espaços e quebras de linha são ignorados, pois outras ferramentas costumam
+/// quebrar textos longos.
+pub fn decode(text: &str) -> Result<Vec<u8>, PasteError> {
+    let text: String = text.split_whitespace().collect();
+    let bytes = STANDARD.decode(text).map_err(PasteError::NotBase64)?;
+    Smf::parse(&bytes).map_err(PasteError::NotMidi)?;
+
+    Ok(bytes)
+}
+
+/// Copia o texto para a área de transferência do sistema.
+#[cfg(feature = "native")]
+pub fn copy(text: String) -> Result<(), Box<dyn Error>> {
+    arboard::Clipboard::new()?.set_text(text)?;
+    Ok(())
+}
+
+/// O texto atual da área de transferência do sistema.
+#[cfg(feature = "native")]
+pub fn paste() -> Result<String, Box<dyn Error>> {
+    Ok(arboard::Clipboard::new()?.get_text()?)
+}
+
+/// Sem a feature `native` não há acesso à área de transferência do sistema.
+#[cfg(not(feature = "native"))]
+pub fn copy(_text: String) -> Result<(), Box<dyn Error>> {
+    Err("the clipboard is not available in this build".into())
+}
+
+#[cfg(not(feature = "native"))]
+pub fn paste() -> Result<String, Box<dyn Error>> {
+    Err("the clipboard is not available in this build".into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        midi_action::MidiAction,
+        text_to_midi::{Sheet, State},
+    };
+
+    #[test]
+    fn round_trip() {
+        let actions = Sheet::builder().text("CDE").build().unwrap().process();
+        let smf = MidiAction::as_track(&actions);
+
+        let text = encode(&smf);
+        let wrapped: String = text
+            .as_bytes()
+            .chunks(20)
+            .map(|line| format!("{}\n", std::str::from_utf8(line).unwrap()))
+            .collect();
+        let bytes = decode(&wrapped).unwrap();
+
+        assert_eq!(Smf::parse(&bytes).unwrap(), smf);
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert!(matches!(
+            decode("not base64!"),
+            Err(PasteError::NotBase64(_))
+        ));
+        assert!(matches!(
+            decode(&STANDARD.encode("MThd")),
+            Err(PasteError::NotMidi(_))
+        ));
+    }
+}
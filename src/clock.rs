@@ -0,0 +1,180 @@
+use std::{
+    cell::Cell,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Abaixo deste tempo restante, a espera é feita ativamente em vez de dormir,
+/// pois o sistema operacional costuma acordar a thread com atraso.
+const SPIN_THRESHOLD: Duration = Duration::from_millis(1);
+
+/// Fonte de tempo usada pela reprodução.
+pub trait Clock {
+    /// Tempo decorrido desde o início do relógio.
+    fn now(&self) -> Duration;
+
+    /// Dorme por pelo menos o tempo dado.
+    fn sleep(&self, duration: Duration);
+
+    /// Espera até o instante dado, contado a partir do início do relógio.
+    ///
+    /// Dorme até faltar um milissegundo e então espera ativamente, de forma que
+    /// atrasos do sistema ao acordar não se acumulem ao longo da música.
+    fn sleep_until(&self, deadline: Duration) {
+        loop {
+            let now = self.now();
+            if now >= deadline {
+                return;
+            }
+
+            let remaining = deadline - now;
+            if remaining > SPIN_THRESHOLD {
+                self.sleep(remaining - SPIN_THRESHOLD);
+            } else {
+                std::hint::spin_loop();
+            }
+        }
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for &C {
+    fn now(&self) -> Duration {
+        (**self).now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        (**self).sleep(duration)
+    }
+
+    fn sleep_until(&self, deadline: Duration) {
+        (**self).sleep_until(deadline)
+    }
+}
+
+/// Relógio real do sistema.
+pub struct SystemClock {
+    /// O início do relógio.
+    start: Instant,
+}
+
+impl SystemClock {
+    /// Cria um relógio começando agora.
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+/// Relógio que não espera de verdade: cada espera só avança o tempo.
+///
+/// Serve para destinos que agendam as mensagens por conta própria, como a Web MIDI API,
+/// em que o player só precisa saber o instante de cada evento.
+#[derive(Debug, Default)]
+pub struct ScheduledClock {
+    /// O instante do evento sendo enviado.
+    now: Cell<Duration>,
+}
+
+impl Clock for ScheduledClock {
+    fn now(&self) -> Duration {
+        self.now.get()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.now.set(self.now.get() + duration);
+    }
+
+    fn sleep_until(&self, deadline: Duration) {
+        self.now.set(self.now.get().max(deadline));
+    }
+}
+
+#[cfg(test)]
+pub mod mock {
+    use super::*;
+
+    /// Relógio falso para testes, que simula um sistema que sempre acorda atrasado.
+    pub struct MockClock {
+        /// O tempo atual.
+        now: Cell<Duration>,
+        /// Quanto cada `sleep` passa do tempo pedido.
+        oversleep: Duration,
+    }
+
+    impl MockClock {
+        /// Cria um relógio em que cada `sleep` atrasa `oversleep`.
+        pub fn new(oversleep: Duration) -> Self {
+            Self {
+                now: Cell::new(Duration::ZERO),
+                oversleep,
+            }
+        }
+    }
+
+    impl Clock for MockClock {
+        fn now(&self) -> Duration {
+            // Consultar o relógio também leva tempo, senão a espera ativa nunca terminaria.
+            let now = self.now.get();
+            self.now.set(now + Duration::from_micros(1));
+            now
+        }
+
+        fn sleep(&self, duration: Duration) {
+            self.now.set(self.now.get() + duration + self.oversleep);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{mock::MockClock, *};
+
+    #[test]
+    fn sleep_until_does_not_overshoot() {
+        let clock = MockClock::new(Duration::from_micros(700));
+
+        clock.sleep_until(Duration::from_millis(10));
+
+        let now = clock.now();
+        assert!(now >= Duration::from_millis(10));
+        assert!(now < Duration::from_millis(10) + Duration::from_micros(10));
+    }
+
+    #[test]
+    fn scheduled_clock_jumps_to_deadline() {
+        let clock = ScheduledClock::default();
+
+        clock.sleep_until(Duration::from_millis(10));
+        clock.sleep_until(Duration::from_millis(5));
+        clock.sleep(Duration::from_millis(1));
+
+        assert_eq!(clock.now(), Duration::from_millis(11));
+    }
+
+    #[test]
+    fn past_deadline_returns_immediately() {
+        let clock = MockClock::new(Duration::ZERO);
+        clock.sleep(Duration::from_secs(1));
+
+        clock.sleep_until(Duration::from_millis(10));
+
+        assert!(clock.now() < Duration::from_secs(1) + Duration::from_micros(10));
+    }
+}
@@ -0,0 +1,254 @@
+//! Composição: várias músicas, de arquivos MIDI ou de textos, juntas em um arquivo de
+//! várias trilhas, cada uma começando no compasso escolhido.
+//!
+//! Partes que começam no mesmo compasso tocam juntas, em camadas. Os compassos são
+//! sempre de 4/4, como os dos arquivos gerados pelo texto.
+
+use std::{error::Error, fs, path::Path};
+
+use midly::{
+    num::u15, Format, Header, MetaMessage, Smf, Timing, Track, TrackEvent, TrackEventKind,
+};
+
+use crate::{
+    midi_action::{MidiAction, TimedAction},
+    text_to_midi::Sheet,
+    ticks,
+};
+
+/// Uma música do arranjo.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Part {
+    /// O nome, que vira o nome da trilha quando a parte só tem uma.
+    pub name: String,
+    /// O compasso onde a parte começa, contado a partir de 1.
+    pub start_bar: u32,
+    /// O arquivo MIDI da parte.
+    bytes: Vec<u8>,
+}
+
+impl Part {
+    /// Uma parte a partir de um arquivo MIDI já lido, recusando arquivos inválidos ou
+    /// com tempo em SMPTE, que não têm compassos.
+    pub fn from_bytes(name: impl Into<String>, bytes: Vec<u8>) -> Result<Self, Box<dyn Error>> {
+        if let Timing::Timecode(..) = Smf::parse(&bytes)?.header.timing {
+            return Err("only files with metrical timing can be composed".into());
+        }
+
+        Ok(Self {
+            name: name.into(),
+            start_bar: 1,
+            bytes,
+        })
+    }
+
+    /// Uma parte a partir de ações já posicionadas no tempo.
+    pub fn from_timed(name: impl Into<String>, timed: &[TimedAction]) -> Self {
+        let mut bytes = Vec::new();
+        TimedAction::as_track(timed)
+            .write_std(&mut bytes)
+            .expect("writing to a vector never fails");
+
+        Self {
+            name: name.into(),
+            start_bar: 1,
+            bytes,
+        }
+    }
+
+    /// Lê uma parte de um arquivo `.mid`, ou de um texto, convertido com o estado padrão.
+    ///
+    /// O nome da parte é o do arquivo, sem a extensão.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        let is_midi = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("mid"));
+        if is_midi {
+            return Self::from_bytes(name, fs::read(path)?);
+        }
+
+        let actions = Sheet::builder()
+            .text(fs::read_to_string(path)?)
+            .build()?
+            .process();
+        Ok(Self::from_timed(name, &TimedAction::from_actions(&actions)))
+    }
+
+    /// O arquivo MIDI da parte.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// As trilhas da parte, com os ticks passados para o TPQN dos arquivos gerados e
+    /// atrasados até o compasso de início.
+    fn tracks(&self) -> Vec<Track<'_>> {
+        let smf = Smf::parse(&self.bytes).expect("parts are checked when created");
+        let Timing::Metrical(tpqn) = smf.header.timing else {
+            unreachable!("parts are checked when created")
+        };
+        let offset = (self.start_bar.max(1) - 1) as u64 * bar_ticks();
+        let single = smf.tracks.len() == 1;
+
+        smf.tracks
+            .into_iter()
+            .map(|track| {
+                let mut tick = 0;
+                let mut last = 0;
+                let mut moved = Track::with_capacity(track.len());
+                for event in track {
+                    tick += event.delta.as_int() as u64;
+                    let at = offset + rescale(tick, tpqn);
+                    let kind = match event.kind {
+                        TrackEventKind::Meta(MetaMessage::TrackName(_)) if single => {
+                            TrackEventKind::Meta(MetaMessage::TrackName(self.name.as_bytes()))
+                        }
+                        kind => kind,
+                    };
+                    moved.push(TrackEvent {
+                        delta: u32::try_from(at - last)
+                            .unwrap_or(u32::MAX)
+                            .min(0x0FFF_FFFF)
+                            .into(),
+                        kind,
+                    });
+                    last = at;
+                }
+                moved
+            })
+            .collect()
+    }
+
+    /// Quantos compassos a parte ocupa, arredondando para cima, e pelo menos um.
+    pub fn bars(&self) -> u32 {
+        let smf = Smf::parse(&self.bytes).expect("parts are checked when created");
+        let Timing::Metrical(tpqn) = smf.header.timing else {
+            unreachable!("parts are checked when created")
+        };
+        let ticks = smf
+            .tracks
+            .iter()
+            .map(|track| track.iter().map(|event| event.delta.as_int() as u64).sum())
+            .max()
+            .unwrap_or(0);
+
+        rescale(ticks, tpqn).div_ceil(bar_ticks()).max(1) as u32
+    }
+
+    /// O compasso logo depois do fim da parte.
+    pub fn end_bar(&self) -> u32 {
+        self.start_bar.max(1) + self.bars()
+    }
+}
+
+/// As partes de uma composição.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Arrangement {
+    pub parts: Vec<Part>,
+}
+
+impl Arrangement {
+    /// Acrescenta a parte começando logo depois da que termina por último.
+    pub fn append(&mut self, mut part: Part) {
+        part.start_bar = self.parts.iter().map(Part::end_bar).max().unwrap_or(1);
+        self.parts.push(part);
+    }
+
+    /// O arquivo com as trilhas de todas as partes (formato 1).
+    ///
+    /// Os eventos de tempo de cada parte continuam nas suas trilhas, então partes em
+    /// camadas com andamentos diferentes disputam o andamento.
+    pub fn to_smf(&self) -> Smf<'_> {
+        Smf {
+            header: Header::new(Format::Parallel, Timing::Metrical(MidiAction::D_TPQN)),
+            tracks: self.parts.iter().flat_map(Part::tracks).collect(),
+        }
+    }
+}
+
+/// Ticks em um compasso de 4/4 com o `MidiAction::D_TPQN`.
+fn bar_ticks() -> u64 {
+    MidiAction::D_TPQN.as_int() as u64 * 4
+}
+
+/// Um tick de um arquivo com o TPQN dado, no TPQN dos arquivos gerados, arredondado
+/// para o mais próximo.
+fn rescale(tick: u64, tpqn: u15) -> u64 {
+    ticks::scale(
+        tick,
+        MidiAction::D_TPQN.as_int() as u64,
+        tpqn.as_int() as u64,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// O tick absoluto da primeira nota de cada trilha.
+    fn first_notes(smf: &Smf) -> Vec<u64> {
+        smf.tracks
+            .iter()
+            .filter_map(|track| {
+                let mut tick = 0;
+                track.iter().find_map(|event| {
+                    tick += event.delta.as_int() as u64;
+                    matches!(event.kind, TrackEventKind::Midi { .. }).then_some(tick)
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parts_start_at_their_bars() {
+        let notes = |count| TimedAction::from_actions(&vec![MidiAction::PlayNote(60); count]);
+        let mut arrangement = Arrangement::default();
+        arrangement.append(Part::from_timed("Intro", &notes(5)));
+        arrangement.append(Part::from_timed("Verse", &notes(1)));
+        let mut layer = Part::from_timed("Pad", &notes(1));
+        layer.start_bar = 3;
+        arrangement.parts.push(layer);
+
+        // Five beats take two bars, so the verse starts on bar 3, with the pad on top
+        assert_eq!(arrangement.parts[1].start_bar, 3);
+        let smf = arrangement.to_smf();
+        assert_eq!(smf.header.format, Format::Parallel);
+        assert_eq!(first_notes(&smf), [0, 2 * 1920, 2 * 1920]);
+        assert!(smf.tracks[1]
+            .iter()
+            .any(|event| event.kind == TrackEventKind::Meta(MetaMessage::TrackName(b"Verse"))));
+    }
+
+    #[test]
+    fn other_resolutions_are_rescaled() {
+        let mut smf = Smf::new(Header::new(
+            Format::SingleTrack,
+            Timing::Metrical(96.into()),
+        ));
+        smf.tracks.push(vec![
+            TrackEvent {
+                delta: 96.into(),
+                kind: TrackEventKind::Midi {
+                    channel: 0.into(),
+                    message: midly::MidiMessage::NoteOn {
+                        key: 60.into(),
+                        vel: 100.into(),
+                    },
+                },
+            },
+            TrackEvent {
+                delta: 0.into(),
+                kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+            },
+        ]);
+        let mut bytes = Vec::new();
+        smf.write_std(&mut bytes).unwrap();
+        let mut part = Part::from_bytes("Loop", bytes).unwrap();
+        part.start_bar = 2;
+
+        let arrangement = Arrangement { parts: vec![part] };
+
+        assert_eq!(first_notes(&arrangement.to_smf()), [1920 + 480]);
+        assert_eq!(arrangement.parts[0].bars(), 1);
+    }
+}
@@ -0,0 +1,226 @@
+use std::{
+    ops::Range,
+    path::PathBuf,
+    sync::{
+        mpsc::{self, Receiver, TryRecvError},
+        Arc,
+    },
+    thread,
+};
+
+use crate::{
+    diagnostics::{self, Diagnostic},
+    midi_action::{MidiAction, TimedAction},
+    progress::Progress,
+    text_to_midi::{ParseError, Parsed, Sheet},
+    transform,
+    validate::{self, Warning},
+};
+
+/// O que fazer com o resultado de uma conversão.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Purpose {
+    /// Tocar a música.
+    Play,
+    /// Salvar o arquivo MIDI no caminho dado.
+    Save(PathBuf),
+    /// Copiar o arquivo MIDI para a área de transferência.
+    Copy,
+    /// Salvar a lista de eventos no caminho dado.
+    DumpEvents(PathBuf),
+    /// Salvar um arquivo MIDI por canal na pasta dada.
+    Stems(PathBuf),
+}
+
+/// O resultado de uma conversão terminada.
+#[derive(Debug)]
+pub struct Converted {
+    /// Para que a conversão foi pedida.
+    pub purpose: Purpose,
+    /// As ações geradas, com as transformações já aplicadas.
+    pub actions: Vec<MidiAction>,
+    /// O arquivo MIDI gerado a partir das ações posicionadas no tempo, serializado.
+    pub smf: Vec<u8>,
+    /// Os avisos das ações e do arquivo.
+    pub warnings: Vec<Warning>,
+    /// Os avisos que vieram de um trecho do texto, com o trecho, e os caracteres ignorados.
+    pub diagnostics: Vec<Diagnostic>,
+    /// O tick em que cada nota ou pausa começa, com o trecho do texto que a gerou.
+    pub notes: Vec<(u64, Range<usize>)>,
+}
+
+/// Uma conversão de texto em MIDI rodando em outra thread.
+///
+/// Processar um texto grande e montar o seu arquivo pode levar segundos, então
+/// nada disso é feito na thread da interface: o resultado chega por um canal.
+pub struct Job {
+    /// O andamento, compartilhado com a thread da conversão.
+    progress: Arc<Progress>,
+    /// Por onde o resultado chega.
+    receiver: Receiver<Result<Converted, ParseError>>,
+}
+
+impl Job {
+    /// Começa a converter a partitura, aplicando `transforms` às ações geradas.
+    ///
+    /// O arquivo é montado a partir das ações posicionadas no tempo, depois de passarem
+    /// por `timed`, que pode acrescentar vozes sobrepostas, como o eco e a harmonia.
+    pub fn spawn(
+        sheet: Sheet,
+        transforms: impl FnOnce(Vec<MidiAction>) -> Vec<MidiAction> + Send + 'static,
+        timed: impl FnOnce(Vec<TimedAction>) -> Vec<TimedAction> + Send + 'static,
+        purpose: Purpose,
+    ) -> Self {
+        let progress = Arc::new(Progress::default());
+        let (sender, receiver) = mpsc::channel();
+
+        let worker_progress = Arc::clone(&progress);
+        let work = move || {
+            let result = sheet.parse(&worker_progress).map(|parsed| {
+                let Parsed {
+                    actions,
+                    sources,
+                    unrecognized,
+                } = parsed;
+                let transformed = transforms(actions.clone());
+                let sources = transform::carry_sources(&actions, &sources, &transformed);
+                let mut converted = Self::finish(transformed, &sources, timed, purpose);
+                converted
+                    .diagnostics
+                    .extend(diagnostics::from_unrecognized(&unrecognized));
+                converted
+            });
+            // A interface pode ter desistido da conversão, e isso não é um erro
+            let _ = sender.send(result);
+        };
+
+        // Não há threads no navegador, então lá a conversão é feita na hora
+        #[cfg(not(target_arch = "wasm32"))]
+        thread::spawn(work);
+        #[cfg(target_arch = "wasm32")]
+        work();
+
+        Self { progress, receiver }
+    }
+
+    /// O tick de cada nota ou pausa da melodia, com o seu trecho do texto, em ordem.
+    fn note_sources(timed: &[TimedAction]) -> Vec<(u64, Range<usize>)> {
+        let mut notes: Vec<(u64, Range<usize>)> = Vec::new();
+        let melody = timed.iter().filter(|timed| {
+            timed.channel == 0
+                && matches!(timed.action, MidiAction::PlayNote(_) | MidiAction::Pause)
+        });
+        for timed in melody {
+            // As repetições do eco vêm logo depois da nota, com o mesmo trecho
+            if notes
+                .last()
+                .is_some_and(|(_, source)| *source == timed.source_range)
+            {
+                continue;
+            }
+            notes.push((timed.tick, timed.source_range.clone()));
+        }
+        notes.sort_by_key(|&(tick, _)| tick);
+
+        notes
+    }
+
+    /// Monta e valida o arquivo a partir das ações e dos seus trechos do texto.
+    fn finish(
+        actions: Vec<MidiAction>,
+        sources: &[Range<usize>],
+        timed: impl FnOnce(Vec<TimedAction>) -> Vec<TimedAction>,
+        purpose: Purpose,
+    ) -> Converted {
+        let timed = timed(TimedAction::from_sourced_actions(&actions, sources));
+        let notes = Self::note_sources(&timed);
+        let file = TimedAction::as_track(&timed);
+        let mut warnings = validate::validate_actions(&actions);
+        let diagnostics = diagnostics::from_warnings(&warnings, sources);
+        warnings.extend(validate::validate_smf(&file));
+
+        let mut smf = Vec::new();
+        file.write_std(&mut smf)
+            .expect("writing to a vector never fails");
+
+        Converted {
+            purpose,
+            actions,
+            smf,
+            warnings,
+            diagnostics,
+            notes,
+        }
+    }
+
+    /// O andamento da conversão.
+    pub fn progress(&self) -> &Progress {
+        &self.progress
+    }
+
+    /// O resultado, se a conversão já terminou.
+    ///
+    /// No modo estrito, um texto com caracteres que não significam nada é um erro.
+    pub fn try_finish(&self) -> Option<Result<Converted, ParseError>> {
+        match self.receiver.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(ParseError::Cancelled)),
+        }
+    }
+}
+
+impl Drop for Job {
+    /// Uma conversão abandonada não precisa continuar gastando processamento.
+    fn drop(&mut self) {
+        self.progress.cancel();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    /// Espera a conversão terminar.
+    fn wait(job: &Job) -> Result<Converted, ParseError> {
+        let start = Instant::now();
+        loop {
+            if let Some(result) = job.try_finish() {
+                return result;
+            }
+            assert!(start.elapsed() < Duration::from_secs(10), "conversion hung");
+            thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn converts_in_background() {
+        let sheet = Sheet::builder().text("CDE").build().unwrap();
+
+        let job = Job::spawn(sheet, |actions| actions, |timed| timed, Purpose::Copy);
+        let converted = wait(&job).unwrap();
+
+        assert_eq!(converted.purpose, Purpose::Copy);
+        assert_eq!(converted.actions.len(), 6);
+        assert!(converted.warnings.is_empty());
+        assert!(midly::Smf::parse(&converted.smf).is_ok());
+        assert_eq!(job.progress().percent(), 100);
+        assert_eq!(converted.notes, [(0, 0..1), (480, 1..2), (960, 2..3)]);
+    }
+
+    #[test]
+    fn transforms_are_applied() {
+        let sheet = Sheet::builder().text("C").build().unwrap();
+
+        let job = Job::spawn(
+            sheet,
+            |_| vec![MidiAction::Pause],
+            |timed| timed,
+            Purpose::Play,
+        );
+
+        assert_eq!(wait(&job).unwrap().actions, [MidiAction::Pause]);
+    }
+}
@@ -0,0 +1,121 @@
+//! Diagnósticos: os problemas de um texto, cada um com o trecho que o causou, para o
+//! editor destacar onde eles estão.
+
+use std::{fmt, ops::Range};
+
+use crate::{header::Header, text_to_midi::Unrecognized, validate::Warning};
+
+/// Um problema do texto e o trecho dele, em bytes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// O trecho do texto que causou o problema.
+    pub span: Range<usize>,
+    /// O que há de errado.
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "bytes {}..{}: {}",
+            self.span.start, self.span.end, self.message
+        )
+    }
+}
+
+impl Diagnostic {
+    /// A linha e a coluna do início do trecho no texto dado, contadas a partir de 1,
+    /// com a coluna em caracteres, como mostram os editores.
+    pub fn position(&self, text: &str) -> (usize, usize) {
+        let before = text.get(..self.span.start).unwrap_or(text);
+        let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+
+        (
+            before.matches('\n').count() + 1,
+            before[line_start..].chars().count() + 1,
+        )
+    }
+}
+
+/// Os diagnósticos dos avisos das ações que vieram de um trecho do texto, dados os
+/// trechos de cada ação, como os do `Sheet::process_with_sources`.
+///
+/// Avisos do arquivo, e de ações sem trecho, como as geradas pelas transformações,
+/// ficam de fora.
+pub fn from_warnings(warnings: &[Warning], sources: &[Range<usize>]) -> Vec<Diagnostic> {
+    warnings
+        .iter()
+        .filter_map(|warning| {
+            let span = sources.get(warning.action()?)?;
+            (!span.is_empty()).then(|| Diagnostic {
+                span: span.clone(),
+                message: warning.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Os diagnósticos das entradas inválidas do cabeçalho do texto, cada uma com o
+/// trecho da entrada, do `@` ao fim do valor.
+pub fn from_header(text: &str) -> Vec<Diagnostic> {
+    let (header, body) = Header::parse(text);
+    let head = &text[..text.len() - body.len()];
+    let mut from = 0;
+
+    header
+        .errors
+        .into_iter()
+        .filter_map(|error| {
+            // As entradas com erro aparecem em ordem no cabeçalho
+            let start = from + head[from..].find(&error.entry)?;
+            from = start + error.entry.len();
+            let at = head[..start].rfind('@').unwrap_or(start);
+            Some(Diagnostic {
+                span: at..from,
+                message: error.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Os diagnósticos dos caracteres que não significam nada, ignorados pela partitura.
+pub fn from_unrecognized(unrecognized: &[Unrecognized]) -> Vec<Diagnostic> {
+    unrecognized
+        .iter()
+        .map(|unrecognized| Diagnostic {
+            span: unrecognized.span.clone(),
+            message: unrecognized.to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{midi_action::MidiAction, text_to_midi::Sheet, validate};
+
+    #[test]
+    fn problems_point_at_their_text() {
+        let text = "@bpm=0 @volume=70\nCX{F0 41}D";
+        let (actions, sources) = Sheet::builder()
+            .text(text)
+            .build()
+            .unwrap()
+            .process_with_sources(&Default::default())
+            .unwrap();
+        assert!(actions.contains(&MidiAction::SysEx(vec![0xF0, 0x41])));
+
+        let diagnostics = from_warnings(&validate::validate_actions(&actions), &sources);
+        let spans: Vec<&str> = diagnostics
+            .iter()
+            .map(|diagnostic| &text[diagnostic.span.clone()])
+            .collect();
+        assert_eq!(spans, ["X{F0 41}"]);
+
+        let header = from_header(text);
+        assert_eq!(header.len(), 1);
+        assert_eq!(&text[header[0].span.clone()], "@bpm=0");
+        assert_eq!(diagnostics[0].position(text), (2, 2));
+    }
+}
@@ -0,0 +1,138 @@
+//! Partituras de exemplo, embutidas no executável.
+
+use crate::i18n::Translated;
+
+/// Uma partitura de exemplo, com a explicação do que ela usa.
+pub struct Example {
+    /// O nome mostrado no menu.
+    pub name: Translated,
+    /// O texto da partitura.
+    pub text: &'static str,
+    /// Os comandos usados no texto, cada um com o que ele faz.
+    pub constructs: &'static [(&'static str, Translated)],
+}
+
+/// Todos os exemplos, na ordem do menu.
+pub const ALL: [Example; 3] = [
+    Example {
+        name: ["Scales", "Escalas"],
+        text: include_str!("../examples/scales.txt"),
+        constructs: &[
+            (
+                "CDEFGAB",
+                [
+                    "Each letter plays a note, from C (Do) to B (Si).",
+                    "Cada letra toca uma nota, de C (Dó) a B (Si).",
+                ],
+            ),
+            (
+                "R+",
+                [
+                    "Goes up one octave, so the scale can end on a higher C.",
+                    "Sobe uma oitava, para a escala terminar em um Dó mais agudo.",
+                ],
+            ),
+            ("R-", ["Goes down one octave.", "Desce uma oitava."]),
+            (
+                " ",
+                [
+                    "A space is a rest: one note of silence.",
+                    "Um espaço é uma pausa: uma nota de silêncio.",
+                ],
+            ),
+        ],
+    },
+    Example {
+        name: ["Twinkle Twinkle Little Star", "Brilha Brilha Estrelinha"],
+        text: include_str!("../examples/twinkle.txt"),
+        constructs: &[
+            (
+                "CCGGAAG",
+                [
+                    "Repeating a letter plays the note again.",
+                    "Repetir uma letra toca a nota de novo.",
+                ],
+            ),
+            (
+                " ",
+                [
+                    "Spaces separate the phrases with a rest.",
+                    "Os espaços separam as frases com uma pausa.",
+                ],
+            ),
+            (
+                "\n",
+                [
+                    "A line break changes to a random instrument, so every run sounds different.",
+                    "Uma quebra de linha troca para um instrumento aleatório, então cada execução soa diferente.",
+                ],
+            ),
+        ],
+    },
+    Example {
+        name: ["Tubular Bells", "Tubular Bells"],
+        text: include_str!("../examples/tubullar_bells.txt"),
+        constructs: &[
+            (
+                "BPM+",
+                [
+                    "Speeds the song up by 80 BPM. It is used twice at the start.",
+                    "Acelera a música em 80 BPM. É usado duas vezes no início.",
+                ],
+            ),
+            (
+                "R+",
+                [
+                    "Goes up one octave, for the high notes of the riff.",
+                    "Sobe uma oitava, para as notas agudas do riff.",
+                ],
+            ),
+            (
+                "R-",
+                ["Goes back down one octave.", "Volta uma oitava para baixo."],
+            ),
+        ],
+    },
+];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{midi_action::MidiAction, text_to_midi::Sheet, validate};
+
+    #[test]
+    fn examples_convert_without_warnings() {
+        for example in &ALL {
+            let actions = Sheet::builder()
+                .text(example.text)
+                .build()
+                .unwrap()
+                .process();
+
+            assert!(!actions.is_empty(), "{}", example.name[0]);
+            assert!(
+                validate::validate_actions(&actions).is_empty(),
+                "{}",
+                example.name[0]
+            );
+            assert!(
+                validate::validate_smf(&MidiAction::as_track(&actions)).is_empty(),
+                "{}",
+                example.name[0]
+            );
+        }
+    }
+
+    #[test]
+    fn explained_constructs_are_used() {
+        for example in &ALL {
+            for (construct, _) in example.constructs {
+                assert!(
+                    example.text.contains(construct),
+                    "{construct:?} in {}",
+                    example.name[0]
+                );
+            }
+        }
+    }
+}
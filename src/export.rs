@@ -0,0 +1,7 @@
+//! Exportação para formatos além do próprio arquivo MIDI.
+
+pub mod clips;
+pub mod events;
+pub mod midicsv;
+pub mod stems;
+pub mod summary;
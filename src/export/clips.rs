@@ -0,0 +1,169 @@
+//! Várias músicas, como as abas abertas, em um arquivo só, separadas por marcadores.
+//!
+//! Experimental: cada DAW trata de um jeito os arquivos de formato 2 e os pontos de
+//! marcação, mas a maioria mostra os marcadores na régua, onde o arquivo pode ser cortado.
+
+use midly::{
+    num::u28, Format, Header, MetaMessage, Smf, Timing, Track, TrackEvent, TrackEventKind,
+};
+
+use crate::midi_action::{MidiAction, TimedAction};
+
+/// Como os trechos são arrumados no arquivo.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ClipLayout {
+    /// Uma trilha só, com os trechos um depois do outro (formato 0).
+    #[default]
+    Sequential,
+    /// Uma trilha independente por trecho (formato 2), como os padrões de uma bateria eletrônica.
+    Patterns,
+}
+
+/// Um trecho nomeado, como o texto de uma aba.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Clip {
+    /// O nome, que vira o marcador e o ponto de marcação do começo do trecho.
+    pub name: String,
+    /// As ações do trecho, posicionadas a partir do tick 0.
+    pub actions: Vec<TimedAction>,
+}
+
+impl Clip {
+    /// A trilha do trecho, com o seu nome e os marcadores no começo.
+    fn track(&self) -> Track<'_> {
+        let smf = TimedAction::as_track(&self.actions);
+        let mut track = smf.tracks.into_iter().next().unwrap_or_default();
+        let name = self.name.as_bytes();
+        for event in &mut track {
+            if let TrackEventKind::Meta(MetaMessage::TrackName(_)) = event.kind {
+                event.kind = TrackEventKind::Meta(MetaMessage::TrackName(name));
+            }
+        }
+
+        let marks = [MetaMessage::Marker(name), MetaMessage::CuePoint(name)];
+        track.splice(
+            0..0,
+            marks.map(|mark| TrackEvent {
+                delta: 0.into(),
+                kind: TrackEventKind::Meta(mark),
+            }),
+        );
+        track
+    }
+}
+
+/// Junta os trechos em um arquivo só.
+pub fn container(clips: &[Clip], layout: ClipLayout) -> Smf<'_> {
+    let header = Header::new(Format::SingleTrack, Timing::Metrical(MidiAction::D_TPQN));
+    let tracks = clips.iter().map(Clip::track);
+
+    match layout {
+        ClipLayout::Patterns => Smf {
+            header: Header {
+                format: Format::Sequential,
+                ..header
+            },
+            tracks: tracks.collect(),
+        },
+        ClipLayout::Sequential => {
+            // O fim de cada trecho e o nome da sua trilha somem, e o tempo que eles
+            // ocupavam passa para o evento seguinte
+            let mut track = Track::new();
+            let mut carried = 0;
+            for event in tracks.flatten() {
+                let delta = carried + event.delta.as_int();
+                match event.kind {
+                    TrackEventKind::Meta(MetaMessage::EndOfTrack | MetaMessage::TrackName(_)) => {
+                        carried = delta;
+                    }
+                    kind => {
+                        track.push(TrackEvent {
+                            delta: saturated(delta),
+                            kind,
+                        });
+                        carried = 0;
+                    }
+                }
+            }
+            track.push(TrackEvent {
+                delta: saturated(carried),
+                kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+            });
+
+            Smf {
+                header,
+                tracks: vec![track],
+            }
+        }
+    }
+}
+
+/// Um delta, saturando no máximo representável.
+fn saturated(delta: u32) -> u28 {
+    u28::try_from(delta).unwrap_or_else(u28::max_value)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn clips() -> Vec<Clip> {
+        [("Verse", 2), ("Chorus", 1)]
+            .map(|(name, notes)| Clip {
+                name: name.to_string(),
+                actions: TimedAction::from_actions(&vec![MidiAction::PlayNote(60); notes]),
+            })
+            .into()
+    }
+
+    /// O tick absoluto de cada marcador da trilha.
+    fn markers<'a>(track: &Track<'a>) -> Vec<(u64, &'a [u8])> {
+        let mut tick = 0;
+        track
+            .iter()
+            .filter_map(|event| {
+                tick += event.delta.as_int() as u64;
+                match event.kind {
+                    TrackEventKind::Meta(MetaMessage::Marker(name)) => Some((tick, name)),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sequential_clips_follow_each_other() {
+        let clips = clips();
+
+        let smf = container(&clips, ClipLayout::Sequential);
+
+        assert_eq!(smf.header.format, Format::SingleTrack);
+        assert_eq!(smf.tracks.len(), 1);
+        let track = &smf.tracks[0];
+        // The verse ends one tick after its last note off
+        assert_eq!(markers(track), [(0, &b"Verse"[..]), (961, &b"Chorus"[..])]);
+        let ends = track
+            .iter()
+            .filter(|event| event.kind == TrackEventKind::Meta(MetaMessage::EndOfTrack))
+            .count();
+        assert_eq!(ends, 1);
+        assert_eq!(
+            track.last().unwrap().kind,
+            TrackEventKind::Meta(MetaMessage::EndOfTrack)
+        );
+    }
+
+    #[test]
+    fn patterns_get_a_track_each() {
+        let clips = clips();
+
+        let smf = container(&clips, ClipLayout::Patterns);
+
+        assert_eq!(smf.header.format, Format::Sequential);
+        assert_eq!(smf.tracks.len(), 2);
+        assert_eq!(markers(&smf.tracks[1]), [(0, &b"Chorus"[..])]);
+        assert!(smf.tracks[1]
+            .iter()
+            .any(|event| event.kind == TrackEventKind::Meta(MetaMessage::TrackName(b"Chorus"))));
+    }
+}
@@ -0,0 +1,156 @@
+use std::{error::Error, fs, path::Path};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::midi_action::{MidiAction, TimedAction};
+
+/// Formatos em que a lista de eventos pode ser escrita.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EventFormat {
+    /// Um vetor JSON de objetos `{tick, time_ms, type, value}`.
+    #[default]
+    Json,
+    /// Uma tabela com as colunas `tick,time_ms,type,value`.
+    Csv,
+}
+
+impl EventFormat {
+    /// O formato correspondente à extensão do arquivo, ou JSON se ela for desconhecida.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("csv") => Self::Csv,
+            _ => Self::Json,
+        }
+    }
+}
+
+/// Um evento como aparece no JSON.
+#[derive(Serialize)]
+struct Record<'a> {
+    tick: u64,
+    time_ms: f64,
+    /// Só aparece fora do canal 0, que é onde o texto gera tudo.
+    #[serde(skip_serializing_if = "is_zero")]
+    channel: u8,
+    #[serde(flatten)]
+    action: &'a MidiAction,
+}
+
+impl<'a> From<&'a TimedAction> for Record<'a> {
+    fn from(timed: &'a TimedAction) -> Self {
+        Self {
+            tick: timed.tick,
+            time_ms: timed.time.as_micros() as f64 / 1000.0,
+            channel: timed.channel,
+            action: &timed.action,
+        }
+    }
+}
+
+fn is_zero(channel: &u8) -> bool {
+    *channel == 0
+}
+
+/// Escreve os eventos em JSON.
+pub fn to_json(actions: &[TimedAction]) -> String {
+    let records: Vec<Record> = actions.iter().map(Record::from).collect();
+    serde_json::to_string_pretty(&records).expect("records are always serializable")
+}
+
+/// Escreve os eventos em CSV, com cabeçalho.
+///
+/// Ações sem valor, como pausas, ficam com a coluna vazia.
+pub fn to_csv(actions: &[TimedAction]) -> String {
+    let mut csv = String::from("tick,time_ms,type,value\n");
+
+    for timed in actions {
+        let record = serde_json::to_value(Record::from(timed)).expect("records are serializable");
+        let value = match &record["value"] {
+            Value::Null => String::new(),
+            Value::String(text) => format!("\"{}\"", text.replace('"', "\"\"")),
+            other => other.to_string(),
+        };
+        csv.push_str(&format!(
+            "{},{},{},{}\n",
+            record["tick"],
+            record["time_ms"],
+            record["type"].as_str().unwrap_or_default(),
+            value
+        ));
+    }
+
+    csv
+}
+
+/// Salva os eventos no arquivo, no formato indicado pela sua extensão.
+pub fn save(actions: &[TimedAction], path: &Path) -> Result<(), Box<dyn Error>> {
+    let contents = match EventFormat::from_path(path) {
+        EventFormat::Json => to_json(actions),
+        EventFormat::Csv => to_csv(actions),
+    };
+    fs::write(path, contents)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn actions() -> Vec<TimedAction> {
+        TimedAction::from_actions(&[
+            MidiAction::ChangeBPM(60),
+            MidiAction::Lyric("say \"hi\"".into()),
+            MidiAction::PlayNote(60),
+            MidiAction::Pause,
+            MidiAction::PlayNote(62),
+        ])
+    }
+
+    #[test]
+    fn json_schema() {
+        let json: Value = serde_json::from_str(&to_json(&actions())).unwrap();
+
+        assert_eq!(
+            json[2],
+            serde_json::json!({"tick": 0, "time_ms": 0.0, "type": "note", "value": 60})
+        );
+        assert_eq!(
+            json[4],
+            serde_json::json!({"tick": 960, "time_ms": 2000.0, "type": "note", "value": 62})
+        );
+        assert_eq!(
+            json[3],
+            serde_json::json!({"tick": 480, "time_ms": 1000.0, "type": "pause"})
+        );
+    }
+
+    #[test]
+    fn csv_rows() {
+        let csv = to_csv(&actions());
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(
+            lines,
+            [
+                "tick,time_ms,type,value",
+                "0,0.0,bpm,60",
+                "0,0.0,lyric,\"say \"\"hi\"\"\"",
+                "0,0.0,note,60",
+                "480,1000.0,pause,",
+                "960,2000.0,note,62",
+            ]
+        );
+    }
+
+    #[test]
+    fn format_from_extension() {
+        assert_eq!(EventFormat::from_path(Path::new("a.CSV")), EventFormat::Csv);
+        assert_eq!(
+            EventFormat::from_path(Path::new("a.json")),
+            EventFormat::Json
+        );
+        assert_eq!(EventFormat::from_path(Path::new("a")), EventFormat::Json);
+    }
+}
@@ -0,0 +1,138 @@
+//! Exportação para o formato de texto do midicsv, uma linha por evento, para editar um
+//! arquivo MIDI em uma planilha e voltar com o `import::midicsv`.
+//!
+//! Cada linha tem a trilha, o tick absoluto e o tipo do evento, seguidos dos seus
+//! valores, como em `1, 480, Note_on_c, 0, 60, 100`. A linha 0 guarda o cabeçalho e o
+//! fim do arquivo.
+
+use std::fmt::Write;
+
+use midly::{Format, Fps, MetaMessage, MidiMessage, Smf, Timing, TrackEventKind};
+
+/// Escreve o arquivo no formato do midicsv.
+pub fn to_csv(smf: &Smf<'_>) -> String {
+    let format = match smf.header.format {
+        Format::SingleTrack => 0,
+        Format::Parallel => 1,
+        Format::Sequential => 2,
+    };
+    let division = u16::from_be_bytes(match smf.header.timing {
+        Timing::Metrical(tpqn) => tpqn.as_int().to_be_bytes(),
+        // Como no arquivo: o FPS negativo no byte mais alto
+        Timing::Timecode(fps, subframes) => [(-(fps.as_int() as i8)) as u8, subframes],
+    });
+    let mut csv = format!("0, 0, Header, {format}, {}, {division}\n", smf.tracks.len());
+
+    for (track, events) in (1..).zip(&smf.tracks) {
+        let _ = writeln!(csv, "{track}, 0, Start_track");
+        let mut tick = 0;
+        for event in events {
+            tick += event.delta.as_int() as u64;
+            let _ = writeln!(csv, "{track}, {tick}, {}", record(&event.kind));
+        }
+    }
+    csv.push_str("0, 0, End_of_file\n");
+
+    csv
+}
+
+/// O tipo do evento e os seus valores.
+fn record(kind: &TrackEventKind<'_>) -> String {
+    match *kind {
+        TrackEventKind::Midi { channel, message } => match message {
+            MidiMessage::NoteOff { key, vel } => format!("Note_off_c, {channel}, {key}, {vel}"),
+            MidiMessage::NoteOn { key, vel } => format!("Note_on_c, {channel}, {key}, {vel}"),
+            MidiMessage::Aftertouch { key, vel } => {
+                format!("Poly_aftertouch_c, {channel}, {key}, {vel}")
+            }
+            MidiMessage::Controller { controller, value } => {
+                format!("Control_c, {channel}, {controller}, {value}")
+            }
+            MidiMessage::ProgramChange { program } => format!("Program_c, {channel}, {program}"),
+            MidiMessage::ChannelAftertouch { vel } => {
+                format!("Channel_aftertouch_c, {channel}, {vel}")
+            }
+            MidiMessage::PitchBend { bend } => format!("Pitch_bend_c, {channel}, {}", bend.0),
+        },
+        TrackEventKind::SysEx(data) => format!("System_exclusive, {}", bytes(data)),
+        TrackEventKind::Escape(data) => format!("System_exclusive_packet, {}", bytes(data)),
+        TrackEventKind::Meta(message) => meta(&message),
+    }
+}
+
+/// O tipo e os valores de uma mensagem meta. As que o midicsv não conhece, como o nome
+/// do programa, ficam como `Unknown_meta_event`, com os bytes.
+fn meta(message: &MetaMessage<'_>) -> String {
+    match *message {
+        MetaMessage::TrackNumber(Some(number)) => format!("Sequence_number, {number}"),
+        MetaMessage::Text(text) => format!("Text_t, {}", quoted(text)),
+        MetaMessage::Copyright(text) => format!("Copyright_t, {}", quoted(text)),
+        MetaMessage::TrackName(text) => format!("Title_t, {}", quoted(text)),
+        MetaMessage::InstrumentName(text) => format!("Instrument_name_t, {}", quoted(text)),
+        MetaMessage::Lyric(text) => format!("Lyric_t, {}", quoted(text)),
+        MetaMessage::Marker(text) => format!("Marker_t, {}", quoted(text)),
+        MetaMessage::CuePoint(text) => format!("Cue_point_t, {}", quoted(text)),
+        MetaMessage::MidiChannel(channel) => format!("Channel_prefix, {channel}"),
+        MetaMessage::MidiPort(port) => format!("MIDI_port, {port}"),
+        MetaMessage::EndOfTrack => "End_track".to_string(),
+        MetaMessage::Tempo(mspqn) => format!("Tempo, {mspqn}"),
+        MetaMessage::SmpteOffset(time) => {
+            // A hora vai com o código do FPS nos bits mais altos, como no arquivo
+            let code = match time.fps() {
+                Fps::Fps24 => 0,
+                Fps::Fps25 => 1,
+                Fps::Fps29 => 2,
+                Fps::Fps30 => 3,
+            };
+            format!(
+                "SMPTE_offset, {}, {}, {}, {}, {}",
+                time.hour() | code << 5,
+                time.minute(),
+                time.second(),
+                time.frame(),
+                time.subframe()
+            )
+        }
+        MetaMessage::TimeSignature(numerator, denominator, clocks, thirty_seconds) => {
+            format!("Time_signature, {numerator}, {denominator}, {clocks}, {thirty_seconds}")
+        }
+        MetaMessage::KeySignature(key, minor) => {
+            let mode = if minor { "minor" } else { "major" };
+            format!("Key_signature, {key}, \"{mode}\"")
+        }
+        MetaMessage::SequencerSpecific(data) => format!("Sequencer_specific, {}", bytes(data)),
+        MetaMessage::TrackNumber(None) => "Unknown_meta_event, 0, 0".to_string(),
+        MetaMessage::ProgramName(data) => format!("Unknown_meta_event, 8, {}", bytes(data)),
+        MetaMessage::DeviceName(data) => format!("Unknown_meta_event, 9, {}", bytes(data)),
+        MetaMessage::Unknown(kind, data) => format!("Unknown_meta_event, {kind}, {}", bytes(data)),
+    }
+}
+
+/// O tamanho dos dados seguido de cada byte, separados por vírgulas.
+fn bytes(data: &[u8]) -> String {
+    let mut values = data.len().to_string();
+    for byte in data {
+        let _ = write!(values, ", {byte}");
+    }
+
+    values
+}
+
+/// O texto entre aspas, com as aspas dobradas e a barra invertida e os bytes fora do
+/// ASCII visível escritos em octal, como `\\` e `\351`.
+fn quoted(text: &[u8]) -> String {
+    let mut quoted = String::from("\"");
+    for &byte in text {
+        match byte {
+            b'"' => quoted.push_str("\"\""),
+            b'\\' => quoted.push_str("\\\\"),
+            b' '..=b'~' => quoted.push(byte as char),
+            _ => {
+                let _ = write!(quoted, "\\{byte:03o}");
+            }
+        }
+    }
+    quoted.push('"');
+
+    quoted
+}
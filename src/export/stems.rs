@@ -0,0 +1,246 @@
+//! Stems: um arquivo MIDI para cada canal de cada trilha, para levar as vozes
+//! separadas para outros programas.
+//!
+//! Cada stem tem só os eventos de um canal, junto dos eventos de tempo, compasso e tom
+//! de todas as trilhas, para tocar no mesmo andamento que o arquivo inteiro.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use midly::{
+    num::u4, Format, Header, MetaMessage, MidiMessage, Smf, Timing, Track, TrackEvent,
+    TrackEventKind,
+};
+
+use crate::instruments;
+
+/// O arquivo de uma voz.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stem<'a> {
+    /// O nome, tirado do nome da trilha ou do instrumento do canal.
+    pub name: String,
+    /// A temporização do arquivo original.
+    pub timing: Timing,
+    /// Os eventos da voz, sem o nome da trilha.
+    pub track: Track<'a>,
+}
+
+impl Stem<'_> {
+    /// O arquivo da voz, com uma trilha só, que leva o nome do stem.
+    pub fn smf(&self) -> Smf<'_> {
+        let mut track = Track::with_capacity(self.track.len() + 1);
+        track.push(TrackEvent {
+            delta: 0.into(),
+            kind: TrackEventKind::Meta(MetaMessage::TrackName(self.name.as_bytes())),
+        });
+        track.extend(self.track.iter().copied());
+
+        Smf {
+            header: Header::new(Format::SingleTrack, self.timing),
+            tracks: vec![track],
+        }
+    }
+
+    /// O nome do arquivo: a posição, para manter a ordem, e o nome sem caracteres que
+    /// os sistemas de arquivos recusam.
+    pub fn file_name(&self, index: usize) -> String {
+        let name: String = self
+            .name
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() || " -_()".contains(c) {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        format!("{:02} {}.mid", index + 1, name.trim())
+    }
+}
+
+/// Um evento com o seu tick absoluto.
+type Timed<'a> = (u64, TrackEventKind<'a>);
+
+/// Separa o arquivo em stems, na ordem das trilhas e dos canais.
+pub fn split<'a>(smf: &Smf<'a>) -> Vec<Stem<'a>> {
+    let tracks: Vec<Vec<Timed<'a>>> = smf.tracks.iter().map(absolute).collect();
+    let global: Vec<Timed<'a>> = tracks
+        .iter()
+        .flatten()
+        .filter(|(_, kind)| {
+            matches!(
+                kind,
+                TrackEventKind::Meta(
+                    MetaMessage::Tempo(_)
+                        | MetaMessage::TimeSignature(..)
+                        | MetaMessage::KeySignature(..)
+                )
+            )
+        })
+        .copied()
+        .collect();
+
+    let mut stems = Vec::new();
+    for events in &tracks {
+        let track_name = events.iter().find_map(|(_, kind)| match kind {
+            TrackEventKind::Meta(MetaMessage::TrackName(name)) => {
+                Some(String::from_utf8_lossy(name).trim().to_string())
+            }
+            _ => None,
+        });
+        let mut channels: Vec<u4> = events
+            .iter()
+            .filter_map(|(_, kind)| match kind {
+                TrackEventKind::Midi { channel, .. } => Some(*channel),
+                _ => None,
+            })
+            .collect();
+        channels.sort_unstable();
+        channels.dedup();
+
+        for &channel in &channels {
+            let mut stem: Vec<Timed<'a>> = global.clone();
+            stem.extend(events.iter().copied().filter(
+                |(_, kind)| matches!(kind, TrackEventKind::Midi { channel: c, .. } if *c == channel),
+            ));
+            // A ordenação é estável, então o tempo vem antes das notas no mesmo tick
+            stem.sort_by_key(|&(tick, _)| tick);
+
+            stems.push(Stem {
+                name: stem_name(track_name.as_deref(), channels.len(), channel, &stem),
+                timing: smf.header.timing,
+                track: relative(&stem),
+            });
+        }
+    }
+
+    stems
+}
+
+/// Salva os stems do arquivo na pasta dada, retornando os arquivos escritos.
+pub fn save(smf: &Smf<'_>, directory: &Path) -> io::Result<Vec<PathBuf>> {
+    fs::create_dir_all(directory)?;
+    split(smf)
+        .iter()
+        .enumerate()
+        .map(|(index, stem)| {
+            let path = directory.join(stem.file_name(index));
+            stem.smf().save(&path)?;
+            Ok(path)
+        })
+        .collect()
+}
+
+/// O nome do stem: o da trilha, se ela só tiver esse canal, senão o instrumento do canal.
+fn stem_name(track: Option<&str>, channels: usize, channel: u4, events: &[Timed<'_>]) -> String {
+    let instrument = if channel == 9 {
+        "Drums".to_string()
+    } else {
+        events
+            .iter()
+            .find_map(|(_, kind)| match kind {
+                TrackEventKind::Midi {
+                    message: MidiMessage::ProgramChange { program },
+                    ..
+                } => instruments::name(program.as_int()),
+                _ => None,
+            })
+            .unwrap_or(instruments::GM_NAMES[0])
+            .to_string()
+    };
+
+    match track.filter(|name| !name.is_empty()) {
+        Some(track) if channels == 1 => track.to_string(),
+        Some(track) => format!("{track} - {instrument} (channel {})", channel.as_int() + 1),
+        None => format!("{instrument} (channel {})", channel.as_int() + 1),
+    }
+}
+
+/// Os eventos da trilha com ticks absolutos, sem o fim da trilha.
+fn absolute<'a>(track: &Track<'a>) -> Vec<Timed<'a>> {
+    let mut tick = 0;
+    track
+        .iter()
+        .filter_map(|event| {
+            tick += event.delta.as_int() as u64;
+            let end = matches!(event.kind, TrackEventKind::Meta(MetaMessage::EndOfTrack));
+            (!end).then_some((tick, event.kind))
+        })
+        .collect()
+}
+
+/// Uma trilha com os eventos dados, em ordem, terminada pelo fim da trilha.
+fn relative<'a>(events: &[Timed<'a>]) -> Track<'a> {
+    let mut track = Track::with_capacity(events.len() + 1);
+    let mut last = 0;
+    for &(tick, kind) in events {
+        let delta = u32::try_from(tick - last).unwrap_or(u32::MAX);
+        track.push(TrackEvent {
+            delta: delta.min(0x0FFF_FFFF).into(),
+            kind,
+        });
+        last = tick;
+    }
+    track.push(TrackEvent {
+        delta: 0.into(),
+        kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+    });
+    track
+}
+
+#[cfg(test)]
+mod test {
+    use midly::num::u7;
+
+    use super::*;
+    use crate::midi_action::MidiAction;
+
+    #[test]
+    fn each_channel_becomes_a_stem() {
+        let actions = [
+            MidiAction::ChangeBPM(90),
+            MidiAction::ChangeInstrument(40),
+            MidiAction::PlayNote(60),
+            MidiAction::DroneStart(36),
+            MidiAction::PlayNote(62),
+        ];
+        let smf = MidiAction::as_track(&actions);
+
+        let stems = split(&smf);
+
+        let names: Vec<&str> = stems.iter().map(|stem| stem.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "tcp_out - Violin (channel 1)",
+                "tcp_out - Acoustic Grand Piano (channel 3)"
+            ]
+        );
+        assert_eq!(
+            stems[1].file_name(1),
+            "02 tcp_out - Acoustic Grand Piano (channel 3).mid"
+        );
+        for stem in &stems {
+            let smf = stem.smf();
+            // Every stem keeps the tempo, and only its own channel
+            assert!(smf.tracks[0]
+                .iter()
+                .any(|event| matches!(event.kind, TrackEventKind::Meta(MetaMessage::Tempo(_)))));
+            let channels: Vec<u4> = smf.tracks[0]
+                .iter()
+                .filter_map(|event| match event.kind {
+                    TrackEventKind::Midi { channel, .. } => Some(channel),
+                    _ => None,
+                })
+                .collect();
+            assert!(channels.windows(2).all(|pair| pair[0] == pair[1]));
+        }
+        let drone_on = stems[1].track.iter().find(|event| {
+            matches!(event.kind, TrackEventKind::Midi { message: MidiMessage::NoteOn { key, .. }, .. } if key == u7::new(36))
+        });
+        assert!(drone_on.is_some());
+    }
+}
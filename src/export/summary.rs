@@ -0,0 +1,117 @@
+//! O resumo de um arquivo MIDI, mostrado antes de ele ser salvo, para conferir se é
+//! o que o aparelho ou o programa de destino espera.
+
+use std::{fmt, time::Duration};
+
+use midly::{Format, MetaMessage, Smf, Timing, TrackEventKind};
+
+use crate::tempo_map::TempoMap;
+
+/// O que um arquivo MIDI tem, sem as notas.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Summary {
+    /// O formato: uma trilha, trilhas paralelas ou sequências independentes.
+    pub format: Format,
+    /// A temporização: os ticks por semínima (TPQN) ou o SMPTE.
+    pub timing: Timing,
+    /// Quantas trilhas há.
+    pub tracks: usize,
+    /// Quantos eventos de tempo há, somando todas as trilhas.
+    pub tempo_events: usize,
+    /// Quanto o arquivo leva para tocar, até o fim da trilha mais longa.
+    pub duration: Duration,
+}
+
+impl Summary {
+    /// Quanto do começo do arquivo a prévia toca.
+    pub const PREVIEW: Duration = Duration::from_secs(10);
+
+    /// O resumo do arquivo dado.
+    pub fn of(smf: &Smf<'_>) -> Self {
+        let tempo_events = smf
+            .tracks
+            .iter()
+            .flatten()
+            .filter(|event| matches!(event.kind, TrackEventKind::Meta(MetaMessage::Tempo(_))))
+            .count();
+        let end = smf
+            .tracks
+            .iter()
+            .map(|track| track.iter().map(|event| event.delta.as_int() as u64).sum())
+            .max()
+            .unwrap_or(0);
+        let duration = match smf.header.timing {
+            Timing::Metrical(tpqn) => TempoMap::from_tracks(&smf.tracks, tpqn).time_at(end),
+            Timing::Timecode(fps, subframes) => {
+                Duration::from_secs_f64(end as f64 / (fps.as_f32() as f64 * subframes as f64))
+            }
+        };
+
+        Self {
+            format: smf.header.format,
+            timing: smf.header.timing,
+            tracks: smf.tracks.len(),
+            tempo_events,
+            duration,
+        }
+    }
+
+    /// O tick onde termina a prévia, se o arquivo tiver compassos.
+    pub fn preview_end(smf: &Smf<'_>) -> Option<u64> {
+        let Timing::Metrical(tpqn) = smf.header.timing else {
+            return None;
+        };
+
+        Some(TempoMap::from_tracks(&smf.tracks, tpqn).tick_at(Self::PREVIEW))
+    }
+
+    /// O formato, com o número usado no cabeçalho.
+    pub fn format_name(&self) -> &'static str {
+        match self.format {
+            Format::SingleTrack => "0 (single track)",
+            Format::Parallel => "1 (parallel tracks)",
+            Format::Sequential => "2 (independent patterns)",
+        }
+    }
+}
+
+/// A temporização, como `480 TPQN` ou `SMPTE 25 fps`.
+pub struct TimingName(pub Timing);
+
+impl fmt::Display for TimingName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Timing::Metrical(tpqn) => write!(f, "{tpqn} TPQN"),
+            Timing::Timecode(fps, subframes) => {
+                write!(f, "SMPTE {} fps, {subframes} ticks/frame", fps.as_f32())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::midi_action::MidiAction;
+
+    #[test]
+    fn files_are_summarized() {
+        let actions = [
+            MidiAction::PlayNote(60),
+            MidiAction::ChangeBPM(60),
+            MidiAction::PlayNote(62),
+        ];
+        let smf = MidiAction::as_track(&actions);
+
+        let summary = Summary::of(&smf);
+
+        assert_eq!(summary.format_name(), "0 (single track)");
+        assert_eq!(TimingName(summary.timing).to_string(), "480 TPQN");
+        assert_eq!((summary.tracks, summary.tempo_events), (1, 1));
+        // Half a second at 120 BPM, then a second at 60 BPM, and the tick before the end
+        assert!(summary.duration >= Duration::from_millis(1500));
+        assert!(summary.duration < Duration::from_millis(1510));
+        // Ten seconds: the first beat at 120 BPM, then nine and a half at 60 BPM
+        assert_eq!(Summary::preview_end(&smf), Some(480 + 480 * 19 / 2));
+    }
+}
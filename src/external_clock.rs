@@ -0,0 +1,170 @@
+use std::error::Error;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+#[cfg(feature = "native")]
+use midir::{Ignore, MidiInput, MidiInputConnection};
+
+#[cfg(feature = "native")]
+use tracing::info;
+
+use crate::play::CLOCKS_PER_QUARTER;
+
+const TIMING_CLOCK: u8 = 0xF8;
+const START: u8 = 0xFA;
+const CONTINUE: u8 = 0xFB;
+const STOP: u8 = 0xFC;
+
+/// De quanto em quanto tempo a espera pelo relógio confere se a reprodução foi
+/// interrompida, já que um relógio parado não acorda ninguém.
+const INTERRUPT_CHECK: Duration = Duration::from_millis(20);
+
+/// O que aconteceu enquanto o player esperava pelo relógio externo.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Wait {
+    /// O relógio chegou ao tick esperado.
+    Reached,
+    /// Chegou um Start: a música deve recomeçar do início.
+    Restarted,
+    /// A reprodução foi interrompida antes de o relógio chegar lá.
+    Interrupted,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// Se o relógio está andando, entre um Start ou Continue e um Stop.
+    running: bool,
+    /// Pulsos recebidos desde o último Start.
+    pulses: u64,
+    /// Quantos Start já chegaram, para o player perceber que precisa recomeçar.
+    starts: u64,
+}
+
+/// Posição dada por um relógio MIDI externo, como o de uma DAW.
+///
+/// As mensagens recebidas são passadas para `handle`, e o player espera com `wait_for_tick`
+/// em vez de dormir, andando 1/24 de semimínima a cada Clock.
+#[derive(Debug, Default)]
+pub struct ExternalClock {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+impl ExternalClock {
+    /// Trata uma mensagem recebida. Mensagens que não são de tempo real são ignoradas.
+    pub fn handle(&self, message: &[u8]) {
+        let mut state = self.state.lock().unwrap();
+        match message.first() {
+            Some(&TIMING_CLOCK) if state.running => state.pulses += 1,
+            Some(&START) => {
+                state.running = true;
+                state.pulses = 0;
+                state.starts += 1;
+            }
+            Some(&CONTINUE) => state.running = true,
+            Some(&STOP) => state.running = false,
+            _ => return,
+        }
+        self.changed.notify_all();
+    }
+
+    /// Quantos Start já chegaram.
+    pub fn starts(&self) -> u64 {
+        self.state.lock().unwrap().starts
+    }
+
+    /// Espera o relógio chegar ao tick dado, em um arquivo com `tpqn` ticks por semimínima.
+    ///
+    /// Se chegar um Start depois de `starts` (o valor de `starts()` quando a música começou),
+    /// retorna `Wait::Restarted` sem esperar mais. Antes do primeiro Start, nem o tick 0
+    /// foi alcançado.
+    ///
+    /// `interrupted` é conferido durante a espera: quando ele for verdadeiro, como depois
+    /// de um Stop na interface, retorna `Wait::Interrupted`, mesmo sem nenhum Clock.
+    pub fn wait_for_tick(
+        &self,
+        tick: u64,
+        tpqn: u64,
+        starts: u64,
+        interrupted: impl Fn() -> bool,
+    ) -> Wait {
+        let pulses = (tick * CLOCKS_PER_QUARTER).div_ceil(tpqn);
+        let mut state = self.state.lock().unwrap();
+        while state.starts == starts && (state.starts == 0 || state.pulses < pulses) {
+            if interrupted() {
+                return Wait::Interrupted;
+            }
+            state = self.changed.wait_timeout(state, INTERRUPT_CHECK).unwrap().0;
+        }
+
+        if state.starts == starts {
+            Wait::Reached
+        } else {
+            Wait::Restarted
+        }
+    }
+}
+
+/// Passa a escutar o relógio da primeira porta de entrada MIDI.
+///
+/// O relógio só anda enquanto a conexão retornada existir.
+#[cfg(feature = "native")]
+pub fn connect() -> Result<(MidiInputConnection<()>, Arc<ExternalClock>), Box<dyn Error>> {
+    let mut midi_in = MidiInput::new("TCP")?;
+    // Por padrão o midir descarta as mensagens de tempo, que são justamente as que interessam
+    midi_in.ignore(Ignore::None);
+
+    let in_ports = midi_in.ports();
+    let in_port = in_ports.first().ok_or("No input port found.")?;
+    info!(
+        port = midi_in.port_name(in_port)?,
+        "following an external clock"
+    );
+
+    let clock = Arc::new(ExternalClock::default());
+    let handler = Arc::clone(&clock);
+    let connection = midi_in.connect(
+        in_port,
+        "midir",
+        move |_, message, _| handler.handle(message),
+        (),
+    )?;
+
+    Ok((connection, clock))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn clocks_only_count_while_running() {
+        let clock = ExternalClock::default();
+
+        clock.handle(&[TIMING_CLOCK]);
+        clock.handle(&[START]);
+        clock.handle(&[TIMING_CLOCK]);
+        clock.handle(&[STOP]);
+        clock.handle(&[TIMING_CLOCK]);
+        clock.handle(&[CONTINUE]);
+        clock.handle(&[TIMING_CLOCK]);
+
+        assert_eq!(clock.state.lock().unwrap().pulses, 2);
+        assert_eq!(clock.starts(), 1);
+    }
+
+    #[test]
+    fn start_interrupts_the_wait() {
+        let clock = ExternalClock::default();
+        clock.handle(&[START]);
+        for _ in 0..24 {
+            clock.handle(&[TIMING_CLOCK]);
+        }
+
+        assert_eq!(clock.wait_for_tick(480, 480, 1, || false), Wait::Reached);
+        clock.handle(&[START]);
+        assert_eq!(clock.wait_for_tick(960, 480, 1, || false), Wait::Restarted);
+        // A clock that never moves does not hold an interrupted playback
+        assert_eq!(clock.wait_for_tick(960, 480, 2, || true), Wait::Interrupted);
+    }
+}
@@ -0,0 +1,262 @@
+//! Interface em C do conversor, para embutir em ferramentas escritas em outras linguagens.
+//!
+//! O cabeçalho `include/tcp.h` é gerado a partir deste módulo pelo `build.rs`.
+
+use std::{
+    ffi::{c_char, CStr},
+    ptr,
+};
+
+use crate::{
+    midi_action::MidiAction,
+    text_to_midi::{ConfigError, Sheet, State},
+};
+
+/// O resultado de uma chamada.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TcpStatus {
+    /// A conversão deu certo.
+    Ok = 0,
+    /// Algum ponteiro obrigatório era nulo.
+    NullArgument,
+    /// O texto não é UTF-8 válido.
+    InvalidUtf8,
+    /// O BPM está fora de 4..=360.
+    InvalidBpm,
+    /// O volume está fora de 0..=127.
+    InvalidVolume,
+    /// A oitava está fora de 0..=12.
+    InvalidOctave,
+    /// O instrumento está fora de 0..=127.
+    InvalidInstrument,
+}
+
+impl From<ConfigError> for TcpStatus {
+    fn from(error: ConfigError) -> Self {
+        match error {
+            ConfigError::Bpm(_) => Self::InvalidBpm,
+            ConfigError::Volume(_) => Self::InvalidVolume,
+            ConfigError::Octave(_) => Self::InvalidOctave,
+            ConfigError::Instrument(_) => Self::InvalidInstrument,
+        }
+    }
+}
+
+/// As configurações iniciais da conversão. Comece de `tcp_default_options`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct TcpOptions {
+    /// O BPM inicial.
+    pub bpm: u16,
+    /// O volume inicial.
+    pub volume: u16,
+    /// A oitava inicial.
+    pub octave: u8,
+    /// O instrumento inicial, do General MIDI.
+    pub instrument: u8,
+    /// Se os caracteres do texto são emitidos como letras (karaokê).
+    pub lyrics: bool,
+    /// Se `seed` deve ser usada. Sem ela, cada conversão sai diferente.
+    pub seeded: bool,
+    /// A semente dos comandos aleatórios.
+    pub seed: u64,
+}
+
+/// Bytes alocados pela biblioteca, que devem ser liberados com `tcp_bytes_free`.
+#[repr(C)]
+#[derive(Debug)]
+pub struct TcpBytes {
+    /// O início dos bytes, ou nulo se não há nenhum.
+    pub data: *mut u8,
+    /// Quantos bytes há.
+    pub len: usize,
+}
+
+impl TcpBytes {
+    const EMPTY: Self = Self {
+        data: ptr::null_mut(),
+        len: 0,
+    };
+
+    fn new(bytes: Vec<u8>) -> Self {
+        let bytes = Box::into_raw(bytes.into_boxed_slice());
+        Self {
+            data: bytes.cast(),
+            len: bytes.len(),
+        }
+    }
+}
+
+/// As configurações padrão, as mesmas da interface.
+#[no_mangle]
+pub extern "C" fn tcp_default_options() -> TcpOptions {
+    TcpOptions {
+        bpm: State::D_BPM,
+        volume: State::D_VOLUME,
+        octave: State::D_OCTAVE,
+        instrument: 0,
+        lyrics: false,
+        seeded: false,
+        seed: 0,
+    }
+}
+
+/// Converte o texto em um arquivo MIDI (SMF), escrito em `out`.
+///
+/// Se `options` for nulo, usa as configurações padrão. Se a conversão falhar,
+/// `out` fica vazio e não precisa ser liberado.
+///
+/// # Safety
+///
+/// `text` deve ser uma string terminada em nulo, `options` deve ser nulo ou
+/// apontar para um `TcpOptions`, e `out` deve apontar para um `TcpBytes` gravável.
+#[no_mangle]
+pub unsafe extern "C" fn tcp_text_to_midi(
+    text: *const c_char,
+    options: *const TcpOptions,
+    out: *mut TcpBytes,
+) -> TcpStatus {
+    if out.is_null() {
+        return TcpStatus::NullArgument;
+    }
+    *out = TcpBytes::EMPTY;
+    if text.is_null() {
+        return TcpStatus::NullArgument;
+    }
+
+    let Ok(text) = CStr::from_ptr(text).to_str() else {
+        return TcpStatus::InvalidUtf8;
+    };
+    let options = options
+        .as_ref()
+        .copied()
+        .unwrap_or_else(|| tcp_default_options());
+
+    match convert(text, options) {
+        Ok(smf) => {
+            *out = TcpBytes::new(smf);
+            TcpStatus::Ok
+        }
+        Err(error) => error.into(),
+    }
+}
+
+/// Libera os bytes devolvidos por `tcp_text_to_midi`.
+///
+/// # Safety
+///
+/// `bytes` deve ter vindo desta biblioteca e não pode ser liberado duas vezes.
+#[no_mangle]
+pub unsafe extern "C" fn tcp_bytes_free(bytes: TcpBytes) {
+    if !bytes.data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+            bytes.data, bytes.len,
+        )));
+    }
+}
+
+/// Uma descrição em inglês do resultado, que não deve ser liberada.
+#[no_mangle]
+pub extern "C" fn tcp_status_message(status: TcpStatus) -> *const c_char {
+    let message: &'static CStr = match status {
+        TcpStatus::Ok => c"ok",
+        TcpStatus::NullArgument => c"a required pointer is null",
+        TcpStatus::InvalidUtf8 => c"the text is not valid UTF-8",
+        TcpStatus::InvalidBpm => c"the BPM is outside 4..=360",
+        TcpStatus::InvalidVolume => c"the volume is outside 0..=127",
+        TcpStatus::InvalidOctave => c"the octave is outside 0..=12",
+        TcpStatus::InvalidInstrument => c"the instrument is outside 0..=127",
+    };
+    message.as_ptr()
+}
+
+/// Faz a conversão em Rust, do mesmo jeito que a interface.
+fn convert(text: &str, options: TcpOptions) -> Result<Vec<u8>, ConfigError> {
+    let mut builder = Sheet::builder()
+        .text(text)
+        .bpm(options.bpm)
+        .volume(options.volume)
+        .octave(options.octave)
+        .instrument(options.instrument)
+        .lyrics(options.lyrics);
+    if options.seeded {
+        builder = builder.seed(options.seed);
+    }
+
+    let actions = builder.build()?.process();
+    let mut smf = Vec::new();
+    MidiAction::as_track(&actions)
+        .write_std(&mut smf)
+        .expect("writing to a vector never fails");
+
+    Ok(smf)
+}
+
+#[cfg(test)]
+mod test {
+    use std::slice;
+
+    use super::*;
+
+    fn convert(text: &CStr, options: Option<&TcpOptions>) -> (TcpStatus, Vec<u8>) {
+        let mut out = TcpBytes::EMPTY;
+        let options = options.map_or(ptr::null(), |options| options as *const _);
+
+        unsafe {
+            let status = tcp_text_to_midi(text.as_ptr(), options, &mut out);
+            let bytes = if out.data.is_null() {
+                Vec::new()
+            } else {
+                slice::from_raw_parts(out.data, out.len).to_vec()
+            };
+            tcp_bytes_free(out);
+            (status, bytes)
+        }
+    }
+
+    #[test]
+    fn converts_to_a_midi_file() {
+        let (status, bytes) = convert(c"CDE", None);
+
+        assert_eq!(status, TcpStatus::Ok);
+        assert!(bytes.starts_with(b"MThd"));
+        assert!(midly::Smf::parse(&bytes).is_ok());
+    }
+
+    #[test]
+    fn invalid_options_leave_out_empty() {
+        let options = TcpOptions {
+            bpm: 0,
+            ..tcp_default_options()
+        };
+
+        assert_eq!(
+            convert(c"CDE", Some(&options)),
+            (TcpStatus::InvalidBpm, Vec::new())
+        );
+        let mut out = TcpBytes::EMPTY;
+        assert_eq!(
+            unsafe { tcp_text_to_midi(ptr::null(), ptr::null(), &mut out) },
+            TcpStatus::NullArgument
+        );
+    }
+
+    #[test]
+    fn header_is_up_to_date() {
+        let root = env!("CARGO_MANIFEST_DIR");
+        let config = cbindgen::Config::from_file(format!("{root}/cbindgen.toml")).unwrap();
+        let mut generated = Vec::new();
+        cbindgen::Builder::new()
+            .with_config(config)
+            .with_src(format!("{root}/src/ffi.rs"))
+            .generate()
+            .unwrap()
+            .write(&mut generated);
+
+        assert!(
+            String::from_utf8(generated).unwrap() == include_str!("../include/tcp.h"),
+            "include/tcp.h is stale: cbindgen --config cbindgen.toml --output include/tcp.h src/ffi.rs"
+        );
+    }
+}
@@ -0,0 +1,163 @@
+//! Correção de exercícios: compara o arquivo MIDI de um aluno com o de referência,
+//! nota por nota, dando uma nota de semelhança e as diferenças.
+//!
+//! Só a ordem das notas e das mudanças de tempo conta, não os ticks exatos: um arquivo
+//! tocado ou exportado com um pouco de atraso aqui e ali tira a nota máxima. As notas
+//! que começam quase juntas, como as de um acorde, são comparadas da mais grave para a
+//! mais aguda.
+
+use midly::{Smf, Timing};
+
+use crate::{
+    analysis::{self, DiffOp, Timeline},
+    midi_action::MidiAction,
+    note::Note,
+    tempo_map::TempoMap,
+};
+
+/// A comparação de um arquivo com o de referência.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Grade {
+    /// As diferenças, da referência para o arquivo do aluno.
+    pub ops: Vec<DiffOp>,
+    /// Quantos eventos da referência, entre notas e mudanças de tempo, há.
+    pub expected: usize,
+    /// Quantos eventos o arquivo do aluno tem.
+    pub played: usize,
+    /// Quantos eventos são iguais nos dois.
+    pub matched: usize,
+    /// Quantas notas trocadas são a mesma nota em outra oitava.
+    pub octave_errors: usize,
+}
+
+impl Grade {
+    /// Quanto, em semínimas, o começo de uma nota pode fugir do de outra para que as
+    /// duas ainda contem como tocadas juntas: uma fusa.
+    const JITTER: (u64, u64) = (1, 8);
+
+    /// Compara o arquivo do aluno com o de referência.
+    pub fn compare(reference: &Smf<'_>, submission: &Smf<'_>) -> Self {
+        let (expected, played) = (sequence(reference), sequence(submission));
+        let ops = analysis::diff(&expected, &played);
+        let matched = ops.iter().filter(|op| !op.is_change()).count();
+
+        Self {
+            octave_errors: octave_errors(&ops),
+            ops,
+            expected: expected.len(),
+            played: played.len(),
+            matched,
+        }
+    }
+
+    /// A semelhança, de 0 a 100: a proporção de eventos iguais nos dois arquivos, com as
+    /// notas na oitava errada valendo metade.
+    pub fn score(&self) -> f64 {
+        let total = self.expected + self.played;
+        if total == 0 {
+            return 100.0;
+        }
+
+        (2 * self.matched + self.octave_errors) as f64 * 100.0 / total as f64
+    }
+
+    /// As diferenças, uma por linha, com até `context` eventos iguais em volta de cada uma.
+    pub fn report(&self, context: usize) -> String {
+        analysis::render(&self.ops, Some(context))
+    }
+}
+
+/// As notas e as mudanças de tempo do arquivo, de todos os canais, em ordem.
+pub fn sequence(smf: &Smf<'_>) -> Vec<MidiAction> {
+    let timeline = Timeline::from_smf(smf);
+    let jitter = timeline.tpqn * Grade::JITTER.0 / Grade::JITTER.1;
+
+    let mut events: Vec<(u64, MidiAction)> = Vec::with_capacity(timeline.notes.len());
+    if let Timing::Metrical(tpqn) = smf.header.timing {
+        let tempo = TempoMap::from_tracks(&smf.tracks, tpqn);
+        for change in tempo.changes() {
+            let bpm = (60_000_000.0 / change.mspqn.as_int() as f64).round() as u16;
+            events.push((change.tick, MidiAction::ChangeBPM(bpm)));
+        }
+    }
+
+    // As notas quase juntas ficam com o começo da primeira delas
+    let mut group_start = None;
+    for note in &timeline.notes {
+        let start = match group_start {
+            Some(start) if note.start - start <= jitter => start,
+            _ => note.start,
+        };
+        group_start = Some(start);
+        events.push((start, MidiAction::PlayNote(note.key)));
+    }
+    // As mudanças de tempo vêm antes das notas do mesmo instante
+    events.sort_by_key(|(tick, action)| {
+        let key = match action {
+            MidiAction::PlayNote(key) => *key as u16 + 1,
+            _ => 0,
+        };
+        (*tick, key)
+    });
+
+    events.into_iter().map(|(_, action)| action).collect()
+}
+
+/// Quantas notas removidas são seguidas de uma inserção da mesma nota em outra oitava.
+fn octave_errors(ops: &[DiffOp]) -> usize {
+    ops.windows(2)
+        .filter(|pair| {
+            let [DiffOp::Delete(MidiAction::PlayNote(a)), DiffOp::Insert(MidiAction::PlayNote(b))] =
+                pair
+            else {
+                return false;
+            };
+            a != b && a % Note::SEMITONES == b % Note::SEMITONES
+        })
+        .count()
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::midi_action::TimedAction;
+
+    #[test]
+    fn timing_jitter_does_not_count() {
+        let timed = |notes: &[(u64, u8)]| -> Vec<TimedAction> {
+            notes
+                .iter()
+                .map(|&(tick, key)| TimedAction {
+                    tick,
+                    time: Duration::ZERO,
+                    channel: 0,
+                    action: MidiAction::PlayNote(key),
+                    source_range: 0..0,
+                })
+                .collect()
+        };
+        let reference = timed(&[(0, 60), (0, 64), (480, 62), (960, 64)]);
+        // The chord notes come swapped and a little late, the last note an octave up
+        let submission = timed(&[(10, 64), (20, 60), (470, 62), (965, 76)]);
+
+        let grade = Grade::compare(
+            &TimedAction::as_track(&reference),
+            &TimedAction::as_track(&submission),
+        );
+
+        assert_eq!((grade.matched, grade.octave_errors), (4, 1));
+        // The tempo at the start, three notes, and half of the octave error
+        assert_eq!(grade.score(), 90.0);
+        assert!(grade.report(0).contains("- note 64\n+ note 76"));
+        assert_eq!(
+            Grade::compare(
+                &TimedAction::as_track(&reference),
+                &TimedAction::as_track(&reference)
+            )
+            .score(),
+            100.0
+        );
+    }
+}
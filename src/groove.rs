@@ -0,0 +1,249 @@
+//! Grooves: pequenos deslocamentos de tempo e de velocidade em cada semicolcheia,
+//! repetidos pela música toda, para tirar a rigidez da grade.
+//!
+//! Um groove pode ser lido de um arquivo TOML como este:
+//!
+//! ```toml
+//! # Swing de colcheias, com as semicolcheias de dentro acompanhando
+//! timing = [0, 38, 76, 38]
+//! velocity = [6, -4, 0, -4]
+//! ```
+//!
+//! Cada lista tem um valor por semicolcheia, começando no tempo 1 do compasso, e se
+//! repete quando acaba; uma lista vazia não muda nada. Os deslocamentos de tempo são
+//! em ticks de `MidiAction::D_TPQN` (uma semicolcheia tem 120) e os de velocidade são
+//! somados à velocidade de cada nota.
+
+use std::{error::Error, fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    midi_action::{MidiAction, TimedAction},
+    midi_value::MidiValue,
+    tempo_map::TempoMap,
+    ticks,
+};
+
+/// Os deslocamentos de cada semicolcheia.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Groove {
+    /// Quantos ticks cada semicolcheia é adiantada (negativo) ou atrasada (positivo).
+    #[serde(default)]
+    pub timing: Vec<i32>,
+    /// Quanto é somado à velocidade das notas em cada semicolcheia.
+    #[serde(default)]
+    pub velocity: Vec<i16>,
+}
+
+impl Groove {
+    /// Ticks em uma semicolcheia, com as 480 por semínima do `MidiAction::D_TPQN`.
+    pub const STEP: u64 = 120;
+
+    /// Os grooves prontos: reto, swing de 55%, 60% e 66% e atrasado.
+    pub fn built_in() -> [Self; 5] {
+        [
+            Self::default(),
+            Self::swing(55),
+            Self::swing(60),
+            Self::swing(66),
+            Self::laid_back(),
+        ]
+    }
+
+    /// Swing de colcheias: a colcheia do contratempo cai em `percent`% do tempo, em vez
+    /// de na metade, e as semicolcheias entre elas se ajustam na mesma proporção.
+    pub fn swing(percent: u8) -> Self {
+        let beat = MidiAction::D_TPQN.as_int() as u64;
+        let percent = percent.clamp(50, 70) as u64;
+        // Cada deslocamento é arredondado a partir da posição exata, não do anterior
+        let offbeat = ticks::scale(beat, percent, 100) as i32 - beat as i32 / 2;
+        let sixteenth = ticks::scale(beat, percent, 200) as i32 - beat as i32 / 4;
+        Self {
+            timing: vec![0, sixteenth, offbeat, sixteenth],
+            velocity: Vec::new(),
+        }
+    }
+
+    /// Tudo um pouco atrás do tempo, mais ainda fora dele, e os contratempos mais leves.
+    pub fn laid_back() -> Self {
+        Self {
+            timing: vec![8, 14, 12, 14],
+            velocity: vec![0, -8, -4, -8],
+        }
+    }
+
+    /// Lê um groove de um texto em TOML, recusando deslocamentos que trocariam a ordem
+    /// das notas ou velocidades fora do MIDI.
+    pub fn from_toml(text: &str) -> Result<Self, Box<dyn Error>> {
+        let groove: Self = toml::from_str(text)?;
+        let step = Self::STEP as i32;
+        if let Some(&offset) = groove.timing.iter().find(|offset| offset.abs() >= step) {
+            return Err(format!("timing offset {offset} is not within ±{} ticks", step - 1).into());
+        }
+        // Uma semicolcheia não pode passar da seguinte
+        let next = groove.timing.iter().cycle().skip(1);
+        if groove
+            .timing
+            .iter()
+            .zip(next)
+            .any(|(from, to)| from - to >= step)
+        {
+            return Err("timing offsets would swap the order of the notes".into());
+        }
+        if let Some(&offset) = groove.velocity.iter().find(|offset| offset.abs() > 127) {
+            return Err(format!("velocity offset {offset} is not within ±127").into());
+        }
+
+        Ok(groove)
+    }
+
+    /// Lê um groove de um arquivo TOML.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        Self::from_toml(&fs::read_to_string(path)?)
+    }
+
+    /// Se o groove não muda nada.
+    pub fn is_straight(&self) -> bool {
+        self.timing.iter().all(|&offset| offset == 0)
+            && self.velocity.iter().all(|&offset| offset == 0)
+    }
+
+    /// O valor da lista na semicolcheia dada, repetindo a lista.
+    fn at<T: Copy + Default>(list: &[T], step: u64) -> T {
+        if list.is_empty() {
+            T::default()
+        } else {
+            list[(step % list.len() as u64) as usize]
+        }
+    }
+
+    /// O tick para onde vai o tick dado.
+    ///
+    /// Entre duas semicolcheias, o deslocamento é interpolado, então notas fora da
+    /// grade, como as de quiálteras, andam junto com as vizinhas sem trocar de ordem.
+    fn shift(&self, tick: u64) -> u64 {
+        let (step, within) = (tick / Self::STEP, tick % Self::STEP);
+        let from = Self::at(&self.timing, step) as i64;
+        let to = Self::at(&self.timing, step + 1) as i64;
+        let offset = from + (to - from) * within as i64 / Self::STEP as i64;
+
+        tick.saturating_add_signed(offset)
+    }
+
+    /// O deslocamento de velocidade da semicolcheia mais próxima do tick dado.
+    fn velocity_offset(&self, tick: u64) -> i16 {
+        Self::at(&self.velocity, (tick + Self::STEP / 2) / Self::STEP)
+    }
+
+    /// Desloca as ações no tempo e muda a velocidade das notas.
+    ///
+    /// As mudanças de andamento ficam onde estão. As notas sem acento ganham um, com a
+    /// velocidade padrão mais o deslocamento, e os acordes das cifras não mudam.
+    pub fn apply(&self, timed: Vec<TimedAction>) -> Vec<TimedAction> {
+        if self.is_straight() {
+            return timed;
+        }
+
+        let map = TempoMap::from_timed(&timed);
+        let mut grooved: Vec<TimedAction> = Vec::with_capacity(timed.len());
+        // O acento ainda não usado, pela posição no resultado e o tick original
+        let mut accent = None;
+        for mut timed in timed {
+            let tick = timed.tick;
+            let offset = self.velocity_offset(tick);
+            let velocity = |velocity: u8| {
+                MidiValue::clamped((velocity as i16 + offset).max(0) as u16).as_int()
+            };
+            if !matches!(timed.action, MidiAction::ChangeBPM(_)) {
+                timed.tick = self.shift(tick);
+                timed.time = map.time_at(timed.tick);
+            }
+
+            match &mut timed.action {
+                MidiAction::Accent(_) => accent = Some((grooved.len(), tick)),
+                MidiAction::PlayNote(_) => match accent.take() {
+                    Some((index, accented)) if accented == tick => {
+                        if let MidiAction::Accent(accented) = &mut grooved[index].action {
+                            *accented = velocity(*accented);
+                        }
+                    }
+                    _ if offset != 0 => grooved.push(TimedAction {
+                        action: MidiAction::Accent(velocity(MidiAction::D_VELOCITY.as_int())),
+                        ..timed.clone()
+                    }),
+                    _ => (),
+                },
+                MidiAction::ChordNote {
+                    velocity: played, ..
+                } => *played = velocity(*played),
+                _ => (),
+            }
+            grooved.push(timed);
+        }
+        grooved.sort_by_key(|timed| timed.tick);
+
+        grooved
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn eighths() -> Vec<TimedAction> {
+        // Four eighth notes, the third with a written accent
+        let mut timed = TimedAction::from_actions(&[
+            MidiAction::PlayNote(60),
+            MidiAction::PlayNote(62),
+            MidiAction::Accent(100),
+            MidiAction::PlayNote(64),
+            MidiAction::PlayNote(65),
+        ]);
+        for (index, timed) in timed.iter_mut().enumerate() {
+            timed.tick = [0, 240, 480, 480, 720][index];
+        }
+        timed
+    }
+
+    #[test]
+    fn swing_delays_the_offbeats() {
+        let grooved = Groove::swing(66).apply(eighths());
+
+        let notes: Vec<(u64, &MidiAction)> = grooved
+            .iter()
+            .map(|timed| (timed.tick, &timed.action))
+            .collect();
+        assert_eq!(
+            notes,
+            [
+                (0, &MidiAction::PlayNote(60)),
+                (317, &MidiAction::PlayNote(62)),
+                (480, &MidiAction::Accent(100)),
+                (480, &MidiAction::PlayNote(64)),
+                (797, &MidiAction::PlayNote(65)),
+            ]
+        );
+        assert_eq!(Groove::default().apply(eighths()), eighths());
+    }
+
+    #[test]
+    fn imported_grooves_change_velocities() {
+        let groove = Groove::from_toml("timing = [0, 0, -10]\nvelocity = [20, 0, -30, 0]").unwrap();
+
+        let grooved = groove.apply(eighths());
+
+        let accents: Vec<(u64, u8)> = grooved
+            .iter()
+            .filter_map(|timed| match timed.action {
+                MidiAction::Accent(velocity) => Some((timed.tick, velocity)),
+                _ => None,
+            })
+            .collect();
+        // Only the third 16th of the timing cycle moves, and the written accent gets its offset
+        assert_eq!(accents, [(0, 83), (230, 33), (480, 120), (720, 33)]);
+        assert!(Groove::from_toml("timing = [120]").is_err());
+        assert!(Groove::from_toml("timing = [100, -30]").is_err());
+        assert!(Groove::from_toml("velocity = [128]").is_err());
+    }
+}
@@ -0,0 +1,239 @@
+use std::{fmt, ops::RangeInclusive};
+
+use crate::{
+    automation::{Lane, Parameter},
+    chord::Voicing,
+    text_to_midi::State,
+};
+
+/// Cabeçalho opcional do texto, que define o estado inicial da música.
+///
+/// São as primeiras linhas do texto que começam com `@`, cada uma com uma ou mais
+/// entradas `@chave=valor`, como em `@bpm=140 @instrument=24 @octave=5 @volume= 90`.
+///
+/// Além do estado inicial, `@accent=3` acentua as notas de 3 em 3 tempos (veja `Accents`)
+/// e `@voicing=open` escolhe como as cifras entre aspas são tocadas (veja `chord`).
+/// As entradas `@lane.volume=0:100, 8:40` guardam as curvas de automação (veja `automation`).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Header {
+    /// BPM inicial.
+    pub bpm: Option<u16>,
+    /// Instrumento inicial.
+    pub instrument: Option<u8>,
+    /// Oitava inicial.
+    pub octave: Option<u8>,
+    /// Volume inicial.
+    pub volume: Option<u16>,
+    /// De quantos em quantos tempos as notas são acentuadas.
+    pub accent: Option<u8>,
+    /// A distribuição das notas dos acordes das cifras.
+    pub voicing: Option<Voicing>,
+    /// As curvas de automação, uma por parâmetro.
+    pub lanes: Vec<Lane>,
+    /// As entradas que não puderam ser lidas, e foram ignoradas.
+    pub errors: Vec<HeaderError>,
+}
+
+/// Uma entrada inválida do cabeçalho.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HeaderError {
+    /// A entrada, sem o `@`.
+    pub entry: String,
+    /// O que há de errado com ela.
+    pub reason: &'static str,
+}
+
+impl fmt::Display for HeaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "header entry @{}: {}", self.entry, self.reason)
+    }
+}
+
+impl std::error::Error for HeaderError {}
+
+impl Header {
+    /// Separa o cabeçalho do resto do texto.
+    pub fn parse(text: &str) -> (Self, &str) {
+        let mut header = Self::default();
+        let mut body = text;
+
+        while body.trim_start_matches([' ', '\t']).starts_with('@') {
+            let (line, rest) = body.split_once('\n').unwrap_or((body, ""));
+            for entry in line.split('@').map(str::trim).filter(|e| !e.is_empty()) {
+                if let Err(reason) = header.set(entry) {
+                    header.errors.push(HeaderError {
+                        entry: entry.to_string(),
+                        reason,
+                    });
+                }
+            }
+            body = rest;
+        }
+
+        (header, body)
+    }
+
+    /// Lê uma entrada `chave=valor`.
+    fn set(&mut self, entry: &str) -> Result<(), &'static str> {
+        let (key, value) = entry.split_once('=').ok_or("expected key=value")?;
+        let value = value.trim();
+
+        match key.trim() {
+            "bpm" => self.bpm = Some(parse_in(value, State::LIMITS.bpm)?),
+            "instrument" => self.instrument = Some(parse_in(value, 0..=127)?),
+            "octave" => self.octave = Some(parse_in(value, State::LIMITS.octave)?),
+            "volume" => self.volume = Some(parse_in(value, State::LIMITS.volume)?),
+            "accent" => self.accent = Some(parse_in(value, 1..=16)?),
+            "voicing" => self.voicing = Some(value.parse()?),
+            key => {
+                let parameter = key
+                    .strip_prefix("lane.")
+                    .and_then(Parameter::from_name)
+                    .ok_or("unknown key")?;
+                let lane = Lane::parse(parameter, value)?;
+                self.lanes.retain(|lane| lane.parameter != parameter);
+                self.lanes.push(lane);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Se o cabeçalho não define nada.
+    pub fn is_empty(&self) -> bool {
+        self.bpm.is_none()
+            && self.instrument.is_none()
+            && self.octave.is_none()
+            && self.volume.is_none()
+            && self.accent.is_none()
+            && self.voicing.is_none()
+            && self.lanes.is_empty()
+    }
+
+    /// O texto com a entrada `@key=value` do cabeçalho trocada, ou retirada sem `value`.
+    ///
+    /// A entrada nova vai em uma linha só dela, no fim do cabeçalho; as outras entradas
+    /// continuam nas suas linhas, separadas por um espaço.
+    pub fn with_entry(text: &str, key: &str, value: Option<&str>) -> String {
+        let (_, body) = Self::parse(text);
+        let header = &text[..text.len() - body.len()];
+        let mut lines: Vec<String> = header
+            .lines()
+            .map(|line| {
+                line.split('@')
+                    .map(str::trim)
+                    .filter(|entry| !entry.is_empty())
+                    .filter(|entry| {
+                        entry
+                            .split_once('=')
+                            .is_none_or(|(name, _)| name.trim() != key)
+                    })
+                    .map(|entry| format!("@{entry}"))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .filter(|line| !line.is_empty())
+            .collect();
+        if let Some(value) = value {
+            lines.push(format!("@{key}={value}"));
+        }
+
+        let mut edited = lines.join("\n");
+        if !edited.is_empty() {
+            edited.push('\n');
+        }
+        edited.push_str(body);
+        edited
+    }
+
+    /// Sobrescreve o estado com os valores definidos no cabeçalho.
+    pub fn apply(&self, state: &mut State) {
+        if let Some(bpm) = self.bpm {
+            state.bpm = bpm;
+        }
+        if let Some(instrument) = self.instrument {
+            state.instrument = instrument;
+        }
+        if let Some(octave) = self.octave {
+            state.octave = octave;
+        }
+        if let Some(volume) = self.volume {
+            state.volume = volume;
+        }
+    }
+}
+
+/// Lê um número dentro da faixa dada.
+fn parse_in<T>(value: &str, range: RangeInclusive<T>) -> Result<T, &'static str>
+where
+    T: std::str::FromStr + PartialOrd,
+{
+    let value: T = value.parse().map_err(|_| "not a number")?;
+    if !range.contains(&value) {
+        return Err("out of range");
+    }
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_entries() {
+        let (header, body) = Header::parse(
+            "@bpm=140 @instrument=24\n@octave=5 @volume= 90 @accent=3 @voicing=drop2\nCDE",
+        );
+
+        assert_eq!(
+            header,
+            Header {
+                bpm: Some(140),
+                instrument: Some(24),
+                octave: Some(5),
+                volume: Some(90),
+                accent: Some(3),
+                voicing: Some(Voicing::Drop2),
+                lanes: Vec::new(),
+                errors: Vec::new(),
+            }
+        );
+        assert_eq!(body, "CDE");
+    }
+
+    #[test]
+    fn lanes_are_kept_in_the_header() {
+        let text = "@bpm=90 @lane.pan=0:0\nCDE";
+
+        let (header, _) = Header::parse(text);
+        assert_eq!(header.lanes[0].to_string(), "0:0");
+
+        let edited = Header::with_entry(text, "lane.pan", Some("0:0, 4:127"));
+        assert_eq!(edited, "@bpm=90\n@lane.pan=0:0, 4:127\nCDE");
+        assert_eq!(
+            Header::with_entry(&edited, "lane.pan", None),
+            "@bpm=90\nCDE"
+        );
+        assert_eq!(Header::with_entry("CDE", "bpm", Some("80")), "@bpm=80\nCDE");
+    }
+
+    #[test]
+    fn no_header() {
+        let (header, body) = Header::parse("CDE\n@bpm=140");
+
+        assert!(header.is_empty());
+        assert_eq!(body, "CDE\n@bpm=140");
+    }
+
+    #[test]
+    fn invalid_entries_are_reported() {
+        let (header, body) = Header::parse("@bpm=0 @tempo=3 @volume=loud @octave=2");
+
+        assert_eq!(header.octave, Some(2));
+        assert_eq!(header.bpm, None);
+        let reasons: Vec<_> = header.errors.iter().map(|e| e.reason).collect();
+        assert_eq!(reasons, ["out of range", "unknown key", "not a number"]);
+        assert_eq!(body, "");
+    }
+}
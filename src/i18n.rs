@@ -0,0 +1,689 @@
+//! Textos da interface em cada idioma.
+
+use std::fmt;
+
+/// Um texto em cada idioma, na ordem de `Language::ALL`.
+pub type Translated = [&'static str; 2];
+
+/// Os idiomas da interface.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Language {
+    #[default]
+    English,
+    Portuguese,
+}
+
+impl Language {
+    pub const ALL: [Self; 2] = [Self::English, Self::Portuguese];
+
+    /// A versão do texto neste idioma.
+    pub const fn pick(self, texts: [&str; 2]) -> &str {
+        texts[self as usize]
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.pick(["English", "Português"]))
+    }
+}
+
+/// Os textos da interface.
+///
+/// Os que têm `{}` devem ser preenchidos com `Key::fill`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Key {
+    Open,
+    Examples,
+    SaveText,
+    SaveTextHelp,
+    Play,
+    Save,
+    DumpEvents,
+    DumpEventsHelp,
+    ExportClips,
+    Markers,
+    MarkersHelp,
+    ExportClipsHelp,
+    ExportStems,
+    ExportStemsHelp,
+    ImportEvents,
+    ImportEventsHelp,
+    CopyMidi,
+    CopyMidiHelp,
+    PasteMidi,
+    PasteMidiHelp,
+    Bpm,
+    Volume,
+    Instrument,
+    AuditionHelp,
+    Lyrics,
+    Strict,
+    StrictHelp,
+    BaseLength,
+    BaseLengthHelp,
+    Quarter,
+    Eighth,
+    Sixteenth,
+    ResetSettings,
+    ResetSettingsHelp,
+    Mixer,
+    Settings,
+    Compare,
+    Find,
+    Grid,
+    GridHelp,
+    GridSteps,
+    PianoRoll,
+    PianoRollEmpty,
+    FollowPlayhead,
+    Zoom,
+    ZoomHelp,
+    Automation,
+    AutomationHelp,
+    Resolution,
+    ClearLane,
+    Keys,
+    Fit,
+    DynamicsLane,
+    DynamicsLaneHelp,
+    AllChannels,
+    Log,
+    LogHelp,
+    CopyLog,
+    ClearLog,
+    NoteNames,
+    Tuning,
+    Latency,
+    PlaybackRate,
+    PlaybackRateHelp,
+    CountIn,
+    CountInOff,
+    OneBar,
+    TwoBars,
+    SendClock,
+    SendClockHelp,
+    AudioClick,
+    AudioClickHelp,
+    ErrorSound,
+    ErrorSoundHelp,
+    CaptureSent,
+    CaptureSentHelp,
+    SaveCapture,
+    FollowClock,
+    FollowClockHelp,
+    Language,
+    Converting,
+    Cancel,
+    PastedMidi,
+    Imported,
+    MixerEmpty,
+    Tracks,
+    Track,
+    Channel,
+    Mute,
+    Solo,
+    Pan,
+    VelocityCurve,
+    FadeIn,
+    FadeOut,
+    FadeHelp,
+    Scale,
+    ScaleHelp,
+    ScaleOff,
+    Major,
+    NaturalMinor,
+    HarmonicMinor,
+    MajorPentatonic,
+    MinorPentatonic,
+    Blues,
+    NoteRange,
+    NoteRangeHelp,
+    TwentyFiveKeys,
+    AllKeys,
+    FoldedNotes,
+    EchoRepeats,
+    EchoDelay,
+    EchoHelp,
+    Harmony,
+    HarmonyOff,
+    Third,
+    Fifth,
+    Octave,
+    Below,
+    Tonality,
+    Chromatic,
+    HarmonyHelp,
+    Strum,
+    StrumOff,
+    GuitarDown,
+    GuitarUp,
+    AlternatingStrum,
+    Harp,
+    CustomStrum,
+    StrumVelocity,
+    StrumHelp,
+    Groove,
+    Straight,
+    Swing,
+    LaidBack,
+    ImportGroove,
+    Preset,
+    PresetHelp,
+    LoadPreset,
+    SavePreset,
+    ExportReview,
+    FileFormat,
+    Timing,
+    TempoEvents,
+    EstimatedDuration,
+    PreviewStart,
+    GrooveHelp,
+    GmReset,
+    BankSelect,
+    ModuleResetHelp,
+    Panic,
+    PanicHelp,
+    OutputPort,
+    Automatic,
+    Refresh,
+    OutputPortHelp,
+    Thru,
+    ThruHelp,
+    KeepChannel,
+    ChannelRemap,
+    ChannelRemapHelp,
+    AllChannelsTo,
+    ResetRemap,
+    PatchMap,
+    PatchMapHelp,
+    PatchMapLoaded,
+    NoPatchMap,
+    LoadPatchMap,
+    UnloadPatchMap,
+    CompareWindow,
+    Compose,
+    ComposeHelp,
+    AddFile,
+    AddTab,
+    StartBar,
+    PartBars,
+    SaveComposition,
+    Live,
+    LiveHelp,
+    StopClips,
+    Playlist,
+    PlaylistHelp,
+    Gap,
+    RepeatOff,
+    RepeatOne,
+    RepeatAll,
+    Shuffle,
+    ShuffleHelp,
+    PreviousItem,
+    NextItem,
+    Pads,
+    PadsHelp,
+    Load,
+    UseCurrent,
+    PlayAThenB,
+    Diff,
+    ReplaceWith,
+    Regex,
+    Replace,
+    ReplaceAll,
+    Matches,
+    Close,
+    NewTab,
+    Untitled,
+    UnsavedChanges,
+    CloseAndDiscard,
+    Discard,
+    Bytes,
+    Warning,
+    Editor,
+    Remove,
+    AddPoint,
+    MoveUp,
+    MoveDown,
+    PreviousPage,
+    NextPage,
+    HighContrast,
+    HighContrastHelp,
+    Keyboard,
+    KeyboardHelp,
+    MaxPlayback,
+    MaxPlaybackHelp,
+    Stop,
+    AlreadyPlaying,
+    Waiting,
+    WhilePlaying,
+    OverlapReject,
+    OverlapQueue,
+    OverlapReplace,
+    WhilePlayingHelp,
+}
+
+impl Key {
+    /// O texto neste idioma.
+    pub const fn text(self, language: Language) -> &'static str {
+        language.pick(match self {
+            Self::Open => ["Open", "Abrir"],
+            Self::Examples => ["Examples", "Exemplos"],
+            Self::SaveText => ["Save text", "Salvar texto"],
+            Self::SaveTextHelp => [
+                "Save the text of the current tab",
+                "Salva o texto da aba atual",
+            ],
+            Self::Play => ["Play", "Tocar"],
+            Self::Save => ["Save", "Salvar"],
+            Self::DumpEvents => ["Dump events", "Exportar eventos"],
+            Self::DumpEventsHelp => [
+                "Save the timed events as JSON or CSV",
+                "Salva os eventos com os seus tempos em JSON ou CSV",
+            ],
+            Self::ExportClips => ["Export clips", "Exportar trechos"],
+            Self::ExportClipsHelp => [
+                "Save every tab in one MIDI file, one after another, each starting at a marker with its name",
+                "Salva todas as abas em um só arquivo MIDI, uma depois da outra, cada uma começando em um marcador com o seu nome",
+            ],
+            Self::ExportStems => ["Export stems", "Exportar stems"],
+            Self::ExportStemsHelp => [
+                "Save one MIDI file per channel in a folder, to bring each voice into other software",
+                "Salva um arquivo MIDI por canal em uma pasta, para levar cada voz para outros programas",
+            ],
+            Self::Markers => ["Jump to:", "Pular para:"],
+            Self::MarkersHelp => [
+                "The sections marked in the text as ==Name==",
+                "As seções marcadas no texto como ==Nome==",
+            ],
+            Self::ImportEvents => ["Import events", "Importar eventos"],
+            Self::ImportEventsHelp => [
+                "Load a JSON event list to play",
+                "Carrega uma lista de eventos em JSON para tocar",
+            ],
+            Self::CopyMidi => ["Copy MIDI", "Copiar MIDI"],
+            Self::CopyMidiHelp => [
+                "Copy the generated MIDI as base64",
+                "Copia o MIDI gerado em base64",
+            ],
+            Self::PasteMidi => ["Paste MIDI", "Colar MIDI"],
+            Self::PasteMidiHelp => [
+                "Load a MIDI copied as base64",
+                "Carrega um MIDI copiado em base64",
+            ],
+            Self::Bpm => ["BPM", "BPM"],
+            Self::Volume => ["Volume", "Volume"],
+            Self::Instrument => ["Instrument", "Instrumento"],
+            Self::AuditionHelp => ["Audition the instrument", "Ouvir o instrumento"],
+            Self::Grid => ["Step grid", "Grade de passos"],
+            Self::GridHelp => [
+                "Toggle the notes of the text on a grid; the text changes with it, and the grid with the text",
+                "Liga e desliga as notas do texto em uma grade; o texto muda junto, e a grade com o texto",
+            ],
+            Self::GridSteps => ["Steps {}", "Passos {}"],
+            Self::PianoRoll => ["Piano roll", "Piano roll"],
+            Self::PianoRollEmpty => [
+                "Play something to see its notes here",
+                "Toque algo para ver as notas aqui",
+            ],
+            Self::FollowPlayhead => ["Follow playback", "Acompanhar a reprodução"],
+            Self::Zoom => ["Zoom", "Zoom"],
+            Self::Automation => ["Automation", "Automação"],
+            Self::AutomationHelp => [
+                "Click to add a point, drag to move it and right-click to remove it. The curves are saved in the header of the text",
+                "Clique para acrescentar um ponto, arraste para movê-lo e use o botão direito para tirá-lo. As curvas ficam salvas no cabeçalho do texto",
+            ],
+            Self::Resolution => ["every", "a cada"],
+            Self::ClearLane => ["Clear", "Limpar"],
+            Self::ZoomHelp => [
+                "Ctrl+scroll over the notes also zooms",
+                "Ctrl+rolagem sobre as notas também muda o zoom",
+            ],
+            Self::Keys => ["Keys:", "Teclas:"],
+            Self::Fit => ["Fit", "Ajustar"],
+            Self::DynamicsLane => ["Dynamics", "Dinâmica"],
+            Self::DynamicsLaneHelp => [
+                "Below the notes, each note's velocity as a stem and the channel volume (CC7) as a line",
+                "Embaixo das notas, a velocidade de cada nota como uma haste e o volume do canal (CC7) como uma linha",
+            ],
+            Self::AllChannels => ["All channels", "Todos os canais"],
+            Self::Lyrics => ["Lyrics", "Letra"],
+            Self::Strict => ["Strict", "Estrito"],
+            Self::StrictHelp => [
+                "Refuse texts with characters that mean nothing, instead of ignoring them",
+                "Recusa textos com caracteres que não significam nada, em vez de ignorá-los",
+            ],
+            Self::BaseLength => ["Note length", "Duração das notas"],
+            Self::BaseLengthHelp => [
+                "How long each note letter and rest lasts, until an L:1/8 in the text changes it",
+                "Quanto dura cada letra de nota e cada pausa, até um L:1/8 no texto mudar",
+            ],
+            Self::Quarter => ["Quarter", "Semínima"],
+            Self::Eighth => ["Eighth", "Colcheia"],
+            Self::Sixteenth => ["Sixteenth", "Semicolcheia"],
+            Self::ResetSettings => ["Reset", "Restaurar"],
+            Self::ResetSettingsHelp => [
+                "Put the BPM, volume, instrument, lyrics, strict mode and note length back to the defaults",
+                "Volta o BPM, o volume, o instrumento, a letra, o modo estrito e a duração das notas para os padrões",
+            ],
+            Self::Mixer => ["Mixer", "Mixer"],
+            Self::Settings => ["Settings", "Configurações"],
+            Self::Compare => ["A/B", "A/B"],
+            Self::Find => ["Find", "Buscar"],
+            Self::Log => ["Log", "Registro"],
+            Self::LogHelp => [
+                "What the converter and the player did, to attach to bug reports (more with --verbose)",
+                "O que o conversor e o player fizeram, para anexar a relatos de bugs (mais com --verbose)",
+            ],
+            Self::CopyLog => ["Copy", "Copiar"],
+            Self::ClearLog => ["Clear", "Limpar"],
+            Self::NoteNames => ["Note names", "Nomes das notas"],
+            Self::Tuning => ["Tuning", "Afinação"],
+            Self::Latency => ["Latency", "Latência"],
+            Self::PlaybackRate => ["Playback speed", "Velocidade de reprodução"],
+            Self::PlaybackRateHelp => [
+                "Play slower or faster without changing the tempo of saved files",
+                "Toca mais devagar ou mais rápido sem mudar o andamento dos arquivos salvos",
+            ],
+            Self::CountIn => ["Count-in", "Contagem"],
+            Self::CountInOff => ["Off", "Desligada"],
+            Self::OneBar => ["1 bar", "1 compasso"],
+            Self::TwoBars => ["2 bars", "2 compassos"],
+            Self::SendClock => ["Send MIDI clock", "Enviar clock MIDI"],
+            Self::SendClockHelp => [
+                "Send Start, Stop and 24 clocks per quarter note so external sequencers follow the playback",
+                "Envia Start, Stop e 24 clocks por semínima para sequenciadores externos acompanharem a reprodução",
+            ],
+            Self::AudioClick => ["Audio metronome", "Metrônomo no áudio"],
+            Self::ErrorSound => ["Error sound", "Som de erro"],
+            Self::ErrorSoundHelp => [
+                "Play a short buzz through the MIDI output when the conversion fails or the text has problems, which also flash in the editor",
+                "Toca um zumbido curto pela saída MIDI quando a conversão falha ou o texto tem problemas, que também piscam no editor",
+            ],
+            Self::AudioClickHelp => [
+                "Click on every beat through the computer's speakers, for when the synth is remote or silent",
+                "Clica a cada tempo pelos alto-falantes do computador, para quando o sintetizador está longe ou mudo",
+            ],
+            Self::CaptureSent => ["Capture", "Capturar"],
+            Self::CaptureSentHelp => [
+                "Record every message sent while playing, at the moment it left, to save as MIDI and check the timing",
+                "Grava cada mensagem enviada durante a reprodução, no momento em que saiu, para salvar como MIDI e conferir o tempo",
+            ],
+            Self::SaveCapture => ["Save capture ({} events)", "Salvar captura ({} eventos)"],
+            Self::FollowClock => ["Follow external clock", "Seguir clock externo"],
+            Self::FollowClockHelp => [
+                "Wait for Start and advance with the clock received on the first MIDI input",
+                "Espera o Start e anda com o clock recebido na primeira entrada MIDI",
+            ],
+            Self::Language => ["Language", "Idioma"],
+            Self::Converting => ["Converting", "Convertendo"],
+            Self::Cancel => ["Cancel", "Cancelar"],
+            Self::PastedMidi => ["Pasted MIDI", "MIDI colado"],
+            Self::Imported => ["Imported {}", "{} importado"],
+            Self::MixerEmpty => [
+                "Play or save to see the channels in use.",
+                "Toque ou salve para ver os canais usados.",
+            ],
+            Self::Tracks => ["Tracks", "Trilhas"],
+            Self::Track => ["Track {}", "Trilha {}"],
+            Self::Channel => ["Ch {}", "Can {}"],
+            Self::Mute => ["M", "M"],
+            Self::Solo => ["S", "S"],
+            Self::Pan => ["Pan", "Pan"],
+            Self::VelocityCurve => ["Velocity curve", "Curva de velocidade"],
+            Self::FadeIn => ["Fade in", "Entrada"],
+            Self::FadeOut => ["Fade out", "Saída"],
+            Self::FadeHelp => [
+                "Beats over which the volume rises at the start and falls at the end",
+                "Tempos em que o volume sobe no começo e desce no fim",
+            ],
+            Self::Scale => ["Scale", "Escala"],
+            Self::ScaleHelp => [
+                "Moves every note to the nearest note of the scale, so random notes still sound consonant",
+                "Leva cada nota para a nota mais próxima da escala, para que notas aleatórias soem consonantes",
+            ],
+            Self::ScaleOff => ["Off", "Desligada"],
+            Self::Major => ["Major", "Maior"],
+            Self::NaturalMinor => ["Natural minor", "Menor natural"],
+            Self::HarmonicMinor => ["Harmonic minor", "Menor harmônica"],
+            Self::MajorPentatonic => ["Major pentatonic", "Pentatônica maior"],
+            Self::MinorPentatonic => ["Minor pentatonic", "Pentatônica menor"],
+            Self::Blues => ["Blues", "Blues"],
+            Self::NoteRange => ["Note range", "Faixa de notas"],
+            Self::NoteRangeHelp => [
+                "Notes outside the range are moved by octaves into it, for small keyboards",
+                "Notas fora da faixa são trazidas para dentro dela de oitava em oitava, para teclados pequenos",
+            ],
+            Self::TwentyFiveKeys => ["25 keys", "25 teclas"],
+            Self::AllKeys => ["All keys", "Todas as teclas"],
+            Self::FoldedNotes => ["{} notes folded last time", "{} notas dobradas da última vez"],
+            Self::EchoRepeats => ["Echo", "Eco"],
+            Self::EchoDelay => ["every", "a cada"],
+            Self::EchoHelp => [
+                "Repeats each note, softer each time (480 ticks = one beat)",
+                "Repete cada nota, mais fraca a cada vez (480 ticks = um tempo)",
+            ],
+            Self::Harmony => ["Harmony", "Harmonia"],
+            Self::HarmonyOff => ["Off", "Desligada"],
+            Self::Third => ["Third", "Terça"],
+            Self::Fifth => ["Fifth", "Quinta"],
+            Self::Octave => ["Octave", "Oitava"],
+            Self::Below => ["Below", "Abaixo"],
+            Self::Tonality => ["Key", "Tom"],
+            Self::Chromatic => ["None", "Nenhum"],
+            Self::HarmonyHelp => [
+                "Adds a parallel voice on channel 2, following the major scale of the key",
+                "Acrescenta uma voz paralela no canal 2, seguindo a escala maior do tom",
+            ],
+            Self::Strum => ["Strum", "Batida"],
+            Self::StrumOff => ["Off", "Desligada"],
+            Self::GuitarDown => ["Guitar, down", "Violão, para baixo"],
+            Self::GuitarUp => ["Guitar, up", "Violão, para cima"],
+            Self::AlternatingStrum => ["Alternating", "Alternada"],
+            Self::Harp => ["Harp", "Harpa"],
+            Self::CustomStrum => ["Custom", "Personalizada"],
+            Self::StrumVelocity => ["velocity", "velocidade"],
+            Self::StrumHelp => [
+                "Plays the notes of quoted chords one after the other, like a strum (480 ticks = one beat)",
+                "Toca as notas das cifras entre aspas uma depois da outra, como uma batida (480 ticks = um tempo)",
+            ],
+            Self::Groove => ["Groove", "Groove"],
+            Self::Straight => ["Straight", "Reto"],
+            Self::Swing => ["Swing {}%", "Swing {}%"],
+            Self::LaidBack => ["Laid back", "Atrasado"],
+            Self::ImportGroove => ["Import…", "Importar…"],
+            Self::Preset => ["Preset", "Preset"],
+            Self::PresetHelp => [
+                "The instrument, BPM, volume, note length and every transform, saved together in a TOML file",
+                "O instrumento, o BPM, o volume, a duração das notas e todas as transformações, salvos juntos em um arquivo TOML",
+            ],
+            Self::LoadPreset => ["Open…", "Abrir…"],
+            Self::SavePreset => ["Save…", "Salvar…"],
+            Self::ExportReview => ["Save MIDI", "Salvar MIDI"],
+            Self::FileFormat => ["Format", "Formato"],
+            Self::Timing => ["Timing", "Temporização"],
+            Self::TempoEvents => ["Tempo events", "Eventos de tempo"],
+            Self::EstimatedDuration => ["Estimated duration", "Duração estimada"],
+            Self::PreviewStart => ["▶ First {} s", "▶ Primeiros {} s"],
+            Self::GrooveHelp => [
+                "Shifts the timing and velocity of each 16th note, the same in every bar",
+                "Desloca o tempo e a velocidade de cada semicolcheia, igual em todo compasso",
+            ],
+            Self::GmReset => ["GM reset", "Reset GM"],
+            Self::BankSelect => ["Bank", "Banco"],
+            Self::ModuleResetHelp => [
+                "Sends GM System On and the bank at the start, for hardware modules",
+                "Envia o GM System On e o banco no começo, para módulos de hardware",
+            ],
+            Self::Panic => ["Panic", "Pânico"],
+            Self::PanicHelp => [
+                "Stop playback and silence stuck notes on every channel (Esc)",
+                "Para a reprodução e cala as notas presas em todos os canais (Esc)",
+            ],
+            Self::OutputPort => ["Output port", "Porta de saída"],
+            Self::Automatic => ["First available", "Primeira disponível"],
+            Self::Refresh => ["Refresh", "Atualizar"],
+            Self::OutputPortHelp => [
+                "Remembered between runs; if it is missing, the first available port is used",
+                "Lembrada entre execuções; se ela faltar, é usada a primeira porta disponível",
+            ],
+            Self::Thru => ["MIDI thru", "MIDI thru"],
+            Self::ThruHelp => [
+                "Pass what arrives at the first MIDI input straight to the output port, optionally moved to one channel",
+                "Repassa o que chega na primeira entrada MIDI direto para a porta de saída, se quiser em um só canal",
+            ],
+            Self::KeepChannel => ["Same channel", "Mesmo canal"],
+            Self::ChannelRemap => ["Channel remap", "Troca de canais"],
+            Self::ChannelRemapHelp => [
+                "Where each channel goes when playing, saving or copying, for hardware that listens to other channels",
+                "Para onde vai cada canal ao tocar, salvar ou copiar, para aparelhos que escutam outros canais",
+            ],
+            Self::AllChannelsTo => ["All to", "Todos para"],
+            Self::ResetRemap => ["No remap", "Sem troca"],
+            Self::PatchMap => ["Patch map:", "Mapa de programas:"],
+            Self::PatchMapHelp => [
+                "A TOML file that replaces GM programs with the program and bank of a non-GM device when playing, saving or copying",
+                "Um arquivo TOML que troca os programas do GM pelo programa e banco de um aparelho sem GM ao tocar, salvar ou copiar",
+            ],
+            Self::PatchMapLoaded => ["({} patches)", "({} trocas)"],
+            Self::NoPatchMap => ["GM programs", "Programas do GM"],
+            Self::LoadPatchMap => ["Load…", "Carregar…"],
+            Self::UnloadPatchMap => ["Use GM", "Usar o GM"],
+            Self::CompareWindow => ["A/B compare", "Comparação A/B"],
+            Self::Compose => ["Compose", "Composição"],
+            Self::ComposeHelp => [
+                "Puts several texts or MIDI files in one file, one track each, starting at the chosen bar. Parts at the same bar play together",
+                "Junta vários textos ou arquivos MIDI em um arquivo, uma trilha cada, começando no compasso escolhido. Partes no mesmo compasso tocam juntas",
+            ],
+            Self::AddFile => ["Add file", "Acrescentar arquivo"],
+            Self::AddTab => ["Add current tab", "Acrescentar a aba atual"],
+            Self::StartBar => ["bar", "compasso"],
+            Self::PartBars => ["{} bars", "{} compassos"],
+            Self::SaveComposition => ["Save MIDI", "Salvar MIDI"],
+            Self::Live => ["Live", "Ao vivo"],
+            Self::LiveHelp => [
+                "Click a clip to play it in a loop. While a clip plays, the next one starts at the next bar",
+                "Clique em um clipe para tocá-lo em repetição. Enquanto um clipe toca, o próximo começa no próximo compasso",
+            ],
+            Self::StopClips => ["Stop", "Parar"],
+            Self::Playlist => ["Playlist", "Playlist"],
+            Self::PlaylistHelp => [
+                "Play the files and tabs one after the other. The order can be changed while nothing plays",
+                "Toca os arquivos e as abas um depois do outro. A ordem pode ser mudada enquanto nada toca",
+            ],
+            Self::Gap => ["gap", "pausa"],
+            Self::RepeatOff => ["No repeat", "Sem repetição"],
+            Self::RepeatOne => ["Repeat one", "Repetir um"],
+            Self::RepeatAll => ["Repeat all", "Repetir todos"],
+            Self::Shuffle => ["Shuffle", "Embaralhar"],
+            Self::ShuffleHelp => [
+                "Play the items in a random order. The same number always gives the same order",
+                "Toca os itens em uma ordem aleatória. O mesmo número dá sempre a mesma ordem",
+            ],
+            Self::PreviousItem => ["Previous item", "Item anterior"],
+            Self::NextItem => ["Next item", "Próximo item"],
+            Self::Pads => ["MIDI pads from", "Pads MIDI a partir de"],
+            Self::PadsHelp => [
+                "Notes from the MIDI input launch the clips: the chosen note launches the first one, and each semitone above, the next",
+                "As notas da entrada MIDI disparam os clipes: a nota escolhida dispara o primeiro, e cada semitom acima, o seguinte",
+            ],
+            Self::Load => ["Load", "Carregar"],
+            Self::UseCurrent => ["Use current", "Usar o atual"],
+            Self::PlayAThenB => ["Play A then B", "Tocar A e depois B"],
+            Self::Diff => ["Diff", "Diferenças"],
+            Self::ReplaceWith => ["Replace with", "Substituir por"],
+            Self::Regex => ["Regex", "Regex"],
+            Self::Replace => ["Replace", "Substituir"],
+            Self::ReplaceAll => ["Replace all", "Substituir todos"],
+            Self::Matches => ["{} matches", "{} ocorrências"],
+            Self::Close => ["Close", "Fechar"],
+            Self::NewTab => ["New tab", "Nova aba"],
+            Self::Untitled => ["Untitled", "Sem título"],
+            Self::UnsavedChanges => ["Unsaved changes", "Mudanças não salvas"],
+            Self::CloseAndDiscard => [
+                "Close {} and discard its changes?",
+                "Fechar {} e descartar as mudanças?",
+            ],
+            Self::Discard => ["Discard", "Descartar"],
+            Self::Bytes => ["{} bytes", "{} bytes"],
+            Self::Warning => ["Warning: {}", "Aviso: {}"],
+            Self::Editor => ["Text of {}", "Texto de {}"],
+            Self::Remove => ["Remove", "Remover"],
+            Self::AddPoint => ["Add point", "Acrescentar ponto"],
+            Self::MoveUp => ["Move up", "Mover para cima"],
+            Self::MoveDown => ["Move down", "Mover para baixo"],
+            Self::PreviousPage => ["Previous page", "Página anterior"],
+            Self::NextPage => ["Next page", "Próxima página"],
+            Self::HighContrast => ["High contrast", "Alto contraste"],
+            Self::HighContrastHelp => [
+                "White text on black, with outlined widgets and the keyboard focus in yellow",
+                "Texto branco sobre preto, com os widgets contornados e o foco do teclado em amarelo",
+            ],
+            Self::Keyboard => ["Keyboard", "Teclado"],
+            Self::MaxPlayback => ["Stop after", "Parar depois de"],
+            Self::Stop => ["Stop", "Parar"],
+            Self::AlreadyPlaying => [
+                "Something is already playing: stop it first",
+                "Algo já está tocando: pare antes",
+            ],
+            Self::Waiting => ["{} waiting", "{} esperando"],
+            Self::WhilePlaying => ["Play while playing", "Tocar durante outra"],
+            Self::OverlapReject => ["Refuse", "Recusar"],
+            Self::OverlapQueue => ["Queue", "Enfileirar"],
+            Self::OverlapReplace => ["Replace", "Substituir"],
+            Self::WhilePlayingHelp => [
+                "What Play does while something else plays: refuse it, play it after the \
+                 current one, or stop the current one and play it",
+                "O que o Play faz enquanto outra coisa toca: recusa, toca depois da atual, \
+                 ou para a atual e toca",
+            ],
+            Self::MaxPlaybackHelp => [
+                "Stops the playback and silences every channel after this many seconds, \
+                 for pieces that would play for too long",
+                "Para a reprodução e silencia todos os canais depois desses segundos, \
+                 para peças que tocariam por tempo demais",
+            ],
+            Self::KeyboardHelp => [
+                "Tab / Shift+Tab: next / previous control\n\
+                 Space or Enter: press the focused control\n\
+                 F5: play\n\
+                 Esc: panic\n\
+                 Ctrl+E: back to the editor\n\
+                 Ctrl+Page Down / Ctrl+Page Up: next / previous tab",
+                "Tab / Shift+Tab: controle seguinte / anterior\n\
+                 Espaço ou Enter: aciona o controle com o foco\n\
+                 F5: tocar\n\
+                 Esc: pânico\n\
+                 Ctrl+E: volta para o editor\n\
+                 Ctrl+Page Down / Ctrl+Page Up: aba seguinte / anterior",
+            ],
+        })
+    }
+
+    /// O texto neste idioma, com o `{}` trocado pelo valor.
+    pub fn fill(self, language: Language, value: impl fmt::Display) -> String {
+        self.text(language).replacen("{}", &value.to_string(), 1)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn texts_follow_the_language() {
+        assert_eq!(Key::Open.text(Language::English), "Open");
+        assert_eq!(Key::Open.text(Language::Portuguese), "Abrir");
+        assert_eq!(Language::Portuguese.to_string(), "Português");
+    }
+
+    #[test]
+    fn fill_replaces_the_placeholder() {
+        assert_eq!(Key::Matches.fill(Language::English, 3), "3 matches");
+        assert_eq!(
+            Key::Imported.fill(Language::Portuguese, "song.json"),
+            "song.json importado"
+        );
+    }
+}
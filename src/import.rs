@@ -0,0 +1,4 @@
+//! Importação de formatos além do próprio texto.
+
+pub mod events;
+pub mod midicsv;
@@ -0,0 +1,94 @@
+use midly::num::u24;
+use serde::Deserialize;
+
+use crate::{
+    midi_action::{MidiAction, TimedAction},
+    tempo_map::TempoMap,
+    time_state::{TimeSignature, TimeState},
+};
+
+/// Um evento como aparece no JSON gerado pelo `export::events`.
+///
+/// O `time_ms` é ignorado: o tempo é recalculado a partir dos ticks e das
+/// mudanças de BPM, para que arquivos editados à mão continuem consistentes.
+#[derive(Deserialize)]
+struct Record {
+    tick: u64,
+    #[serde(default)]
+    channel: u8,
+    #[serde(flatten)]
+    action: MidiAction,
+}
+
+/// Lê uma lista de eventos em JSON, no esquema `{tick, time_ms, type, value}`,
+/// com um `channel` opcional.
+///
+/// Os eventos são ordenados por tick; eventos no mesmo tick mantêm a ordem do arquivo.
+pub fn from_json(json: &str) -> Result<Vec<TimedAction>, serde_json::Error> {
+    let mut records: Vec<Record> = serde_json::from_str(json)?;
+    records.sort_by_key(|record| record.tick);
+
+    let mut map = TempoMap::new(MidiAction::D_TPQN);
+    for record in &records {
+        if let MidiAction::ChangeBPM(bpm) = record.action {
+            let mspqn = TimeState::mspqn_from_bpm(bpm, TimeSignature::COMMON_TIME)
+                .unwrap_or_else(|_| u24::max_value());
+            map.push(record.tick, mspqn);
+        }
+    }
+
+    Ok(records
+        .into_iter()
+        .map(|record| TimedAction {
+            tick: record.tick,
+            time: map.time_at(record.tick),
+            channel: record.channel.min(15),
+            action: record.action,
+            source_range: 0..0,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::export;
+
+    #[test]
+    fn round_trip() {
+        let actions = TimedAction::from_actions(&[
+            MidiAction::ChangeBPM(90),
+            MidiAction::Lyric("la".into()),
+            MidiAction::PlayNote(69),
+            MidiAction::Pause,
+            MidiAction::ChangeVolume(100),
+            MidiAction::PlayNote(71),
+        ]);
+
+        let imported = from_json(&export::events::to_json(&actions)).unwrap();
+
+        assert_eq!(imported, actions);
+    }
+
+    #[test]
+    fn hand_written_events() {
+        let json = r#"[
+            {"tick": 480, "type": "note", "value": 64},
+            {"tick": 0, "type": "bpm", "value": 60},
+            {"tick": 0, "type": "note", "value": 60}
+        ]"#;
+
+        let imported = from_json(json).unwrap();
+
+        assert_eq!(imported[0].action, MidiAction::ChangeBPM(60));
+        assert_eq!(imported[2].action, MidiAction::PlayNote(64));
+        assert_eq!(imported[2].time, Duration::from_secs(1));
+    }
+
+    #[test]
+    fn rejects_unknown_types() {
+        assert!(from_json(r#"[{"tick": 0, "type": "sneeze"}]"#).is_err());
+    }
+}
@@ -0,0 +1,380 @@
+//! Importação do formato de texto do midicsv, como o escrito pelo `export::midicsv` ou
+//! editado em uma planilha, de volta para um arquivo MIDI.
+
+use std::error::Error;
+
+use midly::{
+    num::{u14, u15, u28, u4, u7},
+    Format, Fps, Header, MetaMessage, MidiMessage, PitchBend, Timing, TrackEvent, TrackEventKind,
+};
+
+/// O tipo da mensagem meta de fim de trilha.
+const END_OF_TRACK: u8 = 0x2F;
+
+/// Um evento lido, dono dos seus bytes, antes de virar um `TrackEvent`.
+enum Owned {
+    Midi(u4, MidiMessage),
+    /// Uma mensagem meta, pelo seu tipo e os seus bytes.
+    Meta(u8, Vec<u8>),
+    SysEx(Vec<u8>),
+    Escape(Vec<u8>),
+}
+
+impl Owned {
+    fn kind(&self) -> TrackEventKind<'_> {
+        match self {
+            &Self::Midi(channel, message) => TrackEventKind::Midi { channel, message },
+            // O midly escreve o tipo e os bytes como vieram, conhecendo o tipo ou não
+            Self::Meta(kind, data) => TrackEventKind::Meta(MetaMessage::Unknown(*kind, data)),
+            Self::SysEx(data) => TrackEventKind::SysEx(data),
+            Self::Escape(data) => TrackEventKind::Escape(data),
+        }
+    }
+}
+
+/// O cabeçalho lido: o formato, a divisão do tempo e quantas trilhas ele anuncia.
+type Parsed = (Format, Timing, usize);
+
+/// Lê um arquivo no formato do midicsv, retornando o arquivo MIDI.
+///
+/// Como no csvmidi, linhas vazias ou começadas por `#` ou `;` são ignoradas, e os tipos
+/// valem em maiúsculas ou minúsculas. Os eventos de cada trilha são ordenados pelo
+/// tick, e o `End_track` vai para depois do último, mesmo se faltar.
+pub fn from_csv(csv: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut header = None;
+    let mut tracks: Vec<Vec<(u64, Owned)>> = Vec::new();
+    for (index, line) in csv.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(['#', ';']) {
+            continue;
+        }
+        read_line(line, &mut header, &mut tracks)
+            .map_err(|error| format!("line {}: {error}", index + 1))?;
+    }
+    let (format, timing, count) = header.ok_or("missing Header record")?;
+    tracks.resize_with(tracks.len().max(count), Vec::new);
+
+    for track in &mut tracks {
+        let end = track.iter().map(|(tick, _)| *tick).max().unwrap_or(0);
+        track.retain(|(_, event)| !matches!(event, Owned::Meta(END_OF_TRACK, _)));
+        track.sort_by_key(|(tick, _)| *tick);
+        track.push((end, Owned::Meta(END_OF_TRACK, Vec::new())));
+    }
+    let mut events = Vec::with_capacity(tracks.len());
+    for track in &tracks {
+        let mut previous = 0;
+        let mut converted = Vec::with_capacity(track.len());
+        for (tick, event) in track {
+            let delta = u32::try_from(tick - previous)
+                .ok()
+                .and_then(u28::try_from)
+                .ok_or_else(|| format!("tick {tick} is too far from the previous event"))?;
+            previous = *tick;
+            converted.push(TrackEvent {
+                delta,
+                kind: event.kind(),
+            });
+        }
+        events.push(converted);
+    }
+
+    let mut bytes = Vec::new();
+    midly::write_std(&Header::new(format, timing), &events, &mut bytes)?;
+
+    Ok(bytes)
+}
+
+/// Lê uma linha, guardando o cabeçalho ou o evento na sua trilha.
+fn read_line(
+    line: &str,
+    header: &mut Option<Parsed>,
+    tracks: &mut Vec<Vec<(u64, Owned)>>,
+) -> Result<(), Box<dyn Error>> {
+    let mut fields = line.splitn(4, ',').map(str::trim);
+    let track: usize = parse(fields.next().unwrap_or_default())?;
+    let tick: u64 = parse(fields.next().ok_or("missing time")?)?;
+    let kind = fields
+        .next()
+        .ok_or("missing record type")?
+        .to_ascii_lowercase();
+    let rest = fields.next().unwrap_or_default();
+    let values: Vec<&str> = rest.split(',').map(str::trim).collect();
+
+    let event = match kind.as_str() {
+        "header" => {
+            let format = match parse::<u8>(value(&values, 0)?)? {
+                0 => Format::SingleTrack,
+                1 => Format::Parallel,
+                2 => Format::Sequential,
+                other => return Err(format!("unknown format {other}").into()),
+            };
+            let count = parse(value(&values, 1)?)?;
+            let division: u16 = parse(value(&values, 2)?)?;
+            let timing = match division.to_be_bytes() {
+                [fps, subframes] if fps & 0x80 != 0 => {
+                    let fps = Fps::from_int((fps as i8).unsigned_abs())
+                        .ok_or("invalid SMPTE division")?;
+                    Timing::Timecode(fps, subframes)
+                }
+                _ => Timing::Metrical(u15::new(division)),
+            };
+            *header = Some((format, timing, count));
+            return Ok(());
+        }
+        "end_of_file" => return Ok(()),
+        "start_track" => None,
+        "note_on_c" | "note_off_c" | "poly_aftertouch_c" => {
+            let (key, vel) = (seven_bits(&values, 1)?, seven_bits(&values, 2)?);
+            let message = match kind.as_str() {
+                "note_on_c" => MidiMessage::NoteOn { key, vel },
+                "note_off_c" => MidiMessage::NoteOff { key, vel },
+                _ => MidiMessage::Aftertouch { key, vel },
+            };
+            Some(Owned::Midi(channel(&values)?, message))
+        }
+        "control_c" => Some(Owned::Midi(
+            channel(&values)?,
+            MidiMessage::Controller {
+                controller: seven_bits(&values, 1)?,
+                value: seven_bits(&values, 2)?,
+            },
+        )),
+        "program_c" => Some(Owned::Midi(
+            channel(&values)?,
+            MidiMessage::ProgramChange {
+                program: seven_bits(&values, 1)?,
+            },
+        )),
+        "channel_aftertouch_c" => Some(Owned::Midi(
+            channel(&values)?,
+            MidiMessage::ChannelAftertouch {
+                vel: seven_bits(&values, 1)?,
+            },
+        )),
+        "pitch_bend_c" => {
+            let bend = u14::try_from(parse::<u16>(value(&values, 1)?)?)
+                .ok_or("pitch bend out of range")?;
+            Some(Owned::Midi(
+                channel(&values)?,
+                MidiMessage::PitchBend {
+                    bend: PitchBend(bend),
+                },
+            ))
+        }
+        "system_exclusive" => Some(Owned::SysEx(data(&values, 0)?)),
+        "system_exclusive_packet" => Some(Owned::Escape(data(&values, 0)?)),
+        "sequence_number" => Some(Owned::Meta(
+            0x00,
+            parse::<u16>(value(&values, 0)?)?.to_be_bytes().to_vec(),
+        )),
+        "text_t" | "copyright_t" | "title_t" | "instrument_name_t" | "lyric_t" | "marker_t"
+        | "cue_point_t" => {
+            let meta = match kind.as_str() {
+                "text_t" => 0x01,
+                "copyright_t" => 0x02,
+                "title_t" => 0x03,
+                "instrument_name_t" => 0x04,
+                "lyric_t" => 0x05,
+                "marker_t" => 0x06,
+                _ => 0x07,
+            };
+            // O texto pode ter vírgulas, então é o resto inteiro da linha
+            Some(Owned::Meta(meta, unquote(rest)?))
+        }
+        "channel_prefix" => Some(Owned::Meta(0x20, vec![channel(&values)?.as_int()])),
+        "midi_port" => Some(Owned::Meta(0x21, vec![seven_bits(&values, 0)?.as_int()])),
+        "end_track" => Some(Owned::Meta(END_OF_TRACK, Vec::new())),
+        "tempo" => {
+            let mspqn: u32 = parse(value(&values, 0)?)?;
+            if mspqn > 0xFF_FFFF {
+                return Err("tempo out of range".into());
+            }
+            Some(Owned::Meta(0x51, mspqn.to_be_bytes()[1..].to_vec()))
+        }
+        "smpte_offset" | "time_signature" => {
+            let (meta, count) = if kind == "smpte_offset" {
+                (0x54, 5)
+            } else {
+                (0x58, 4)
+            };
+            let bytes = (0..count)
+                .map(|index| parse(value(&values, index)?))
+                .collect::<Result<_, _>>()?;
+            Some(Owned::Meta(meta, bytes))
+        }
+        "key_signature" => {
+            let key: i8 = parse(value(&values, 0)?)?;
+            let minor = match value(&values, 1)?
+                .trim_matches('"')
+                .to_ascii_lowercase()
+                .as_str()
+            {
+                "major" => 0,
+                "minor" => 1,
+                other => return Err(format!("unknown mode {other}").into()),
+            };
+            Some(Owned::Meta(0x59, vec![key as u8, minor]))
+        }
+        "sequencer_specific" => Some(Owned::Meta(0x7F, data(&values, 0)?)),
+        "unknown_meta_event" => Some(Owned::Meta(parse(value(&values, 0)?)?, data(&values, 1)?)),
+        other => return Err(format!("unknown record type {other}").into()),
+    };
+
+    let index = track
+        .checked_sub(1)
+        .ok_or("events need a track from 1 on")?;
+    if tracks.len() <= index {
+        tracks.resize_with(index + 1, Vec::new);
+    }
+    if let Some(event) = event {
+        tracks[index].push((tick, event));
+    }
+
+    Ok(())
+}
+
+/// O campo dado dos valores de um evento.
+fn value<'a>(values: &[&'a str], index: usize) -> Result<&'a str, Box<dyn Error>> {
+    values
+        .get(index)
+        .copied()
+        .filter(|value| !value.is_empty())
+        .ok_or_else(|| format!("missing value {}", index + 1).into())
+}
+
+/// Um número inteiro, com uma mensagem que diz qual.
+fn parse<T: std::str::FromStr>(field: &str) -> Result<T, Box<dyn Error>> {
+    field
+        .parse()
+        .map_err(|_| format!("invalid number {field:?}").into())
+}
+
+/// O canal, sempre o primeiro valor dos eventos de canal.
+fn channel(values: &[&str]) -> Result<u4, Box<dyn Error>> {
+    u4::try_from(parse::<u8>(value(values, 0)?)?).ok_or_else(|| "channel out of range".into())
+}
+
+/// Um valor de 0 a 127.
+fn seven_bits(values: &[&str], index: usize) -> Result<u7, Box<dyn Error>> {
+    u7::try_from(parse::<u8>(value(values, index)?)?)
+        .ok_or_else(|| format!("value {} out of range", index + 1).into())
+}
+
+/// Os bytes depois do tamanho no campo dado, que precisa bater com quantos há.
+fn data(values: &[&str], index: usize) -> Result<Vec<u8>, Box<dyn Error>> {
+    let length: usize = parse(value(values, index)?)?;
+    let bytes: Vec<u8> = values[index + 1..]
+        .iter()
+        .filter(|value| !value.is_empty())
+        .map(|value| parse(value))
+        .collect::<Result<_, _>>()?;
+    if bytes.len() != length {
+        return Err(format!("expected {length} bytes, found {}", bytes.len()).into());
+    }
+
+    Ok(bytes)
+}
+
+/// O texto entre aspas, com as aspas dobradas e os escapes com barra invertida, como
+/// `\\` e os bytes em octal, como `\351`.
+fn unquote(field: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let inner = field
+        .strip_prefix('"')
+        .and_then(|field| field.strip_suffix('"'))
+        .ok_or("text must be between quotes")?
+        .as_bytes();
+    let mut text = Vec::with_capacity(inner.len());
+    let mut index = 0;
+    while let Some(&byte) = inner.get(index) {
+        index += 1;
+        match (byte, inner.get(index)) {
+            (b'"', Some(b'"')) => {
+                text.push(b'"');
+                index += 1;
+            }
+            (b'\\', Some(b'0'..=b'7')) => {
+                let digits = inner[index..]
+                    .iter()
+                    .take(3)
+                    .take_while(|digit| matches!(digit, b'0'..=b'7'))
+                    .count();
+                let octal = std::str::from_utf8(&inner[index..index + digits])?;
+                text.push(u8::from_str_radix(octal, 8).map_err(|_| "octal escape above \\377")?);
+                index += digits;
+            }
+            (b'\\', Some(&escaped)) => {
+                text.push(escaped);
+                index += 1;
+            }
+            _ => text.push(byte),
+        }
+    }
+
+    Ok(text)
+}
+
+#[cfg(test)]
+mod test {
+    use midly::Smf;
+
+    use super::*;
+    use crate::{export, midi_action::MidiAction};
+
+    #[test]
+    fn midicsv_round_trip() {
+        let actions = [
+            MidiAction::ChangeBPM(90),
+            MidiAction::Lyric("say \"olá\", \\o/".to_string()),
+            MidiAction::ChangeInstrument(40),
+            MidiAction::PlayNote(60),
+            MidiAction::SysEx(vec![0xF0, 0x41, 0xF7]),
+            MidiAction::Chord(vec![48, 52, 55]),
+        ];
+        let mut original = Vec::new();
+        MidiAction::as_track(&actions)
+            .write_std(&mut original)
+            .unwrap();
+
+        let csv = export::midicsv::to_csv(&Smf::parse(&original).unwrap());
+
+        assert!(csv.starts_with("0, 0, Header, 0, 1, 480\n1, 0, Start_track\n"));
+        assert!(csv.contains("1, 0, Lyric_t, \"say \"\"ol\\303\\241\"\", \\\\o/\"\n"));
+        assert!(csv.contains(", System_exclusive, 2, 65, 247\n"));
+        assert!(csv.ends_with("End_track\n0, 0, End_of_file\n"));
+        assert_eq!(from_csv(&csv).unwrap(), original);
+    }
+
+    #[test]
+    fn hand_edited_csv() {
+        let csv = "# made in a spreadsheet
+            0, 0, header, 1, 2, 96
+            2, 96, NOTE_OFF_C, 9, 36, 0
+            2, 0, Note_on_c, 9, 36, 100
+            1, 0, Title_t, \"Drums, live\"
+            1, 0, Key_signature, -2, \"minor\"
+            0, 0, End_of_file";
+
+        let bytes = from_csv(csv).unwrap();
+        let smf = Smf::parse(&bytes).unwrap();
+
+        assert_eq!(smf.tracks.len(), 2);
+        assert_eq!(
+            smf.tracks[0][0].kind,
+            TrackEventKind::Meta(MetaMessage::TrackName(b"Drums, live"))
+        );
+        assert_eq!(
+            smf.tracks[0][1].kind,
+            TrackEventKind::Meta(MetaMessage::KeySignature(-2, true))
+        );
+        // The note off comes after the note on, and the track gets its end
+        let drums = &smf.tracks[1];
+        assert_eq!(drums[1].delta, 96);
+        assert_eq!(drums[2].kind, TrackEventKind::Meta(MetaMessage::EndOfTrack));
+        assert_eq!(
+            from_csv("0, 0, Header, 0, 1, 480\n1, 0, Note_on_c, 16, 60, 100")
+                .unwrap_err()
+                .to_string(),
+            "line 2: channel out of range"
+        );
+    }
+}
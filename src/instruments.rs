@@ -0,0 +1,198 @@
+use crate::midi_action::MidiAction;
+
+/// Os nomes dos 128 instrumentos do General MIDI, na ordem dos programas.
+pub const GM_NAMES: [&str; 128] = [
+    "Acoustic Grand Piano",
+    "Bright Acoustic Piano",
+    "Electric Grand Piano",
+    "Honky-tonk Piano",
+    "Electric Piano 1",
+    "Electric Piano 2",
+    "Harpsichord",
+    "Clavinet",
+    "Celesta",
+    "Glockenspiel",
+    "Music Box",
+    "Vibraphone",
+    "Marimba",
+    "Xylophone",
+    "Tubular Bells",
+    "Dulcimer",
+    "Drawbar Organ",
+    "Percussive Organ",
+    "Rock Organ",
+    "Church Organ",
+    "Reed Organ",
+    "Accordion",
+    "Harmonica",
+    "Tango Accordion",
+    "Acoustic Guitar (nylon)",
+    "Acoustic Guitar (steel)",
+    "Electric Guitar (jazz)",
+    "Electric Guitar (clean)",
+    "Electric Guitar (muted)",
+    "Overdriven Guitar",
+    "Distortion Guitar",
+    "Guitar Harmonics",
+    "Acoustic Bass",
+    "Electric Bass (finger)",
+    "Electric Bass (pick)",
+    "Fretless Bass",
+    "Slap Bass 1",
+    "Slap Bass 2",
+    "Synth Bass 1",
+    "Synth Bass 2",
+    "Violin",
+    "Viola",
+    "Cello",
+    "Contrabass",
+    "Tremolo Strings",
+    "Pizzicato Strings",
+    "Orchestral Harp",
+    "Timpani",
+    "String Ensemble 1",
+    "String Ensemble 2",
+    "Synth Strings 1",
+    "Synth Strings 2",
+    "Choir Aahs",
+    "Voice Oohs",
+    "Synth Voice",
+    "Orchestra Hit",
+    "Trumpet",
+    "Trombone",
+    "Tuba",
+    "Muted Trumpet",
+    "French Horn",
+    "Brass Section",
+    "Synth Brass 1",
+    "Synth Brass 2",
+    "Soprano Sax",
+    "Alto Sax",
+    "Tenor Sax",
+    "Baritone Sax",
+    "Oboe",
+    "English Horn",
+    "Bassoon",
+    "Clarinet",
+    "Piccolo",
+    "Flute",
+    "Recorder",
+    "Pan Flute",
+    "Blown Bottle",
+    "Shakuhachi",
+    "Whistle",
+    "Ocarina",
+    "Lead 1 (square)",
+    "Lead 2 (sawtooth)",
+    "Lead 3 (calliope)",
+    "Lead 4 (chiff)",
+    "Lead 5 (charang)",
+    "Lead 6 (voice)",
+    "Lead 7 (fifths)",
+    "Lead 8 (bass + lead)",
+    "Pad 1 (new age)",
+    "Pad 2 (warm)",
+    "Pad 3 (polysynth)",
+    "Pad 4 (choir)",
+    "Pad 5 (bowed)",
+    "Pad 6 (metallic)",
+    "Pad 7 (halo)",
+    "Pad 8 (sweep)",
+    "FX 1 (rain)",
+    "FX 2 (soundtrack)",
+    "FX 3 (crystal)",
+    "FX 4 (atmosphere)",
+    "FX 5 (brightness)",
+    "FX 6 (goblins)",
+    "FX 7 (echoes)",
+    "FX 8 (sci-fi)",
+    "Sitar",
+    "Banjo",
+    "Shamisen",
+    "Koto",
+    "Kalimba",
+    "Bagpipe",
+    "Fiddle",
+    "Shanai",
+    "Tinkle Bell",
+    "Agogo",
+    "Steel Drums",
+    "Woodblock",
+    "Taiko Drum",
+    "Melodic Tom",
+    "Synth Drum",
+    "Reverse Cymbal",
+    "Guitar Fret Noise",
+    "Breath Noise",
+    "Seashore",
+    "Bird Tweet",
+    "Telephone Ring",
+    "Helicopter",
+    "Applause",
+    "Gunshot",
+];
+
+/// O BPM usado na prévia de instrumentos.
+const AUDITION_BPM: u16 = 240;
+
+/// Volume usado na prévia de instrumentos.
+const AUDITION_VOLUME: u16 = 100;
+
+/// O programa do som de erro: Lead 1 (square), que não se confunde com a música.
+const ERROR_PROGRAM: u8 = 80;
+
+/// O nome do instrumento do General MIDI, ou `None` se o programa não existir.
+pub fn name(program: u8) -> Option<&'static str> {
+    GM_NAMES.get(program as usize).copied()
+}
+
+/// Um arpejo curto de dó maior (C4, E4, G4, C5) tocado no instrumento dado,
+/// para ouvir como ele soa sem alterar o texto.
+pub fn audition(program: u8) -> Vec<MidiAction> {
+    let mut actions = vec![
+        MidiAction::ChangeBPM(AUDITION_BPM),
+        MidiAction::ChangeInstrument(program),
+        MidiAction::ChangeVolume(AUDITION_VOLUME),
+    ];
+    actions.extend([60, 64, 67, 72].map(MidiAction::PlayNote));
+
+    actions
+}
+
+/// Duas notas curtas descendo um semitom, tocadas quando a conversão falha ou o texto
+/// tem problemas.
+pub fn error_sound() -> Vec<MidiAction> {
+    vec![
+        MidiAction::ChangeBPM(AUDITION_BPM),
+        MidiAction::ChangeInstrument(ERROR_PROGRAM),
+        MidiAction::ChangeVolume(AUDITION_VOLUME),
+        MidiAction::NoteLength(MidiAction::D_TPQN.as_int() as u32 / 2),
+        MidiAction::PlayNote(47),
+        MidiAction::PlayNote(46),
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn telephone_name() {
+        assert_eq!(name(124), Some("Telephone Ring"));
+        assert_eq!(name(128), None);
+    }
+
+    #[test]
+    fn audition_uses_program() {
+        let actions = audition(40);
+
+        assert!(actions.contains(&MidiAction::ChangeInstrument(40)));
+        assert_eq!(
+            actions
+                .iter()
+                .filter(|action| matches!(action, MidiAction::PlayNote(_)))
+                .count(),
+            4
+        );
+    }
+}
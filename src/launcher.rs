@@ -0,0 +1,315 @@
+//! Modo ao vivo: uma grade de clipes, de textos ou de arquivos MIDI, para tocar em
+//! apresentações.
+//!
+//! Só um clipe toca por vez, repetindo até o fim do seu último compasso. O clipe
+//! escolhido durante a reprodução entra no lugar do atual no próximo compasso, para
+//! que a troca caia no tempo.
+//!
+//! Os clipes também podem ser disparados pelas notas da entrada MIDI, como as dos pads
+//! de um controlador: a nota base dispara o primeiro clipe, e cada semitom acima, o
+//! clipe seguinte.
+
+use std::{
+    error::Error,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc, Mutex,
+    },
+};
+
+#[cfg(feature = "native")]
+use midir::{Ignore, MidiInput, MidiInputConnection};
+use midly::{live::LiveEvent, MetaMessage, MidiMessage, Smf, Timing, TrackEventKind};
+#[cfg(feature = "native")]
+use tracing::info;
+
+use crate::{
+    clock::Clock,
+    play::{self, LiveControls, MidiSink, PlaybackOptions, Player, Playhead},
+};
+
+/// O que está tocando e o que vai tocar.
+#[derive(Debug, Default)]
+struct Queue {
+    /// O clipe que entra no próximo compasso, com a sua posição na grade.
+    next: Option<(usize, Vec<u8>)>,
+    /// A posição na grade do clipe tocando.
+    playing: Option<usize>,
+    /// Ticks em um compasso do clipe tocando.
+    bar: u64,
+}
+
+/// Os clipes tocados ao vivo, compartilhados entre a interface e a thread da reprodução.
+#[derive(Debug, Default)]
+pub struct Launcher {
+    queue: Mutex<Queue>,
+    controls: Arc<LiveControls>,
+    playhead: Arc<Playhead>,
+}
+
+impl Launcher {
+    /// Escolhe o arquivo do clipe da posição dada para tocar: logo, se nada estiver
+    /// tocando, senão no próximo compasso do clipe atual.
+    pub fn queue(&self, slot: usize, bytes: Vec<u8>) {
+        let mut queue = self.queue.lock().unwrap();
+        queue.next = Some((slot, bytes));
+        if queue.playing.is_some() {
+            let boundary = next_bar(self.playhead.tick(), queue.bar);
+            let end = self
+                .controls
+                .end()
+                .map_or(boundary, |end| end.min(boundary));
+            self.controls.set_end(Some(end));
+        }
+    }
+
+    /// A posição do clipe tocando.
+    pub fn playing(&self) -> Option<usize> {
+        self.queue.lock().unwrap().playing
+    }
+
+    /// A posição do clipe que entra no próximo compasso.
+    pub fn queued(&self) -> Option<usize> {
+        self.queue
+            .lock()
+            .unwrap()
+            .next
+            .as_ref()
+            .map(|(slot, _)| *slot)
+    }
+
+    /// Para a reprodução, que então envia as mensagens de pânico.
+    pub fn stop(&self) {
+        self.controls.stop();
+    }
+
+    /// A posição da reprodução, com os problemas da saída.
+    pub fn playhead(&self) -> &Playhead {
+        &self.playhead
+    }
+
+    /// Toca os clipes escolhidos no destino dado, sem fechar a conexão entre eles, até
+    /// `stop`. O primeiro clipe já deve ter sido escolhido.
+    pub fn run<S: MidiSink, C: Clock>(
+        &self,
+        mut sink: S,
+        clock: C,
+        options: PlaybackOptions,
+    ) -> Result<(), Box<dyn Error>> {
+        let result = self.play_clips(&mut sink, &clock, options);
+        self.queue.lock().unwrap().playing = None;
+
+        result
+    }
+
+    fn play_clips<S: MidiSink, C: Clock>(
+        &self,
+        sink: &mut S,
+        clock: &C,
+        options: PlaybackOptions,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut clip = None;
+        loop {
+            let file = {
+                let mut queue = self.queue.lock().unwrap();
+                if let Some(next) = queue.next.take() {
+                    clip = Some(next);
+                }
+                let Some((slot, bytes)) = clip.as_ref().filter(|_| !self.controls.is_stopped())
+                else {
+                    return Ok(());
+                };
+                let file = Smf::parse(bytes)?;
+                queue.bar = bar_ticks(&file);
+                queue.playing = Some(*slot);
+                // Repete até o fim do último compasso, ou até a troca pedida pela fila
+                self.controls
+                    .set_end(Some(length(&file).div_ceil(queue.bar).max(1) * queue.bar));
+                self.playhead.set_tick(0);
+                file
+            };
+
+            Player::new(&mut *sink, clock)
+                .with_options(options)
+                .with_controls(Arc::clone(&self.controls))
+                .with_playhead(Arc::clone(&self.playhead))
+                .play(&file)?;
+        }
+    }
+}
+
+/// As notas da entrada MIDI que disparam clipes, compartilhadas entre a interface e a
+/// conexão de entrada.
+#[derive(Debug)]
+pub struct Pads {
+    /// A nota que dispara o primeiro clipe.
+    base: AtomicU8,
+    /// Os clipes disparados desde a última consulta, em ordem.
+    triggered: Mutex<Vec<usize>>,
+}
+
+impl Pads {
+    /// A nota base padrão: o C1 (36), a primeira nota dos pads da maioria dos controladores.
+    pub const D_BASE: u8 = 36;
+
+    /// A nota que dispara o primeiro clipe.
+    pub fn base(&self) -> u8 {
+        self.base.load(Ordering::Relaxed)
+    }
+
+    /// Troca a nota que dispara o primeiro clipe.
+    pub fn set_base(&self, base: u8) {
+        self.base.store(base.min(127), Ordering::Relaxed);
+    }
+
+    /// Recebe uma mensagem da entrada, retornando o clipe disparado, se ela for uma
+    /// nota ligada de qualquer canal a partir da nota base.
+    pub fn receive(&self, message: &[u8]) -> Option<usize> {
+        let Ok(LiveEvent::Midi {
+            message: MidiMessage::NoteOn { key, vel },
+            ..
+        }) = LiveEvent::parse(message)
+        else {
+            return None;
+        };
+        let slot = key.as_int().checked_sub(self.base()).filter(|_| vel > 0)? as usize;
+        self.triggered.lock().unwrap().push(slot);
+
+        Some(slot)
+    }
+
+    /// Os clipes disparados desde a última chamada.
+    pub fn take_triggered(&self) -> Vec<usize> {
+        std::mem::take(&mut self.triggered.lock().unwrap())
+    }
+}
+
+impl Default for Pads {
+    fn default() -> Self {
+        Self {
+            base: AtomicU8::new(Self::D_BASE),
+            triggered: Mutex::default(),
+        }
+    }
+}
+
+/// Escuta a primeira porta de entrada, passando as mensagens para os pads e chamando
+/// `on_trigger` a cada clipe disparado, para que a interface o toque.
+///
+/// Os pads ficam ligados enquanto a conexão retornada existir.
+#[cfg(feature = "native")]
+pub fn connect_pads(
+    pads: Arc<Pads>,
+    on_trigger: impl Fn() + Send + 'static,
+) -> Result<MidiInputConnection<()>, Box<dyn Error>> {
+    let mut midi_in = MidiInput::new("TCP pads")?;
+    // Só as notas interessam
+    midi_in.ignore(Ignore::All);
+
+    let in_ports = midi_in.ports();
+    let in_port = in_ports.first().ok_or("No input port found.")?;
+    info!(
+        port = midi_in.port_name(in_port)?,
+        "launching clips from pads"
+    );
+
+    let connection = midi_in.connect(
+        in_port,
+        "midir",
+        move |_, message, _| {
+            if pads.receive(message).is_some() {
+                on_trigger();
+            }
+        },
+        (),
+    )?;
+
+    Ok(connection)
+}
+
+/// O primeiro início de compasso depois do tick dado.
+fn next_bar(tick: u64, bar: u64) -> u64 {
+    let bar = bar.max(1);
+    (tick / bar + 1) * bar
+}
+
+/// Ticks em um compasso do arquivo, pelo compasso do início.
+///
+/// Arquivos em SMPTE não têm compassos, mas são recusados pelo player.
+fn bar_ticks(file: &Smf<'_>) -> u64 {
+    let Timing::Metrical(tpqn) = file.header.timing else {
+        return 1;
+    };
+    let time_signature = play::initial_time_signature(&play::merge_tracks(&file.tracks));
+
+    (tpqn.as_int() as u64 * 4 * time_signature.numerator as u64
+        / time_signature.denominator.max(1) as u64)
+        .max(1)
+}
+
+/// O tick do último evento do arquivo, sem contar os fins de trilha, que os arquivos
+/// gerados põem um tick depois da última nota.
+fn length(file: &Smf<'_>) -> u64 {
+    play::merge_tracks(&file.tracks)
+        .iter()
+        .rev()
+        .find(|event| !matches!(event.kind, TrackEventKind::Meta(MetaMessage::EndOfTrack)))
+        .map_or(0, |event| event.tick)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::midi_action::MidiAction;
+
+    #[test]
+    fn clips_change_on_the_next_bar() {
+        let launcher = Launcher::default();
+        let mut bytes = Vec::new();
+        MidiAction::as_track(&vec![MidiAction::PlayNote(60); 8])
+            .write_std(&mut bytes)
+            .unwrap();
+        let file = Smf::parse(&bytes).unwrap();
+        // Eight beats fill two bars exactly, without the end of track
+        assert_eq!(bar_ticks(&file), 1920);
+        assert_eq!(length(&file).div_ceil(1920), 2);
+
+        // Nothing is playing, so the first clip does not wait
+        launcher.queue(0, bytes.clone());
+        assert_eq!(
+            (launcher.queued(), launcher.controls.end()),
+            (Some(0), None)
+        );
+
+        // Halfway through the first bar of a two-bar loop, the next clip waits for bar 2
+        {
+            let mut queue = launcher.queue.lock().unwrap();
+            queue.next = None;
+            queue.playing = Some(0);
+            queue.bar = 1920;
+        }
+        launcher.controls.set_end(Some(3840));
+        launcher.playhead.set_tick(960);
+        launcher.queue(1, bytes);
+        assert_eq!(launcher.controls.end(), Some(1920));
+        assert_eq!((launcher.playing(), launcher.queued()), (Some(0), Some(1)));
+        assert_eq!(next_bar(1920, 1920), 3840);
+    }
+
+    #[test]
+    fn pads_trigger_slots_from_the_base_note() {
+        let pads = Pads::default();
+
+        assert_eq!(pads.receive(&[0x99, 36, 100]), Some(0));
+        assert_eq!(pads.receive(&[0x90, 39, 1]), Some(3));
+        // Releases, notes below the base and other messages do nothing
+        assert_eq!(pads.receive(&[0x99, 36, 0]), None);
+        assert_eq!(pads.receive(&[0x89, 37, 64]), None);
+        assert_eq!(pads.receive(&[0x90, 35, 100]), None);
+        assert_eq!(pads.receive(&[0xF8]), None);
+        pads.set_base(60);
+        assert_eq!(pads.receive(&[0x90, 61, 100]), Some(1));
+        assert_eq!(pads.take_triggered(), [0, 3, 1]);
+        assert!(pads.take_triggered().is_empty());
+    }
+}
@@ -0,0 +1,21 @@
+//! O conversor de texto para MIDI, sem a interface, para ser usado por outros programas.
+#![allow(unused)]
+
+pub mod automation;
+pub mod channel_map;
+pub mod chord;
+pub mod ffi;
+pub mod groove;
+pub mod header;
+pub mod midi_action;
+pub mod midi_value;
+pub mod note;
+pub mod progress;
+#[cfg(feature = "python")]
+mod python;
+pub mod stream;
+pub mod tempo_map;
+pub mod text_to_midi;
+pub mod ticks;
+pub mod time_state;
+pub mod transform;
@@ -0,0 +1,119 @@
+//! Registro do que o conversor e o player estão fazendo, para anexar a relatos de bugs.
+//!
+//! As mensagens do `tracing` ficam guardadas para o painel de registro da interface e,
+//! com `--verbose`, também são escritas na saída de erro.
+
+use std::{
+    collections::VecDeque,
+    fmt::{self, Write},
+    sync::Mutex,
+};
+
+use tracing::{
+    field::{Field, Visit},
+    level_filters::LevelFilter,
+    span, Event, Level, Metadata, Subscriber,
+};
+
+/// Quantas linhas são guardadas; as mais antigas são descartadas.
+const CAPACITY: usize = 2000;
+
+/// As linhas registradas desde o início do programa.
+static LINES: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// Recebe as mensagens do `tracing` e as guarda em `LINES`.
+struct Logger {
+    /// Se os detalhes (eventos enviados, esperas) também são registrados e escritos
+    /// na saída de erro.
+    verbose: bool,
+}
+
+impl Subscriber for Logger {
+    /// Os avisos vêm de qualquer lugar, mas as informações e os detalhes só deste programa,
+    /// para as bibliotecas da interface não encherem o registro.
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        let level = *metadata.level();
+        level <= Level::WARN
+            || metadata.target().starts_with("tcp") && (self.verbose || level <= Level::INFO)
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        Some(if self.verbose {
+            LevelFilter::TRACE
+        } else {
+            LevelFilter::INFO
+        })
+    }
+
+    fn event(&self, event: &Event<'_>) {
+        let metadata = event.metadata();
+        let mut line = format!("{:>5} {}:", metadata.level(), metadata.target());
+        event.record(&mut Fields(&mut line));
+
+        if self.verbose || *metadata.level() <= Level::WARN {
+            eprintln!("{line}");
+        }
+        let mut lines = LINES.lock().unwrap();
+        if lines.len() == CAPACITY {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+
+    // Os spans não são usados, só os eventos.
+    fn new_span(&self, _: &span::Attributes<'_>) -> span::Id {
+        span::Id::from_u64(1)
+    }
+
+    fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+
+    fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+    fn enter(&self, _: &span::Id) {}
+
+    fn exit(&self, _: &span::Id) {}
+}
+
+/// Escreve os campos de um evento, a mensagem primeiro e os outros como `nome=valor`.
+struct Fields<'a>(&'a mut String);
+
+impl Visit for Fields<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        let _ = if field.name() == "message" {
+            write!(self.0, " {value:?}")
+        } else {
+            write!(self.0, " {}={value:?}", field.name())
+        };
+    }
+}
+
+/// Passa a registrar as mensagens. Só a primeira chamada tem efeito.
+pub fn init(verbose: bool) {
+    let _ = tracing::subscriber::set_global_default(Logger { verbose });
+}
+
+/// As linhas registradas, da mais antiga para a mais nova.
+pub fn lines() -> Vec<String> {
+    LINES.lock().unwrap().iter().cloned().collect()
+}
+
+/// Esquece as linhas registradas até agora.
+pub fn clear() {
+    LINES.lock().unwrap().clear();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fields_follow_the_message() {
+        let logger = Logger { verbose: false };
+
+        tracing::subscriber::with_default(logger, || {
+            tracing::info!(bpm = 120, "tempo changed");
+        });
+
+        assert!(lines().contains(&" INFO tcp::logging::test: tempo changed bpm=120".to_string()));
+    }
+}
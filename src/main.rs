@@ -1,20 +1,91 @@
 #![allow(unused)]
 #![windows_subsystem = "windows"]
 
-mod midi_action;
+mod analysis;
+mod capture;
+mod channel_state;
+mod cli;
+mod clipboard;
+mod clock;
+mod compose;
+mod conversion;
+mod diagnostics;
+mod examples;
+mod export;
+mod external_clock;
+mod grade;
+mod i18n;
+mod import;
+mod instruments;
+mod launcher;
+mod logging;
+mod metronome;
+mod midi_thru;
+mod mixer;
+mod patch_map;
 mod play;
-mod text_to_midi;
-mod time_state;
+mod playback_guard;
+mod playlist;
+#[cfg(feature = "native")]
+mod preferences;
+mod preset;
+mod quantize;
+mod session;
+mod tuning;
 pub mod user_interface;
-mod note;
-extern crate midir;
+mod validate;
+#[cfg(target_arch = "wasm32")]
+mod web_midi;
+
+use tcp::{
+    automation, channel_map, chord, groove, header, midi_action, midi_value, note, progress,
+    tempo_map, text_to_midi, ticks, time_state, transform,
+};
 
 use text_to_midi::Sheet;
 use user_interface::UserInterface;
+
+#[cfg(all(feature = "native", target_arch = "wasm32"))]
+compile_error!("the native feature does not build for the web, use --no-default-features");
+
+#[cfg(not(target_arch = "wasm32"))]
 fn main() {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let verbose = args.iter().any(|arg| arg == "--verbose");
+    args.retain(|arg| arg != "--verbose");
+    logging::init(verbose);
+    let (recorder, replay) = cli::take_session(&mut args).unwrap_or_else(|error| {
+        eprintln!("{error}");
+        std::process::exit(1);
+    });
+
+    if !args.is_empty() {
+        if let Err(error) = cli::run(&args) {
+            eprintln!("{error}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
     eframe::run_native(
         "Text to MIDI",
         eframe::NativeOptions::default(),
-        Box::new(|_cc| Box::new(UserInterface::new_interface())),
+        Box::new(|_cc| Box::new(UserInterface::new_interface().with_session(recorder, replay))),
     );
 }
+
+/// No navegador, a interface é desenhada no canvas `the_canvas_id` do `index.html`.
+#[cfg(target_arch = "wasm32")]
+fn main() {
+    logging::init(false);
+    wasm_bindgen_futures::spawn_local(async {
+        eframe::WebRunner::new()
+            .start(
+                "the_canvas_id",
+                eframe::WebOptions::default(),
+                Box::new(|_cc| Box::new(UserInterface::new_interface())),
+            )
+            .await
+            .expect("failed to start eframe");
+    });
+}
@@ -0,0 +1,192 @@
+//! Metrônomo tocado no áudio do computador, para quem não ouve o sintetizador,
+//! como quando ele está em outra sala ou sem som.
+//!
+//! O player decide quando cada tempo cai, pelo mapa de tempo, e só pede o clique a um
+//! `ClickSink`; a saída de áudio toca em uma thread própria, sem atrasar os eventos MIDI.
+
+use std::{error::Error, f32::consts::TAU, time::Duration};
+
+/// Destino dos cliques do metrônomo.
+pub trait ClickSink: Send {
+    /// Toca um clique no instante dado do relógio do player, mais forte no primeiro
+    /// tempo do compasso.
+    ///
+    /// O player já esperou até o instante, então quem toca na hora pode ignorá-lo.
+    fn click(&mut self, time: Duration, accent: bool);
+}
+
+/// A duração de um clique.
+const CLICK_LENGTH: Duration = Duration::from_millis(30);
+
+/// Um clique com a taxa de amostragem dada: um seno curto que some depressa, mais
+/// agudo e mais forte no acento.
+pub fn click_samples(accent: bool, sample_rate: u32) -> Vec<i16> {
+    let (frequency, amplitude) = if accent { (1760.0, 0.8) } else { (1320.0, 0.5) };
+    let length = (sample_rate as f32 * CLICK_LENGTH.as_secs_f32()) as usize;
+
+    (0..length)
+        .map(|sample| {
+            let time = sample as f32 / sample_rate as f32;
+            let envelope = (-time * 150.0).exp();
+            ((TAU * frequency * time).sin() * envelope * amplitude * i16::MAX as f32) as i16
+        })
+        .collect()
+}
+
+/// Abre a saída de áudio padrão para os cliques.
+#[cfg(feature = "native")]
+pub fn connect() -> Result<Box<dyn ClickSink>, Box<dyn Error>> {
+    cpal_click::CpalClick::open().map(|click| Box::new(click) as Box<dyn ClickSink>)
+}
+
+/// Abre a saída de áudio padrão para os cliques.
+#[cfg(not(feature = "native"))]
+pub fn connect() -> Result<Box<dyn ClickSink>, Box<dyn Error>> {
+    Err("the audio metronome needs the native feature".into())
+}
+
+#[cfg(feature = "native")]
+mod cpal_click {
+    use std::{
+        error::Error,
+        sync::{
+            atomic::{AtomicU8, Ordering},
+            mpsc, Arc,
+        },
+        thread,
+        time::Duration,
+    };
+
+    use cpal::{
+        traits::{DeviceTrait, HostTrait, StreamTrait},
+        FromSample, SampleFormat, SizedSample, Stream, StreamConfig,
+    };
+    use tracing::{info, warn};
+
+    use super::{click_samples, ClickSink};
+
+    /// Nenhum clique pedido desde o último bloco de áudio.
+    const NONE: u8 = 0;
+    /// Um clique pedido, sem acento.
+    const CLICK: u8 = 1;
+    /// Um clique pedido, com acento.
+    const ACCENT: u8 = 2;
+
+    /// Cliques tocados pelo cpal, na saída padrão do sistema.
+    ///
+    /// O fluxo de áudio não pode mudar de thread em todos os sistemas, então ele vive
+    /// em uma thread própria até o `CpalClick` ser descartado.
+    pub struct CpalClick {
+        /// Os cliques pedidos, se são acentos, para a thread do áudio.
+        clicks: mpsc::Sender<bool>,
+    }
+
+    impl CpalClick {
+        /// Abre a saída na thread que vai tocar os cliques, esperando ela dizer se deu certo.
+        pub fn open() -> Result<Self, Box<dyn Error>> {
+            let (clicks, requests) = mpsc::channel::<bool>();
+            let (opened, result) = mpsc::channel();
+            thread::spawn(move || {
+                let pending = Arc::new(AtomicU8::new(NONE));
+                let stream = match open_stream(Arc::clone(&pending)) {
+                    Ok(stream) => {
+                        let _ = opened.send(Ok(()));
+                        stream
+                    }
+                    Err(error) => {
+                        let _ = opened.send(Err(error.to_string()));
+                        return;
+                    }
+                };
+                // O fluxo toca até o remetente ser descartado
+                for accent in requests {
+                    pending.store(if accent { ACCENT } else { CLICK }, Ordering::Relaxed);
+                }
+                drop(stream);
+            });
+
+            result.recv()??;
+            info!("audio metronome ready");
+            Ok(Self { clicks })
+        }
+    }
+
+    impl ClickSink for CpalClick {
+        fn click(&mut self, _: Duration, accent: bool) {
+            let _ = self.clicks.send(accent);
+        }
+    }
+
+    /// Abre e inicia o fluxo da saída padrão, que toca o clique pedido em `pending` no
+    /// próximo bloco de áudio e silêncio no resto do tempo.
+    fn open_stream(pending: Arc<AtomicU8>) -> Result<Stream, Box<dyn Error>> {
+        let device = cpal::default_host()
+            .default_output_device()
+            .ok_or("no audio output device")?;
+        let supported = device.default_output_config()?;
+        let format = supported.sample_format();
+        let config = supported.config();
+        let stream = match format {
+            SampleFormat::F32 => build_stream::<f32>(&device, &config, pending)?,
+            SampleFormat::I16 => build_stream::<i16>(&device, &config, pending)?,
+            SampleFormat::U16 => build_stream::<u16>(&device, &config, pending)?,
+            format => return Err(format!("unsupported sample format {format}").into()),
+        };
+        stream.play()?;
+
+        Ok(stream)
+    }
+
+    /// O fluxo com amostras do tipo `T`, repetindo o clique em todos os canais.
+    fn build_stream<T>(
+        device: &cpal::Device,
+        config: &StreamConfig,
+        pending: Arc<AtomicU8>,
+    ) -> Result<Stream, cpal::BuildStreamError>
+    where
+        T: SizedSample + FromSample<i16>,
+    {
+        let channels = config.channels.max(1) as usize;
+        let sounds = [false, true].map(|accent| click_samples(accent, config.sample_rate.0));
+        // O som tocando e a próxima amostra dele
+        let mut playing: Option<(usize, usize)> = None;
+
+        device.build_output_stream(
+            config,
+            move |data: &mut [T], _| {
+                match pending.swap(NONE, Ordering::Relaxed) {
+                    CLICK => playing = Some((0, 0)),
+                    ACCENT => playing = Some((1, 0)),
+                    _ => (),
+                }
+                for frame in data.chunks_mut(channels) {
+                    let sample = match &mut playing {
+                        Some((sound, position)) => {
+                            *position += 1;
+                            sounds[*sound].get(*position - 1).copied().unwrap_or(0)
+                        }
+                        None => 0,
+                    };
+                    frame.fill(T::from_sample(sample));
+                }
+            },
+            |error| warn!(%error, "metronome click failed"),
+            None,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn accents_are_louder() {
+        let peak = |samples: Vec<i16>| samples.iter().map(|s| s.unsigned_abs()).max().unwrap();
+
+        let (accent, click) = (click_samples(true, 44_100), click_samples(false, 44_100));
+
+        assert_eq!(accent.len(), 1323);
+        assert!(peak(accent) > peak(click));
+    }
+}
@@ -1,39 +1,376 @@
-use crate::time_state::TimeState;
+use std::{fmt, mem, ops::Range, slice, time::Duration};
+
+use crate::{
+    midi_value::{self, MidiValue},
+    tempo_map::TempoMap,
+    time_state::{TimeSignature, TimeState},
+};
 
 use midly::{num::*, *};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
 
 /// Enum representando as possíveis ações de MIDI.
-#[derive(Clone, Copy)]
+///
+/// Serializada como `{"type": ..., "value": ...}`, o esquema usado pelo `export::events`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
 pub enum MidiAction {
-    /// Toca uma nota semimínima.
+    /// Toca uma nota, por uma semimínima ou pela duração da última `NoteLength`.
     ///
     /// O parâmetro é uma nota do MIDI, ou seja, já ajustada com sua oitava,
     /// onde C4 seria (4 (oitava) + 1 (porque C0 é a nota 12)) * 12 (notas totais, contando acidentes).
+    #[serde(rename = "note")]
     PlayNote(u8),
     /// Muda para um dos 128 instrumentos do General MIDI
+    #[serde(rename = "instrument")]
     ChangeInstrument(u8),
     /// Muda para um volume contido no intervalo [0, 2^15]
+    #[serde(rename = "volume")]
     ChangeVolume(u16),
-    /// Pausa por uma semimínima
+    /// Pausa por uma semimínima, ou pela duração da última `NoteLength`.
+    ///
+    /// Não gera eventos: a pausa vira um intervalo no delta do evento seguinte.
     Pause,
-    /// Troca MSPQN para a BPM dada
+    /// Troca MSPQN para a BPM dada, contada em semínimas (o compasso é sempre 4/4)
+    #[serde(rename = "bpm")]
     ChangeBPM(u16),
+    /// Letra (karaokê) associada à próxima nota, normalmente o caractere que a originou.
+    Lyric(String),
+    /// Pitch bend de 14 bits, com 8192 no centro. Usado pelas afinações alternativas.
+    PitchBend(u16),
+    /// A próxima nota é tocada com a velocidade dada, em vez da padrão. Não gera eventos.
+    Accent(u8),
+    /// Começa a segurar a nota dada no canal do pedal, por baixo das notas seguintes.
+    DroneStart(u8),
+    /// Solta a nota do pedal. Se faltar, a nota é solta no fim da música.
+    DroneStop(u8),
+    /// Pressão do canal (aftertouch), de 0 a 127, que muitos timbres usam para expressão.
+    #[serde(rename = "pressure")]
+    ChannelPressure(u8),
+    /// Pan (CC 10), de 0 (esquerda) a 127 (direita), com o centro em 64.
+    Pan(u8),
+    /// Mensagem exclusiva do sistema, com todos os bytes de `F0` a `F7`.
+    #[serde(rename = "sysex")]
+    SysEx(Vec<u8>),
+    /// Escolhe o banco de timbres (14 bits) usado pelas próximas trocas de instrumento.
+    #[serde(rename = "bank")]
+    BankSelect(u16),
+    /// Marca o começo de uma seção, como `==Refrão==` no texto. Não gera som.
+    Marker(String),
+    /// Toca as notas dadas juntas no canal do acompanhamento, soltando o acorde anterior,
+    /// e as segura até o próximo acorde. Sem notas, só solta o anterior.
+    Chord(Vec<u8>),
+    /// Acrescenta uma nota, com a velocidade dada, ao acorde segurado, que a solta junto
+    /// com as outras. Usada pelo `Strum` para tocar as notas do acorde uma de cada vez.
+    ChordNote { key: u8, velocity: u8 },
+    /// Troca a duração das próximas notas e pausas, em ticks do `MidiAction::D_TPQN`
+    /// (480 é uma semimínima). Não gera eventos.
+    #[serde(rename = "length")]
+    NoteLength(u32),
+}
+
+impl fmt::Display for MidiAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PlayNote(key) => write!(f, "note {key}"),
+            Self::ChangeInstrument(program) => write!(f, "instrument {program}"),
+            Self::ChangeVolume(volume) => write!(f, "volume {volume}"),
+            Self::Pause => write!(f, "pause"),
+            Self::ChangeBPM(bpm) => write!(f, "bpm {bpm}"),
+            Self::Lyric(text) => write!(f, "lyric {text:?}"),
+            Self::PitchBend(bend) => write!(f, "pitch bend {bend}"),
+            Self::Accent(velocity) => write!(f, "accent {velocity}"),
+            Self::DroneStart(key) => write!(f, "drone start {key}"),
+            Self::DroneStop(key) => write!(f, "drone stop {key}"),
+            Self::ChannelPressure(pressure) => write!(f, "pressure {pressure}"),
+            Self::Pan(pan) => write!(f, "pan {pan}"),
+            Self::SysEx(bytes) => {
+                write!(f, "sysex")?;
+                for byte in bytes {
+                    write!(f, " {byte:02X}")?;
+                }
+                Ok(())
+            }
+            Self::BankSelect(bank) => write!(f, "bank {bank}"),
+            Self::Marker(name) => write!(f, "marker {name:?}"),
+            Self::ChordNote { key, velocity } => write!(f, "chord note {key} ({velocity})"),
+            Self::NoteLength(ticks) => write!(f, "length {ticks}"),
+            Self::Chord(keys) => {
+                write!(f, "chord")?;
+                for key in keys {
+                    write!(f, " {key}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A conversão de ações em uma trilha do `MidiAction::as_track`, uma ação por vez, com o
+/// que ainda depende das próximas: as pausas e o acento ainda não aplicados, o pedal e
+/// o acorde segurados e a duração das notas.
+///
+/// Assim a trilha não precisa estar inteira na memória, como no `stream::StreamWriter`.
+#[derive(Clone, Debug)]
+pub(crate) struct Sequencer {
+    /// Os ticks de pausa que atrasam o próximo evento.
+    rest: u32,
+    accent: Option<u8>,
+    drone: Option<u8>,
+    chord: Vec<u8>,
+    /// A duração das notas e pausas, da última `NoteLength`.
+    length: u32,
+}
+
+impl Default for Sequencer {
+    fn default() -> Self {
+        Self {
+            rest: 0,
+            accent: None,
+            drone: None,
+            chord: Vec::new(),
+            length: MidiAction::quarter_note_delta().as_int(),
+        }
+    }
+}
+
+impl Sequencer {
+    /// Acrescenta à trilha os eventos da ação. Pausas e acentos não acrescentam nada,
+    /// e valem para o próximo evento.
+    pub(crate) fn push<'a>(&mut self, action: &'a MidiAction, track: &mut Track<'a>) {
+        if let &MidiAction::NoteLength(ticks) = action {
+            self.length = ticks.max(1);
+        }
+        let duration = action.ticks().min(1) * self.length;
+        match *action {
+            MidiAction::Pause => {
+                self.rest += duration;
+                return;
+            }
+            MidiAction::Accent(velocity) => {
+                self.accent = Some(velocity);
+                return;
+            }
+            MidiAction::DroneStart(key) => self.drone = Some(key),
+            MidiAction::DroneStop(_) => self.drone = None,
+            _ => (),
+        }
+
+        let first = track.len();
+        if let MidiAction::Chord(keys) = action {
+            let released = mem::replace(&mut self.chord, keys.clone());
+            track.extend(
+                released
+                    .into_iter()
+                    .map(|key| MidiAction::chord_event(key, false)),
+            );
+        }
+        action.push_as_event(track);
+        if let &MidiAction::ChordNote { key, .. } = action {
+            self.chord.push(key);
+        }
+        if let MidiAction::PlayNote(_) = action {
+            MidiAction::hold(&mut track[first..], duration);
+        }
+        if let (MidiAction::PlayNote(_), Some(velocity)) = (action, self.accent) {
+            MidiAction::accent(&mut track[first..], velocity);
+            self.accent = None;
+        }
+        if let Some(event) = track.get_mut(first) {
+            event.delta = MidiAction::delayed(event.delta, self.rest);
+            self.rest = 0;
+        }
+    }
+
+    /// Termina a trilha, soltando o pedal e o acorde segurados, com as pausas que ainda
+    /// não foram aplicadas.
+    pub(crate) fn finish(mut self, track: &mut Track<'_>) {
+        let held = self
+            .drone
+            .into_iter()
+            .map(|key| MidiAction::drone_event(key, false));
+        let chord = self
+            .chord
+            .iter()
+            .map(|&key| MidiAction::chord_event(key, false));
+        for mut event in held.chain(chord) {
+            event.delta = MidiAction::delayed(event.delta, self.rest);
+            track.push(event);
+            self.rest = 0;
+        }
+        MidiAction::add_end(track, self.rest);
+    }
+}
+
+/// Uma ação junto do momento em que acontece na trilha gerada.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimedAction {
+    /// Tick absoluto da ação.
+    pub tick: u64,
+    /// Tempo desde o início da música.
+    pub time: Duration,
+    /// O canal (de 0 a 15) das mensagens da ação. O texto gera tudo no canal 0; os outros
+    /// são usados pelas transformações que acrescentam vozes, como o `Harmonizer`.
+    pub channel: u8,
+    /// A ação.
+    pub action: MidiAction,
+    /// O trecho do texto, em bytes, que gerou a ação, como os do
+    /// `Sheet::process_with_sources`. Vazio quando a ação não veio de um texto.
+    pub source_range: Range<usize>,
+}
+
+impl TimedAction {
+    /// Marca cada ação com o seu tick e tempo, seguindo a temporização do `MidiAction::as_track`.
+    pub fn from_actions(actions: &[MidiAction]) -> Vec<Self> {
+        Self::from_sourced_actions(actions, &[])
+    }
+
+    /// Como o `from_actions`, guardando também o trecho do texto de cada ação.
+    ///
+    /// As ações sem trecho correspondente em `sources` ficam com um trecho vazio.
+    pub fn from_sourced_actions(actions: &[MidiAction], sources: &[Range<usize>]) -> Vec<Self> {
+        let map = TempoMap::from_actions(actions);
+        let mut tick = 0;
+
+        actions
+            .iter()
+            .zip(MidiAction::durations(actions))
+            .enumerate()
+            .map(|(index, (action, duration))| {
+                let timed = Self {
+                    tick,
+                    time: map.time_at(tick),
+                    channel: 0,
+                    action: action.clone(),
+                    source_range: sources.get(index).cloned().unwrap_or_default(),
+                };
+                tick += duration as u64;
+                timed
+            })
+            .collect()
+    }
+
+    /// Transforma ações com ticks absolutos em uma trilha do MIDI.
+    ///
+    /// Diferente do `MidiAction::as_track`, cada ação acontece no seu próprio tick,
+    /// então notas podem se sobrepor. As ações não precisam estar em ordem, mas as
+    /// durações das notas seguem as `NoteLength` na ordem das ações.
+    pub fn as_track(actions: &[Self]) -> Smf<'_> {
+        Self::as_track_with_sources(actions).0
+    }
+
+    /// Como o `as_track`, retornando também o trecho do texto de cada evento da trilha.
+    ///
+    /// Os eventos que não vieram de uma ação com trecho, como os do começo e o do fim
+    /// da trilha, ficam com um trecho vazio.
+    pub fn as_track_with_sources(actions: &[Self]) -> (Smf<'_>, Vec<Range<usize>>) {
+        // Quase sempre uma nota, com dois eventos, por ação
+        let mut events = Vec::with_capacity(actions.len() * 2);
+        let mut end = 0;
+        // O acento ainda não aplicado, e o tick da nota que ele acentua
+        let mut accent = None;
+        let mut drone = None;
+        let mut chord = Vec::new();
+        let mut durations = MidiAction::durations(actions.iter().map(|timed| &timed.action));
+        for timed in actions {
+            let duration = durations.next().unwrap_or_default();
+            let mut pushed = Track::new();
+            if let MidiAction::Chord(keys) = &timed.action {
+                let released = mem::replace(&mut chord, keys.clone());
+                pushed.extend(
+                    released
+                        .into_iter()
+                        .map(|key| MidiAction::chord_event(key, false)),
+                );
+            }
+            timed.action.push_as_event(&mut pushed);
+            if let &MidiAction::ChordNote { key, .. } = &timed.action {
+                chord.push(key);
+            }
+            if timed.channel != 0 {
+                MidiAction::move_to_channel(&mut pushed, midi_value::channel(timed.channel));
+            }
+            match timed.action {
+                MidiAction::Accent(velocity) => accent = Some((timed.tick, velocity)),
+                MidiAction::DroneStart(key) => drone = Some(key),
+                MidiAction::DroneStop(_) => drone = None,
+                MidiAction::PlayNote(_) => {
+                    MidiAction::hold(&mut pushed, duration);
+                    if let Some((_, velocity)) = accent.filter(|&(tick, _)| tick == timed.tick) {
+                        MidiAction::accent(&mut pushed, velocity);
+                        accent = None;
+                    }
+                }
+                _ => (),
+            }
+
+            let mut tick = timed.tick;
+            for event in pushed {
+                tick += event.delta.as_int() as u64;
+                events.push((tick, event, timed.source_range.clone()));
+            }
+            end = end.max(timed.tick + duration as u64);
+        }
+        if let Some(key) = drone {
+            events.push((end, MidiAction::drone_event(key, false), 0..0));
+        }
+        for key in chord {
+            events.push((end, MidiAction::chord_event(key, false), 0..0));
+        }
+        // A ordenação é estável, então eventos no mesmo tick mantêm a ordem das ações
+        events.sort_by_key(|&(tick, _, _)| tick);
+
+        let mut track = Track::with_capacity(events.len() + MidiAction::EXTRA_EVENTS);
+        MidiAction::add_beggining(&mut track);
+        let mut sources = vec![0..0; track.len()];
+        sources.reserve(events.len() + 1);
+        let mut last = 0;
+        for (tick, mut event, source) in events {
+            event.delta = MidiAction::delayed(MidiAction::INSTANT, Self::gap(last, tick));
+            last = tick;
+            track.push(event);
+            sources.push(source);
+        }
+        MidiAction::add_end(&mut track, Self::gap(last, end.max(last)));
+        sources.resize(track.len(), 0..0);
+        debug!(
+            actions = actions.len(),
+            events = track.len(),
+            "built the track"
+        );
+
+        let mut smf = Smf::new(MidiAction::header());
+        smf.tracks.push(track);
+        (smf, sources)
+    }
+
+    /// Quantos ticks há entre dois ticks absolutos, saturando no máximo de um `u32`.
+    fn gap(from: u64, to: u64) -> u32 {
+        u32::try_from(to - from).unwrap_or(u32::MAX)
+    }
 }
 
 impl MidiAction {
     /// Canal padrão
-    const D_CHANNEL: u4 = u4::from_int_lossy(0);
+    const D_CHANNEL: u4 = midi_value::channel(0);
+
+    /// Canal das notas do pedal, para que elas não sejam soltas pelas notas da melodia.
+    pub const DRONE_CHANNEL: u4 = midi_value::channel(2);
+
+    /// Canal dos acordes das cifras, que ficam segurados por baixo da melodia.
+    pub const CHORD_CHANNEL: u4 = midi_value::channel(3);
 
     /// Velocidade (força das teclas) padrão
-    const D_VELOCITY: u7 = u7::from_int_lossy((i8::MAX / 2) as u8);
+    pub const D_VELOCITY: MidiValue = MidiValue::new((i8::MAX / 2) as u8);
 
     /// Delta para eventos instantâneos
-    const INSTANT: u28 = u28::from_int_lossy(0);
+    const INSTANT: u28 = midi_value::ticks(0);
 
     /// Ticks por semimínimas padrão.
     ///
     /// Este valor foi escolhido para maximizar a resolução e qualidade do arquivo.
-    pub const D_TPQN: u15 = u15::from_int_lossy(480);
+    pub const D_TPQN: u15 = midi_value::tpqn(480);
 
     /// O compasso padrão é 4/4. Igual para todos os arquivos.
     const D_TIME_SIGNATURE: MetaMessage<'_> = midly::MetaMessage::TimeSignature(4, 2, 24, 8);
@@ -46,37 +383,51 @@ impl MidiAction {
         MetaMessage::TrackName(b"tcp_out"),
         Self::D_TIME_SIGNATURE,
         Self::D_KEY_SIGNATURE,
-        MetaMessage::MidiPort(u7::from_int_lossy(0)),
+        MetaMessage::MidiPort(MidiValue::MIN.as_u7()),
     ];
 
+    /// Quantos eventos toda trilha tem além dos das ações: os iniciais, o do pedal e o fim.
+    const EXTRA_EVENTS: usize = Self::TO_BE_ADDED.len() + 2;
+
     /// Transofrma uma sequência de ações em uma trilha válida do MIDI, adicionando
     /// todo o boiler-plate necessário para sua correta reprodução.
-    pub fn as_track<'a>(slice: &[Self]) -> Smf<'a> {
-        let header: Header = Header {
-            format: midly::Format::SingleTrack,
-            timing: midly::Timing::Metrical(u15::from_int_lossy(Self::D_TPQN.as_int())),
-        };
-        let mut smf = Smf::new(header);
+    pub fn as_track<'a>(slice: &'a [Self]) -> Smf<'a> {
+        let mut smf = Smf::new(Self::header());
 
-        let mut track = Track::new();
+        // Quase sempre uma nota, com dois eventos, por ação
+        let mut track = Track::with_capacity(slice.len() * 2 + Self::EXTRA_EVENTS);
 
         // Add the default meta messages
         Self::add_beggining(&mut track);
 
         // Main loop
+        let mut sequencer = Sequencer::default();
         for action in slice {
-            action.push_as_event(&mut track);
+            sequencer.push(action, &mut track);
         }
 
         // Finishes
-        Self::add_end(&mut track);
+        sequencer.finish(&mut track);
+        debug!(
+            actions = slice.len(),
+            events = track.len(),
+            "built the track"
+        );
 
         smf.tracks.push(track);
         smf
     }
 
+    /// O cabeçalho dos arquivos gerados: uma única trilha com o TPQN padrão.
+    pub(crate) fn header() -> Header {
+        Header {
+            format: midly::Format::SingleTrack,
+            timing: midly::Timing::Metrical(Self::D_TPQN),
+        }
+    }
+
     /// Adiciona as mensagens iniciais a uma trilha
-    fn add_beggining(track: &mut Track) {
+    pub(crate) fn add_beggining(track: &mut Track) {
         for message in Self::TO_BE_ADDED {
             track.push(TrackEvent {
                 delta: Self::INSTANT,
@@ -85,33 +436,128 @@ impl MidiAction {
         }
     }
 
-    /// Finaliza a trilha.
-    fn add_end(track: &mut Track) {
+    /// Finaliza a trilha, respeitando as pausas que ainda não foram aplicadas.
+    fn add_end(track: &mut Track, rest: u32) {
         track.push(TrackEvent {
-            delta: u28::from_int_lossy(1),
+            delta: Self::delayed(midi_value::ticks(1), rest),
             kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
         });
     }
 
+    /// Soma ticks de pausa a um delta, saturando no máximo representável.
+    fn delayed(delta: u28, rest: u32) -> u28 {
+        u28::try_from(delta.as_int().saturating_add(rest)).unwrap_or_else(u28::max_value)
+    }
+
     /// Calcula o intervalo de tempo (em ticks) necessário para tocar uma semimínima.
     ///
     /// Como essa quantidade é fixa pelo TPQN do cabeçalho, simplesmente converte
-    /// esse valor para u8.
+    /// esse valor para u28.
     fn quarter_note_delta() -> u28 {
-        u28::from_int_lossy(Self::D_TPQN.as_int() as u32)
+        u28::try_from(Self::D_TPQN.as_int() as u32).expect("a u15 always fits in a u28")
+    }
+
+    /// Troca a velocidade das notas ligadas entre os eventos dados.
+    fn accent(events: &mut [TrackEvent<'_>], velocity: u8) {
+        for event in events {
+            if let TrackEventKind::Midi {
+                message: MidiMessage::NoteOn { vel, .. },
+                ..
+            } = &mut event.kind
+            {
+                *vel = MidiValue::clamped(velocity).into();
+            }
+        }
+    }
+
+    /// Troca a duração das notas entre os eventos dados, atrasando as notas desligadas.
+    fn hold(events: &mut [TrackEvent<'_>], ticks: u32) {
+        for event in events {
+            if let TrackEventKind::Midi {
+                message: MidiMessage::NoteOff { .. },
+                ..
+            } = event.kind
+            {
+                event.delta = Self::delayed(Self::INSTANT, ticks);
+            }
+        }
+    }
+
+    /// Liga ou desliga a nota do pedal, no canal `Self::DRONE_CHANNEL`.
+    fn drone_event(key: u8, on: bool) -> TrackEvent<'static> {
+        let key = MidiValue::clamped(key).into();
+        let vel = Self::D_VELOCITY.into();
+        TrackEvent {
+            delta: Self::INSTANT,
+            kind: TrackEventKind::Midi {
+                channel: Self::DRONE_CHANNEL,
+                message: if on {
+                    MidiMessage::NoteOn { key, vel }
+                } else {
+                    MidiMessage::NoteOff { key, vel }
+                },
+            },
+        }
+    }
+
+    /// Troca o canal das mensagens entre os eventos dados.
+    fn move_to_channel(events: &mut [TrackEvent<'_>], to: u4) {
+        for event in events {
+            if let TrackEventKind::Midi { channel, .. } = &mut event.kind {
+                *channel = to;
+            }
+        }
+    }
+
+    /// Quantos ticks a ação ocupa na trilha gerada pelo `as_track`, com a duração padrão,
+    /// de uma semimínima. Para seguir as `NoteLength`, veja `durations`.
+    pub fn ticks(&self) -> u32 {
+        match self {
+            Self::PlayNote(_) | Self::Pause => Self::quarter_note_delta().as_int(),
+            _ => 0,
+        }
+    }
+
+    /// Quantos ticks cada ação ocupa na trilha gerada pelo `as_track`, seguindo as
+    /// `NoteLength` anteriores a ela.
+    pub fn durations<'a, I>(actions: I) -> impl Iterator<Item = u32> + 'a
+    where
+        I: IntoIterator<Item = &'a Self>,
+        I::IntoIter: 'a,
+    {
+        actions
+            .into_iter()
+            .scan(Self::quarter_note_delta().as_int(), |length, action| {
+                if let &Self::NoteLength(ticks) = action {
+                    *length = ticks.max(1);
+                }
+                Some(action.ticks().min(1) * *length)
+            })
+    }
+
+    /// Liga ou desliga uma nota de acorde, no canal `Self::CHORD_CHANNEL`.
+    fn chord_event(key: u8, on: bool) -> TrackEvent<'static> {
+        let mut event = Self::drone_event(key, on);
+        if let TrackEventKind::Midi { channel, .. } = &mut event.kind {
+            *channel = Self::CHORD_CHANNEL;
+        }
+        event
     }
 
     /// Adicioa o a ação como um evento do MIDI para a track passada.
-    pub fn push_as_event(self, track: &mut Track) {
+    ///
+    /// Pausas e acentos não adicionam nada, pois dependem do próximo evento;
+    /// são tratados no `as_track`.
+    pub fn push_as_event<'a>(&'a self, track: &mut Track<'a>) {
         match self {
-            Self::PlayNote(note) => {
+            &Self::PlayNote(note) => {
                 track.push(TrackEvent {
                     delta: Self::INSTANT,
                     kind: TrackEventKind::Midi {
                         channel: Self::D_CHANNEL,
                         message: MidiMessage::NoteOn {
-                            key: note.into(),
-                            vel: Self::D_VELOCITY,
+                            key: MidiValue::clamped(note).into(),
+                            vel: Self::D_VELOCITY.into(),
                         },
                     },
                 });
@@ -120,61 +566,120 @@ impl MidiAction {
                     kind: TrackEventKind::Midi {
                         channel: Self::D_CHANNEL,
                         message: MidiMessage::NoteOff {
-                            key: note.into(),
-                            vel: Self::D_VELOCITY,
+                            key: MidiValue::clamped(note).into(),
+                            vel: Self::D_VELOCITY.into(),
                         },
                     },
                 });
             }
-            Self::ChangeInstrument(instrument) => {
+            &Self::ChangeInstrument(instrument) => {
                 track.push(TrackEvent {
                     delta: Self::INSTANT,
                     kind: TrackEventKind::Midi {
                         channel: Self::D_CHANNEL,
                         message: MidiMessage::ProgramChange {
-                            program: u7::from_int_lossy(instrument),
+                            program: MidiValue::clamped(instrument).into(),
                         },
                     },
                 });
             }
-            Self::ChangeVolume(volume) => track.push(TrackEvent {
+            &Self::ChangeVolume(volume) => track.push(TrackEvent {
                 delta: Self::INSTANT,
                 kind: TrackEventKind::Midi {
                     channel: Self::D_CHANNEL,
                     message: MidiMessage::Controller {
-                        controller: u7::from_int_lossy(midi_msg::ControlNumber::Volume as u8),
-                        value: u7::from_int_lossy(volume as u8),
+                        controller: MidiValue::new(midi_msg::ControlNumber::Volume as u8).into(),
+                        value: MidiValue::clamped(volume).into(),
+                    },
+                },
+            }),
+            Self::Pause | Self::Accent(_) | Self::NoteLength(_) => (),
+            &Self::DroneStart(key) => track.push(Self::drone_event(key, true)),
+            &Self::DroneStop(key) => track.push(Self::drone_event(key, false)),
+            &Self::ChannelPressure(pressure) => track.push(TrackEvent {
+                delta: Self::INSTANT,
+                kind: TrackEventKind::Midi {
+                    channel: Self::D_CHANNEL,
+                    message: MidiMessage::ChannelAftertouch {
+                        vel: MidiValue::clamped(pressure).into(),
                     },
                 },
             }),
-            Self::Pause => {
+            &Self::Pan(pan) => track.push(TrackEvent {
+                delta: Self::INSTANT,
+                kind: TrackEventKind::Midi {
+                    channel: Self::D_CHANNEL,
+                    message: MidiMessage::Controller {
+                        controller: MidiValue::new(midi_msg::ControlNumber::Pan as u8).into(),
+                        value: MidiValue::clamped(pan).into(),
+                    },
+                },
+            }),
+            Self::SysEx(bytes) => track.push(TrackEvent {
+                delta: Self::INSTANT,
+                // No arquivo, o `F0` fica implícito no tipo do evento
+                kind: TrackEventKind::SysEx(bytes.strip_prefix(&[0xF0]).unwrap_or(bytes)),
+            }),
+            &Self::BankSelect(bank) => {
+                let halves = [
+                    (midi_msg::ControlNumber::BankSelect, bank >> 7),
+                    (midi_msg::ControlNumber::BankSelectLSB, bank),
+                ];
+                for (controller, value) in halves {
+                    track.push(TrackEvent {
+                        delta: Self::INSTANT,
+                        kind: TrackEventKind::Midi {
+                            channel: Self::D_CHANNEL,
+                            message: MidiMessage::Controller {
+                                controller: MidiValue::new(controller as u8).into(),
+                                value: MidiValue::clamped(value & 0x7F).into(),
+                            },
+                        },
+                    });
+                }
+            }
+            &Self::ChangeBPM(bpm) => {
+                // BPMs inválidos (0 ou lentos demais) viram o tempo mais lento possível;
+                // o `validate` avisa sobre eles.
+                let mspqn = TimeState::mspqn_from_bpm(bpm, TimeSignature::COMMON_TIME)
+                    .unwrap_or_else(|_| u24::max_value());
                 track.push(TrackEvent {
                     delta: Self::INSTANT,
-                    kind: TrackEventKind::Midi {
-                        channel: Self::D_CHANNEL,
-                        message: MidiMessage::Controller {
-                            controller: u7::from_int_lossy(0x7B),
-                            value: u7::from_int_lossy(0),
-                        },
-                    },
+                    kind: midly::TrackEventKind::Meta(MetaMessage::Tempo(mspqn)),
                 });
+            }
+            Self::Lyric(text) => {
                 track.push(TrackEvent {
-                    delta: Self::quarter_note_delta(),
-                    kind: TrackEventKind::Midi {
-                        channel: Self::D_CHANNEL,
-                        message: MidiMessage::Controller {
-                            controller: u7::from_int_lossy(0x7B),
-                            value: u7::from_int_lossy(0),
-                        },
-                    },
+                    delta: Self::INSTANT,
+                    kind: TrackEventKind::Meta(MetaMessage::Lyric(text.as_bytes())),
                 });
             }
-            Self::ChangeBPM(bpm) => {
+            Self::Marker(name) => {
                 track.push(TrackEvent {
                     delta: Self::INSTANT,
-                    kind: midly::TrackEventKind::Meta(MetaMessage::Tempo(
-                        TimeState::mspqn_from_bpm(bpm, 4),
-                    )),
+                    kind: TrackEventKind::Meta(MetaMessage::Marker(name.as_bytes())),
+                });
+            }
+            Self::Chord(keys) => {
+                // As notas do acorde anterior são soltas no `as_track`, que sabe quais são
+                track.extend(keys.iter().map(|&key| Self::chord_event(key, true)));
+            }
+            &Self::ChordNote { key, velocity } => {
+                let mut event = Self::chord_event(key, true);
+                Self::accent(slice::from_mut(&mut event), velocity);
+                track.push(event);
+            }
+            &Self::PitchBend(bend) => {
+                track.push(TrackEvent {
+                    delta: Self::INSTANT,
+                    kind: TrackEventKind::Midi {
+                        channel: Self::D_CHANNEL,
+                        message: MidiMessage::PitchBend {
+                            bend: midly::PitchBend(
+                                u14::try_from(bend).unwrap_or_else(u14::max_value),
+                            ),
+                        },
+                    },
                 });
             }
         };
@@ -184,9 +689,10 @@ impl MidiAction {
 #[cfg(test)]
 mod test {
 
-    use midly::{num::*, Track, TrackEventKind};
+    use midly::{num::*, MetaMessage, Track, TrackEventKind};
 
-    use super::MidiAction;
+    use super::{MidiAction, TimedAction};
+    use crate::text_to_midi::{Sheet, State};
 
     #[test]
     fn change_instrument() {
@@ -198,10 +704,244 @@ mod test {
             },
         };
 
+        let action = MidiAction::ChangeInstrument(0);
         let mut midi_vec = Track::new();
-        MidiAction::ChangeInstrument(0).push_as_event(&mut midi_vec);
+        action.push_as_event(&mut midi_vec);
 
         // Assert
         assert_eq!(correct, midi_vec[0].kind);
     }
+
+    #[test]
+    fn volume_is_clamped() {
+        // Arrange
+        let action = MidiAction::ChangeVolume(200);
+        let mut track = Track::new();
+
+        // Act
+        action.push_as_event(&mut track);
+
+        // Assert
+        assert!(matches!(
+            track[0].kind,
+            TrackEventKind::Midi {
+                message: midly::MidiMessage::Controller { value, .. },
+                ..
+            } if value == 127
+        ));
+    }
+
+    /// Calcula o tick absoluto de cada evento da trilha.
+    fn ticks(track: &Track) -> Vec<u32> {
+        track
+            .iter()
+            .scan(0, |tick, event| {
+                *tick += event.delta.as_int();
+                Some(*tick)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn pause_is_a_gap() {
+        // Arrange
+        let with_pause = [
+            MidiAction::PlayNote(60),
+            MidiAction::Pause,
+            MidiAction::PlayNote(62),
+        ];
+        let without_pause = [MidiAction::PlayNote(60), MidiAction::PlayNote(62)];
+
+        // Act
+        let with_pause = MidiAction::as_track(&with_pause);
+        let without_pause = MidiAction::as_track(&without_pause);
+
+        // Assert
+        let before: Vec<u32> = ticks(&without_pause.tracks[0]);
+        let after: Vec<u32> = ticks(&with_pause.tracks[0]);
+        assert_eq!(before.len(), after.len());
+        // Meta events, C on and C off
+        assert_eq!(before[..6], after[..6]);
+        // D on, D off and EndOfTrack happen one quarter note later
+        for (before, after) in before[6..].iter().zip(&after[6..]) {
+            assert_eq!(after - before, 480);
+        }
+    }
+
+    #[test]
+    fn pause_emits_no_events() {
+        let actions = [MidiAction::Pause, MidiAction::Pause];
+
+        let smf = MidiAction::as_track(&actions);
+
+        // Only the meta events and EndOfTrack, two quarter notes later
+        assert_eq!(smf.tracks[0].len(), 5);
+        assert_eq!(ticks(&smf.tracks[0])[4], 2 * 480 + 1);
+    }
+
+    #[test]
+    fn lyrics_precede_their_notes() {
+        // Arrange
+        let actions = Sheet::builder()
+            .text("Co")
+            .lyrics(true)
+            .build()
+            .unwrap()
+            .process();
+
+        // Act
+        let smf = MidiAction::as_track(&actions);
+        let lyrics: Vec<&[u8]> = smf.tracks[0]
+            .iter()
+            .filter_map(|event| match event.kind {
+                TrackEventKind::Meta(MetaMessage::Lyric(text)) => Some(text),
+                _ => None,
+            })
+            .collect();
+
+        // Assert
+        assert_eq!(lyrics, [b"C", b"o"]);
+        assert!(matches!(
+            smf.tracks[0][7].kind,
+            TrackEventKind::Meta(MetaMessage::Lyric(b"C"))
+        ));
+        assert!(matches!(
+            smf.tracks[0][8].kind,
+            TrackEventKind::Midi {
+                message: midly::MidiMessage::NoteOn { .. },
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn timed_track_matches_sequential_track() {
+        let actions = Sheet::builder()
+            .text("C+D oE  ")
+            .lyrics(true)
+            .build()
+            .unwrap()
+            .process();
+
+        let timed = TimedAction::from_actions(&actions);
+
+        assert_eq!(
+            TimedAction::as_track(&timed),
+            MidiAction::as_track(&actions)
+        );
+    }
+
+    #[test]
+    fn unreleased_drone_stops_at_the_end() {
+        let actions = [MidiAction::DroneStart(48), MidiAction::PlayNote(60)];
+
+        let smf = MidiAction::as_track(&actions);
+
+        let drone: Vec<(u32, bool)> = ticks(&smf.tracks[0])
+            .into_iter()
+            .zip(&smf.tracks[0])
+            .filter_map(|(tick, event)| match event.kind {
+                TrackEventKind::Midi {
+                    channel,
+                    message: midly::MidiMessage::NoteOn { .. },
+                } if channel == MidiAction::DRONE_CHANNEL => Some((tick, true)),
+                TrackEventKind::Midi {
+                    channel,
+                    message: midly::MidiMessage::NoteOff { .. },
+                } if channel == MidiAction::DRONE_CHANNEL => Some((tick, false)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(drone, [(0, true), (480, false)]);
+        assert_eq!(
+            TimedAction::as_track(&TimedAction::from_actions(&actions)),
+            smf
+        );
+    }
+
+    #[test]
+    fn chords_hold_until_the_next_one() {
+        let actions = [
+            MidiAction::Chord(vec![48, 52]),
+            MidiAction::PlayNote(60),
+            MidiAction::Chord(vec![50]),
+            MidiAction::PlayNote(62),
+        ];
+
+        let smf = MidiAction::as_track(&actions);
+
+        let chord: Vec<(u32, u8, bool)> = ticks(&smf.tracks[0])
+            .into_iter()
+            .zip(&smf.tracks[0])
+            .filter_map(|(tick, event)| match event.kind {
+                TrackEventKind::Midi {
+                    channel,
+                    message: midly::MidiMessage::NoteOn { key, .. },
+                } if channel == MidiAction::CHORD_CHANNEL => Some((tick, key.as_int(), true)),
+                TrackEventKind::Midi {
+                    channel,
+                    message: midly::MidiMessage::NoteOff { key, .. },
+                } if channel == MidiAction::CHORD_CHANNEL => Some((tick, key.as_int(), false)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            chord,
+            [
+                (0, 48, true),
+                (0, 52, true),
+                (480, 48, false),
+                (480, 52, false),
+                (480, 50, true),
+                (960, 50, false),
+            ]
+        );
+        assert_eq!(
+            TimedAction::as_track(&TimedAction::from_actions(&actions)),
+            smf
+        );
+    }
+
+    #[test]
+    fn timed_notes_can_overlap() {
+        let chord: Vec<TimedAction> = [64, 60, 67]
+            .map(|key| TimedAction {
+                tick: 0,
+                time: Default::default(),
+                channel: 0,
+                action: MidiAction::PlayNote(key),
+                source_range: 0..0,
+            })
+            .into();
+
+        let smf = TimedAction::as_track(&chord);
+
+        let ticks = ticks(&smf.tracks[0]);
+        // Meta events, three notes on at 0 and three notes off at 480
+        assert_eq!(ticks[4..], [0, 0, 0, 480, 480, 480, 481]);
+    }
+
+    #[test]
+    fn events_keep_their_sources() {
+        let timed = TimedAction::from_sourced_actions(
+            &[MidiAction::PlayNote(60), MidiAction::PlayNote(62)],
+            &[0..1, 1..2],
+        );
+
+        let (smf, sources) = TimedAction::as_track_with_sources(&timed);
+
+        assert_eq!(sources.len(), smf.tracks[0].len());
+        // Meta events, each note on and off, and the end of the track
+        assert_eq!(sources[..4], [0..0, 0..0, 0..0, 0..0]);
+        assert_eq!(sources[4..], [0..1, 0..1, 1..2, 1..2, 0..0]);
+    }
+
+    #[test]
+    fn no_lyrics_by_default() {
+        let actions = Sheet::builder().text("CDE").build().unwrap().process();
+
+        assert!(!actions
+            .iter()
+            .any(|action| matches!(action, MidiAction::Lyric(_))));
+    }
 }
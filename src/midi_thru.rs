@@ -0,0 +1,103 @@
+//! MIDI thru: repassa o que chega na entrada MIDI direto para a saída escolhida,
+//! trocando o canal se pedido, como um roteador simples.
+
+use std::sync::{
+    atomic::{AtomicU8, Ordering},
+    Arc,
+};
+
+#[cfg(feature = "native")]
+use std::error::Error;
+
+#[cfg(feature = "native")]
+use midir::{Ignore, MidiInput, MidiInputConnection};
+#[cfg(feature = "native")]
+use tracing::info;
+
+#[cfg(feature = "native")]
+use crate::play::{self, MidiSink, OutputPort};
+
+/// Troca o canal de uma mensagem de canal (de 0x80 a 0xEF) pelo dado.
+///
+/// As outras mensagens, como SysEx e as de tempo real, passam como vieram.
+pub fn remap(message: &[u8], channel: Option<u8>) -> Vec<u8> {
+    let mut message = message.to_vec();
+    if let (Some(status), Some(channel)) = (message.first_mut(), channel) {
+        if (0x80..0xF0).contains(status) {
+            *status = (*status & 0xF0) | (channel & 0x0F);
+        }
+    }
+
+    message
+}
+
+/// O canal para onde o thru manda as mensagens, que pode mudar com ele ligado.
+#[derive(Debug)]
+pub struct ThruChannel(AtomicU8);
+
+impl ThruChannel {
+    /// Valor guardado quando os canais são mantidos.
+    const KEEP: u8 = u8::MAX;
+
+    /// O canal de destino, ou `None` para manter o de cada mensagem.
+    pub fn get(&self) -> Option<u8> {
+        Some(self.0.load(Ordering::Relaxed)).filter(|&channel| channel != Self::KEEP)
+    }
+
+    /// Troca o canal de destino.
+    pub fn set(&self, channel: Option<u8>) {
+        self.0
+            .store(channel.unwrap_or(Self::KEEP), Ordering::Relaxed);
+    }
+}
+
+impl Default for ThruChannel {
+    fn default() -> Self {
+        Self(AtomicU8::new(Self::KEEP))
+    }
+}
+
+/// Liga o thru, da primeira porta de entrada para a saída preferida (ou a primeira).
+///
+/// O thru fica ligado enquanto a conexão retornada existir.
+#[cfg(feature = "native")]
+pub fn connect(
+    preferred_output: Option<&str>,
+    channel: Arc<ThruChannel>,
+) -> Result<MidiInputConnection<OutputPort>, Box<dyn Error>> {
+    let mut midi_in = MidiInput::new("TCP thru")?;
+    midi_in.ignore(Ignore::None);
+
+    let in_ports = midi_in.ports();
+    let in_port = in_ports.first().ok_or("No input port found.")?;
+    let output = play::prepare_connection(preferred_output)?;
+    info!(
+        input = midi_in.port_name(in_port)?,
+        output = output.name(),
+        "MIDI thru on"
+    );
+
+    let connection = midi_in.connect(
+        in_port,
+        "midir",
+        move |_, message, output: &mut OutputPort| {
+            let _ = output.send(&remap(message, channel.get()));
+        },
+        output,
+    )?;
+
+    Ok(connection)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn only_channel_messages_move() {
+        assert_eq!(remap(&[0x93, 60, 100], Some(9)), [0x99, 60, 100]);
+        assert_eq!(remap(&[0xC0, 5], None), [0xC0, 5]);
+        assert_eq!(remap(&[0xF8], Some(2)), [0xF8]);
+        assert_eq!(remap(&[0xF0, 0x7E, 0xF7], Some(2)), [0xF0, 0x7E, 0xF7]);
+    }
+}
@@ -0,0 +1,130 @@
+use std::{error::Error, fmt};
+
+use midly::num::*;
+
+/// Valor de um byte de dados do MIDI, restrito ao intervalo [0, 127].
+///
+/// Ao contrário do `from_int_lossy` do `midly`, que descarta os bits mais altos
+/// (transformando 128 em 0, por exemplo), a construção é sempre explícita:
+/// ou o valor é saturado, ou é retornado um erro.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MidiValue(u8);
+
+/// Erro para valores que não cabem no tipo do MIDI desejado.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OutOfRange {
+    /// O valor recebido.
+    pub value: u32,
+    /// O maior valor aceito.
+    pub max: u32,
+}
+
+impl fmt::Display for OutOfRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "value {} is above the maximum of {}",
+            self.value, self.max
+        )
+    }
+}
+
+impl Error for OutOfRange {}
+
+impl MidiValue {
+    /// O menor valor possível.
+    pub const MIN: Self = Self(0);
+
+    /// O maior valor possível.
+    pub const MAX: Self = Self(127);
+
+    /// Cria um valor em contexto constante, falhando a compilação se estiver fora do intervalo.
+    pub const fn new(value: u8) -> Self {
+        assert!(value <= Self::MAX.0, "MIDI data values must be at most 127");
+        Self(value)
+    }
+
+    /// Cria um valor, saturando no máximo caso seja grande demais.
+    pub fn clamped(value: impl Into<u32>) -> Self {
+        Self(value.into().min(Self::MAX.0 as u32) as u8)
+    }
+
+    /// Cria um valor, retornando erro caso seja grande demais.
+    pub fn try_new(value: impl Into<u32>) -> Result<Self, OutOfRange> {
+        let value = value.into();
+        if value <= Self::MAX.0 as u32 {
+            Ok(Self(value as u8))
+        } else {
+            Err(OutOfRange {
+                value,
+                max: Self::MAX.0 as u32,
+            })
+        }
+    }
+
+    /// O valor como inteiro.
+    pub const fn as_int(self) -> u8 {
+        self.0
+    }
+
+    /// O valor no tipo do `midly`, também disponível em contexto constante.
+    pub const fn as_u7(self) -> u7 {
+        u7::new(self.0)
+    }
+}
+
+impl From<MidiValue> for u7 {
+    fn from(value: MidiValue) -> Self {
+        value.as_u7()
+    }
+}
+
+/// Cria um canal em contexto constante, falhando a compilação se não couber em 4 bits.
+pub const fn channel(value: u8) -> u4 {
+    assert!(value <= 0xF, "MIDI channels must be at most 15");
+    u4::new(value)
+}
+
+/// Cria um delta em ticks em contexto constante, falhando a compilação se não couber em 28 bits.
+pub const fn ticks(value: u32) -> u28 {
+    assert!(value <= 0x0FFF_FFFF, "deltas must fit in 28 bits");
+    u28::new(value)
+}
+
+/// Cria uma resolução (ticks por semimínima) em contexto constante,
+/// falhando a compilação se não couber em 15 bits.
+pub const fn tpqn(value: u16) -> u15 {
+    assert!(value <= 0x7FFF, "the TPQN must fit in 15 bits");
+    u15::new(value)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn clamps_at_the_boundaries() {
+        assert_eq!(MidiValue::clamped(0_u8), MidiValue::MIN);
+        assert_eq!(MidiValue::clamped(127_u8).as_int(), 127);
+        assert_eq!(MidiValue::clamped(128_u8), MidiValue::MAX);
+        assert_eq!(MidiValue::clamped(u16::MAX), MidiValue::MAX);
+    }
+
+    #[test]
+    fn rejects_out_of_range() {
+        assert_eq!(MidiValue::try_new(127_u8), Ok(MidiValue::MAX));
+        assert_eq!(
+            MidiValue::try_new(128_u16),
+            Err(OutOfRange {
+                value: 128,
+                max: 127
+            })
+        );
+    }
+
+    #[test]
+    fn no_wrap_around() {
+        // `from_int_lossy` would turn 200 into 72.
+        assert_eq!(u7::from(MidiValue::clamped(200_u16)).as_int(), 127);
+    }
+}
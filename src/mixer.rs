@@ -0,0 +1,232 @@
+use midly::{num::*, MetaMessage, MidiMessage, Smf, Track, TrackEvent, TrackEventKind};
+
+use crate::midi_value::{self, MidiValue};
+
+/// Controlador usado pelo fader do mixer.
+///
+/// É usado o de expressão (CC 11), e não o de volume (CC 7), para não conflitar
+/// com as mudanças de volume feitas pelo próprio texto.
+pub const FADER_CONTROLLER: MidiValue = MidiValue::new(11);
+
+/// Controlador de pan (CC 10).
+pub const PAN_CONTROLLER: MidiValue = MidiValue::new(10);
+
+/// Controlador que desliga todas as notas do canal (CC 123).
+pub const ALL_NOTES_OFF: MidiValue = MidiValue::new(123);
+
+/// Quantidade de canais do MIDI.
+pub const CHANNELS: usize = 16;
+
+/// Configuração de um canal no mixer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChannelStrip {
+    /// Volume do fader, de 0 a 127.
+    pub volume: u8,
+    /// Pan, de 0 (esquerda) a 127 (direita), com 64 no centro.
+    pub pan: u8,
+    /// Se o canal está silenciado.
+    pub mute: bool,
+    /// Se o canal está em solo.
+    pub solo: bool,
+}
+
+impl Default for ChannelStrip {
+    fn default() -> Self {
+        Self {
+            volume: MidiValue::MAX.as_int(),
+            pan: 64,
+            mute: false,
+            solo: false,
+        }
+    }
+}
+
+/// Configuração de uma trilha no mixer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TrackStrip {
+    /// Se a trilha está silenciada.
+    pub mute: bool,
+    /// Se a trilha está em solo.
+    pub solo: bool,
+}
+
+/// Mixer com um canal para cada canal do MIDI, e uma faixa para cada trilha.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Mixer {
+    /// Os canais.
+    pub strips: [ChannelStrip; CHANNELS],
+    /// As trilhas. Trilhas sem faixa correspondente são sempre ouvidas.
+    pub tracks: Vec<TrackStrip>,
+}
+
+impl Mixer {
+    /// Se as notas da trilha devem ser ouvidas, considerando mute e solo.
+    pub fn is_track_audible(&self, track: usize) -> bool {
+        let strip = self.tracks.get(track).copied().unwrap_or_default();
+        let any_solo = self.tracks.iter().any(|strip| strip.solo);
+
+        !strip.mute && (!any_solo || strip.solo)
+    }
+
+    /// Remove do arquivo as trilhas silenciadas, para a exportação.
+    ///
+    /// Trilhas sem nenhuma mensagem de canal (como a trilha de tempo do formato 1)
+    /// são mantidas, pois não tocam notas mas definem a temporização.
+    pub fn remove_muted_tracks(&self, smf: &mut Smf<'_>) {
+        let mut index = 0;
+        smf.tracks.retain(|track| {
+            let keep = self.is_track_audible(index) || channels_in_use([track]).is_empty();
+            index += 1;
+            keep
+        });
+    }
+
+    /// Se as notas do canal devem ser ouvidas, considerando mute e solo.
+    pub fn is_audible(&self, channel: u4) -> bool {
+        let strip = self.strips[channel.as_int() as usize];
+        let any_solo = self.strips.iter().any(|strip| strip.solo);
+
+        !strip.mute && (!any_solo || strip.solo)
+    }
+
+    /// As mensagens que levam o canal aos valores do mixer.
+    pub fn channel_messages(&self, channel: u4) -> [MidiMessage; 2] {
+        let strip = self.strips[channel.as_int() as usize];
+        [
+            MidiMessage::Controller {
+                controller: FADER_CONTROLLER.into(),
+                value: MidiValue::clamped(strip.volume).into(),
+            },
+            MidiMessage::Controller {
+                controller: PAN_CONTROLLER.into(),
+                value: MidiValue::clamped(strip.pan).into(),
+            },
+        ]
+    }
+
+    /// Adiciona os valores iniciais do mixer no começo da trilha,
+    /// para cada canal usado por ela.
+    pub fn apply(&self, track: &mut Track<'_>) {
+        let events: Vec<_> = channels_in_use([&*track])
+            .into_iter()
+            .flat_map(|channel| {
+                self.channel_messages(channel).map(|message| TrackEvent {
+                    delta: midi_value::ticks(0),
+                    kind: TrackEventKind::Midi { channel, message },
+                })
+            })
+            .collect();
+
+        // Os eventos vão antes do primeiro evento com duração, herdando o seu delta
+        let first_delay = track
+            .iter()
+            .position(|event| event.delta > 0)
+            .unwrap_or(track.len());
+        track.splice(first_delay..first_delay, events);
+    }
+}
+
+/// O nome da trilha, caso ela tenha um.
+pub fn track_name(track: &Track<'_>) -> Option<String> {
+    track.iter().find_map(|event| match event.kind {
+        TrackEventKind::Meta(MetaMessage::TrackName(name)) => {
+            Some(String::from_utf8_lossy(name).into_owned())
+        }
+        _ => None,
+    })
+}
+
+/// Os canais que possuem mensagens nas trilhas, em ordem.
+pub fn channels_in_use<'a, 'b: 'a>(tracks: impl IntoIterator<Item = &'a Track<'b>>) -> Vec<u4> {
+    let mut used = [false; CHANNELS];
+    for event in tracks.into_iter().flatten() {
+        if let TrackEventKind::Midi { channel, .. } = event.kind {
+            used[channel.as_int() as usize] = true;
+        }
+    }
+
+    (0..CHANNELS as u8)
+        .filter(|&channel| used[channel as usize])
+        .map(midi_value::channel)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        midi_action::MidiAction,
+        text_to_midi::{Sheet, State},
+    };
+
+    #[test]
+    fn solo_silences_others() {
+        let mut mixer = Mixer::default();
+        assert!(mixer.is_audible(u4::from(0)));
+
+        mixer.strips[1].solo = true;
+
+        assert!(!mixer.is_audible(u4::from(0)));
+        assert!(mixer.is_audible(u4::from(1)));
+
+        mixer.strips[1].mute = true;
+
+        assert!(!mixer.is_audible(u4::from(1)));
+    }
+
+    #[test]
+    fn track_solo_and_export() {
+        // Arrange
+        let actions = Sheet::builder().text("CD").build().unwrap().process();
+        let mut smf = MidiAction::as_track(&actions);
+        smf.tracks.push(smf.tracks[0].clone());
+        let mixer = Mixer {
+            tracks: vec![
+                TrackStrip::default(),
+                TrackStrip {
+                    mute: true,
+                    solo: false,
+                },
+            ],
+            ..Default::default()
+        };
+
+        // Act
+        mixer.remove_muted_tracks(&mut smf);
+
+        // Assert
+        assert!(mixer.is_track_audible(0));
+        assert!(!mixer.is_track_audible(1));
+        assert!(mixer.is_track_audible(2));
+        assert_eq!(smf.tracks.len(), 1);
+        assert_eq!(track_name(&smf.tracks[0]).as_deref(), Some("tcp_out"));
+    }
+
+    #[test]
+    fn initial_values_in_export() {
+        // Arrange
+        let actions = Sheet::builder().text("CD").build().unwrap().process();
+        let mut smf = MidiAction::as_track(&actions);
+        let mut mixer = Mixer::default();
+        mixer.strips[0].pan = 0;
+        let before = smf.tracks[0].len();
+
+        // Act
+        mixer.apply(&mut smf.tracks[0]);
+
+        // Assert
+        let track = &smf.tracks[0];
+        assert_eq!(track.len(), before + 2);
+        assert!(track.iter().any(|event| event.kind
+            == TrackEventKind::Midi {
+                channel: u4::from(0),
+                message: MidiMessage::Controller {
+                    controller: PAN_CONTROLLER.into(),
+                    value: u7::from(0),
+                },
+            }));
+        // Nothing moved in time
+        let end: u32 = track.iter().map(|event| event.delta.as_int()).sum();
+        assert_eq!(end, 2 * 480 + 1);
+    }
+}
@@ -1,42 +1,67 @@
+use std::{
+    fmt,
+    ops::{Add, Sub},
+};
+
+use midly::num::u7;
 use rand::{
     distributions::{Distribution, Standard},
     Rng,
 };
+use serde::{Deserialize, Serialize};
+
+/// Estilo usado para exibir o nome das notas.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NameStyle {
+    /// Letras: C, D, E, F, G, A, B.
+    #[default]
+    Letter,
+    /// Solfejo: Dó, Ré, Mi, Fá, Sol, Lá, Si.
+    Solfege,
+}
+
+impl NameStyle {
+    /// Todos os estilos, para seleção na interface.
+    pub const ALL: [Self; 2] = [Self::Letter, Self::Solfege];
+}
+
+impl fmt::Display for NameStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Letter => write!(f, "C-D-E"),
+            Self::Solfege => write!(f, "Dó-Ré-Mi"),
+        }
+    }
+}
 
 /// Enum com as notas possíveis.
-#[derive(Clone, Copy, Default)]
-#[repr(u8)]
+///
+/// As notas não carregam valor numérico; a altura de cada uma é dada pelo `semitone`,
+/// e a pausa não tem altura.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Note {
     /// Nota dó.
     #[default]
-    Do = 0,
+    Do,
     /// Nota ré.
-    Re = 2,
+    Re,
     /// Nota mi.
-    Mi = 4,
+    Mi,
     /// Nota fa.
-    Fa = 5,
+    Fa,
     /// Nota sol.
-    Sol = 7,
+    Sol,
     /// Nota la.
-    La = 9,
+    La,
     /// Nota si.
-    Si = 11,
+    Si,
     /// Nota de pausa.
-    Pause = 13,
+    Pause,
 }
 
 impl Distribution<Note> for Standard {
     fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Note {
-        match rng.gen_range(0..=Note::TOTAL_NOTES) {
-            0 => Note::Do,
-            1 => Note::Re,
-            2 => Note::Mi,
-            3 => Note::Fa,
-            4 => Note::Sol,
-            5 => Note::La,
-            _ => Note::Si,
-        }
+        Note::NATURALS[rng.gen_range(0..Note::NATURALS.len())]
     }
 }
 
@@ -44,6 +69,51 @@ impl Note {
     /// Descontando os acidentes que não estão na especificação do trabalho.
     pub const TOTAL_NOTES: usize = 8;
 
+    /// Todas as notas, incluindo a pausa.
+    pub const ALL: [Self; Self::TOTAL_NOTES] = [
+        Self::Do,
+        Self::Re,
+        Self::Mi,
+        Self::Fa,
+        Self::Sol,
+        Self::La,
+        Self::Si,
+        Self::Pause,
+    ];
+
+    /// As sete notas naturais, de dó a si, sem a pausa.
+    pub const NATURALS: [Self; 7] = [
+        Self::Do,
+        Self::Re,
+        Self::Mi,
+        Self::Fa,
+        Self::Sol,
+        Self::La,
+        Self::Si,
+    ];
+
+    /// Semitons por oitava, contando os acidentes.
+    pub const SEMITONES: u8 = 12;
+
+    /// Itera sobre todas as notas, incluindo a pausa.
+    pub fn iter() -> impl Iterator<Item = Self> {
+        Self::ALL.into_iter()
+    }
+
+    /// Distância em semitons a partir do dó da mesma oitava, ou `None` para a pausa.
+    pub const fn semitone(self) -> Option<u8> {
+        match self {
+            Self::Do => Some(0),
+            Self::Re => Some(2),
+            Self::Mi => Some(4),
+            Self::Fa => Some(5),
+            Self::Sol => Some(7),
+            Self::La => Some(9),
+            Self::Si => Some(11),
+            Self::Pause => None,
+        }
+    }
+
     /// A partir de um caractere, cria uma nota.
     pub const fn from_char(ch: char) -> Option<Self> {
         match ch {
@@ -59,8 +129,373 @@ impl Note {
         }
     }
 
+    /// O nome da nota no estilo dado.
+    pub const fn to_name(self, style: NameStyle) -> &'static str {
+        match style {
+            NameStyle::Letter => match self {
+                Self::Do => "C",
+                Self::Re => "D",
+                Self::Mi => "E",
+                Self::Fa => "F",
+                Self::Sol => "G",
+                Self::La => "A",
+                Self::Si => "B",
+                Self::Pause => "-",
+            },
+            NameStyle::Solfege => match self {
+                Self::Do => "Dó",
+                Self::Re => "Ré",
+                Self::Mi => "Mi",
+                Self::Fa => "Fá",
+                Self::Sol => "Sol",
+                Self::La => "Lá",
+                Self::Si => "Si",
+                Self::Pause => "-",
+            },
+        }
+    }
+
+    /// O nome da nota com a oitava, no estilo dado, como "C4" ou "Dó4".
+    ///
+    /// A oitava segue a mesma numeração do `to_midi`. Pausas não têm oitava.
+    pub fn name_with_octave(self, octave: u8, style: NameStyle) -> String {
+        match self {
+            Self::Pause => self.to_name(style).to_string(),
+            note => format!("{}{octave}", note.to_name(style)),
+        }
+    }
+
     /// Calcula o valor da nota para a reprodução em MIDI.
-    pub const fn to_midi(self, octave: u8) -> u8 {
-        self as u8 + 12 * (1 + octave)
+    ///
+    /// Retorna `None` para a pausa. O valor pode passar de 127 em oitavas altas;
+    /// use `to_u7` para uma conversão verificada.
+    pub const fn to_midi(self, octave: u8) -> Option<u8> {
+        match self.semitone() {
+            Some(semitone) => Some(semitone + Self::SEMITONES * (1 + octave)),
+            None => None,
+        }
+    }
+
+    /// Calcula o valor da nota já no tipo do MIDI, ou `None` se for pausa ou não couber.
+    pub fn to_u7(self, octave: u8) -> Option<u7> {
+        self.to_midi(octave).and_then(u7::try_from)
+    }
+
+    /// Encontra a nota, a oitava e o acidente de uma nota do MIDI, inverso do `to_midi`.
+    ///
+    /// Notas com acidente são escritas com sustenido (C#, e não Db).
+    /// Retorna `None` para as notas abaixo da oitava 0 (MIDI 0 a 11).
+    pub fn from_midi(key: u8) -> Option<(Self, u8, Accidental)> {
+        let pitch = Pitch::from_midi(key)?;
+        Some((pitch.note, pitch.octave, pitch.accidental))
+    }
+
+    /// Igual ao `from_midi`, a partir do tipo do MIDI.
+    pub fn from_u7(key: u7) -> Option<(Self, u8, Accidental)> {
+        Self::from_midi(key.as_int())
+    }
+}
+
+/// Acidente de uma nota.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Accidental {
+    /// Sem acidente.
+    #[default]
+    Natural,
+    /// Meio tom acima.
+    Sharp,
+}
+
+impl Accidental {
+    /// Quantos semitons o acidente adiciona.
+    pub const fn semitones(self) -> u8 {
+        match self {
+            Self::Natural => 0,
+            Self::Sharp => 1,
+        }
+    }
+
+    /// O símbolo do acidente.
+    pub const fn symbol(self) -> &'static str {
+        match self {
+            Self::Natural => "",
+            Self::Sharp => "#",
+        }
+    }
+}
+
+/// Intervalo entre duas notas, em semitons.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Interval {
+    /// A mesma nota (0 semitons).
+    Unison,
+    /// Segunda menor (1 semitom).
+    MinorSecond,
+    /// Segunda maior (2 semitons).
+    MajorSecond,
+    /// Terça menor (3 semitons).
+    MinorThird,
+    /// Terça maior (4 semitons).
+    MajorThird,
+    /// Quarta justa (5 semitons).
+    PerfectFourth,
+    /// Trítono, a quarta aumentada (6 semitons).
+    Tritone,
+    /// Quinta justa (7 semitons).
+    PerfectFifth,
+    /// Sexta menor (8 semitons).
+    MinorSixth,
+    /// Sexta maior (9 semitons).
+    MajorSixth,
+    /// Sétima menor (10 semitons).
+    MinorSeventh,
+    /// Sétima maior (11 semitons).
+    MajorSeventh,
+    /// Oitava (12 semitons).
+    Octave,
+}
+
+impl Interval {
+    /// Todos os intervalos, do uníssono à oitava.
+    pub const ALL: [Self; 13] = [
+        Self::Unison,
+        Self::MinorSecond,
+        Self::MajorSecond,
+        Self::MinorThird,
+        Self::MajorThird,
+        Self::PerfectFourth,
+        Self::Tritone,
+        Self::PerfectFifth,
+        Self::MinorSixth,
+        Self::MajorSixth,
+        Self::MinorSeventh,
+        Self::MajorSeventh,
+        Self::Octave,
+    ];
+
+    /// O tamanho do intervalo em semitons.
+    pub const fn semitones(self) -> u8 {
+        self as u8
+    }
+
+    /// O intervalo entre duas notas do MIDI, reduzido a uma oitava
+    /// (uma décima vira uma terça; a oitava exata é mantida).
+    pub fn between(low: u8, high: u8) -> Self {
+        let distance = low.abs_diff(high);
+        match distance % Note::SEMITONES {
+            0 if distance > 0 => Self::Octave,
+            semitones => Self::ALL[semitones as usize],
+        }
+    }
+}
+
+/// Uma nota com oitava e acidente, ou seja, uma altura definida.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Pitch {
+    /// A nota natural.
+    pub note: Note,
+    /// A oitava, com a mesma numeração do `Note::to_midi`.
+    pub octave: u8,
+    /// O acidente.
+    pub accidental: Accidental,
+}
+
+impl Pitch {
+    /// Cria uma altura sem acidente.
+    pub const fn new(note: Note, octave: u8) -> Self {
+        Self {
+            note,
+            octave,
+            accidental: Accidental::Natural,
+        }
+    }
+
+    /// A nota do MIDI correspondente, ou `None` para a pausa.
+    pub const fn to_midi(self) -> Option<u8> {
+        match self.note.to_midi(self.octave) {
+            Some(key) => Some(key + self.accidental.semitones()),
+            None => None,
+        }
+    }
+
+    /// A altura de uma nota do MIDI, escrita com sustenidos.
+    ///
+    /// Retorna `None` para as notas abaixo da oitava 0 (MIDI 0 a 11) e acima de 127.
+    pub fn from_midi(key: u8) -> Option<Self> {
+        u7::try_from(key)?;
+        let octave = (key / Note::SEMITONES).checked_sub(1)?;
+        let semitone = key % Note::SEMITONES;
+        let natural = |semitone| {
+            Note::NATURALS
+                .into_iter()
+                .find(|note| note.semitone() == Some(semitone))
+        };
+
+        Some(match natural(semitone) {
+            Some(note) => Self::new(note, octave),
+            None => Self {
+                note: natural(semitone - 1)?,
+                octave,
+                accidental: Accidental::Sharp,
+            },
+        })
+    }
+
+    /// Transpõe por uma quantidade de semitons, positiva ou negativa.
+    ///
+    /// Retorna `None` para pausas, e se o resultado sair da faixa representável.
+    pub fn transpose(self, semitones: i16) -> Option<Self> {
+        let key = self.to_midi()? as i16 + semitones;
+        Self::from_midi(u8::try_from(key).ok()?)
+    }
+
+    /// O nome da altura no estilo dado, como "C#4" ou "Dó#4".
+    pub fn name(self, style: NameStyle) -> String {
+        match self.note {
+            Note::Pause => self.note.to_name(style).to_string(),
+            note => format!(
+                "{}{}{}",
+                note.to_name(style),
+                self.accidental.symbol(),
+                self.octave
+            ),
+        }
+    }
+}
+
+/// Sobe um intervalo. Entra em pânico se o resultado sair da faixa, como a soma de inteiros;
+/// use `Pitch::transpose` para uma versão verificada.
+impl Add<Interval> for Pitch {
+    type Output = Self;
+
+    fn add(self, interval: Interval) -> Self {
+        self.transpose(interval.semitones() as i16)
+            .expect("transposition out of the MIDI range")
+    }
+}
+
+/// Desce um intervalo. Entra em pânico se o resultado sair da faixa.
+impl Sub<Interval> for Pitch {
+    type Output = Self;
+
+    fn sub(self, interval: Interval) -> Self {
+        self.transpose(-(interval.semitones() as i16))
+            .expect("transposition out of the MIDI range")
+    }
+}
+
+impl fmt::Display for Pitch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name(NameStyle::Letter))
+    }
+}
+
+/// Exibe a nota com letras; use `{:#}` para solfejo.
+impl fmt::Display for Note {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let style = if f.alternate() {
+            NameStyle::Solfege
+        } else {
+            NameStyle::Letter
+        };
+        f.write_str(self.to_name(style))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn names_in_both_styles() {
+        assert_eq!(Note::Re.to_name(NameStyle::Letter), "D");
+        assert_eq!(Note::Re.to_name(NameStyle::Solfege), "Ré");
+        assert_eq!(format!("{} {:#}", Note::Sol, Note::Sol), "G Sol");
+    }
+
+    #[test]
+    fn names_with_octave() {
+        assert_eq!(Note::Do.name_with_octave(4, NameStyle::Letter), "C4");
+        assert_eq!(Note::La.name_with_octave(5, NameStyle::Solfege), "Lá5");
+        assert_eq!(Note::Pause.name_with_octave(4, NameStyle::Solfege), "-");
+    }
+
+    #[test]
+    fn midi_values() {
+        assert_eq!(Note::Do.to_midi(4), Some(60));
+        assert_eq!(Note::La.to_midi(4), Some(69));
+        assert_eq!(Note::Pause.to_midi(4), None);
+        assert_eq!(Note::Si.to_u7(12), None);
+        assert_eq!(Note::Do.to_u7(9), Some(u7::from(120)));
+    }
+
+    #[test]
+    fn midi_round_trip() {
+        for note in Note::NATURALS {
+            for octave in 0..=8 {
+                let key = note.to_u7(octave).unwrap();
+                assert_eq!(
+                    Note::from_u7(key),
+                    Some((note, octave, Accidental::Natural))
+                );
+            }
+        }
+        // Notes below octave 0 are not representable
+        assert_eq!(Note::from_midi(11), None);
+    }
+
+    #[test]
+    fn accidentals_use_sharps() {
+        assert_eq!(Note::from_midi(61), Some((Note::Do, 4, Accidental::Sharp)));
+        assert_eq!(Pitch::from_midi(70).unwrap().to_string(), "A#4");
+        assert_eq!(
+            Pitch::from_midi(66).unwrap().name(NameStyle::Solfege),
+            "Fá#4"
+        );
+        for key in 12..=127 {
+            assert_eq!(Pitch::from_midi(key).unwrap().to_midi(), Some(key));
+        }
+    }
+
+    #[test]
+    fn interval_arithmetic() {
+        let c4 = Pitch::new(Note::Do, 4);
+
+        assert_eq!(c4 + Interval::MajorThird, Pitch::new(Note::Mi, 4));
+        assert_eq!(c4 + Interval::PerfectFifth, Pitch::new(Note::Sol, 4));
+        assert_eq!(c4 + Interval::Octave, Pitch::new(Note::Do, 5));
+        assert_eq!(c4 - Interval::MinorThird, Pitch::new(Note::La, 3));
+        assert_eq!((c4 + Interval::MinorThird).to_string(), "D#4");
+        assert_eq!(Pitch::new(Note::Si, 8).transpose(12), None);
+        assert_eq!(Pitch::new(Note::Pause, 4).transpose(1), None);
+    }
+
+    #[test]
+    fn interval_between() {
+        assert_eq!(Interval::between(60, 64), Interval::MajorThird);
+        assert_eq!(Interval::between(67, 60), Interval::PerfectFifth);
+        assert_eq!(Interval::between(60, 72), Interval::Octave);
+        assert_eq!(Interval::between(60, 76), Interval::MajorThird);
+        assert_eq!(Interval::between(60, 60), Interval::Unison);
+    }
+
+    #[test]
+    fn random_notes_are_uniform() {
+        let mut rng = rand::thread_rng();
+        let mut counts = [0; 7];
+        for _ in 0..7000 {
+            let note: Note = rng.gen();
+            counts[Note::NATURALS.iter().position(|&n| n == note).unwrap()] += 1;
+        }
+
+        assert!(counts.iter().all(|&count| (700..1300).contains(&count)));
+    }
+
+    #[test]
+    fn every_letter_round_trips() {
+        for ch in "ABCDEFG".chars() {
+            let note = Note::from_char(ch).unwrap();
+            assert_eq!(note.to_name(NameStyle::Letter), ch.to_string());
+        }
     }
 }
@@ -0,0 +1,169 @@
+//! Troca dos programas do General MIDI pelos de aparelhos que não seguem o GM,
+//! lida de um arquivo TOML como este:
+//!
+//! ```toml
+//! # Acoustic Grand Piano
+//! [[patch]]
+//! gm = 0
+//! program = 12
+//! bank = 130
+//! ```
+//!
+//! Os programas são contados a partir de 0, como em `instruments::GM_NAMES`, e o banco,
+//! opcional, é o valor de 14 bits enviado em CC 0 e CC 32 antes do programa.
+
+use std::{error::Error, fs, path::Path};
+
+use midly::{
+    num::{u4, u7},
+    MidiMessage, Track, TrackEvent, TrackEventKind,
+};
+use serde::Deserialize;
+
+/// O programa e o banco do aparelho que tocam um programa do GM.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub struct Patch {
+    /// O programa do GM trocado.
+    pub gm: u8,
+    /// O programa do aparelho.
+    pub program: u8,
+    /// O banco do aparelho, se precisar de um.
+    pub bank: Option<u16>,
+}
+
+/// As trocas de programa de um aparelho. Programas sem troca passam como vieram.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+pub struct PatchMap {
+    #[serde(default, rename = "patch")]
+    patches: Vec<Patch>,
+}
+
+impl PatchMap {
+    /// Lê as trocas de um texto em TOML, recusando programas e bancos fora do MIDI.
+    pub fn from_toml(text: &str) -> Result<Self, Box<dyn Error>> {
+        let map: Self = toml::from_str(text)?;
+        for patch in &map.patches {
+            if patch.gm > 127 || patch.program > 127 {
+                return Err(format!("program {} is above 127", patch.gm.max(patch.program)).into());
+            }
+            if let Some(bank) = patch.bank.filter(|&bank| bank > 0x3FFF) {
+                return Err(format!("bank {bank} is above 16383").into());
+            }
+        }
+
+        Ok(map)
+    }
+
+    /// Lê as trocas de um arquivo TOML.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        Self::from_toml(&fs::read_to_string(path)?)
+    }
+
+    /// A troca do programa do GM dado, se houver.
+    pub fn get(&self, gm: u8) -> Option<&Patch> {
+        // A última troca de um programa vale, como em uma tabela reescrita
+        self.patches.iter().rev().find(|patch| patch.gm == gm)
+    }
+
+    /// Quantas trocas há.
+    pub fn len(&self) -> usize {
+        self.patches.len()
+    }
+
+    /// Troca os programas da trilha, pondo a seleção de banco logo antes, no mesmo tick.
+    pub fn apply(&self, track: &mut Track<'_>) {
+        if self.patches.is_empty() {
+            return;
+        }
+        let mut mapped = Vec::with_capacity(track.len());
+        for event in track.drain(..) {
+            let TrackEventKind::Midi {
+                channel,
+                message: MidiMessage::ProgramChange { program },
+            } = event.kind
+            else {
+                mapped.push(event);
+                continue;
+            };
+            let Some(patch) = self.get(program.as_int()) else {
+                mapped.push(event);
+                continue;
+            };
+
+            let mut delta = event.delta;
+            if let Some(bank) = patch.bank {
+                for (controller, value) in [(0, bank >> 7), (32, bank & 0x7F)] {
+                    mapped.push(control(delta, channel, controller, value as u8));
+                    delta = 0.into();
+                }
+            }
+            mapped.push(TrackEvent {
+                delta,
+                kind: TrackEventKind::Midi {
+                    channel,
+                    message: MidiMessage::ProgramChange {
+                        program: u7::new(patch.program),
+                    },
+                },
+            });
+        }
+        *track = mapped;
+    }
+}
+
+/// Um evento de controle.
+fn control<'a>(delta: midly::num::u28, channel: u4, controller: u8, value: u8) -> TrackEvent<'a> {
+    TrackEvent {
+        delta,
+        kind: TrackEventKind::Midi {
+            channel,
+            message: MidiMessage::Controller {
+                controller: u7::new(controller),
+                value: u7::new(value),
+            },
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn programs_become_device_patches() {
+        let map = PatchMap::from_toml(
+            "[[patch]]\ngm = 0\nprogram = 12\nbank = 130\n\n[[patch]]\ngm = 40\nprogram = 3\n",
+        )
+        .unwrap();
+        let program = |delta: u32, program: u8| TrackEvent {
+            delta: delta.into(),
+            kind: TrackEventKind::Midi {
+                channel: 2.into(),
+                message: MidiMessage::ProgramChange {
+                    program: program.into(),
+                },
+            },
+        };
+        let mut track = vec![program(10, 0), program(5, 40), program(0, 7)];
+
+        map.apply(&mut track);
+
+        assert_eq!(
+            track,
+            [
+                control(10.into(), 2.into(), 0, 1),
+                control(0.into(), 2.into(), 32, 2),
+                program(0, 12),
+                program(5, 3),
+                program(0, 7),
+            ]
+        );
+    }
+
+    #[test]
+    fn out_of_range_patches_are_refused() {
+        assert!(PatchMap::from_toml("[[patch]]\ngm = 0\nprogram = 128\n").is_err());
+        assert!(PatchMap::from_toml("[[patch]]\ngm = 0\nprogram = 1\nbank = 20000\n").is_err());
+        assert!(PatchMap::from_toml("").unwrap().get(0).is_none());
+    }
+}
@@ -0,0 +1,130 @@
+//! Uma reprodução por vez: o que fazer quando o Play é apertado com outra música
+//! tocando, em vez de abrir uma segunda reprodução por cima da primeira.
+//!
+//! A nova reprodução pode ser recusada, esperar na fila ou tomar o lugar da atual. Em
+//! todos os casos ela só começa quando nada mais está tocando, então as mensagens de
+//! pânico da reprodução parada nunca calam as notas da seguinte.
+
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+/// O que fazer com um Play apertado enquanto outra reprodução toca.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Overlap {
+    /// Recusa a nova reprodução, com um aviso.
+    Reject,
+    /// A nova reprodução espera as anteriores terminarem.
+    Queue,
+    /// Para a reprodução atual e toca a nova logo depois.
+    #[default]
+    Replace,
+}
+
+/// A resposta a um pedido de reprodução.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Admission<T> {
+    /// Nada está tocando: a reprodução começa já.
+    Start(T),
+    /// A reprodução atual deve ser parada. A nova começa quando ela terminar.
+    StopCurrent,
+    /// A nova reprodução espera na fila.
+    Queued,
+    /// A nova reprodução foi recusada.
+    Rejected,
+}
+
+/// Decide quando as reproduções pedidas começam, guardando as que esperam.
+#[derive(Debug)]
+pub struct PlaybackGuard<T> {
+    /// O que fazer com os pedidos feitos durante uma reprodução.
+    pub overlap: Overlap,
+    pending: VecDeque<T>,
+}
+
+// O derive pediria `T: Default`
+impl<T> Default for PlaybackGuard<T> {
+    fn default() -> Self {
+        Self {
+            overlap: Overlap::default(),
+            pending: VecDeque::new(),
+        }
+    }
+}
+
+impl<T> PlaybackGuard<T> {
+    /// Pede para tocar `item`, com `busy` dizendo se outra reprodução está em andamento.
+    pub fn request(&mut self, busy: bool, item: T) -> Admission<T> {
+        if !busy && self.pending.is_empty() {
+            return Admission::Start(item);
+        }
+
+        match self.overlap {
+            Overlap::Reject => Admission::Rejected,
+            Overlap::Queue => {
+                self.pending.push_back(item);
+                Admission::Queued
+            }
+            Overlap::Replace => {
+                self.pending.clear();
+                self.pending.push_back(item);
+                Admission::StopCurrent
+            }
+        }
+    }
+
+    /// A próxima reprodução da fila, se nada mais está tocando.
+    pub fn next(&mut self, busy: bool) -> Option<T> {
+        if busy {
+            return None;
+        }
+
+        self.pending.pop_front()
+    }
+
+    /// Quantas reproduções esperam.
+    pub fn waiting(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Esquece as reproduções que esperam, como depois de um Stop.
+    pub fn clear(&mut self) {
+        self.pending.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn only_one_playback_at_a_time() {
+        let mut guard = PlaybackGuard::default();
+        assert_eq!(guard.request(false, "a"), Admission::Start("a"));
+
+        guard.overlap = Overlap::Reject;
+        assert_eq!(guard.request(true, "b"), Admission::Rejected);
+        assert_eq!(guard.next(false), None);
+
+        guard.overlap = Overlap::Queue;
+        assert_eq!(guard.request(true, "b"), Admission::Queued);
+        assert_eq!(guard.request(true, "c"), Admission::Queued);
+        assert_eq!(guard.next(true), None);
+        // Once the current one ends, a new request still waits behind the queue
+        assert_eq!(guard.request(false, "d"), Admission::Queued);
+        assert_eq!(guard.waiting(), 3);
+        assert_eq!(guard.next(false), Some("b"));
+
+        // Replacing drops the queue, keeping only the newest
+        guard.overlap = Overlap::Replace;
+        assert_eq!(guard.request(true, "e"), Admission::StopCurrent);
+        assert_eq!(guard.waiting(), 1);
+        assert_eq!(guard.next(false), Some("e"));
+        assert_eq!(guard.next(false), None);
+
+        guard.request(true, "f");
+        guard.clear();
+        assert_eq!(guard.next(false), None);
+    }
+}
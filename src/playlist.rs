@@ -0,0 +1,314 @@
+//! Playlist: arquivos e textos tocados um depois do outro, com uma pausa entre eles,
+//! como em uma audição ou em uma aula com vários exercícios.
+//!
+//! A lista é copiada quando a reprodução começa, então mudanças na ordem só valem na
+//! próxima vez. A repetição pode ser trocada durante a reprodução, e os botões de
+//! próximo e anterior param o item tocando, com as mensagens de pânico, para pular.
+
+use std::{
+    error::Error,
+    sync::{
+        atomic::{AtomicBool, AtomicIsize, AtomicU8, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use midly::Smf;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+use crate::{
+    clock::Clock,
+    play::{LiveControls, MidiSink, PlaybackOptions, Player, Playhead},
+};
+
+/// O que tocar depois de um item que terminou sozinho.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Repeat {
+    /// O próximo, parando depois do último.
+    #[default]
+    Off,
+    /// O mesmo item de novo.
+    One,
+    /// O próximo, voltando ao primeiro depois do último.
+    All,
+}
+
+impl Repeat {
+    /// Todos os modos, na ordem da interface.
+    pub const ALL: [Self; 3] = [Self::Off, Self::One, Self::All];
+
+    fn from_index(index: u8) -> Self {
+        Self::ALL.get(index as usize).copied().unwrap_or_default()
+    }
+
+    fn index(self) -> u8 {
+        Self::ALL
+            .iter()
+            .position(|&repeat| repeat == self)
+            .unwrap_or(0) as u8
+    }
+}
+
+/// Os arquivos tocados em sequência, compartilhados entre a interface e a thread da
+/// reprodução.
+#[derive(Debug)]
+pub struct Playlist {
+    /// Os arquivos MIDI, na ordem da lista.
+    items: Vec<Vec<u8>>,
+    /// A ordem em que os itens tocam: a da lista, ou embaralhada.
+    order: Vec<usize>,
+    /// O silêncio entre um arquivo e o próximo.
+    gap: Duration,
+    /// O `Repeat`, pelo seu índice em `Repeat::ALL`.
+    repeat: AtomicU8,
+    /// A posição em `order` do item tocando, ou `usize::MAX` se nenhum.
+    position: AtomicUsize,
+    /// O salto pedido pelos botões: 1 para o próximo, -1 para o anterior, 0 nenhum.
+    skip: AtomicIsize,
+    /// Se a interface pediu para parar a lista inteira.
+    stopped: AtomicBool,
+    /// Os controles do item tocando, que são parados para pular para outro.
+    controls: Mutex<Arc<LiveControls>>,
+    playhead: Arc<Playhead>,
+}
+
+impl Playlist {
+    /// De quanto em quanto tempo a pausa entre os arquivos confere se a reprodução parou.
+    const STOP_CHECK: Duration = Duration::from_millis(50);
+
+    /// Uma lista com os arquivos dados, na ordem dada, com `gap` de silêncio entre eles.
+    pub fn new(items: Vec<Vec<u8>>, gap: Duration) -> Self {
+        Self {
+            order: (0..items.len()).collect(),
+            items,
+            gap,
+            repeat: AtomicU8::new(Repeat::Off.index()),
+            position: AtomicUsize::new(usize::MAX),
+            skip: AtomicIsize::new(0),
+            stopped: AtomicBool::new(false),
+            controls: Mutex::default(),
+            playhead: Arc::default(),
+        }
+    }
+
+    /// Define o que tocar depois de cada item.
+    pub fn with_repeat(self, repeat: Repeat) -> Self {
+        self.set_repeat(repeat);
+        self
+    }
+
+    /// Embaralha a ordem dos itens. A mesma semente dá sempre a mesma ordem.
+    pub fn with_shuffle(mut self, seed: u64) -> Self {
+        self.order.shuffle(&mut StdRng::seed_from_u64(seed));
+        self
+    }
+
+    /// O que tocar depois de cada item.
+    pub fn repeat(&self) -> Repeat {
+        Repeat::from_index(self.repeat.load(Ordering::Relaxed))
+    }
+
+    /// Troca o que tocar depois de cada item, valendo já para o item tocando.
+    pub fn set_repeat(&self, repeat: Repeat) {
+        self.repeat.store(repeat.index(), Ordering::Relaxed);
+    }
+
+    /// A posição na lista do item tocando.
+    pub fn current(&self) -> Option<usize> {
+        self.order
+            .get(self.position.load(Ordering::Relaxed))
+            .copied()
+    }
+
+    /// Para o item tocando e pula para o próximo. Na pausa entre dois itens, o próximo
+    /// começa logo.
+    pub fn next(&self) {
+        self.skip.store(1, Ordering::Relaxed);
+        self.controls.lock().unwrap().stop();
+    }
+
+    /// Para o item tocando e volta para o anterior. Na pausa entre dois itens, o que
+    /// acabou de tocar começa de novo.
+    pub fn previous(&self) {
+        self.skip.store(-1, Ordering::Relaxed);
+        self.controls.lock().unwrap().stop();
+    }
+
+    /// Para a reprodução, que então envia as mensagens de pânico.
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+        self.controls.lock().unwrap().stop();
+    }
+
+    /// A posição da reprodução no arquivo tocando, com os problemas da saída.
+    pub fn playhead(&self) -> &Playhead {
+        &self.playhead
+    }
+
+    /// Toca os arquivos no destino dado, sem fechar a conexão entre eles, até o fim da
+    /// lista ou até `stop`.
+    pub fn run<S: MidiSink, C: Clock>(
+        &self,
+        mut sink: S,
+        clock: C,
+        options: PlaybackOptions,
+    ) -> Result<(), Box<dyn Error>> {
+        let result = self.play_items(&mut sink, &clock, options);
+        self.position.store(usize::MAX, Ordering::Relaxed);
+
+        result
+    }
+
+    fn play_items<S: MidiSink, C: Clock>(
+        &self,
+        sink: &mut S,
+        clock: &C,
+        options: PlaybackOptions,
+    ) -> Result<(), Box<dyn Error>> {
+        let len = self.order.len();
+        let mut position = (len > 0).then_some(0);
+        while let Some(current) = position.filter(|_| !self.stopped.load(Ordering::Relaxed)) {
+            self.position.store(current, Ordering::Relaxed);
+            let file = Smf::parse(&self.items[self.order[current]])?;
+            let controls = Arc::new(LiveControls::default());
+            *self.controls.lock().unwrap() = Arc::clone(&controls);
+            self.playhead.set_tick(0);
+            Player::new(&mut *sink, clock)
+                .with_options(options)
+                .with_controls(controls)
+                .with_playhead(Arc::clone(&self.playhead))
+                .play(&file)?;
+
+            let repeat = self.repeat();
+            position = match self.skip.swap(0, Ordering::Relaxed) {
+                0 => next(current, len, repeat),
+                delta => step(current, len, delta, repeat != Repeat::Off),
+            };
+            if position.is_some() && self.wait_gap(clock) {
+                position = match self.skip.swap(0, Ordering::Relaxed) {
+                    delta if delta < 0 => Some(current),
+                    _ => position,
+                };
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Espera a pausa entre os arquivos, retornando se ela foi interrompida por um
+    /// salto. Também sai antes se a reprodução parar.
+    fn wait_gap<C: Clock>(&self, clock: &C) -> bool {
+        let end = clock.now() + self.gap;
+        while !self.stopped.load(Ordering::Relaxed) {
+            if self.skip.load(Ordering::Relaxed) != 0 {
+                return true;
+            }
+            let remaining = end.saturating_sub(clock.now());
+            if remaining.is_zero() {
+                break;
+            }
+            clock.sleep(remaining.min(Self::STOP_CHECK));
+        }
+
+        false
+    }
+}
+
+/// A posição depois da dada, quando o item termina sozinho, em uma lista de `len` itens.
+fn next(position: usize, len: usize, repeat: Repeat) -> Option<usize> {
+    match repeat {
+        Repeat::One => Some(position),
+        Repeat::Off | Repeat::All => step(position, len, 1, repeat == Repeat::All),
+    }
+}
+
+/// A posição `delta` itens depois da dada, dando a volta na lista se `wrap`.
+///
+/// Sem dar a volta, voltar antes do primeiro fica no primeiro, e passar do último
+/// termina a lista.
+fn step(position: usize, len: usize, delta: isize, wrap: bool) -> Option<usize> {
+    if len == 0 {
+        return None;
+    }
+    let target = position as isize + delta;
+    if wrap {
+        Some(target.rem_euclid(len as isize) as usize)
+    } else if target < 0 {
+        Some(0)
+    } else {
+        Some(target as usize).filter(|&target| target < len)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{clock::mock::MockClock, midi_action::MidiAction};
+
+    /// Conta as notas ligadas recebidas.
+    #[derive(Default)]
+    struct NoteCounter(usize);
+
+    impl MidiSink for NoteCounter {
+        fn send(&mut self, message: &[u8]) -> Result<(), Box<dyn Error>> {
+            if message[0] & 0xF0 == 0x90 && message.get(2).is_some_and(|&vel| vel > 0) {
+                self.0 += 1;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn items_play_in_order_with_gaps() {
+        let file = |notes: usize| {
+            let mut bytes = Vec::new();
+            MidiAction::as_track(&vec![MidiAction::PlayNote(60); notes])
+                .write_std(&mut bytes)
+                .unwrap();
+            bytes
+        };
+        let playlist = Playlist::new(vec![file(2), file(3)], Duration::from_secs(2));
+        let clock = MockClock::new(Duration::ZERO);
+        let mut notes = NoteCounter::default();
+
+        playlist
+            .run(&mut notes, &clock, PlaybackOptions::default())
+            .unwrap();
+
+        assert_eq!(notes.0, 5);
+        assert_eq!(playlist.current(), None);
+        // Five half-second notes at 120 BPM, and one gap between the two files
+        assert!(clock.now() >= Duration::from_millis(4500));
+    }
+
+    #[test]
+    fn repeat_modes_and_skips() {
+        assert_eq!(next(1, 2, Repeat::Off), None);
+        assert_eq!(next(1, 2, Repeat::All), Some(0));
+        assert_eq!(next(1, 2, Repeat::One), Some(1));
+        assert_eq!(next(0, 0, Repeat::All), None);
+        // Skipping back from the first item wraps only when repeating
+        assert_eq!(step(0, 3, -1, false), Some(0));
+        assert_eq!(step(0, 3, -1, true), Some(2));
+        assert_eq!(step(2, 3, 1, false), None);
+
+        // The same seed always gives the same order
+        let items = vec![Vec::new(); 8];
+        let order = |seed| {
+            Playlist::new(items.clone(), Duration::ZERO)
+                .with_shuffle(seed)
+                .order
+        };
+        assert_eq!(order(7), order(7));
+        assert_ne!(order(7), (0..8).collect::<Vec<_>>());
+        let mut sorted = order(7);
+        sorted.sort_unstable();
+        assert_eq!(sorted, (0..8).collect::<Vec<_>>());
+
+        let playlist = Playlist::new(items, Duration::ZERO).with_repeat(Repeat::One);
+        assert_eq!(playlist.repeat(), Repeat::One);
+        playlist.next();
+        assert!(playlist.controls.lock().unwrap().is_stopped());
+    }
+}
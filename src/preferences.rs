@@ -0,0 +1,74 @@
+use std::{env, error::Error, fs, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::playback_guard::Overlap;
+
+/// Preferências guardadas entre uma execução e outra, em `tcp/preferences.json`
+/// na pasta de configuração do usuário.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Preferences {
+    /// O nome da porta de saída preferida. Sem ela, ou se ela não existir, é usada a primeira.
+    pub output_port: Option<String>,
+    /// Se a interface usa o visual de alto contraste.
+    pub high_contrast: bool,
+    /// O que fazer com um Play apertado enquanto outra reprodução toca.
+    pub overlap: Overlap,
+}
+
+impl Preferences {
+    /// Lê as preferências salvas, ou as padrão se não houver nenhuma ou se o arquivo
+    /// estiver estragado.
+    pub fn load() -> Self {
+        path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    /// Salva as preferências, criando a pasta se preciso.
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let path = path().ok_or("no configuration folder found")?;
+        if let Some(folder) = path.parent() {
+            fs::create_dir_all(folder)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+
+        Ok(())
+    }
+}
+
+/// O arquivo de preferências: em `$XDG_CONFIG_HOME`, `%APPDATA%` ou `~/.config`.
+fn path() -> Option<PathBuf> {
+    let folder = env::var_os("XDG_CONFIG_HOME")
+        .or_else(|| env::var_os("APPDATA"))
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+    Some(folder.join("tcp").join("preferences.json"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn missing_fields_use_defaults() {
+        let preferences = Preferences {
+            output_port: Some("FluidSynth".to_string()),
+            high_contrast: true,
+            overlap: Overlap::Queue,
+        };
+        let json = serde_json::to_string(&preferences).unwrap();
+
+        assert_eq!(
+            serde_json::from_str::<Preferences>(&json).unwrap(),
+            preferences
+        );
+        assert_eq!(
+            serde_json::from_str::<Preferences>("{}").unwrap(),
+            Preferences::default()
+        );
+    }
+}
@@ -0,0 +1,173 @@
+//! Presets: as configurações da conversão com um nome, salvas em arquivos TOML para
+//! serem usadas de novo ou passadas adiante, como este:
+//!
+//! ```toml
+//! version = 1
+//! instrument = 40
+//! bpm = 90
+//! base_length = "eighth"
+//!
+//! [echo]
+//! repeats = 2
+//! delay = 240
+//! ```
+//!
+//! As configurações que faltam ficam com os valores padrão.
+
+use std::{error::Error, fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    groove::Groove,
+    midi_action::{MidiAction, TimedAction},
+    text_to_midi::{BaseLength, ConfigError, Sheet, SheetBuilder, State},
+    transform::{
+        ActionTransform, Echo, Fade, Harmonizer, ModuleReset, NoteRange, ScaleQuantizer, Strum,
+        VelocityCurve,
+    },
+    tuning::Tuning,
+};
+
+/// As configurações de uma conversão: as da aba e as transformações.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Preset {
+    /// A versão do mapeamento do texto para MIDI com que o preset foi salvo.
+    pub version: u32,
+    /// O instrumento inicial.
+    pub instrument: u8,
+    /// O BPM inicial.
+    pub bpm: u16,
+    /// O volume inicial.
+    pub volume: u16,
+    /// A figura das notas antes da primeira diretiva `L:`.
+    pub base_length: BaseLength,
+    pub velocity_curve: VelocityCurve,
+    pub fade: Fade,
+    pub scale: ScaleQuantizer,
+    pub note_range: NoteRange,
+    pub tuning: Tuning,
+    pub module_reset: ModuleReset,
+    pub echo: Echo,
+    pub harmonizer: Harmonizer,
+    pub strum: Strum,
+    pub groove: Groove,
+}
+
+impl Preset {
+    /// A versão atual do mapeamento do texto para MIDI. Presets de versões mais novas
+    /// são recusados, porque o mesmo texto poderia soar diferente.
+    pub const VERSION: u32 = 1;
+
+    /// Lê um preset de um texto em TOML, recusando configurações fora das faixas aceitas.
+    pub fn from_toml(text: &str) -> Result<Self, Box<dyn Error>> {
+        let preset: Self = toml::from_str(text)?;
+        if preset.version > Self::VERSION {
+            return Err(format!(
+                "preset version {} is newer than {}",
+                preset.version,
+                Self::VERSION
+            )
+            .into());
+        }
+        // As mesmas verificações da partitura
+        preset.sheet("")?;
+
+        Ok(preset)
+    }
+
+    /// Lê um preset de um arquivo TOML.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        Self::from_toml(&fs::read_to_string(path)?)
+    }
+
+    /// Salva o preset em um arquivo TOML.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        fs::write(path, toml::to_string_pretty(self)?)?;
+
+        Ok(())
+    }
+
+    /// Começa a montar uma partitura com as configurações do preset.
+    pub fn builder(&self) -> SheetBuilder {
+        Sheet::builder()
+            .bpm(self.bpm)
+            .volume(self.volume)
+            .instrument(self.instrument)
+            .base_length(self.base_length)
+    }
+
+    /// A partitura do texto dado, com as configurações do preset.
+    pub fn sheet(&self, text: &str) -> Result<Sheet, ConfigError> {
+        self.builder().text(text).build()
+    }
+
+    /// Aplica as transformações às ações do texto, na ordem da interface.
+    pub fn transform(&self, actions: Vec<MidiAction>) -> Vec<MidiAction> {
+        let actions = self
+            .scale
+            .apply(self.fade.apply(self.velocity_curve.apply(actions)));
+        let actions = self.note_range.apply(actions);
+        self.module_reset.apply(self.tuning.apply(actions))
+    }
+
+    /// Aplica os efeitos que posicionam as ações no tempo, na ordem da interface.
+    pub fn transform_timed(&self, timed: Vec<TimedAction>) -> Vec<TimedAction> {
+        self.harmonizer
+            .apply(self.strum.apply(self.echo.apply(self.groove.apply(timed))))
+    }
+}
+
+impl Default for Preset {
+    fn default() -> Self {
+        Self {
+            version: Self::VERSION,
+            instrument: 0,
+            bpm: State::D_BPM,
+            volume: State::D_VOLUME,
+            base_length: BaseLength::default(),
+            velocity_curve: VelocityCurve::default(),
+            fade: Fade::default(),
+            scale: ScaleQuantizer::default(),
+            note_range: NoteRange::default(),
+            tuning: Tuning::default(),
+            module_reset: ModuleReset::default(),
+            echo: Echo::default(),
+            harmonizer: Harmonizer::default(),
+            strum: Strum::default(),
+            groove: Groove::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn presets_round_trip_through_toml() {
+        let preset = Preset {
+            instrument: 40,
+            bpm: 90,
+            base_length: BaseLength::Eighth,
+            echo: Echo {
+                repeats: 2,
+                delay: 240,
+            },
+            groove: Groove::swing(60),
+            ..Preset::default()
+        };
+
+        let text = toml::to_string_pretty(&preset).unwrap();
+        assert_eq!(Preset::from_toml(&text).unwrap(), preset);
+        // Missing settings keep their defaults
+        let partial = Preset::from_toml("bpm = 90\n[echo]\nrepeats = 2").unwrap();
+        assert_eq!(
+            (partial.bpm, partial.echo.delay),
+            (90, Echo::default().delay)
+        );
+        assert!(Preset::from_toml("bpm = 0").is_err());
+        assert!(Preset::from_toml("version = 2").is_err());
+    }
+}
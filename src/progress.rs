@@ -0,0 +1,58 @@
+use std::{
+    error::Error,
+    fmt,
+    sync::atomic::{AtomicBool, AtomicU8, Ordering},
+};
+
+/// Andamento de uma tarefa longa, compartilhado entre a thread que a executa
+/// e quem a acompanha (normalmente dentro de um `Arc`).
+#[derive(Debug, Default)]
+pub struct Progress {
+    /// Porcentagem concluída, de 0 a 100.
+    percent: AtomicU8,
+    /// Se foi pedido o cancelamento da tarefa.
+    cancelled: AtomicBool,
+}
+
+/// A tarefa foi cancelada antes de terminar.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cancelled")
+    }
+}
+
+impl Error for Cancelled {}
+
+impl Progress {
+    /// A porcentagem concluída, de 0 a 100.
+    pub fn percent(&self) -> u8 {
+        self.percent.load(Ordering::Relaxed)
+    }
+
+    /// Pede que a tarefa pare no próximo passo.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Se foi pedido o cancelamento.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Registra que a fração dada (de 0 a 1) da tarefa foi concluída.
+    ///
+    /// Retorna `Cancelled` se a tarefa deve parar.
+    pub fn set(&self, fraction: f32) -> Result<(), Cancelled> {
+        let percent = (fraction.clamp(0.0, 1.0) * 100.0) as u8;
+        self.percent.store(percent, Ordering::Relaxed);
+
+        if self.is_cancelled() {
+            Err(Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! Módulo `tcp` para Python, para usar o conversor em notebooks.
+//!
+//! ```python
+//! import tcp
+//!
+//! sheet = tcp.Sheet("CDE R+ C", bpm=90, seed=1)
+//! sheet.process()  # [("bpm", 90), ("instrument", 0), ("volume", 50), ("note", 60), ...]
+//! open("song.mid", "wb").write(sheet.smf())
+//! ```
+
+// O código gerado pelo `#[pymethods]` do pyo3 0.22 converte o `PyErr` nele mesmo.
+#![allow(clippy::useless_conversion)]
+
+use pyo3::{exceptions::PyValueError, prelude::*, types::PyBytes};
+
+use crate::{
+    midi_action::MidiAction,
+    text_to_midi::{self, SheetBuilder, State},
+};
+
+/// Uma partitura: o texto e as configurações iniciais.
+///
+/// Cada chamada monta uma `text_to_midi::Sheet` nova, então sem `seed`
+/// os comandos aleatórios sorteiam de novo a cada vez.
+#[pyclass]
+struct Sheet {
+    builder: SheetBuilder,
+}
+
+#[pymethods]
+impl Sheet {
+    #[new]
+    #[pyo3(signature = (
+        text,
+        bpm = State::D_BPM,
+        volume = State::D_VOLUME,
+        octave = State::D_OCTAVE,
+        instrument = 0,
+        lyrics = false,
+        seed = None,
+    ))]
+    fn new(
+        text: String,
+        bpm: u16,
+        volume: u16,
+        octave: u8,
+        instrument: u8,
+        lyrics: bool,
+        seed: Option<u64>,
+    ) -> PyResult<Self> {
+        let mut builder = text_to_midi::Sheet::builder()
+            .text(text)
+            .bpm(bpm)
+            .volume(volume)
+            .octave(octave)
+            .instrument(instrument)
+            .lyrics(lyrics);
+        if let Some(seed) = seed {
+            builder = builder.seed(seed);
+        }
+
+        // Valida já na criação, para o erro aparecer onde a partitura foi escrita.
+        builder.clone().build().map_err(config_error)?;
+        Ok(Self { builder })
+    }
+
+    /// As ações geradas, como tuplas `(tipo, valor)` com os mesmos nomes do JSON de eventos.
+    fn process(&self, py: Python<'_>) -> PyResult<Vec<(&'static str, PyObject)>> {
+        Ok(self
+            .actions()?
+            .into_iter()
+            .map(|action| to_tuple(py, action))
+            .collect())
+    }
+
+    /// O arquivo MIDI (SMF) gerado.
+    fn smf<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        let mut smf = Vec::new();
+        MidiAction::as_track(&self.actions()?)
+            .write_std(&mut smf)
+            .expect("writing to a vector never fails");
+
+        Ok(PyBytes::new_bound(py, &smf))
+    }
+}
+
+impl Sheet {
+    fn actions(&self) -> PyResult<Vec<MidiAction>> {
+        Ok(self
+            .builder
+            .clone()
+            .build()
+            .map_err(config_error)?
+            .process())
+    }
+}
+
+fn config_error(error: text_to_midi::ConfigError) -> PyErr {
+    PyValueError::new_err(error.to_string())
+}
+
+fn to_tuple(py: Python<'_>, action: MidiAction) -> (&'static str, PyObject) {
+    match action {
+        MidiAction::PlayNote(key) => ("note", key.into_py(py)),
+        MidiAction::ChangeInstrument(program) => ("instrument", program.into_py(py)),
+        MidiAction::ChangeVolume(volume) => ("volume", volume.into_py(py)),
+        MidiAction::Pause => ("pause", py.None()),
+        MidiAction::ChangeBPM(bpm) => ("bpm", bpm.into_py(py)),
+        MidiAction::Lyric(text) => ("lyric", text.into_py(py)),
+        MidiAction::PitchBend(bend) => ("pitch_bend", bend.into_py(py)),
+        MidiAction::Accent(velocity) => ("accent", velocity.into_py(py)),
+        MidiAction::DroneStart(key) => ("drone_start", key.into_py(py)),
+        MidiAction::DroneStop(key) => ("drone_stop", key.into_py(py)),
+        MidiAction::ChannelPressure(pressure) => ("pressure", pressure.into_py(py)),
+        MidiAction::Pan(pan) => ("pan", pan.into_py(py)),
+        MidiAction::SysEx(bytes) => ("sysex", PyBytes::new_bound(py, &bytes).into_py(py)),
+        MidiAction::BankSelect(bank) => ("bank", bank.into_py(py)),
+        MidiAction::Marker(name) => ("marker", name.into_py(py)),
+        MidiAction::Chord(keys) => ("chord", keys.into_py(py)),
+        MidiAction::ChordNote { key, velocity } => ("chord_note", (key, velocity).into_py(py)),
+        MidiAction::NoteLength(ticks) => ("length", ticks.into_py(py)),
+    }
+}
+
+#[pymodule]
+fn tcp(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<Sheet>()
+}
@@ -0,0 +1,239 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use midly::{num::*, MidiMessage, Track, TrackEvent, TrackEventKind};
+
+use crate::ticks;
+
+/// A grade para a qual as notas são levadas.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Grid {
+    /// Semínima (1/4).
+    Quarter,
+    /// Colcheia (1/8).
+    Eighth,
+    /// Semicolcheia (1/16).
+    #[default]
+    Sixteenth,
+    /// Fusa (1/32).
+    ThirtySecond,
+}
+
+impl Grid {
+    /// Todas as grades, da maior para a menor.
+    pub const ALL: [Self; 4] = [
+        Self::Quarter,
+        Self::Eighth,
+        Self::Sixteenth,
+        Self::ThirtySecond,
+    ];
+
+    /// O denominador da figura, ou seja, quantas cabem em uma semibreve.
+    pub const fn denominator(self) -> u32 {
+        match self {
+            Self::Quarter => 4,
+            Self::Eighth => 8,
+            Self::Sixteenth => 16,
+            Self::ThirtySecond => 32,
+        }
+    }
+
+    /// O tamanho de uma divisão da grade, em ticks.
+    pub fn ticks(self, tpqn: u15) -> u64 {
+        (tpqn.as_int() as u64 * 4 / self.denominator() as u64).max(1)
+    }
+}
+
+impl fmt::Display for Grid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "1/{}", self.denominator())
+    }
+}
+
+impl FromStr for Grid {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|grid| grid.to_string() == s)
+            .ok_or_else(|| format!("unknown grid {s:?}, expected one of 1/4, 1/8, 1/16, 1/32"))
+    }
+}
+
+/// Quantizador: aproxima o início das notas da grade.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quantizer {
+    /// A grade usada.
+    pub grid: Grid,
+    /// Quanto da distância até a grade é percorrida, de 0 a 100%.
+    pub strength: u8,
+}
+
+impl Default for Quantizer {
+    fn default() -> Self {
+        Self {
+            grid: Grid::default(),
+            strength: 100,
+        }
+    }
+}
+
+impl Quantizer {
+    /// Onde um tick fica depois de quantizado.
+    pub fn snap(&self, tick: u64, tpqn: u15) -> u64 {
+        // As linhas da grade são arredondadas a partir da semibreve, para que mesmo
+        // divisões fracionárias do TPQN caiam exatas no início de cada compasso
+        let (whole, denominator) = (tpqn.as_int() as u64 * 4, self.grid.denominator() as u64);
+        let line = ticks::scale(tick, denominator, whole);
+        let target = ticks::scale(line, whole, denominator);
+        let strength = self.strength.min(100) as i64;
+
+        (tick as i64 + (target as i64 - tick as i64) * strength / 100) as u64
+    }
+
+    /// Quantiza a trilha.
+    ///
+    /// Somente o início das notas é levado à grade; o fim de cada nota anda junto,
+    /// de modo que as durações são mantidas. Os demais eventos ficam onde estão.
+    pub fn quantize(&self, track: &mut Track<'_>, tpqn: u15) {
+        let mut tick = 0_u64;
+        // O deslocamento da nota que está soando em cada canal e tecla
+        let mut shifts: HashMap<(u4, u7), i64> = HashMap::new();
+
+        let mut timed: Vec<(u64, TrackEvent)> = track
+            .iter()
+            .map(|event| {
+                tick += event.delta.as_int() as u64;
+                let new_tick = match event.kind {
+                    TrackEventKind::Midi {
+                        channel,
+                        message: MidiMessage::NoteOn { key, vel },
+                    } if vel > 0 => {
+                        let snapped = self.snap(tick, tpqn);
+                        shifts.insert((channel, key), snapped as i64 - tick as i64);
+                        snapped
+                    }
+                    TrackEventKind::Midi {
+                        channel,
+                        message: MidiMessage::NoteOff { key, .. } | MidiMessage::NoteOn { key, .. },
+                    } => {
+                        let shift = shifts.remove(&(channel, key)).unwrap_or(0);
+                        (tick as i64 + shift).max(0) as u64
+                    }
+                    _ => tick,
+                };
+                (new_tick, *event)
+            })
+            .collect();
+
+        // A ordenação é estável, então eventos no mesmo tick mantêm a ordem original
+        timed.sort_by_key(|&(tick, _)| tick);
+
+        let mut last = 0;
+        for (slot, (tick, mut event)) in track.iter_mut().zip(timed) {
+            event.delta = u32::try_from(tick - last)
+                .ok()
+                .and_then(u28::try_from)
+                .unwrap_or_else(u28::max_value);
+            last = tick;
+            *slot = event;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::midi_value;
+
+    /// Cria uma trilha com notas nos ticks (início, fim) dados.
+    fn track(notes: &[(u32, u32)]) -> Track<'static> {
+        let mut events: Vec<(u32, bool)> = notes
+            .iter()
+            .flat_map(|&(on, off)| [(on, true), (off, false)])
+            .collect();
+        events.sort_by_key(|&(tick, _)| tick);
+
+        let mut last = 0;
+        events
+            .into_iter()
+            .map(|(tick, on)| {
+                let key = u7::from(60);
+                let event = TrackEvent {
+                    delta: midi_value::ticks(tick - last),
+                    kind: TrackEventKind::Midi {
+                        channel: midi_value::channel(0),
+                        message: if on {
+                            MidiMessage::NoteOn {
+                                key,
+                                vel: 64.into(),
+                            }
+                        } else {
+                            MidiMessage::NoteOff {
+                                key,
+                                vel: 64.into(),
+                            }
+                        },
+                    },
+                };
+                last = tick;
+                event
+            })
+            .collect()
+    }
+
+    /// O tick absoluto de cada evento.
+    fn ticks(track: &Track) -> Vec<u32> {
+        track
+            .iter()
+            .scan(0, |tick, event| {
+                *tick += event.delta.as_int();
+                Some(*tick)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn snaps_to_the_grid() {
+        let quantizer = Quantizer {
+            grid: Grid::Quarter,
+            strength: 100,
+        };
+        let tpqn = u15::from(480);
+
+        assert_eq!(quantizer.snap(230, tpqn), 0);
+        assert_eq!(quantizer.snap(250, tpqn), 480);
+        assert_eq!(Grid::ThirtySecond.ticks(tpqn), 60);
+        // 1/32 of a 100-tick beat is 12.5 ticks, and the grid still lands on the bar
+        let fine = Quantizer {
+            grid: Grid::ThirtySecond,
+            strength: 100,
+        };
+        assert_eq!(fine.snap(390, u15::from(100)), 388);
+        assert_eq!(fine.snap(399, u15::from(100)), 400);
+        assert_eq!("1/8".parse(), Ok(Grid::Eighth));
+    }
+
+    #[test]
+    fn strength_moves_part_of_the_way() {
+        let quantizer = Quantizer {
+            grid: Grid::Quarter,
+            strength: 50,
+        };
+
+        assert_eq!(quantizer.snap(440, u15::from(480)), 460);
+    }
+
+    #[test]
+    fn keeps_note_lengths() {
+        let mut track = track(&[(10, 400), (470, 950)]);
+
+        Quantizer {
+            grid: Grid::Quarter,
+            strength: 100,
+        }
+        .quantize(&mut track, u15::from(480));
+
+        assert_eq!(ticks(&track), [0, 390, 480, 960]);
+    }
+}
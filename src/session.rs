@@ -0,0 +1,308 @@
+//! Diário da sessão: o que é feito na interface, gravado em um arquivo para ser feito
+//! de novo, como para reproduzir um problema ou para uma demonstração.
+//!
+//! O diário tem uma ação por linha, em JSON, com o instante em milissegundos desde o
+//! começo da gravação:
+//!
+//! ```json
+//! {"at_ms":0,"action":"edit","text":"CDE"}
+//! {"at_ms":1500,"action":"settings","bpm":90,"volume":50,"instrument":0,"lyrics":false,"strict":false,"base_length":"quarter"}
+//! {"at_ms":2100,"action":"play"}
+//! ```
+//!
+//! Na reprodução, cada ação passa pelos mesmos métodos da interface que a geraram.
+
+use std::{
+    collections::VecDeque,
+    error::Error,
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::text_to_midi::BaseLength;
+
+/// As configurações de uma aba que vão para o diário.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Settings {
+    pub bpm: u16,
+    pub volume: u16,
+    pub instrument: u8,
+    pub lyrics: bool,
+    pub strict: bool,
+    pub base_length: BaseLength,
+}
+
+/// Uma ação feita na interface.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Action {
+    /// Uma aba nova, aberta de um arquivo, de um exemplo ou vazia, com o seu texto.
+    NewTab { text: String },
+    /// A aba da posição dada foi fechada.
+    CloseTab { tab: usize },
+    /// A aba da posição dada passou a ser a editada.
+    Select { tab: usize },
+    /// O texto da aba editada mudou para o dado.
+    Edit { text: String },
+    /// As configurações da aba editada mudaram.
+    Settings(Settings),
+    /// A aba editada começou a tocar.
+    Play,
+    /// O botão de parar, que também esquece as reproduções esperando.
+    Stop,
+    /// O botão de pânico.
+    Panic,
+}
+
+/// O que o diário compara de um quadro da interface para o próximo.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    /// Quantas abas há.
+    pub tabs: usize,
+    /// A posição da aba editada.
+    pub active: usize,
+    /// O texto da aba editada.
+    pub text: String,
+    /// As configurações da aba editada.
+    pub settings: Settings,
+}
+
+impl Snapshot {
+    /// As ações que levam de `self` ao `after`, na ordem em que devem ser feitas.
+    ///
+    /// Abas fechadas não aparecem aqui, porque não se sabe qual delas foi: elas são
+    /// gravadas por quem as fecha.
+    pub fn changes(&self, after: &Self) -> Vec<Action> {
+        let mut actions = Vec::new();
+        let same_tab = if after.tabs > self.tabs {
+            actions.push(Action::NewTab {
+                text: after.text.clone(),
+            });
+            // A aba nova é a última e já vem com o texto e as configurações
+            if after.active == after.tabs - 1 {
+                return actions;
+            }
+            false
+        } else {
+            after.tabs == self.tabs && after.active == self.active
+        };
+        if !same_tab {
+            actions.push(Action::Select { tab: after.active });
+            return actions;
+        }
+        if after.text != self.text {
+            actions.push(Action::Edit {
+                text: after.text.clone(),
+            });
+        }
+        if after.settings != self.settings {
+            actions.push(Action::Settings(after.settings));
+        }
+
+        actions
+    }
+}
+
+/// Uma linha do diário.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct Entry {
+    /// Milissegundos desde o começo da gravação.
+    at_ms: u64,
+    #[serde(flatten)]
+    action: Action,
+}
+
+/// Grava as ações em um diário, uma por linha, assim que elas acontecem.
+pub struct Recorder {
+    writer: Box<dyn Write>,
+    /// O instante, em segundos do relógio da interface, da primeira ação.
+    start: Option<f64>,
+    /// O instante do quadro atual, em segundos do relógio da interface.
+    now: f64,
+}
+
+impl Recorder {
+    /// Grava no arquivo dado, apagando o que ele tinha.
+    pub fn create(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+
+    /// Grava no destino dado.
+    pub fn new(writer: impl Write + 'static) -> Self {
+        Self {
+            writer: Box::new(writer),
+            start: None,
+            now: 0.0,
+        }
+    }
+
+    /// Marca o instante do quadro atual, usado pelas próximas ações.
+    pub fn set_time(&mut self, now: f64) {
+        self.now = now;
+    }
+
+    /// Grava a ação, no instante do quadro atual.
+    pub fn record(&mut self, action: Action) -> Result<(), Box<dyn Error>> {
+        let start = *self.start.get_or_insert(self.now);
+        let entry = Entry {
+            at_ms: ((self.now - start).max(0.0) * 1000.0).round() as u64,
+            action,
+        };
+        serde_json::to_writer(&mut self.writer, &entry)?;
+        writeln!(self.writer)?;
+        // Uma sessão que termina em um travamento é justamente a que interessa
+        self.writer.flush()?;
+
+        Ok(())
+    }
+}
+
+/// As ações de um diário, entregues no mesmo ritmo em que foram gravadas.
+#[derive(Debug, Default)]
+pub struct Replay {
+    entries: VecDeque<Entry>,
+    /// O instante, em segundos do relógio da interface, em que a reprodução começou.
+    start: Option<f64>,
+}
+
+impl Replay {
+    /// Lê o diário de um texto, uma ação por linha, ignorando as linhas vazias.
+    pub fn parse(text: &str) -> Result<Self, Box<dyn Error>> {
+        let entries = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| {
+                serde_json::from_str(line).map_err(|error| format!("line {}: {error}", index + 1))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            entries,
+            start: None,
+        })
+    }
+
+    /// Lê o diário de um arquivo.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// As ações que já deviam ter sido feitas no instante dado, em segundos do relógio
+    /// da interface. A reprodução começa na primeira chamada.
+    pub fn due(&mut self, now: f64) -> Vec<Action> {
+        let start = *self.start.get_or_insert(now);
+        let elapsed_ms = ((now - start).max(0.0) * 1000.0) as u64;
+        let count = self
+            .entries
+            .iter()
+            .take_while(|entry| entry.at_ms <= elapsed_ms)
+            .count();
+
+        self.entries
+            .drain(..count)
+            .map(|entry| entry.action)
+            .collect()
+    }
+
+    /// Quanto falta, em segundos, para a próxima ação, se houver.
+    pub fn until_next(&self, now: f64) -> Option<f64> {
+        let start = self.start.unwrap_or(now);
+        let next = self.entries.front()?.at_ms as f64 / 1000.0;
+
+        Some((start + next - now).max(0.0))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+
+    /// Um destino que guarda o que foi escrito, para ser lido depois.
+    #[derive(Clone, Default)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(bytes)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn sessions_replay_at_the_recorded_pace() {
+        let settings = Settings {
+            bpm: 90,
+            volume: 50,
+            instrument: 0,
+            lyrics: false,
+            strict: true,
+            base_length: BaseLength::Eighth,
+        };
+        let before = Snapshot {
+            tabs: 1,
+            active: 0,
+            text: String::new(),
+            settings,
+        };
+        let edited = Snapshot {
+            text: "CDE".to_string(),
+            settings: Settings {
+                bpm: 120,
+                ..settings
+            },
+            ..before.clone()
+        };
+        assert_eq!(
+            before.changes(&edited),
+            [
+                Action::Edit {
+                    text: "CDE".to_string()
+                },
+                Action::Settings(edited.settings),
+            ]
+        );
+        let opened = Snapshot {
+            tabs: 2,
+            active: 1,
+            ..edited.clone()
+        };
+        assert_eq!(
+            edited.changes(&opened),
+            [Action::NewTab {
+                text: "CDE".to_string()
+            }]
+        );
+        assert!(opened.changes(&opened).is_empty());
+
+        let journal = Shared::default();
+        let mut recorder = Recorder::new(journal.clone());
+        recorder.set_time(10.0);
+        recorder.record(before.changes(&edited).remove(0)).unwrap();
+        recorder.set_time(11.5);
+        recorder.record(Action::Play).unwrap();
+        let text = String::from_utf8(journal.0.take()).unwrap();
+        assert!(text.ends_with("{\"at_ms\":1500,\"action\":\"play\"}\n"));
+
+        let mut replay = Replay::parse(&text).unwrap();
+        assert_eq!(
+            replay.due(100.0),
+            [Action::Edit {
+                text: "CDE".to_string()
+            }]
+        );
+        assert!(replay.due(101.0).is_empty());
+        assert_eq!(replay.until_next(101.0), Some(0.5));
+        assert_eq!(replay.due(101.5), [Action::Play]);
+        assert_eq!(replay.until_next(102.0), None);
+        assert!(Replay::parse("{\"action\":\"dance\"}").is_err());
+    }
+}
@@ -0,0 +1,181 @@
+//! Escrita de arquivos MIDI aos poucos, para peças longas demais para caberem na
+//! memória, como as geradas por algoritmos que tocam por horas.
+//!
+//! O `StreamWriter` escreve cada ação assim que ela chega, com os mesmos eventos do
+//! `MidiAction::as_track`, e só volta ao começo da trilha no fim, para escrever o seu
+//! tamanho. Na memória fica só o estado da conversão, não a trilha.
+//!
+//! ```no_run
+//! use std::{fs::File, io::BufWriter};
+//! use tcp::{midi_action::MidiAction, stream::StreamWriter};
+//!
+//! let file = BufWriter::new(File::create("endless.mid")?);
+//! let mut writer = StreamWriter::new(file)?;
+//! for key in (0..1_000_000).map(|i| 48 + (i * 7 % 24) as u8) {
+//!     writer.push(&MidiAction::PlayNote(key))?;
+//! }
+//! writer.finish()?;
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::{
+    io::{self, Seek, SeekFrom, Write},
+    mem,
+};
+
+use midly::{Track, TrackEvent, TrackEventKind};
+
+use crate::midi_action::{MidiAction, Sequencer};
+
+/// Escreve uma trilha MIDI no destino dado, ação por ação.
+///
+/// O arquivo só fica válido depois do `finish`, que escreve o fim e o tamanho da trilha.
+#[derive(Debug)]
+pub struct StreamWriter<W: Write + Seek> {
+    out: W,
+    sequencer: Sequencer,
+    /// Onde fica o tamanho da trilha, escrito no `finish`.
+    length_at: u64,
+    /// Quantos bytes de eventos já foram escritos.
+    length: u64,
+    /// O status da última mensagem, que as seguintes com o mesmo status omitem.
+    running_status: Option<u8>,
+    /// Os eventos de uma ação, antes de serem escritos.
+    events: Vec<u8>,
+}
+
+impl<W: Write + Seek> StreamWriter<W> {
+    /// O tamanho do cabeçalho do arquivo e do começo da trilha, antes dos eventos.
+    const PREFIX: usize = 14 + 8;
+
+    /// Começa o arquivo: o cabeçalho, o começo da trilha e as mensagens iniciais.
+    pub fn new(mut out: W) -> io::Result<Self> {
+        // O cabeçalho de um arquivo com uma trilha vazia, sem o tamanho dela
+        let mut header = Vec::new();
+        midly::write_std(&MidiAction::header(), [&Track::new()], &mut header)?;
+        header.truncate(header.len() - 4);
+        out.write_all(&header)?;
+        let length_at = out.stream_position()?;
+        out.write_all(&[0; 4])?;
+
+        let mut writer = Self {
+            out,
+            sequencer: Sequencer::default(),
+            length_at,
+            length: 0,
+            running_status: None,
+            events: Vec::new(),
+        };
+        let mut track = Track::new();
+        MidiAction::add_beggining(&mut track);
+        writer.write_events(&track)?;
+
+        Ok(writer)
+    }
+
+    /// Escreve os eventos da ação. Pausas e acentos só aparecem no próximo evento.
+    pub fn push(&mut self, action: &MidiAction) -> io::Result<()> {
+        let mut track = Track::new();
+        self.sequencer.push(action, &mut track);
+        self.write_events(&track)
+    }
+
+    /// Escreve o fim da trilha e o seu tamanho, devolvendo o destino.
+    pub fn finish(mut self) -> io::Result<W> {
+        let mut track = Track::new();
+        mem::take(&mut self.sequencer).finish(&mut track);
+        self.write_events(&track)?;
+
+        let length = u32::try_from(self.length)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "track longer than 4 GiB"))?;
+        let end = self.out.stream_position()?;
+        self.out.seek(SeekFrom::Start(self.length_at))?;
+        self.out.write_all(&length.to_be_bytes())?;
+        self.out.seek(SeekFrom::Start(end))?;
+        self.out.flush()?;
+
+        Ok(self.out)
+    }
+
+    fn write_events(&mut self, track: &Track<'_>) -> io::Result<()> {
+        self.events.clear();
+        for event in track {
+            self.encode(event)?;
+        }
+        self.out.write_all(&self.events)?;
+        self.length += self.events.len() as u64;
+
+        Ok(())
+    }
+
+    /// Codifica o evento no fim de `events`, sem o status se ele for o da mensagem
+    /// anterior, como o midly faz dentro de uma trilha.
+    fn encode(&mut self, event: &TrackEvent<'_>) -> io::Result<()> {
+        // O midly só escreve arquivos inteiros: o evento vai sozinho em uma trilha, e o
+        // que vem antes dele é jogado fora
+        let mut file = Vec::new();
+        midly::write_std(&MidiAction::header(), [[event]], &mut file)?;
+        let encoded = &file[Self::PREFIX..];
+
+        let status = match event.kind {
+            TrackEventKind::Midi { .. } => {
+                let delta_len = encoded
+                    .iter()
+                    .position(|byte| byte & 0x80 == 0)
+                    .unwrap_or(0)
+                    + 1;
+                let status = encoded[delta_len];
+                if self.running_status == Some(status) {
+                    self.events.extend_from_slice(&encoded[..delta_len]);
+                    self.events.extend_from_slice(&encoded[delta_len + 1..]);
+                    return Ok(());
+                }
+                Some(status)
+            }
+            // SysEx e mensagens meta cancelam o status corrente
+            _ => None,
+        };
+        self.running_status = status;
+        self.events.extend_from_slice(encoded);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn streamed_files_match_the_whole_track() {
+        let actions = [
+            MidiAction::ChangeBPM(90),
+            MidiAction::Lyric("la".to_string()),
+            MidiAction::PlayNote(60),
+            MidiAction::Pause,
+            MidiAction::NoteLength(240),
+            MidiAction::Accent(100),
+            MidiAction::PlayNote(62),
+            MidiAction::DroneStart(36),
+            MidiAction::PlayNote(64),
+            MidiAction::SysEx(vec![0xF0, 0x41, 0xF7]),
+            MidiAction::Chord(vec![48, 52, 55]),
+            MidiAction::PlayNote(67),
+            MidiAction::Pause,
+        ];
+        let mut whole = Vec::new();
+        MidiAction::as_track(&actions)
+            .write_std(&mut whole)
+            .unwrap();
+
+        let mut writer = StreamWriter::new(Cursor::new(Vec::new())).unwrap();
+        for action in &actions {
+            writer.push(action).unwrap();
+        }
+        let streamed = writer.finish().unwrap().into_inner();
+
+        assert_eq!(streamed, whole);
+    }
+}
@@ -0,0 +1,242 @@
+use std::time::Duration;
+
+use midly::{num::*, MetaMessage, Track, TrackEventKind};
+
+use crate::{
+    midi_action::{MidiAction, TimedAction},
+    time_state::{TimeSignature, TimeState},
+};
+
+/// Uma mudança de tempo em um tick absoluto.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TempoChange {
+    /// Tick absoluto em que o tempo muda.
+    pub tick: u64,
+    /// Microsegundos por semimínima a partir deste tick.
+    pub mspqn: u24,
+    /// Microsegundos decorridos desde o início até este tick.
+    micros: u64,
+}
+
+/// Mapa de tempo de uma trilha: todas as mudanças de MSPQN e os ticks em que ocorrem.
+///
+/// É montado uma única vez, a partir das ações ou dos eventos de uma trilha,
+/// e converte entre ticks e tempo real sem precisar percorrer a música novamente.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TempoMap {
+    /// Ticks por semimínima.
+    tpqn: u15,
+    /// As mudanças em ordem de tick. A primeira está sempre no tick 0.
+    changes: Vec<TempoChange>,
+}
+
+impl TempoMap {
+    /// Cria um mapa com o tempo padrão do MIDI (120 BPM) durante toda a música.
+    pub fn new(tpqn: u15) -> Self {
+        Self {
+            tpqn,
+            changes: vec![TempoChange {
+                tick: 0,
+                mspqn: TimeState::default().mspqn(),
+                micros: 0,
+            }],
+        }
+    }
+
+    /// Monta o mapa a partir das ações, usando a mesma temporização do `MidiAction::as_track`.
+    pub fn from_actions(actions: &[MidiAction]) -> Self {
+        let mut map = Self::new(MidiAction::D_TPQN);
+        let mut tick = 0;
+
+        for (action, duration) in actions.iter().zip(MidiAction::durations(actions)) {
+            if let &MidiAction::ChangeBPM(bpm) = action {
+                let mspqn = TimeState::mspqn_from_bpm(bpm, TimeSignature::COMMON_TIME)
+                    .unwrap_or_else(|_| u24::max_value());
+                map.push(tick, mspqn);
+            }
+            tick += duration as u64;
+        }
+
+        map
+    }
+
+    /// Monta o mapa a partir de ações já posicionadas no tempo, em ordem.
+    pub fn from_timed(actions: &[TimedAction]) -> Self {
+        let mut map = Self::new(MidiAction::D_TPQN);
+        for timed in actions {
+            if let MidiAction::ChangeBPM(bpm) = timed.action {
+                let mspqn = TimeState::mspqn_from_bpm(bpm, TimeSignature::COMMON_TIME)
+                    .unwrap_or_else(|_| u24::max_value());
+                map.push(timed.tick, mspqn);
+            }
+        }
+
+        map
+    }
+
+    /// Monta o mapa a partir dos eventos de tempo de uma trilha.
+    pub fn from_track(track: &Track<'_>, tpqn: u15) -> Self {
+        Self::from_tracks(std::slice::from_ref(track), tpqn)
+    }
+
+    /// Monta o mapa a partir dos eventos de tempo de todas as trilhas de um arquivo.
+    pub fn from_tracks(tracks: &[Track<'_>], tpqn: u15) -> Self {
+        let mut changes = Vec::new();
+        for track in tracks {
+            let mut tick = 0;
+            for event in track {
+                tick += event.delta.as_int() as u64;
+                if let TrackEventKind::Meta(MetaMessage::Tempo(mspqn)) = event.kind {
+                    changes.push((tick, mspqn));
+                }
+            }
+        }
+        changes.sort_by_key(|&(tick, _)| tick);
+
+        let mut map = Self::new(tpqn);
+        for (tick, mspqn) in changes {
+            map.push(tick, mspqn);
+        }
+
+        map
+    }
+
+    /// Adiciona uma mudança de tempo. Ticks devem ser adicionados em ordem crescente.
+    ///
+    /// Uma mudança no mesmo tick da última a substitui.
+    pub fn push(&mut self, tick: u64, mspqn: u24) {
+        let last = *self
+            .changes
+            .last()
+            .expect("the map always has a change at 0");
+        debug_assert!(tick >= last.tick, "tempo changes must be pushed in order");
+
+        let change = TempoChange {
+            tick,
+            mspqn,
+            micros: last.micros + self.micros_between(last, tick),
+        };
+
+        if last.tick == tick {
+            *self.changes.last_mut().unwrap() = TempoChange {
+                micros: last.micros,
+                ..change
+            };
+        } else {
+            self.changes.push(change);
+        }
+    }
+
+    /// Ticks por semimínima.
+    pub fn tpqn(&self) -> u15 {
+        self.tpqn
+    }
+
+    /// Todas as mudanças de tempo, em ordem.
+    pub fn changes(&self) -> &[TempoChange] {
+        &self.changes
+    }
+
+    /// A mudança de tempo vigente no tick dado.
+    fn change_at(&self, tick: u64) -> TempoChange {
+        let index = self.changes.partition_point(|change| change.tick <= tick);
+        self.changes[index.saturating_sub(1)]
+    }
+
+    /// Microsegundos entre uma mudança e um tick posterior, sem outras mudanças no meio.
+    fn micros_between(&self, change: TempoChange, tick: u64) -> u64 {
+        (tick - change.tick) * change.mspqn.as_int() as u64 / self.tpqn.as_int() as u64
+    }
+
+    /// O MSPQN vigente no tick dado.
+    pub fn mspqn_at(&self, tick: u64) -> u24 {
+        self.change_at(tick).mspqn
+    }
+
+    /// O instante, a partir do início da música, em que o tick dado toca.
+    pub fn time_at(&self, tick: u64) -> Duration {
+        let change = self.change_at(tick);
+        Duration::from_micros(change.micros + self.micros_between(change, tick))
+    }
+
+    /// O tick que está tocando no instante dado, contado a partir do início da música.
+    pub fn tick_at(&self, time: Duration) -> u64 {
+        let micros = time.as_micros() as u64;
+        let index = self
+            .changes
+            .partition_point(|change| change.micros <= micros);
+        let change = self.changes[index.saturating_sub(1)];
+
+        change.tick
+            + (micros - change.micros) * self.tpqn.as_int() as u64 / change.mspqn.as_int() as u64
+    }
+}
+
+/// Estima quanto tempo as ações levam para serem tocadas.
+pub fn estimate_duration(actions: &[MidiAction]) -> Duration {
+    let end: u64 = MidiAction::durations(actions).map(u64::from).sum();
+    TempoMap::from_actions(actions).time_at(end)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn default_tempo() {
+        let map = TempoMap::new(MidiAction::D_TPQN);
+
+        assert_eq!(map.time_at(480), Duration::from_millis(500));
+        assert_eq!(map.tick_at(Duration::from_millis(500)), 480);
+    }
+
+    #[test]
+    fn tempo_changes_from_actions() {
+        // Arrange
+        let actions = [
+            MidiAction::ChangeBPM(120),
+            MidiAction::PlayNote(60),
+            MidiAction::ChangeBPM(60),
+            MidiAction::PlayNote(60),
+            MidiAction::Pause,
+        ];
+
+        // Act
+        let map = TempoMap::from_actions(&actions);
+
+        // Assert
+        assert_eq!(map.changes().len(), 2);
+        assert_eq!(map.mspqn_at(479).as_int(), 500_000);
+        assert_eq!(map.mspqn_at(480).as_int(), 1_000_000);
+        assert_eq!(map.time_at(960), Duration::from_millis(1500));
+        assert_eq!(map.tick_at(Duration::from_millis(1500)), 960);
+        assert_eq!(estimate_duration(&actions), Duration::from_millis(2500));
+    }
+
+    #[test]
+    fn same_tick_replaces() {
+        let mut map = TempoMap::new(MidiAction::D_TPQN);
+
+        map.push(0, u24::from(1_000_000));
+
+        assert_eq!(map.changes().len(), 1);
+        assert_eq!(map.time_at(480), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn matches_generated_track() {
+        let actions = [
+            MidiAction::ChangeBPM(200),
+            MidiAction::PlayNote(60),
+            MidiAction::Pause,
+            MidiAction::ChangeBPM(90),
+            MidiAction::PlayNote(62),
+        ];
+        let smf = MidiAction::as_track(&actions);
+
+        assert_eq!(
+            TempoMap::from_track(&smf.tracks[0], MidiAction::D_TPQN),
+            TempoMap::from_actions(&actions)
+        );
+    }
+}
@@ -1,7 +1,23 @@
-use rand::Rng;
+use std::{
+    error::Error,
+    fmt, mem,
+    ops::{Range, RangeInclusive},
+    sync::OnceLock,
+};
 
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, trace};
+
+use crate::chord::{Chord, Voicing};
+use crate::header::Header;
 use crate::midi_action::MidiAction;
 use crate::note::*;
+use crate::progress::{Cancelled, Progress};
+use crate::ticks::TickAccumulator;
+use crate::time_state::{TimeSignature, TimeState};
+use crate::transform::{Accents, ActionTransform};
 
 /// Estrutura que guarda o estado atual da música.
 #[derive(Clone, Copy)]
@@ -16,6 +32,15 @@ pub struct State {
     pub volume: u16,
     /// A nota atual.
     pub note: Option<Note>,
+    /// A nota segurada no pedal, se houver.
+    pub drone: Option<u8>,
+    /// A pressão do canal (aftertouch) atual.
+    pub pressure: u8,
+    /// A ação escrita diretamente neste caractere, como uma SysEx ou um marcador,
+    /// como índice em `Sheet::blocks`.
+    pub block: Option<usize>,
+    /// O caractere do texto original que gerou este estado.
+    pub source: char,
 }
 
 impl State {
@@ -34,9 +59,32 @@ impl State {
     /// O BPM padrão.
     pub const D_BPM: u16 = 120;
 
+    /// O BPM mínimo: o menor cujo tempo ainda cabe no MSPQN de 24 bits em 4/4. Abaixo
+    /// dele, o `ChangeBPM` tocaria o andamento mais lento possível, e não o escolhido.
+    pub const MIN_BPM: u16 = {
+        let mut bpm = 1;
+        while TimeState::mspqn_from_bpm(bpm, TimeSignature::COMMON_TIME).is_err() {
+            bpm += 1;
+        }
+        bpm
+    };
+
     /// O BPM máximo.
     pub const MAX_BPM: u16 = 360;
 
+    /// As faixas aceitas das configurações, para a interface e o parser usarem as mesmas.
+    pub const LIMITS: Limits = Limits {
+        bpm: Self::MIN_BPM..=Self::MAX_BPM,
+        volume: 0..=Self::MAX_VOLUME,
+        octave: 0..=Self::MAX_OCTAVE,
+    };
+
+    /// Quanto cada `P+` aumenta a pressão do canal.
+    pub const PRESSURE_STEP: u8 = 32;
+
+    /// A pressão máxima do canal.
+    pub const MAX_PRESSURE: u8 = i8::MAX as u8;
+
     /// Cria um estado novo.
     pub const fn new(instrument: u8, octave: u8, volume: u16, bpm: u16, note: Note) -> Self {
         Self {
@@ -45,6 +93,10 @@ impl State {
             volume,
             bpm,
             note: Some(note),
+            drone: None,
+            pressure: 0,
+            block: None,
+            source: '\0',
         }
     }
 }
@@ -57,98 +109,808 @@ impl Default for State {
             volume: Self::D_VOLUME,
             bpm: Self::D_BPM,
             note: Option::default(),
+            drone: None,
+            pressure: 0,
+            block: None,
+            source: '\0',
+        }
+    }
+}
+
+/// As faixas aceitas das configurações do estado.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Limits {
+    /// O BPM.
+    pub bpm: RangeInclusive<u16>,
+    /// O volume.
+    pub volume: RangeInclusive<u16>,
+    /// A oitava.
+    pub octave: RangeInclusive<u8>,
+}
+
+/// A figura de cada letra de nota e de cada pausa, até uma diretiva `L:` no texto.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BaseLength {
+    /// Semimínima, como sempre foi.
+    #[default]
+    Quarter,
+    /// Colcheia.
+    Eighth,
+    /// Semicolcheia.
+    Sixteenth,
+}
+
+impl BaseLength {
+    /// Todas as figuras, da mais longa à mais curta.
+    pub const ALL: [Self; 3] = [Self::Quarter, Self::Eighth, Self::Sixteenth];
+
+    /// A duração, em ticks do `MidiAction::D_TPQN`.
+    pub fn ticks(self) -> u32 {
+        let quarter = MidiAction::D_TPQN.as_int() as u32;
+        match self {
+            Self::Quarter => quarter,
+            Self::Eighth => quarter / 2,
+            Self::Sixteenth => quarter / 4,
+        }
+    }
+}
+
+/// O que fazer com os caracteres que não significam nada no texto.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParseMode {
+    /// Ignora os caracteres, como sempre foi: eles não mudam o estado nem tocam nada.
+    #[default]
+    Lenient,
+    /// Recusa o texto no `Sheet::parse`, apontando os caracteres.
+    Strict,
+}
+
+/// Um caractere do texto que não significa nada, e que por isso foi ignorado.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Unrecognized {
+    /// O caractere.
+    pub character: char,
+    /// O trecho dele no texto original, em bytes.
+    pub span: Range<usize>,
+}
+
+impl fmt::Display for Unrecognized {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unrecognized character {:?}", self.character)
+    }
+}
+
+/// Por que o `Sheet::parse` não terminou.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// O processamento foi cancelado.
+    Cancelled,
+    /// A partitura é estrita e o texto tem caracteres que não significam nada.
+    Unrecognized(Vec<Unrecognized>),
+}
+
+impl From<Cancelled> for ParseError {
+    fn from(_: Cancelled) -> Self {
+        Self::Cancelled
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cancelled => write!(f, "{Cancelled}"),
+            Self::Unrecognized(unrecognized) => {
+                write!(f, "unrecognized characters in strict mode:")?;
+                let mut seen = Vec::new();
+                for character in unrecognized.iter().map(|u| u.character) {
+                    if !seen.contains(&character) {
+                        write!(f, " {character:?}")?;
+                        seen.push(character);
+                    }
+                }
+                Ok(())
+            }
         }
     }
 }
 
+impl Error for ParseError {}
+
+/// O texto processado pelo `Sheet::parse`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Parsed {
+    /// As ações geradas.
+    pub actions: Vec<MidiAction>,
+    /// O trecho do texto original que gerou cada ação, como no `Sheet::process_with_sources`.
+    pub sources: Vec<Range<usize>>,
+    /// Os caracteres ignorados, em ordem de reprodução.
+    pub unrecognized: Vec<Unrecognized>,
+}
+
 /// Segura informações sobre a música e oferece métodos para seu processamento.
+///
+/// É criada pelo `SheetBuilder`, a partir de `Sheet::builder()`.
 pub struct Sheet {
-    /// O BPM da partitura.
-    bpm: u16,
     /// O estado atual.
     current_state: State,
-    /// Os estados já processados.
-    states: Vec<State>,
+    /// O estado do caractere anterior, com o qual o próximo é comparado.
+    previous: Option<State>,
+    /// As ações geradas até agora.
+    actions: Vec<MidiAction>,
+    /// A última nota tocada, que é a segurada pelo `DRONE+`.
+    last_key: Option<u8>,
+    /// As ações escritas diretamente no texto, como as SysEx `X{F0 .. F7}` e os
+    /// marcadores `==Refrão==`, na ordem em que aparecem.
+    blocks: Vec<MidiAction>,
+    /// Quantos blocos o `parse_char` já encontrou.
+    blocks_parsed: usize,
+    /// O tamanho, em bytes, de cada bloco no texto original.
+    block_sizes: Vec<usize>,
+    /// O trecho do texto original de cada ação, se pedido pelo `process_with_sources`.
+    sources: Option<Vec<Range<usize>>>,
+    /// O trecho do texto original do caractere sendo processado.
+    span: Range<usize>,
+    /// O texto a ser processado.
+    text: String,
+    /// Se os caracteres originais devem ser emitidos como letras (karaokê).
+    lyrics: bool,
+    /// A figura das notas antes da primeira diretiva `L:`.
+    base_length: BaseLength,
+    /// A duração escrita das notas, em ticks: a da figura base ou a da última diretiva `L:`.
+    length: u32,
+    /// A duração da última `NoteLength` gerada, que vale para as próximas notas.
+    sounding: u32,
+    /// As durações já geradas da quiáltera aberta por `t(`, em terços de tick.
+    tuplet: Option<TickAccumulator>,
+    /// O que fazer com os caracteres que não significam nada.
+    mode: ParseMode,
+    /// Os caracteres ignorados até agora.
+    unrecognized: Vec<Unrecognized>,
+    /// Gerador dos números aleatórios usados pelos comandos aleatórios do texto.
+    rng: StdRng,
+}
+
+/// Um valor de configuração da partitura fora da faixa aceita.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    /// O BPM deve estar entre `State::MIN_BPM` e `State::MAX_BPM`.
+    Bpm(u16),
+    /// O volume deve estar entre 0 e `State::MAX_VOLUME`.
+    Volume(u16),
+    /// A oitava deve estar entre 0 e `State::MAX_OCTAVE`.
+    Octave(u8),
+    /// O instrumento deve ser um dos 128 do General MIDI.
+    Instrument(u8),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bpm(bpm) => write!(f, "BPM {bpm} is outside {:?}", State::LIMITS.bpm),
+            Self::Volume(volume) => {
+                write!(f, "volume {volume} is outside {:?}", State::LIMITS.volume)
+            }
+            Self::Octave(octave) => {
+                write!(f, "octave {octave} is outside {:?}", State::LIMITS.octave)
+            }
+            Self::Instrument(program) => write!(f, "instrument {program} is outside 0..=127"),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+/// Monta uma `Sheet`, validando as configurações no `build`.
+#[derive(Clone, Default)]
+pub struct SheetBuilder {
+    /// O estado inicial.
+    state: State,
     /// O texto a ser processado.
     text: String,
+    /// Se os caracteres originais devem ser emitidos como letras.
+    lyrics: bool,
+    /// A figura das notas antes da primeira diretiva `L:`.
+    base_length: BaseLength,
+    /// Semente dos comandos aleatórios. Sem ela, cada partitura sai diferente.
+    seed: Option<u64>,
+    /// O que fazer com os caracteres que não significam nada.
+    mode: ParseMode,
+}
+
+impl SheetBuilder {
+    /// Define o BPM inicial.
+    pub fn bpm(mut self, bpm: u16) -> Self {
+        self.state.bpm = bpm;
+        self
+    }
+
+    /// Define o volume inicial.
+    pub fn volume(mut self, volume: u16) -> Self {
+        self.state.volume = volume;
+        self
+    }
+
+    /// Define a oitava inicial.
+    pub fn octave(mut self, octave: u8) -> Self {
+        self.state.octave = octave;
+        self
+    }
+
+    /// Define o instrumento inicial.
+    pub fn instrument(mut self, instrument: u8) -> Self {
+        self.state.instrument = instrument;
+        self
+    }
+
+    /// Habilita ou desabilita a emissão dos caracteres originais como eventos de letra,
+    /// alinhados com as notas, para acompanhamento em players de karaokê.
+    pub fn lyrics(mut self, lyrics: bool) -> Self {
+        self.lyrics = lyrics;
+        self
+    }
+
+    /// Define a figura de cada letra de nota e de cada pausa, até uma diretiva `L:`.
+    pub fn base_length(mut self, base_length: BaseLength) -> Self {
+        self.base_length = base_length;
+        self
+    }
+
+    /// Fixa a semente dos comandos aleatórios, para que o mesmo texto gere sempre a mesma música.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Define se os caracteres que não significam nada são ignorados ou recusados.
+    pub fn mode(mut self, mode: ParseMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Define o texto a ser processado.
+    pub fn text(mut self, text: impl ToString) -> Self {
+        self.text = text.to_string();
+        self
+    }
+
+    /// Cria a partitura, se todas as configurações estiverem dentro das faixas aceitas.
+    pub fn build(self) -> Result<Sheet, ConfigError> {
+        let (state, limits) = (self.state, State::LIMITS);
+        if !limits.bpm.contains(&state.bpm) {
+            return Err(ConfigError::Bpm(state.bpm));
+        }
+        if !limits.volume.contains(&state.volume) {
+            return Err(ConfigError::Volume(state.volume));
+        }
+        if !limits.octave.contains(&state.octave) {
+            return Err(ConfigError::Octave(state.octave));
+        }
+        if state.instrument > i8::MAX as u8 {
+            return Err(ConfigError::Instrument(state.instrument));
+        }
+
+        Ok(Sheet {
+            current_state: state,
+            previous: None,
+            actions: Vec::new(),
+            last_key: None,
+            blocks: Vec::new(),
+            blocks_parsed: 0,
+            block_sizes: Vec::new(),
+            sources: None,
+            span: 0..0,
+            text: self.text,
+            lyrics: self.lyrics,
+            base_length: self.base_length,
+            length: self.base_length.ticks(),
+            sounding: self.base_length.ticks(),
+            tuplet: None,
+            mode: self.mode,
+            unrecognized: Vec::new(),
+            rng: match self.seed {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => StdRng::from_entropy(),
+            },
+        })
+    }
 }
 
 impl Sheet {
     const R_PLUS: char = '東';
     const R_MINUS: char = '世';
     const BPM_PLUS: char = 'ß';
+    const DRONE_PLUS: char = '持';
+    const DRONE_MINUS: char = '放';
+    const PRESSURE_PLUS: char = '圧';
+    const PRESSURE_MINUS: char = '緩';
+    const BLOCK: char = '系';
+    const SEGNO: char = '印';
+    const DAL_SEGNO: char = '返';
+    const DA_CAPO: char = '頭';
+    const FINE: char = '終';
+    const TUPLET_START: char = '連';
     const TELEPHONE_PROGRAM: u8 = 124;
 
-    /// Cria uma nova partitura a partir de uma BPM básica e um texto.
-    pub fn new(bpm: u16, volume: u16, text: impl ToString) -> Self {
-        Self {
-            bpm,
-            states: Vec::new(),
-            text: text.to_string(),
-            current_state: State {
-                bpm,
-                volume,
-                ..Default::default()
-            },
+    /// Os comandos de mais de um caractere e os caracteres que os substituem.
+    ///
+    /// Nenhum comando começa com o fim de outro, então a ordem não importa.
+    const COMMANDS: [(&'static str, char); 12] = [
+        ("DRONE+", Self::DRONE_PLUS),
+        ("DRONE-", Self::DRONE_MINUS),
+        ("BPM+", Self::BPM_PLUS),
+        ("P+", Self::PRESSURE_PLUS),
+        ("P-", Self::PRESSURE_MINUS),
+        ("R+", Self::R_PLUS),
+        ("R-", Self::R_MINUS),
+        ("[Segno]", Self::SEGNO),
+        ("[DS]", Self::DAL_SEGNO),
+        ("[DC]", Self::DA_CAPO),
+        ("[Fine]", Self::FINE),
+        ("t(", Self::TUPLET_START),
+    ];
+
+    /// Começa a montar uma partitura com as configurações padrão.
+    pub fn builder() -> SheetBuilder {
+        SheetBuilder::default()
+    }
+
+    /// Pega o vetor com os estados e aplica as mudanças conforme a especificação.
+    ///
+    /// Se o texto começar com um cabeçalho, ele define o estado inicial e não é tocado.
+    pub fn process(self) -> Vec<MidiAction> {
+        self.process_with_progress(&Progress::default())
+            .expect("only the caller could cancel its own progress")
+    }
+
+    /// Igual ao `process`, mas informa o andamento e para se for cancelado.
+    pub fn process_with_progress(
+        mut self,
+        progress: &Progress,
+    ) -> Result<Vec<MidiAction>, Cancelled> {
+        self.run(progress).map(|parsed| parsed.actions)
+    }
+
+    /// Igual ao `process_with_progress`, mas também retorna o trecho do texto, em bytes,
+    /// que gerou cada ação. As ações iniciais têm trechos vazios.
+    pub fn process_with_sources(
+        mut self,
+        progress: &Progress,
+    ) -> Result<(Vec<MidiAction>, Vec<Range<usize>>), Cancelled> {
+        self.sources = Some(Vec::new());
+        self.run(progress)
+            .map(|parsed| (parsed.actions, parsed.sources))
+    }
+
+    /// Igual ao `process_with_sources`, mas também retorna os caracteres ignorados.
+    ///
+    /// No modo estrito, eles são um erro. Os outros métodos sempre os ignoram.
+    pub fn parse(mut self, progress: &Progress) -> Result<Parsed, ParseError> {
+        self.sources = Some(Vec::new());
+        let mode = self.mode;
+        let parsed = self.run(progress)?;
+        if mode == ParseMode::Strict && !parsed.unrecognized.is_empty() {
+            return Err(ParseError::Unrecognized(parsed.unrecognized));
         }
+
+        Ok(parsed)
     }
 
-    pub fn with_default_volume(bpm: u16, text: impl ToString) -> Self {
-        Self {
-            bpm,
-            states: Vec::new(),
-            text: text.to_string(),
-            current_state: State {
-                bpm,
-                ..Default::default()
-            },
+    /// Processa o texto inteiro, com os trechos das ações se eles foram pedidos.
+    fn run(mut self, progress: &Progress) -> Result<Parsed, Cancelled> {
+        let (header, sources, text, spans) = self.prepare();
+        self.process_text(&sources, &text, &spans, progress)?;
+        progress.set(1.0)?;
+
+        let unrecognized = mem::take(&mut self.unrecognized);
+        let (actions, sources) = self.finish(&header);
+        Ok(Parsed {
+            actions,
+            sources,
+            unrecognized,
+        })
+    }
+
+    /// Aplica o cabeçalho ao estado inicial e troca os blocos e os comandos por caracteres.
+    ///
+    /// Retorna o cabeçalho, o texto com os comandos trocados (os caracteres originais),
+    /// o texto a ser processado, que também tem as repetições de notas trocadas, e o
+    /// trecho do texto original de cada caractere, se os trechos foram pedidos. Os dois
+    /// textos já seguem os saltos da forma da música, na ordem em que são tocados.
+    fn prepare(&mut self) -> (Header, String, String, Vec<Range<usize>>) {
+        let (header, body) = Header::parse(&self.text);
+        let body_start = self.text.len() - body.len();
+        let body = body.to_string();
+        header.apply(&mut self.current_state);
+        self.text = self.extract_blocks(&body, header.voicing.unwrap_or_default());
+
+        let sources = self.replace_substrings();
+        let text = Self::map_repeated_notes(&sources);
+        let spans = match self.sources {
+            Some(_) => self.spans(&sources, body_start),
+            None => Vec::new(),
+        };
+        if !sources.contains([Self::SEGNO, Self::DAL_SEGNO, Self::DA_CAPO, Self::FINE]) {
+            return (header, sources, text, spans);
         }
+
+        let (sources, text, spans) = self.linearize(&sources, &text, &spans);
+        (header, sources, text, spans)
     }
 
-    /// Pega o vetor com os estados e aplica as mudanças conforme a especificação.
-    pub fn process(mut self) -> Vec<MidiAction> {
-        self.process_text();
-        let mut ret = Vec::<MidiAction>::new();
-
-        self.current_state = self.states[0];
-        ret.push(MidiAction::ChangeBPM(self.current_state.bpm));
-        ret.push(MidiAction::ChangeInstrument(self.current_state.instrument));
-        ret.push(MidiAction::ChangeVolume(self.current_state.volume));
-
-        for actual_state in self.states {
-            if actual_state.bpm != self.current_state.bpm {
-                ret.push(MidiAction::ChangeBPM(actual_state.bpm));
-            } else if actual_state.instrument != self.current_state.instrument {
-                ret.push(MidiAction::ChangeInstrument(actual_state.instrument));
-            } else if actual_state.volume != self.current_state.volume {
-                ret.push(MidiAction::ChangeVolume(actual_state.volume));
-            } else if let Some(note) = actual_state.note {
-                match note {
-                    Note::Pause => {
-                        ret.push(MidiAction::Pause);
-                    }
-                    note => {
-                        ret.push(MidiAction::PlayNote(
-                            note.to_midi(self.current_state.octave),
-                        ));
-                    }
+    /// O trecho do texto original de cada caractere, sabendo que o texto começa em `start`.
+    fn spans(&self, sources: &str, start: usize) -> Vec<Range<usize>> {
+        let mut position = start;
+        let mut block = 0;
+        sources
+            .chars()
+            .map(|source| {
+                let len = self.source_len(source, block);
+                block += usize::from(source == Self::BLOCK);
+                position += len;
+                position - len..position
+            })
+            .collect()
+    }
+
+    /// A ordem em que os caracteres são tocados, seguindo a forma da música.
+    ///
+    /// `[DC]` volta ao começo e `[DS]` ao último `[Segno]` (ou ao começo, se não houver),
+    /// e depois de voltar a música termina no `[Fine]`, se houver. Como nas partituras,
+    /// só o primeiro salto é seguido. As próprias marcações não são tocadas.
+    fn play_order(sources: &[char]) -> Vec<usize> {
+        let mut order = Vec::with_capacity(sources.len());
+        let mut segno = 0;
+        let mut jumped = false;
+        let mut index = 0;
+        while let Some(&source) = sources.get(index) {
+            index += 1;
+            match source {
+                Self::SEGNO => segno = index,
+                Self::FINE if jumped => break,
+                Self::DA_CAPO | Self::DAL_SEGNO if !jumped => {
+                    jumped = true;
+                    index = if source == Self::DA_CAPO { 0 } else { segno };
                 }
+                Self::FINE | Self::DA_CAPO | Self::DAL_SEGNO => (),
+                _ => order.push(index - 1),
+            }
+        }
+
+        order
+    }
+
+    /// Deixa os textos, os seus trechos e os blocos na ordem do `play_order`, como se
+    /// as seções repetidas tivessem sido copiadas.
+    fn linearize(
+        &mut self,
+        sources: &str,
+        text: &str,
+        spans: &[Range<usize>],
+    ) -> (String, String, Vec<Range<usize>>) {
+        let sources: Vec<char> = sources.chars().collect();
+        let text: Vec<char> = text.chars().collect();
+        let mut blocks = Vec::with_capacity(sources.len());
+        let mut block = 0;
+        for &source in &sources {
+            blocks.push(block);
+            block += usize::from(source == Self::BLOCK);
+        }
+
+        let order = Self::play_order(&sources);
+        let written = mem::take(&mut self.blocks);
+        let mut linear = (String::new(), String::new(), Vec::new());
+        for index in order {
+            if sources[index] == Self::BLOCK {
+                self.blocks.push(written[blocks[index]].clone());
+            }
+            linear.0.push(sources[index]);
+            linear.1.push(text[index]);
+            linear.2.extend(spans.get(index).cloned());
+        }
+        debug!(
+            written = sources.len(),
+            played = linear.0.chars().count(),
+            "followed the form"
+        );
+
+        linear
+    }
+
+    /// As ações geradas, com as iniciais se nenhum caractere as gerou e com os acentos
+    /// do cabeçalho, junto dos seus trechos (vazio se eles não foram pedidos).
+    fn finish(mut self, header: &Header) -> (Vec<MidiAction>, Vec<Range<usize>>) {
+        if self.previous.is_none() {
+            // Um texto vazio só tem as ações iniciais
+            self.start(self.current_state);
+            self.record_sources(self.span.end..self.span.end);
+        }
+        debug!(blocks = self.blocks.len(), "parsed the text");
+
+        let mut ret = self.actions;
+        let mut sources = self.sources.unwrap_or_default();
+        if let Some(every) = header.accent {
+            ret = Accents::new(every).apply(ret);
+            if !sources.is_empty() {
+                // Cada acento vem do mesmo trecho que a nota que ele acentua
+                let mut original = sources.into_iter().peekable();
+                sources = ret
+                    .iter()
+                    .map(|action| match action {
+                        MidiAction::Accent(_) => original.peek().cloned().unwrap_or_default(),
+                        _ => original.next().unwrap_or_default(),
+                    })
+                    .collect();
+            }
+        }
+        debug!(actions = ret.len(), "generated the actions");
+
+        (ret, sources)
+    }
+
+    /// Dá o trecho dado às ações geradas desde a última chamada, se os trechos foram pedidos.
+    fn record_sources(&mut self, span: Range<usize>) {
+        if let Some(sources) = &mut self.sources {
+            sources.resize(self.actions.len(), span);
+        }
+    }
+
+    /// Quantos bytes do texto original o caractere ocupava, antes de os comandos e o
+    /// bloco de número `block` serem trocados.
+    fn source_len(&self, source: char, block: usize) -> usize {
+        if source == Self::BLOCK {
+            if let Some(&size) = self.block_sizes.get(block) {
+                return size;
             }
+        }
+        Self::COMMANDS
+            .iter()
+            .find(|&&(_, placeholder)| placeholder == source)
+            .map_or(source.len_utf8(), |(command, _)| command.len())
+    }
+
+    /// O ponto do processamento antes do caractere dado.
+    fn checkpoint(&self, index: usize) -> Checkpoint {
+        Checkpoint {
+            index,
+            actions: self.actions.len(),
+            current_state: self.current_state,
+            previous: self.previous,
+            last_key: self.last_key,
+            blocks_parsed: self.blocks_parsed,
+            length: self.length,
+            sounding: self.sounding,
+            tuplet: self.tuplet,
+            rng: self.rng.clone(),
+            span: self.span.clone(),
+        }
+    }
+
+    /// Volta ao ponto dado, com as ações geradas até ele e os seus trechos.
+    fn restore(
+        &mut self,
+        checkpoint: &Checkpoint,
+        actions: Vec<MidiAction>,
+        sources: Vec<Range<usize>>,
+    ) {
+        self.actions = actions;
+        self.sources = Some(sources);
+        self.current_state = checkpoint.current_state;
+        self.previous = checkpoint.previous;
+        self.last_key = checkpoint.last_key;
+        self.blocks_parsed = checkpoint.blocks_parsed;
+        self.length = checkpoint.length;
+        self.sounding = checkpoint.sounding;
+        self.tuplet = checkpoint.tuplet;
+        self.rng = checkpoint.rng.clone();
+        self.span = checkpoint.span.clone();
+    }
+
+    /// Gera as ações iniciais, com as configurações do primeiro estado.
+    fn start(&mut self, first: State) {
+        self.actions.push(MidiAction::ChangeBPM(first.bpm));
+        self.actions
+            .push(MidiAction::ChangeInstrument(first.instrument));
+        self.actions.push(MidiAction::ChangeVolume(first.volume));
+        if self.base_length != BaseLength::default() {
+            self.actions
+                .push(MidiAction::NoteLength(self.base_length.ticks()));
+        }
+        if first.pressure != 0 {
+            self.actions
+                .push(MidiAction::ChannelPressure(first.pressure));
+        }
+        self.actions.extend(first.drone.map(MidiAction::DroneStart));
+    }
 
-            self.current_state = actual_state;
+    /// Gera a ação que leva do estado anterior ao dado, que passa a ser o anterior.
+    ///
+    /// Cada estado gera no máximo uma ação, e o primeiro também gera as iniciais.
+    /// Assim só as ações são guardadas, e não um estado por caractere.
+    fn push_state(&mut self, state: State) {
+        let previous = match self.previous {
+            Some(previous) => previous,
+            None => {
+                self.start(state);
+                self.record_sources(self.span.start..self.span.start);
+                state
+            }
+        };
+
+        if let Some(block) = state.block {
+            if let MidiAction::NoteLength(ticks) = self.blocks[block] {
+                self.length = ticks;
+                self.sounding = ticks;
+            }
+            self.actions.push(self.blocks[block].clone());
+        } else if state.bpm != previous.bpm {
+            trace!(bpm = state.bpm, "tempo change");
+            self.actions.push(MidiAction::ChangeBPM(state.bpm));
+        } else if state.instrument != previous.instrument {
+            self.actions
+                .push(MidiAction::ChangeInstrument(state.instrument));
+        } else if state.volume != previous.volume {
+            self.actions.push(MidiAction::ChangeVolume(state.volume));
+        } else if state.drone != previous.drone {
+            self.actions
+                .extend(previous.drone.map(MidiAction::DroneStop));
+            self.actions.extend(state.drone.map(MidiAction::DroneStart));
+        } else if state.pressure != previous.pressure {
+            self.actions
+                .push(MidiAction::ChannelPressure(state.pressure));
+        } else if let Some(note) = state.note {
+            if self.lyrics {
+                self.actions
+                    .push(MidiAction::Lyric(state.source.to_string()));
+            }
+            let length = self.next_length();
+            if length != self.sounding {
+                self.actions.push(MidiAction::NoteLength(length));
+                self.sounding = length;
+            }
+            match note.to_midi(previous.octave) {
+                Some(key) => self.actions.push(MidiAction::PlayNote(key)),
+                None => self.actions.push(MidiAction::Pause),
+            }
         }
+        self.record_sources(self.span.clone());
 
-        ret
+        self.previous = Some(state);
     }
 
+    /// A duração da próxima nota ou pausa, em ticks.
+    ///
+    /// Numa quiáltera, cada três notas ocupam o espaço de duas. Quando a duração não
+    /// se divide por três, o acumulador compensa o arredondamento das notas anteriores,
+    /// então cada grupo de três ocupa exatamente duas durações e os compassos seguintes
+    /// continuam alinhados.
+    fn next_length(&mut self) -> u32 {
+        match &mut self.tuplet {
+            Some(tuplet) => tuplet.advance(2 * self.length as u64) as u32,
+            None => self.length,
+        }
+    }
+
+    /// Troca cada bloco do texto por um único caractere, guardando a sua ação em
+    /// `self.blocks`: as SysEx `X{F0 41 .. F7}`, os marcadores de seção `==Refrão==`,
+    /// as cifras `"Am7"`, tocadas com a distribuição dada, e as diretivas `L:1/8`, que
+    /// trocam a figura das notas seguintes, como no ABC.
+    ///
+    /// SysEx com algo além de pares de dígitos hexadecimais, cifras que não são
+    /// acordes e figuras que não cabem em um tick ficam como estão.
+    fn extract_blocks(&mut self, text: &str, voicing: Voicing) -> String {
+        // Compilada uma vez só: o `IncrementalSheet` passa por aqui a cada edição
+        static PATTERN: OnceLock<Regex> = OnceLock::new();
+        let pattern = PATTERN.get_or_init(|| {
+            Regex::new(
+                r#"X\{\s*((?:[0-9A-Fa-f]{2}\s*)+)\}|==([^=\n]+)==|"([^"\n]*)"|L:(\d{1,3})/(\d{1,3})"#,
+            )
+            .expect("the pattern is valid")
+        });
+
+        pattern
+            .replace_all(text, |captures: &Captures| {
+                let action = if let Some(digits) = captures.get(1) {
+                    MidiAction::SysEx(Self::parse_hex(digits.as_str()))
+                } else if let Some(name) = captures.get(2) {
+                    MidiAction::Marker(name.as_str().trim().to_string())
+                } else if let (Some(numerator), Some(denominator)) =
+                    (captures.get(4), captures.get(5))
+                {
+                    match Self::parse_length(numerator.as_str(), denominator.as_str()) {
+                        Some(ticks) => MidiAction::NoteLength(ticks),
+                        None => return captures[0].to_string(),
+                    }
+                } else {
+                    match Self::parse_chord(&captures[3], voicing) {
+                        Some(keys) => MidiAction::Chord(keys),
+                        None => return captures[0].to_string(),
+                    }
+                };
+                self.block_sizes.push(captures[0].len());
+                self.blocks.push(action);
+                Self::BLOCK.to_string()
+            })
+            .into_owned()
+    }
+
+    /// As notas de uma cifra, ou nenhuma para `N.C.` (sem acorde) e aspas vazias.
+    fn parse_chord(symbol: &str, voicing: Voicing) -> Option<Vec<u8>> {
+        let symbol = symbol.trim();
+        if symbol.is_empty() || matches!(symbol, "N.C." | "NC") {
+            return Some(Vec::new());
+        }
+
+        let chord: Chord = symbol.parse().ok()?;
+        Some(chord.keys(voicing, Chord::OCTAVE))
+    }
+
+    /// Os ticks de uma figura escrita como fração da semibreve, como `1/8`, se ela
+    /// tiver pelo menos um tick.
+    fn parse_length(numerator: &str, denominator: &str) -> Option<u32> {
+        let whole = MidiAction::D_TPQN.as_int() as u32 * 4;
+        let numerator: u32 = numerator.parse().ok()?;
+        let denominator: u32 = denominator.parse().ok()?;
+
+        whole
+            .checked_mul(numerator)?
+            .checked_div(denominator)
+            .filter(|&ticks| ticks > 0)
+    }
+
+    /// Os bytes escritos em pares de dígitos hexadecimais, separados ou não por espaços.
+    fn parse_hex(text: &str) -> Vec<u8> {
+        let digits: Vec<u8> = text
+            .bytes()
+            .filter(|byte| !byte.is_ascii_whitespace())
+            .collect();
+        digits
+            .chunks(2)
+            .map(|pair| {
+                let pair = std::str::from_utf8(pair).expect("hex digits are ASCII");
+                u8::from_str_radix(pair, 16).expect("the pattern only has hex digits")
+            })
+            .collect()
+    }
+
+    /// Substitui as sequências de mais de um caractere e as vogais que repetem notas,
+    /// de forma que cada caractere resultante corresponda a uma única ação.
     pub fn map_substring_to_char(&mut self) -> String {
-        let text = self
-            .text
-            .replace("BPM+", &Self::BPM_PLUS.to_string())
-            .replace("R+", &Self::R_PLUS.to_string())
-            .replace("R-", &Self::R_MINUS.to_string());
+        Self::map_repeated_notes(&self.replace_substrings())
+    }
+
+    /// Troca os comandos de mais de um caractere pelos seus caracteres internos.
+    ///
+    /// Percorre o texto uma única vez, em vez de uma vez por comando.
+    fn replace_substrings(&self) -> String {
+        let mut replaced = String::with_capacity(self.text.len());
+        let mut rest = self.text.as_str();
 
-        let mut aux = String::new();
+        while let Some(c) = rest.chars().next() {
+            let command = Self::COMMANDS
+                .iter()
+                .find(|(command, _)| rest.starts_with(command));
+            match command {
+                Some(&(command, placeholder)) => {
+                    replaced.push(placeholder);
+                    rest = &rest[command.len()..];
+                }
+                None => {
+                    replaced.push(c);
+                    rest = &rest[c.len_utf8()..];
+                }
+            }
+        }
+
+        replaced
+    }
+
+    /// Troca as vogais que seguem uma nota pela própria nota.
+    ///
+    /// Não altera a quantidade de caracteres do texto.
+    fn map_repeated_notes(text: &str) -> String {
+        let mut aux = String::with_capacity(text.len());
         let mut prev_char = '\0';
 
         for c in text.chars() {
@@ -166,21 +928,42 @@ impl Sheet {
         aux
     }
 
-    pub fn process_text(&mut self) {
-        let text = self.map_substring_to_char();
+    /// Transforma cada caractere do texto em um estado, gerando as suas ações.
+    ///
+    /// `spans` são os trechos do texto original de cada caractere, se eles foram pedidos.
+    fn process_text(
+        &mut self,
+        sources: &str,
+        text: &str,
+        spans: &[Range<usize>],
+        progress: &Progress,
+    ) -> Result<(), Cancelled> {
+        let count = text.chars().count();
+        let total = count.max(1) as f32;
+        // Quase sempre uma ação por caractere, mais as iniciais
+        self.actions.reserve(count + 5);
 
-        for c in text.chars() {
+        for (index, (c, source)) in text.chars().zip(sources.chars()).enumerate() {
+            progress.set(index as f32 / total)?;
+            if let Some(span) = spans.get(index) {
+                self.span = span.clone();
+            }
+            self.current_state.source = source;
             self.parse_char(c);
         }
+
+        Ok(())
     }
 
-    /// Altera o `current_state` e coloca no fim do vetor
+    /// Altera o `current_state` e gera as ações da mudança
     fn parse_char(&mut self, ch: char) {
         // ABCDEFG
         let new_note: Option<Note> = Note::from_char(ch);
 
+        self.current_state.block = None;
         if let Some(note) = new_note {
             self.current_state.note = Some(note);
+            self.last_key = note.to_midi(self.current_state.octave).or(self.last_key);
         } else {
             self.current_state.note = None;
             match ch {
@@ -202,10 +985,10 @@ impl Sheet {
                     let aux = self.current_state;
 
                     self.current_state.instrument = Self::TELEPHONE_PROGRAM;
-                    self.states.push(self.current_state);
+                    self.push_state(self.current_state);
 
                     self.current_state.note = Some(Note::Fa);
-                    self.states.push(self.current_state);
+                    self.push_state(self.current_state);
 
                     self.current_state = aux;
                 }
@@ -226,39 +1009,667 @@ impl Sheet {
                     // Aumenta BPM em 80 unidades
                     self.current_state.bpm = self.current_state.bpm.saturating_add(80);
                 }
+                Self::DRONE_PLUS => {
+                    // Segura a última nota tocada no pedal; sem nenhuma, o dó da oitava atual
+                    self.current_state.drone = self
+                        .last_key
+                        .or_else(|| Note::Do.to_midi(self.current_state.octave));
+                }
+                Self::BLOCK => {
+                    // Os blocos são numerados na ordem em que aparecem
+                    self.current_state.block = Some(self.blocks_parsed);
+                    self.blocks_parsed += 1;
+                }
+                Self::PRESSURE_PLUS => {
+                    // Aperta mais as teclas, para inchar o som; para no máximo
+                    self.current_state.pressure = self
+                        .current_state
+                        .pressure
+                        .saturating_add(State::PRESSURE_STEP)
+                        .min(State::MAX_PRESSURE);
+                }
+                Self::PRESSURE_MINUS => {
+                    // Solta a pressão
+                    self.current_state.pressure = 0;
+                }
+                Self::DRONE_MINUS => {
+                    // Solta o pedal
+                    self.current_state.drone = None;
+                }
+                Self::TUPLET_START => {
+                    // As próximas notas são de quiáltera, três no espaço de duas
+                    self.tuplet = Some(TickAccumulator::new(3));
+                }
+                ')' if self.tuplet.is_some() => {
+                    // Fecha a quiáltera: a duração escrita volta na próxima nota. Fora
+                    // de uma, o parêntese não significa nada
+                    self.tuplet = None;
+                }
                 '?' => {
                     // Toca uma nota aleatória (de A a G), randomicamente escolhida
-                    let mut rng = rand::thread_rng();
-                    let random_note: Note = rng.gen();
+                    let random_note: Note = self.rng.gen();
                     self.current_state.note = Some(random_note);
                 }
                 '\n' => {
                     // Troca para um instrumento aleatorio
-                    let mut rng = rand::thread_rng();
-                    self.current_state.instrument = rng.gen_range(0..=i8::MAX as u8);
+                    self.current_state.instrument = self.rng.gen_range(0..=i8::MAX as u8);
                 }
                 ';' => {
                     // Atribui valor aleatorio ao BPM
-                    let mut rng = rand::thread_rng();
-                    self.current_state.bpm = rng.gen_range(1..State::MAX_BPM);
+                    self.current_state.bpm = self.rng.gen_range(State::MIN_BPM..State::MAX_BPM);
+                }
+                ' ' | '\t' | '\r' => {
+                    // Os espaços só separam as palavras
                 }
-                _ => { // NOP
+                _ => {
+                    // Não significa nada: o estado continua o mesmo
+                    self.unrecognized.push(Unrecognized {
+                        character: ch,
+                        span: self.span.clone(),
+                    });
                 }
             }
         }
 
-        self.states.push(self.current_state);
+        self.push_state(self.current_state);
+    }
+}
+
+/// O ponto do processamento antes de um caractere, de onde o `IncrementalSheet` recomeça.
+#[derive(Clone)]
+struct Checkpoint {
+    /// O índice do caractere, no texto processado.
+    index: usize,
+    /// Quantas ações já tinham sido geradas.
+    actions: usize,
+    current_state: State,
+    previous: Option<State>,
+    last_key: Option<u8>,
+    blocks_parsed: usize,
+    length: u32,
+    sounding: u32,
+    tuplet: Option<TickAccumulator>,
+    /// O gerador, para os comandos aleatórios depois do ponto sortearem o mesmo de antes.
+    rng: StdRng,
+    /// O trecho do último caractere antes do ponto.
+    span: Range<usize>,
+}
+
+/// Uma partitura reprocessada a cada edição só a partir do primeiro caractere mudado,
+/// para que visualizações do texto acompanhem a digitação mesmo em textos longos.
+///
+/// A cada `CHECKPOINT_EVERY` caracteres é guardado o ponto do processamento, e a próxima
+/// edição recomeça do último ponto antes da mudança, com as ações geradas até ele.
+/// As ações e os trechos são sempre os mesmos que o `Sheet::process_with_sources`
+/// geraria para o texto inteiro.
+pub struct IncrementalSheet {
+    /// As configurações da partitura, com qualquer texto.
+    builder: SheetBuilder,
+    /// O cabeçalho do último texto. Se ele muda, tudo é processado de novo.
+    header: Header,
+    /// Cada caractere processado do último texto, junto do original.
+    chars: Vec<(char, char)>,
+    /// O trecho do texto original de cada caractere do último texto.
+    spans: Vec<Range<usize>>,
+    /// Os blocos do último texto, que não aparecem em `chars`.
+    blocks: Vec<MidiAction>,
+    /// As ações do último texto, antes das iniciais de um texto vazio e dos acentos.
+    actions: Vec<MidiAction>,
+    /// O trecho do texto de cada uma das `actions`.
+    sources: Vec<Range<usize>>,
+    /// Os pontos guardados, em ordem.
+    checkpoints: Vec<Checkpoint>,
+}
+
+impl IncrementalSheet {
+    /// De quantos em quantos caracteres o ponto do processamento é guardado.
+    const CHECKPOINT_EVERY: usize = 256;
+
+    /// Começa com as configurações dadas, validando-as. O texto do `builder` é ignorado.
+    pub fn new(builder: SheetBuilder) -> Result<Self, ConfigError> {
+        builder.clone().build()?;
+
+        Ok(Self {
+            builder,
+            header: Header::default(),
+            chars: Vec::new(),
+            spans: Vec::new(),
+            blocks: Vec::new(),
+            actions: Vec::new(),
+            sources: Vec::new(),
+            checkpoints: Vec::new(),
+        })
+    }
+
+    /// As ações do texto editado e o trecho do texto de cada uma, como no
+    /// `Sheet::process_with_sources`, reprocessando só a partir da primeira mudança.
+    pub fn update(&mut self, text: &str) -> (Vec<MidiAction>, Vec<Range<usize>>) {
+        let mut sheet = self
+            .builder
+            .clone()
+            .text(text)
+            .build()
+            .expect("the options were validated in new");
+        sheet.sources = Some(Vec::new());
+        let (header, sources, processed, spans) = sheet.prepare();
+        let chars: Vec<(char, char)> = processed.chars().zip(sources.chars()).collect();
+        let blocks = sheet.blocks.clone();
+
+        let changed = if header == self.header {
+            self.first_change(&chars, &spans, &blocks)
+        } else {
+            0
+        };
+        let kept = self
+            .checkpoints
+            .partition_point(|checkpoint| checkpoint.index <= changed);
+        self.checkpoints.truncate(kept);
+        let start = match self.checkpoints.last() {
+            Some(checkpoint) => {
+                self.actions.truncate(checkpoint.actions);
+                self.sources.truncate(checkpoint.actions);
+                sheet.restore(
+                    checkpoint,
+                    mem::take(&mut self.actions),
+                    mem::take(&mut self.sources),
+                );
+                checkpoint.index
+            }
+            None => 0,
+        };
+        trace!(changed, start, "reprocessing the edited text");
+
+        for (index, &(c, source)) in chars.iter().enumerate().skip(start) {
+            if index % Self::CHECKPOINT_EVERY == 0 && index > start {
+                self.checkpoints.push(sheet.checkpoint(index));
+            }
+            sheet.span = spans[index].clone();
+            sheet.current_state.source = source;
+            sheet.parse_char(c);
+        }
+
+        self.header = header.clone();
+        self.chars = chars;
+        self.spans = spans;
+        self.blocks = blocks;
+        self.actions = sheet.actions.clone();
+        self.sources = sheet.sources.clone().unwrap_or_default();
+        sheet.finish(&header)
+    }
+
+    /// O índice do primeiro caractere processado que mudou desde o último texto.
+    ///
+    /// Um caractere também muda quando o seu trecho muda de lugar, como ao editar o
+    /// cabeçalho sem mudar os seus valores.
+    fn first_change(
+        &self,
+        chars: &[(char, char)],
+        spans: &[Range<usize>],
+        blocks: &[MidiAction],
+    ) -> usize {
+        let mut block = 0;
+        let old = self.chars.iter().zip(&self.spans);
+        for (index, (old, new)) in old.zip(chars.iter().zip(spans)).enumerate() {
+            if old != new {
+                return index;
+            }
+            let (new, _) = new;
+            if new.0 == Sheet::BLOCK {
+                // O caractere dos blocos é sempre o mesmo, então as ações são comparadas
+                if self.blocks.get(block) != blocks.get(block) {
+                    return index;
+                }
+                block += 1;
+            }
+        }
+
+        self.chars.len().min(chars.len())
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::{Sheet, State};
+    use super::{
+        BaseLength, Cancelled, ConfigError, IncrementalSheet, MidiAction, ParseError, ParseMode,
+        Progress, Sheet, State,
+    };
+    use crate::{
+        midi_action::TimedAction,
+        tempo_map,
+        time_state::{TimeSignature, TimeState},
+    };
+
+    #[test]
+    fn initial_instrument() {
+        let actions = Sheet::builder()
+            .text("C")
+            .instrument(40)
+            .build()
+            .unwrap()
+            .process();
+
+        assert_eq!(actions[1], MidiAction::ChangeInstrument(40));
+    }
+
+    #[test]
+    fn header_sets_initial_state() {
+        let actions = Sheet::builder()
+            .text("@bpm=140 @octave=5\nC")
+            .build()
+            .unwrap()
+            .process();
+
+        assert_eq!(actions[0], MidiAction::ChangeBPM(140));
+        assert_eq!(actions[3], MidiAction::PlayNote(72));
+        assert_eq!(actions.len(), 4);
+    }
+
+    #[test]
+    fn accent_directive_raises_velocity() {
+        let actions = Sheet::builder()
+            .text("@accent=2\nCDCD")
+            .build()
+            .unwrap()
+            .process();
+        let smf = MidiAction::as_track(&actions);
+
+        let velocities: Vec<u8> = smf.tracks[0]
+            .iter()
+            .filter_map(|event| match event.kind {
+                midly::TrackEventKind::Midi {
+                    message: midly::MidiMessage::NoteOn { vel, .. },
+                    ..
+                } => Some(vel.as_int()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(velocities, [127, 63, 127, 63]);
+    }
+
+    #[test]
+    fn length_directive_changes_the_following_notes() {
+        let actions = Sheet::builder()
+            .base_length(BaseLength::Eighth)
+            .text("CD L:1/16EF")
+            .build()
+            .unwrap()
+            .process();
+
+        assert!(actions.contains(&MidiAction::NoteLength(240)));
+        assert!(actions.contains(&MidiAction::NoteLength(120)));
+        let notes: Vec<u64> = TimedAction::from_actions(&actions)
+            .into_iter()
+            .filter(|timed| matches!(timed.action, MidiAction::PlayNote(_)))
+            .map(|timed| timed.tick)
+            .collect();
+        // Two eighths and an eighth rest, then sixteenths
+        assert_eq!(notes, [0, 240, 720, 840]);
+        assert_eq!(
+            tempo_map::estimate_duration(&actions),
+            std::time::Duration::from_millis(1000)
+        );
+        let smf = MidiAction::as_track(&actions);
+        let timed = TimedAction::from_actions(&actions);
+        assert_eq!(smf.tracks, TimedAction::as_track(&timed).tracks);
+        // Impossible lengths are left as text
+        let actions = Sheet::builder().text("L:1/0C").build().unwrap().process();
+        assert!(!actions
+            .iter()
+            .any(|action| matches!(action, MidiAction::NoteLength(_))));
+    }
+
+    #[test]
+    fn triplets_fit_three_notes_in_two() {
+        let ticks = |text: &str| -> Vec<u64> {
+            let actions = Sheet::builder().text(text).build().unwrap().process();
+            TimedAction::from_actions(&actions)
+                .into_iter()
+                .filter(|timed| matches!(timed.action, MidiAction::PlayNote(_)))
+                .map(|timed| timed.tick)
+                .collect()
+        };
+
+        assert_eq!(ticks("t(CDE)FG"), [0, 320, 640, 960, 1440]);
+        // 100 ticks do not split in three, but every group still ends on 200
+        assert_eq!(
+            ticks("L:5/96t(CDEFGA)BC"),
+            [0, 67, 133, 200, 267, 333, 400, 500]
+        );
+    }
+
+    #[test]
+    fn drone_holds_the_last_note() {
+        let actions = Sheet::builder()
+            .text("CDRONE+ EDRONE-DRONE-")
+            .build()
+            .unwrap()
+            .process();
+
+        assert_eq!(
+            actions[3..],
+            [
+                MidiAction::PlayNote(60),
+                MidiAction::DroneStart(60),
+                MidiAction::Pause,
+                MidiAction::PlayNote(64),
+                MidiAction::DroneStop(60),
+            ]
+        );
+    }
+
+    #[test]
+    fn pressure_swells_and_releases() {
+        let actions = Sheet::builder()
+            .text("P+CP+P+P+P+DP-")
+            .build()
+            .unwrap()
+            .process();
+
+        let pressures: Vec<u8> = actions
+            .iter()
+            .filter_map(|action| match *action {
+                MidiAction::ChannelPressure(pressure) => Some(pressure),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(pressures, [32, 64, 96, 127, 0]);
+    }
+
+    #[test]
+    fn sysex_blocks_pass_through() {
+        let actions = Sheet::builder()
+            .text("X{F0 7e7F 09 01 F7}C X{ZZ}")
+            .build()
+            .unwrap()
+            .process();
+
+        assert_eq!(
+            actions[3..5],
+            [
+                MidiAction::SysEx(vec![0xF0, 0x7E, 0x7F, 0x09, 0x01, 0xF7]),
+                MidiAction::PlayNote(60)
+            ]
+        );
+        assert_eq!(
+            actions
+                .iter()
+                .filter(|action| matches!(action, MidiAction::SysEx(_)))
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn chord_symbols_play_under_the_melody() {
+        let actions = Sheet::builder()
+            .text("@voicing=open\n\"Am\"CD\"N.C.\"E \"Hello\"")
+            .build()
+            .unwrap()
+            .process();
+
+        let chords: Vec<&[u8]> = actions
+            .iter()
+            .filter_map(|action| match action {
+                MidiAction::Chord(keys) => Some(keys.as_slice()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(chords, [&[45, 60, 64][..], &[]]);
+        // Quotes that are not chords stay text: the e and the o of Hello still play
+        let notes = actions
+            .iter()
+            .filter(|action| matches!(action, MidiAction::PlayNote(_)))
+            .count();
+        assert_eq!(notes, 5);
+    }
+
+    #[test]
+    fn markers_keep_their_place() {
+        let text = "C== Chorus ==DX{F0 F7}";
+        let (actions, sources) = Sheet::builder()
+            .text(text)
+            .build()
+            .unwrap()
+            .process_with_sources(&Progress::default())
+            .unwrap();
+
+        assert_eq!(
+            actions[3..],
+            [
+                MidiAction::PlayNote(60),
+                MidiAction::Marker("Chorus".to_string()),
+                MidiAction::PlayNote(62),
+                MidiAction::SysEx(vec![0xF0, 0xF7]),
+            ]
+        );
+        assert_eq!(&text[sources[4].clone()], "== Chorus ==");
+    }
+
+    #[test]
+    fn form_directives_are_expanded() {
+        let notes = |text: &str| -> Vec<MidiAction> {
+            Sheet::builder().text(text).build().unwrap().process()[3..].to_vec()
+        };
+
+        assert_eq!(notes("CD[Fine]E[DC]"), notes("CDECD"));
+        assert_eq!(notes("C[Segno]D[Fine]E[DS]F"), notes("CDED"));
+        assert_eq!(notes("CX{F0 F7}[DC]D[DC]"), notes("CX{F0 F7}CX{F0 F7}D"));
+        assert_eq!(notes("C[DS]"), notes("CC"));
+
+        let text = "C[Segno]D[DS]";
+        let (_, sources) = Sheet::builder()
+            .text(text)
+            .build()
+            .unwrap()
+            .process_with_sources(&Progress::default())
+            .unwrap();
+        let spans: Vec<&str> = sources[3..]
+            .iter()
+            .map(|range| &text[range.clone()])
+            .collect();
+        assert_eq!(spans, ["C", "D", "D"]);
+    }
+
+    #[test]
+    fn commands_are_replaced_in_one_pass() {
+        let text = "DRONE+DRONE-BPM+BPMP+P-R+R-RP+DRONE P++R--ção";
+        let mut sheet = Sheet::builder().text(text).build().unwrap();
+
+        // O mesmo que substituir um comando de cada vez
+        let chained = Sheet::COMMANDS
+            .iter()
+            .fold(text.to_string(), |text, (command, placeholder)| {
+                text.replace(command, &placeholder.to_string())
+            });
+        assert_eq!(sheet.replace_substrings(), chained);
+        assert_eq!(
+            sheet.map_substring_to_char(),
+            Sheet::map_repeated_notes(&chained)
+        );
+    }
+
+    #[test]
+    fn every_command_matches_the_recorded_file() {
+        // Gravado antes de os estados deixarem de ser guardados, um por caractere
+        let text = "CDE FGA Bo R+ Ci D- +-+ DRONE+ P+ P+ Ee P- DRONE- R- R- BPM+ ;?\n\
+                    X{F0 7E 7F 09 01 F7} oi Gu ??; R+R+R+R+R+R+R+R+R+R+R+R+ A R+ B";
+        let actions = Sheet::builder()
+            .text(text)
+            .lyrics(true)
+            .seed(7)
+            .build()
+            .unwrap()
+            .process();
+
+        let mut smf = Vec::new();
+        MidiAction::as_track(&actions).write_std(&mut smf).unwrap();
+        assert_eq!(smf, include_bytes!("../test-asset/every_command.mid"));
+    }
+
+    #[test]
+    fn incremental_sheet_matches_a_full_reprocess() {
+        let builder = Sheet::builder().lyrics(true).seed(3);
+        let mut incremental = IncrementalSheet::new(builder.clone()).unwrap();
+        let long = "CDE ?; R+ Fo DRONE+ P+ G X{F0 01 F7} P- DRONE- R- A\n".repeat(40);
+        let edits = [
+            long.clone(),
+            format!("{long}BPM+ CC"),
+            long.replacen("Fo", "FG", 20),
+            long.replacen("X{F0 01 F7}", "X{F0 02 F7}", 30),
+            long.replacen("R-", "R", 25),
+            format!("[Segno]{long}[Fine]CDE[DS]"),
+            format!("[Segno]{long}[Fine]CDF[DS]"),
+            format!("@bpm=90 @accent=2\n{long}"),
+            format!("@bpm=90  @accent=2\n{long}"),
+            long[..long.len() / 2].to_string(),
+            String::new(),
+            long.clone(),
+        ];
+
+        for text in edits {
+            let full = builder
+                .clone()
+                .text(&text)
+                .build()
+                .unwrap()
+                .process_with_sources(&Progress::default())
+                .unwrap();
+            assert_eq!(incremental.update(&text), full);
+        }
+    }
+
+    #[test]
+    fn actions_point_back_to_their_text() {
+        let text = "@bpm=90 @accent=1\nCR+D X{F0 01 F7}Eo";
+        let (actions, sources) = Sheet::builder()
+            .text(text)
+            .build()
+            .unwrap()
+            .process_with_sources(&Progress::default())
+            .unwrap();
+
+        assert_eq!(actions.len(), sources.len());
+        assert!(sources[..3].iter().all(|range| range == &(18..18)));
+        let spans: Vec<&str> = sources[3..]
+            .iter()
+            .map(|range| &text[range.clone()])
+            .collect();
+        assert_eq!(
+            spans,
+            ["C", "C", "D", "D", " ", "X{F0 01 F7}", "E", "E", "o", "o"]
+        );
+        assert_eq!(actions[3], MidiAction::Accent(127));
+    }
+
+    #[test]
+    fn builder_validates_ranges() {
+        assert_eq!(
+            Sheet::builder().bpm(0).build().err(),
+            Some(ConfigError::Bpm(0))
+        );
+        assert_eq!(
+            Sheet::builder().volume(200).build().err(),
+            Some(ConfigError::Volume(200))
+        );
+        assert_eq!(
+            Sheet::builder().octave(13).build().err(),
+            Some(ConfigError::Octave(13))
+        );
+        assert_eq!(
+            Sheet::builder().instrument(128).build().err(),
+            Some(ConfigError::Instrument(128))
+        );
+        // The limits are the ones the interface offers
+        assert_eq!(ConfigError::Bpm(0).to_string(), "BPM 0 is outside 4..=360");
+        // The slowest accepted BPM still fits a MIDI tempo, the one below it does not
+        assert_eq!(State::MIN_BPM, 4);
+        assert!(TimeState::mspqn_from_bpm(State::MIN_BPM, TimeSignature::COMMON_TIME).is_ok());
+        assert!(TimeState::mspqn_from_bpm(State::MIN_BPM - 1, TimeSignature::COMMON_TIME).is_err());
+        assert!(Sheet::builder().bpm(State::MIN_BPM).build().is_ok());
+        assert_eq!(
+            Sheet::builder().bpm(State::MIN_BPM - 1).build().err(),
+            Some(ConfigError::Bpm(3))
+        );
+    }
+
+    #[test]
+    fn strict_sheets_refuse_unrecognized_characters() {
+        let text = "C D%\tBPM+E#";
+        let sheet = |mode| Sheet::builder().mode(mode).text(text).build().unwrap();
+
+        // Lenient sheets play the same, only listing what was ignored
+        let parsed = sheet(ParseMode::Lenient)
+            .parse(&Progress::default())
+            .unwrap();
+        assert_eq!(parsed.actions, sheet(ParseMode::Lenient).process());
+        let ignored: Vec<(char, &str)> = parsed
+            .unrecognized
+            .iter()
+            .map(|u| (u.character, &text[u.span.clone()]))
+            .collect();
+        assert_eq!(ignored, [('%', "%"), ('#', "#")]);
+
+        let error = sheet(ParseMode::Strict)
+            .parse(&Progress::default())
+            .unwrap_err();
+        assert_eq!(error, ParseError::Unrecognized(parsed.unrecognized));
+        assert_eq!(
+            error.to_string(),
+            "unrecognized characters in strict mode: '%' '#'"
+        );
+        // Only parse refuses the text
+        assert_eq!(sheet(ParseMode::Strict).process(), parsed.actions);
+
+        // A parenthesis only means something while it closes a tuplet
+        let strict = |text: &str| {
+            Sheet::builder()
+                .mode(ParseMode::Strict)
+                .text(text)
+                .build()
+                .unwrap()
+                .parse(&Progress::default())
+        };
+        assert!(strict("t(CDE)F").is_ok());
+        let lone: Vec<usize> = match strict("C)t(DEF))") {
+            Err(ParseError::Unrecognized(unrecognized)) => {
+                unrecognized.iter().map(|u| u.span.start).collect()
+            }
+            other => panic!("expected the stray parentheses, got {other:?}"),
+        };
+        assert_eq!(lone, [1, 8]);
+    }
+
+    #[test]
+    fn seed_makes_randomness_repeatable() {
+        let sheet = || {
+            Sheet::builder()
+                .seed(42)
+                .text("??????;\n??")
+                .build()
+                .unwrap()
+        };
+
+        assert_eq!(sheet().process(), sheet().process());
+    }
+
+    #[test]
+    fn progress_and_cancel() {
+        let sheet = || Sheet::builder().text("CDEFGAB".repeat(10)).build().unwrap();
+
+        let progress = Progress::default();
+        assert!(sheet().process_with_progress(&progress).is_ok());
+        assert_eq!(progress.percent(), 100);
+
+        let progress = Progress::default();
+        progress.cancel();
+        assert_eq!(sheet().process_with_progress(&progress), Err(Cancelled));
+        assert_eq!(progress.percent(), 0);
+    }
 
     #[test]
     fn match_process_general_text_behavior() {
         let text = "; \nasBPM+ ?!;-+".to_string();
-        let mut sheet = Sheet::with_default_volume(State::D_BPM, text);
+        let mut sheet = Sheet::builder().text(text).build().unwrap();
         let received_text = sheet.map_substring_to_char();
 
         let expected_text = "; \nasß ?!;-+".to_string();
@@ -269,7 +1680,7 @@ mod test {
     #[test]
     fn match_process_note_text_behavior() {
         let text = "AaBbCcDdEeFfGg".to_string();
-        let mut sheet = Sheet::with_default_volume(State::D_BPM, text);
+        let mut sheet = Sheet::builder().text(text).build().unwrap();
         let received_text = sheet.map_substring_to_char();
 
         let expected_text = "AaBbCcDdEeFfGg".to_string();
@@ -280,7 +1691,7 @@ mod test {
     #[test]
     fn match_process_substring_text_behavior() {
         let text = "BPM+R+R-".to_string();
-        let mut sheet = Sheet::with_default_volume(State::D_BPM, text);
+        let mut sheet = Sheet::builder().text(text).build().unwrap();
         let received_text = sheet.map_substring_to_char();
 
         let mut expected_text = Sheet::BPM_PLUS.to_string();
@@ -293,7 +1704,7 @@ mod test {
     #[test]
     fn match_process_vogals_text_behavior() {
         let text = "OoIiUuAiBICuDUEoFo".to_string();
-        let mut sheet = Sheet::with_default_volume(State::D_BPM, text);
+        let mut sheet = Sheet::builder().text(text).build().unwrap();
         let received_text = sheet.map_substring_to_char();
 
         let expected_text = "OoIiUuAABBCCDDEEFF".to_string();
@@ -0,0 +1,74 @@
+//! Durações que não caem em ticks inteiros, como as das quiálteras, do swing e dos
+//! arquivos com outro TPQN.
+//!
+//! Arredondar cada duração sozinha acumula o erro: três notas de quiáltera de um
+//! tempo de 100 ticks dariam 3 × 67 = 201 ticks, e cada compasso seguinte sairia um
+//! pouco mais fora do lugar. O acumulador arredonda o fim de cada nota, a partir da
+//! posição exata, em vez da sua duração, então o erro nunca passa de meio tick e
+//! toda posição exata inteira, como o início de um compasso, cai no tick certo.
+
+/// A soma exata de durações em frações de tick, entregues em ticks inteiros.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TickAccumulator {
+    /// A posição exata, em `1/denominator` de tick.
+    exact: u64,
+    /// Em quantas partes um tick é dividido.
+    denominator: u64,
+    /// O tick onde terminou a última duração entregue.
+    tick: u64,
+}
+
+impl TickAccumulator {
+    /// Um acumulador no tick 0, para durações medidas em `1/denominator` de tick.
+    pub const fn new(denominator: u64) -> Self {
+        Self {
+            exact: 0,
+            denominator: if denominator == 0 { 1 } else { denominator },
+            tick: 0,
+        }
+    }
+
+    /// O tick onde terminou a última duração entregue.
+    pub const fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Avança `numerator / denominator` ticks, retornando a duração arredondada, que
+    /// compensa o arredondamento das anteriores.
+    pub fn advance(&mut self, numerator: u64) -> u64 {
+        self.exact += numerator;
+        let end = scale(self.exact, 1, self.denominator);
+        let duration = end - self.tick;
+        self.tick = end;
+
+        duration
+    }
+}
+
+/// `value × numerator / denominator`, arredondado para o inteiro mais próximo, com as
+/// metades para cima.
+pub fn scale(value: u64, numerator: u64, denominator: u64) -> u64 {
+    let denominator = denominator.max(1) as u128;
+    ((value as u128 * numerator as u128 + denominator / 2) / denominator) as u64
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rounding_errors_do_not_accumulate() {
+        // Triplets of a 100-tick beat: every third note lands back on the grid
+        let mut triplets = TickAccumulator::new(3);
+        let durations: Vec<u64> = (0..6).map(|_| triplets.advance(200)).collect();
+        assert_eq!(durations, [67, 66, 67, 67, 66, 67]);
+        assert_eq!(triplets.tick(), 400);
+
+        // Rounding each duration alone would drift a tick every 32nd note here
+        let mut thirty_seconds = TickAccumulator::new(8);
+        let bar: u64 = (0..32).map(|_| thirty_seconds.advance(100)).sum();
+        assert_eq!(bar, 400);
+        assert_eq!(scale(316, 5, 10), 158);
+        assert_eq!(scale(7, 1, 0), 7);
+    }
+}
@@ -1,4 +1,4 @@
-use std::time::Duration;
+use std::{error::Error, fmt, time::Duration};
 
 use midly::num::*;
 
@@ -7,9 +7,32 @@ use crate::{
     text_to_midi::{Sheet, State},
 };
 
-const ONE_MINUTE_IN_MICROSECONDS: u32 = 60_000_000;
+const ONE_MINUTE_IN_MICROSECONDS: u64 = 60_000_000;
 
-#[derive(Clone, Copy)]
+/// O maior MSPQN representável no evento de tempo do MIDI (24 bits).
+const MAX_MSPQN: u64 = 0xFF_FFFF;
+
+/// Erros no cálculo do tempo a partir do BPM.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TempoError {
+    /// Um BPM de 0 não tem duração definida.
+    ZeroBpm,
+    /// O BPM é lento demais para caber nos 24 bits do evento de tempo.
+    TooSlow(u16),
+}
+
+impl fmt::Display for TempoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ZeroBpm => write!(f, "a BPM of 0 is not a valid tempo"),
+            Self::TooSlow(bpm) => write!(f, "a BPM of {bpm} is too slow to be represented"),
+        }
+    }
+}
+
+impl Error for TempoError {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 /// Guarda um compasso.
 pub struct TimeSignature {
     /// Numerador
@@ -20,14 +43,32 @@ pub struct TimeSignature {
 
 impl Default for TimeSignature {
     fn default() -> Self {
-        Self {
-            numerator: 4,
-            denominator: 4,
-        }
+        Self::COMMON_TIME
     }
 }
 
 impl TimeSignature {
+    /// O compasso 4/4.
+    pub const COMMON_TIME: Self = Self {
+        numerator: 4,
+        denominator: 4,
+    };
+
+    /// Se o compasso é composto (6/8, 9/8, 12/8, ...), ou seja, se o tempo é pontuado
+    /// e agrupa três figuras do denominador.
+    pub const fn is_compound(self) -> bool {
+        self.numerator > 3 && self.numerator.is_multiple_of(3)
+    }
+
+    /// A duração de um tempo em figuras do denominador.
+    pub const fn units_per_beat(self) -> u64 {
+        if self.is_compound() {
+            3
+        } else {
+            1
+        }
+    }
+
     /// Construtor a partir das informações cruas,
     /// onde numerador não é alterado, e denominador é um logaritmo de 2 da real nota.
     pub const fn from_raw(numerator: u8, denominator: u8) -> Self {
@@ -50,8 +91,11 @@ pub struct TimeState {
 }
 
 impl TimeState {
-    /// Presume um BPM de 120.
-    const D_MSPQN: u24 = Self::mspqn_from_bpm(State::D_BPM, 4);
+    /// Presume um BPM de 120 em 4/4.
+    const D_MSPQN: u24 = match Self::mspqn_from_bpm(State::D_BPM, TimeSignature::COMMON_TIME) {
+        Ok(mspqn) => mspqn,
+        Err(_) => panic!("the default BPM must be valid"),
+    };
 
     /// Define a quantidade de microsegundos por semimínima.
     ///
@@ -75,25 +119,46 @@ impl TimeState {
         self.microsecspqn
     }
 
+    /// Calcula o MSPQN a partir de um BPM e do compasso.
     ///
-    pub const fn mspqn_from_bpm(bpm: u16, denominator: u32) -> u24 {
-        u24::from_int_lossy(((ONE_MINUTE_IN_MICROSECONDS) * denominator) / (bpm * 4) as u32)
+    /// O BPM conta tempos do compasso: a figura do denominador em compassos simples
+    /// (semínima em 3/4, colcheia em 3/8), e a figura pontuada em compassos compostos
+    /// (semínima pontuada em 6/8). Como o MSPQN é sempre relativo à semínima,
+    /// a duração do tempo é convertida para semínimas.
+    pub const fn mspqn_from_bpm(
+        bpm: u16,
+        time_signature: TimeSignature,
+    ) -> Result<u24, TempoError> {
+        if bpm == 0 {
+            return Err(TempoError::ZeroBpm);
+        }
+
+        // Semínimas por tempo = 4 * unidades / denominador
+        let mspqn = (ONE_MINUTE_IN_MICROSECONDS * time_signature.denominator as u64)
+            / (bpm as u64 * 4 * time_signature.units_per_beat());
+
+        if mspqn > MAX_MSPQN {
+            Err(TempoError::TooSlow(bpm))
+        } else {
+            Ok(u24::new(mspqn as u32))
+        }
     }
+
     /// Sets the MSPQN based on a BPM
-    pub fn set_mspqn_from_bpm(&mut self, bpm: u16) {
-        self.set_mspqn(Self::mspqn_from_bpm(
-            bpm,
-            self.time_signature.denominator as _,
-        ));
+    pub fn set_mspqn_from_bpm(&mut self, bpm: u16) -> Result<(), TempoError> {
+        self.set_mspqn(Self::mspqn_from_bpm(bpm, self.time_signature)?);
+        Ok(())
     }
 
     /// Getter para o BPM, utilizando o MSPQN.
     ///
-    /// Para o cálculo, é ncessário ajustar de acordo com o denominador do compasso,
+    /// Inverso do `mspqn_from_bpm`: é ncessário ajustar de acordo com o compasso,
     /// pois o MSPQN é fixo para semimínimas.
     pub fn bpm(self) -> u16 {
-        ((ONE_MINUTE_IN_MICROSECONDS as f64 / self.microsecspqn.as_int() as f64)
-            * (self.time_signature().denominator as f64 / 4_f64)) as u16
+        let time_signature = self.time_signature();
+        ((ONE_MINUTE_IN_MICROSECONDS * time_signature.denominator as u64) as f64
+            / (self.microsecspqn.as_int() as u64 * 4 * time_signature.units_per_beat()) as f64)
+            .round() as u16
     }
 
     /// A duração de um tick do MIDI.
@@ -134,9 +199,59 @@ mod test {
         let mut regular = TimeState::default();
 
         // Act
-        regular.set_mspqn_from_bpm(120);
+        regular.set_mspqn_from_bpm(120).unwrap();
 
         // Assert
         assert_eq!(regular.mspqn(), 500_000);
     }
+
+    #[test]
+    fn mspqn_from_extreme_bpm() {
+        let common = TimeSignature::COMMON_TIME;
+
+        assert_eq!(
+            TimeState::mspqn_from_bpm(0, common),
+            Err(TempoError::ZeroBpm)
+        );
+        assert_eq!(
+            TimeState::mspqn_from_bpm(1, common),
+            Err(TempoError::TooSlow(1))
+        );
+        assert_eq!(
+            TimeState::mspqn_from_bpm(u16::MAX, common).map(u24::as_int),
+            Ok(915)
+        );
+    }
+
+    fn assert_mspqn(time_signature: TimeSignature, bpm: u16, mspqn: u32) {
+        // Arrange
+        let mut state = TimeState::default();
+        state.set_time_signature(time_signature);
+
+        // Act
+        state.set_mspqn_from_bpm(bpm).unwrap();
+
+        // Assert
+        assert_eq!(state.mspqn().as_int(), mspqn);
+        assert_eq!(state.bpm(), bpm);
+    }
+
+    #[test]
+    fn three_four() {
+        let three_four = TimeSignature::from_raw(3, 2);
+
+        assert_mspqn(three_four, 60, 1_000_000);
+        assert_mspqn(three_four, 120, 500_000);
+        assert_mspqn(three_four, 180, 333_333);
+    }
+
+    #[test]
+    fn six_eight() {
+        // The beat is a dotted quarter, so a quarter lasts 2/3 of a beat
+        let six_eight = TimeSignature::from_raw(6, 3);
+
+        assert_mspqn(six_eight, 60, 666_666);
+        assert_mspqn(six_eight, 120, 333_333);
+        assert_mspqn(six_eight, 180, 222_222);
+    }
 }
@@ -0,0 +1,1167 @@
+use std::{fmt, ops::Range};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    midi_action::{MidiAction, TimedAction},
+    midi_value::MidiValue,
+    note::{Interval, Note},
+    tempo_map::TempoMap,
+    time_state::TimeSignature,
+};
+
+/// Uma transformação aplicada às ações depois que o texto é processado,
+/// antes de virarem eventos do MIDI.
+pub trait ActionTransform {
+    /// Transforma as ações, podendo alterar, remover ou inserir novas.
+    fn apply(&self, actions: Vec<MidiAction>) -> Vec<MidiAction>;
+}
+
+/// O trecho do texto de cada ação transformada, a partir dos trechos das ações originais.
+///
+/// As transformações mantêm as notas e pausas, na mesma ordem. Entre uma e outra, as ações
+/// que continuam iguais ficam com o seu trecho, e as acrescentadas ou alteradas ficam com o
+/// da nota ou pausa seguinte, a que elas se referem.
+pub fn carry_sources(
+    before: &[MidiAction],
+    sources: &[Range<usize>],
+    after: &[MidiAction],
+) -> Vec<Range<usize>> {
+    let mut carried = Vec::with_capacity(after.len());
+    let mut start = 0;
+    let mut used = vec![false; before.len()];
+    for action in after {
+        // O trecho de `before` até a próxima nota ou pausa, inclusive
+        let end = before[start..]
+            .iter()
+            .position(|action| action.ticks() > 0)
+            .map_or(before.len(), |position| start + position + 1);
+        let same = (start..end).find(|&index| !used[index] && before[index] == *action);
+        let index = same.or(end.checked_sub(1).filter(|&last| last >= start));
+        if let Some(index) = same {
+            used[index] = true;
+        }
+        carried.push(
+            index
+                .and_then(|index| sources.get(index).cloned())
+                .unwrap_or_default(),
+        );
+
+        if action.ticks() > 0 {
+            start = end;
+        }
+    }
+
+    carried
+}
+
+/// Curva que converte o volume interno para a faixa de 0 a 127 do MIDI.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VelocityCurve {
+    /// Mantém os valores.
+    #[default]
+    Linear,
+    /// Realça os volumes baixos (raiz quadrada).
+    Soft,
+    /// Atenua os volumes baixos (quadrado).
+    Hard,
+    /// Interpola linearmente entre pontos (entrada, saída) dados pelo usuário.
+    ///
+    /// Antes do primeiro ponto e depois do último, a saída é a do ponto mais próximo.
+    Custom(Vec<(u8, u8)>),
+}
+
+impl VelocityCurve {
+    /// As curvas disponíveis, com os pontos padrão da curva do usuário.
+    pub fn all() -> [Self; 4] {
+        [
+            Self::Linear,
+            Self::Soft,
+            Self::Hard,
+            Self::Custom(vec![(0, 0), (64, 64), (127, 127)]),
+        ]
+    }
+
+    /// Converte um volume, que é limitado a 127 antes de passar pela curva.
+    pub fn map(&self, value: u16) -> u8 {
+        let max = MidiValue::MAX.as_int() as f64;
+        let value = MidiValue::clamped(value).as_int();
+        let x = value as f64 / max;
+
+        match self {
+            Self::Linear => value,
+            Self::Soft => (x.sqrt() * max).round() as u8,
+            Self::Hard => (x * x * max).round() as u8,
+            Self::Custom(points) => Self::interpolate(points, value),
+        }
+    }
+
+    /// Interpola entre os pontos da curva do usuário.
+    fn interpolate(points: &[(u8, u8)], value: u8) -> u8 {
+        let mut points = points.to_vec();
+        points.sort_unstable();
+
+        let after = points.partition_point(|&(input, _)| input <= value);
+        match (after.checked_sub(1).map(|i| points[i]), points.get(after)) {
+            (None, None) => value,
+            (Some((_, out)), None) | (None, Some(&(_, out))) => out,
+            (Some((x0, y0)), Some(&(x1, y1))) => {
+                let t = (value - x0) as f64 / (x1 - x0) as f64;
+                (y0 as f64 + t * (y1 as f64 - y0 as f64)).round() as u8
+            }
+        }
+    }
+}
+
+impl ActionTransform for VelocityCurve {
+    fn apply(&self, actions: Vec<MidiAction>) -> Vec<MidiAction> {
+        if *self == Self::Linear {
+            return actions;
+        }
+
+        actions
+            .into_iter()
+            .map(|action| match action {
+                MidiAction::ChangeVolume(volume) => {
+                    MidiAction::ChangeVolume(self.map(volume).into())
+                }
+                other => other,
+            })
+            .collect()
+    }
+}
+
+/// Rampas de volume no começo e no fim da música.
+///
+/// O volume de cada nota (ou pausa) é multiplicado por um ganho que sobe até 1
+/// nos primeiros `fade_in` tempos e desce nos últimos `fade_out`, contando os
+/// tempos pela duração das notas (`L:`). As mudanças de volume do próprio texto continuam valendo,
+/// só que com o ganho aplicado.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Fade {
+    /// Tempos (semínimas) da entrada. 0 desliga.
+    pub fade_in: u32,
+    /// Tempos (semínimas) da saída. 0 desliga.
+    pub fade_out: u32,
+}
+
+impl Fade {
+    /// O ganho de uma nota que vai do tempo `beat` até `end`, em uma música com `total` tempos.
+    ///
+    /// Os tempos podem ser fracionários: com `L:1/16`, cada nota anda um quarto de tempo.
+    fn gain(self, beat: f64, end: f64, total: f64) -> f64 {
+        let fade_in = self.fade_in as f64;
+        let fade_out = self.fade_out as f64;
+        let mut gain: f64 = 1.0;
+        if beat < fade_in {
+            gain = gain.min(end.min(fade_in) / fade_in);
+        }
+        if total - beat <= fade_out {
+            gain = gain.min((total - beat) / fade_out);
+        }
+
+        gain
+    }
+}
+
+impl ActionTransform for Fade {
+    fn apply(&self, actions: Vec<MidiAction>) -> Vec<MidiAction> {
+        if *self == Self::default() {
+            return actions;
+        }
+
+        let tpqn = MidiAction::D_TPQN.as_int() as f64;
+        let durations: Vec<u32> = MidiAction::durations(&actions).collect();
+        let total = durations.iter().map(|&ticks| ticks as u64).sum::<u64>() as f64 / tpqn;
+        let mut volume = None;
+        let mut sent = None;
+        let mut tick = 0;
+        let mut faded = Vec::with_capacity(actions.len());
+        for (action, duration) in actions.into_iter().zip(durations) {
+            match action {
+                MidiAction::ChangeVolume(written) => volume = Some(written),
+                action if action.ticks() > 0 => {
+                    // Sem volume no texto, vale o padrão do sintetizador
+                    let written = volume.unwrap_or(100);
+                    let beat = tick as f64 / tpqn;
+                    let end = (tick + duration as u64) as f64 / tpqn;
+                    let gain = self.gain(beat, end, total);
+                    let scaled = (written as f64 * gain).round() as u16;
+                    if sent != Some(scaled) {
+                        faded.push(MidiAction::ChangeVolume(scaled));
+                        sent = Some(scaled);
+                    }
+                    faded.push(action);
+                }
+                other => faded.push(other),
+            }
+            tick += duration as u64;
+        }
+
+        faded
+    }
+}
+
+/// Acentua as notas que caem nos tempos do padrão, como o `@accent=3` do cabeçalho.
+///
+/// A contagem recomeça em cada compasso, então o primeiro tempo é sempre acentuado:
+/// com `every = 3` em 4/4, são o primeiro e o quarto tempos. Notas fora do início
+/// de um tempo não são acentuadas.
+#[derive(Clone, Copy)]
+pub struct Accents {
+    /// De quantos em quantos tempos há um acento.
+    pub every: u8,
+    /// O compasso da música.
+    pub time_signature: TimeSignature,
+}
+
+impl Accents {
+    /// A velocidade das notas acentuadas.
+    pub const VELOCITY: u8 = MidiValue::MAX.as_int();
+
+    /// Acentos de `every` em `every` tempos, no compasso padrão das músicas geradas.
+    pub fn new(every: u8) -> Self {
+        Self {
+            every: every.max(1),
+            time_signature: TimeSignature::COMMON_TIME,
+        }
+    }
+
+    /// Se o tick cai em um tempo acentuado.
+    fn is_hit(self, tick: u64) -> bool {
+        let signature = self.time_signature;
+        let units = signature.units_per_beat();
+        let beat_ticks =
+            MidiAction::D_TPQN.as_int() as u64 * 4 * units / signature.denominator as u64;
+        let beats_per_bar = (signature.numerator as u64 / units).max(1);
+
+        tick.is_multiple_of(beat_ticks)
+            && (tick / beat_ticks % beats_per_bar).is_multiple_of(self.every as u64)
+    }
+}
+
+impl ActionTransform for Accents {
+    fn apply(&self, actions: Vec<MidiAction>) -> Vec<MidiAction> {
+        let timed = TimedAction::from_actions(&actions);
+        let mut accented = Vec::with_capacity(actions.len());
+        for (action, timed) in actions.into_iter().zip(timed) {
+            if matches!(action, MidiAction::PlayNote(_)) && self.is_hit(timed.tick) {
+                accented.push(MidiAction::Accent(Self::VELOCITY));
+            }
+            accented.push(action);
+        }
+
+        accented
+    }
+}
+
+/// Mensagens no começo da música que deixam módulos de hardware em um estado conhecido.
+///
+/// Um módulo pode ter ficado com outro modo, banco ou controles de uma música anterior;
+/// o GM System On volta tudo ao padrão do General MIDI.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModuleReset {
+    /// Se o GM System On é enviado.
+    pub gm_system_on: bool,
+    /// O banco escolhido antes do primeiro instrumento, se houver.
+    pub bank: Option<u16>,
+}
+
+impl ModuleReset {
+    /// A mensagem GM System On, para todos os aparelhos (`7F`).
+    pub const GM_SYSTEM_ON: [u8; 6] = [0xF0, 0x7E, 0x7F, 0x09, 0x01, 0xF7];
+}
+
+impl ActionTransform for ModuleReset {
+    fn apply(&self, actions: Vec<MidiAction>) -> Vec<MidiAction> {
+        let mut reset = Vec::with_capacity(actions.len() + 2);
+        if self.gm_system_on {
+            reset.push(MidiAction::SysEx(Self::GM_SYSTEM_ON.to_vec()));
+        }
+        reset.extend(self.bank.map(MidiAction::BankSelect));
+        // O reset vem antes de tudo, inclusive da primeira troca de instrumento
+        reset.extend(actions);
+
+        reset
+    }
+}
+
+/// Dobra as notas fora de uma faixa para dentro dela, de oitava em oitava, para
+/// teclados pequenos e sintetizadores de brinquedo.
+///
+/// Com uma faixa menor que uma oitava, nem toda nota cabe mantendo o nome; essas
+/// ficam na ponta mais próxima.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NoteRange {
+    /// A nota mais grave aceita.
+    pub low: u8,
+    /// A nota mais aguda aceita.
+    pub high: u8,
+}
+
+impl Default for NoteRange {
+    /// Todas as notas do MIDI, sem dobrar nenhuma.
+    fn default() -> Self {
+        Self {
+            low: 0,
+            high: MidiValue::MAX.as_int(),
+        }
+    }
+}
+
+impl NoteRange {
+    /// A nota dobrada para dentro da faixa.
+    pub fn fold(&self, key: u8) -> u8 {
+        let (low, high) = (self.low.min(self.high), self.high.max(self.low));
+        if (low..=high).contains(&key) {
+            return key;
+        }
+
+        let octave = Note::SEMITONES as i16;
+        let (key, low, high) = (key as i16, low as i16, high as i16);
+        // A mesma nota logo acima da ponta de baixo, e logo abaixo da de cima
+        let up = low + (key - low).rem_euclid(octave);
+        let down = high - (high - key).rem_euclid(octave);
+        let folded = if up > high {
+            // Não cabe: fica na ponta mais perto de uma das duas oitavas
+            if up - high < low - (up - octave) {
+                high
+            } else {
+                low
+            }
+        } else if key < low {
+            up
+        } else {
+            down
+        };
+
+        folded as u8
+    }
+
+    /// Dobra as notas das ações, contando quantas estavam fora da faixa.
+    pub fn fold_counting(&self, actions: Vec<MidiAction>) -> (Vec<MidiAction>, usize) {
+        let mut folded = 0;
+        let actions = actions
+            .into_iter()
+            .map(|action| match action {
+                MidiAction::PlayNote(key) if self.fold(key) != key => {
+                    folded += 1;
+                    MidiAction::PlayNote(self.fold(key))
+                }
+                other => other,
+            })
+            .collect();
+
+        (actions, folded)
+    }
+}
+
+impl ActionTransform for NoteRange {
+    fn apply(&self, actions: Vec<MidiAction>) -> Vec<MidiAction> {
+        self.fold_counting(actions).0
+    }
+}
+
+/// Uma escala, pelos semitons de cada grau a partir da tônica.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scale {
+    Major,
+    NaturalMinor,
+    HarmonicMinor,
+    MajorPentatonic,
+    MinorPentatonic,
+    Blues,
+}
+
+impl Scale {
+    pub const ALL: [Self; 6] = [
+        Self::Major,
+        Self::NaturalMinor,
+        Self::HarmonicMinor,
+        Self::MajorPentatonic,
+        Self::MinorPentatonic,
+        Self::Blues,
+    ];
+
+    /// Os semitons dos graus da escala, a partir da tônica, em ordem.
+    pub const fn semitones(self) -> &'static [u8] {
+        match self {
+            Self::Major => &[0, 2, 4, 5, 7, 9, 11],
+            Self::NaturalMinor => &[0, 2, 3, 5, 7, 8, 10],
+            Self::HarmonicMinor => &[0, 2, 3, 5, 7, 8, 11],
+            Self::MajorPentatonic => &[0, 2, 4, 7, 9],
+            Self::MinorPentatonic => &[0, 3, 5, 7, 10],
+            Self::Blues => &[0, 3, 5, 6, 7, 10],
+        }
+    }
+}
+
+/// Leva cada nota para a nota mais próxima de uma escala, para que textos cheios de
+/// `?` soem consonantes.
+///
+/// Uma nota a meio caminho de duas da escala desce para a de baixo.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScaleQuantizer {
+    /// A escala das notas. `None` desliga.
+    pub scale: Option<Scale>,
+    /// A tônica da escala. A pausa vale como dó.
+    pub tonic: Note,
+}
+
+impl ScaleQuantizer {
+    /// A nota da escala mais próxima da dada.
+    pub fn snap(&self, key: u8) -> u8 {
+        let Some(scale) = self.scale else {
+            return key;
+        };
+        let octave = Note::SEMITONES as i16;
+        let tonic = self.tonic.semitone().unwrap_or(0) as i16;
+        let degree = (key as i16 - tonic).rem_euclid(octave);
+        // A distância até cada grau, nas duas direções, preferindo descer no empate
+        let offset = scale
+            .semitones()
+            .iter()
+            .flat_map(|&semitone| {
+                let up = (semitone as i16 - degree).rem_euclid(octave);
+                [up, up - octave]
+            })
+            .min_by_key(|&offset| (offset.abs(), offset > 0))
+            .unwrap_or(0);
+
+        let snapped = key as i16 + offset;
+        // Nas pontas do MIDI, a nota da escala do outro lado
+        if snapped > MidiValue::MAX.as_int() as i16 {
+            (snapped - octave) as u8
+        } else if snapped < 0 {
+            (snapped + octave) as u8
+        } else {
+            snapped as u8
+        }
+    }
+}
+
+impl ActionTransform for ScaleQuantizer {
+    fn apply(&self, actions: Vec<MidiAction>) -> Vec<MidiAction> {
+        if self.scale.is_none() {
+            return actions;
+        }
+
+        actions
+            .into_iter()
+            .map(|action| match action {
+                MidiAction::PlayNote(key) => MidiAction::PlayNote(self.snap(key)),
+                other => other,
+            })
+            .collect()
+    }
+}
+
+/// Eco feito só com notas: cada nota se repete `repeats` vezes, a cada `delay` ticks,
+/// cada vez mais fraca.
+///
+/// Como as repetições se sobrepõem às notas seguintes, o resultado são ações com
+/// ticks absolutos, para o `TimedAction::as_track`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Echo {
+    /// Quantas repetições cada nota ganha. 0 desliga.
+    pub repeats: u8,
+    /// Ticks entre uma repetição e a próxima.
+    pub delay: u32,
+}
+
+impl Default for Echo {
+    /// Desligado, com repetições a cada colcheia quando for ligado.
+    fn default() -> Self {
+        Self {
+            repeats: 0,
+            delay: MidiAction::D_TPQN.as_int() as u32 / 2,
+        }
+    }
+}
+
+impl Echo {
+    /// Posiciona as ações no tempo, acrescentando as repetições logo depois de cada nota.
+    ///
+    /// A velocidade cai linearmente: com 3 repetições, elas saem com 3/4, 2/4 e 1/4
+    /// da velocidade da nota original, acentuada ou não. As repetições ficam com o trecho
+    /// do texto da nota.
+    pub fn apply(&self, timed: Vec<TimedAction>) -> Vec<TimedAction> {
+        if self.repeats == 0 || self.delay == 0 {
+            return timed;
+        }
+
+        let map = TempoMap::from_timed(&timed);
+        let steps = self.repeats as u32 + 1;
+        let mut echoed = Vec::with_capacity(timed.len());
+        let mut accent = None;
+        for timed in timed {
+            let MidiAction::PlayNote(key) = timed.action else {
+                if let MidiAction::Accent(velocity) = timed.action {
+                    accent = Some((timed.tick, velocity));
+                }
+                echoed.push(timed);
+                continue;
+            };
+
+            let velocity = match accent.take() {
+                Some((tick, velocity)) if tick == timed.tick => velocity,
+                _ => MidiAction::D_VELOCITY.as_int(),
+            } as u32;
+            let (tick, channel) = (timed.tick, timed.channel);
+            let source_range = timed.source_range.clone();
+            echoed.push(timed);
+            for repeat in 1..steps {
+                let tick = tick + (repeat * self.delay) as u64;
+                let time = map.time_at(tick);
+                let velocity = (velocity * (steps - repeat) / steps) as u8;
+                echoed.extend(
+                    [MidiAction::Accent(velocity), MidiAction::PlayNote(key)].map(|action| {
+                        TimedAction {
+                            tick,
+                            time,
+                            channel,
+                            action,
+                            source_range: source_range.clone(),
+                        }
+                    }),
+                );
+            }
+        }
+
+        echoed
+    }
+}
+
+/// O sentido em que as cordas são tocadas.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StrumDirection {
+    /// Da nota mais grave para a mais aguda, como a palheta descendo no violão.
+    #[default]
+    Down,
+    /// Da mais aguda para a mais grave.
+    Up,
+    /// Um acorde para baixo e o seguinte para cima, alternando.
+    Alternate,
+}
+
+/// Toca as notas dos acordes das cifras uma depois da outra, como em um violão,
+/// com a velocidade indo de `first_velocity` na primeira a `last_velocity` na última.
+///
+/// Como o eco, posiciona as notas no tempo, para o `TimedAction::as_track`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Strum {
+    /// O sentido das notas.
+    pub direction: StrumDirection,
+    /// Ticks entre uma nota e a seguinte. 0 desliga.
+    pub spread: u32,
+    /// A velocidade da primeira nota.
+    pub first_velocity: u8,
+    /// A velocidade da última nota.
+    pub last_velocity: u8,
+}
+
+impl Default for Strum {
+    /// Desligado.
+    fn default() -> Self {
+        Self::PRESETS[0]
+    }
+}
+
+impl Strum {
+    /// Batidas prontas: desligada, violão para baixo, para cima, alternando e harpa.
+    pub const PRESETS: [Self; 5] = [
+        Self::preset(StrumDirection::Down, 0, 64, 64),
+        Self::preset(StrumDirection::Down, 15, 96, 72),
+        Self::preset(StrumDirection::Up, 12, 72, 88),
+        Self::preset(StrumDirection::Alternate, 15, 90, 70),
+        Self::preset(StrumDirection::Down, 80, 60, 90),
+    ];
+
+    const fn preset(direction: StrumDirection, spread: u32, first: u8, last: u8) -> Self {
+        Self {
+            direction,
+            spread,
+            first_velocity: first,
+            last_velocity: last,
+        }
+    }
+
+    /// A velocidade da nota `index` de `count`.
+    fn velocity(&self, index: usize, count: usize) -> u8 {
+        if count < 2 {
+            return self.first_velocity;
+        }
+        let (first, last) = (self.first_velocity as i32, self.last_velocity as i32);
+        let velocity = first + (last - first) * index as i32 / (count - 1) as i32;
+
+        MidiValue::clamped(velocity.max(0) as u16).as_int()
+    }
+
+    /// Troca cada acorde por um acorde vazio, que solta o anterior, e pelas suas notas
+    /// uma a uma, a `spread` ticks umas das outras.
+    pub fn apply(&self, timed: Vec<TimedAction>) -> Vec<TimedAction> {
+        if self.spread == 0 {
+            return timed;
+        }
+
+        let map = TempoMap::from_timed(&timed);
+        let mut strummed = Vec::with_capacity(timed.len());
+        let mut upward = true;
+        for timed in timed {
+            let MidiAction::Chord(keys) = &timed.action else {
+                strummed.push(timed);
+                continue;
+            };
+            if keys.is_empty() {
+                strummed.push(timed);
+                continue;
+            }
+
+            // Alternando, o primeiro acorde desce
+            upward = match self.direction {
+                StrumDirection::Down => false,
+                StrumDirection::Up => true,
+                StrumDirection::Alternate => !upward,
+            };
+            let mut keys = keys.clone();
+            keys.sort_unstable();
+            if upward {
+                keys.reverse();
+            }
+
+            let count = keys.len();
+            let notes = keys.into_iter().enumerate().map(|(index, key)| {
+                let tick = timed.tick + index as u64 * self.spread as u64;
+                TimedAction {
+                    tick,
+                    time: map.time_at(tick),
+                    channel: timed.channel,
+                    action: MidiAction::ChordNote {
+                        key,
+                        velocity: self.velocity(index, count),
+                    },
+                    source_range: timed.source_range.clone(),
+                }
+            });
+            let release = TimedAction {
+                action: MidiAction::Chord(Vec::new()),
+                ..timed.clone()
+            };
+            strummed.push(release);
+            strummed.extend(notes);
+        }
+        // As notas atrasadas ficam depois das ações dos ticks em que caem
+        strummed.sort_by_key(|timed| timed.tick);
+
+        strummed
+    }
+}
+
+/// O intervalo da voz acrescentada pelo `Harmonizer`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HarmonyInterval {
+    Third,
+    Fifth,
+    Octave,
+}
+
+impl HarmonyInterval {
+    pub const ALL: [Self; 3] = [Self::Third, Self::Fifth, Self::Octave];
+
+    /// Quantos graus da escala o intervalo anda.
+    const fn steps(self) -> i16 {
+        match self {
+            Self::Third => 2,
+            Self::Fifth => 4,
+            Self::Octave => 7,
+        }
+    }
+
+    /// O intervalo usado fora de uma tonalidade.
+    const fn chromatic(self) -> Interval {
+        match self {
+            Self::Third => Interval::MajorThird,
+            Self::Fifth => Interval::PerfectFifth,
+            Self::Octave => Interval::Octave,
+        }
+    }
+}
+
+/// Acrescenta uma segunda voz, paralela à melodia, no canal `Harmonizer::CHANNEL`.
+///
+/// Sem tonalidade, a voz anda um intervalo fixo (terça maior, quinta justa ou oitava).
+/// Com uma tonalidade maior, as notas da escala ganham a terça ou quinta da própria
+/// escala, então dó vira mi mas ré vira fá; as notas fora dela seguem o intervalo fixo.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Harmonizer {
+    /// O intervalo da segunda voz. `None` desliga.
+    pub interval: Option<HarmonyInterval>,
+    /// Se a voz fica abaixo da melodia, em vez de acima.
+    pub below: bool,
+    /// A tônica da tonalidade maior, se houver uma.
+    pub key: Option<Note>,
+}
+
+impl Harmonizer {
+    /// O canal da segunda voz, que recebe também as trocas de instrumento e volume.
+    pub const CHANNEL: u8 = 1;
+
+    /// Os semitons da escala maior, a partir da tônica.
+    const MAJOR_SCALE: [i16; 7] = [0, 2, 4, 5, 7, 9, 11];
+
+    /// A nota da segunda voz, se ela couber no MIDI.
+    pub fn harmony(&self, key: u8) -> Option<u8> {
+        let interval = self.interval?;
+        let steps = if self.below {
+            -interval.steps()
+        } else {
+            interval.steps()
+        };
+
+        let diatonic = self.key.and_then(|tonic| {
+            let pitch_class = (key + Note::SEMITONES - tonic.semitone()?) % Note::SEMITONES;
+            let degree = Self::MAJOR_SCALE
+                .iter()
+                .position(|&semitone| semitone == pitch_class as i16)?;
+            let target = degree as i16 + steps;
+            Some(
+                Note::SEMITONES as i16 * target.div_euclid(7)
+                    + Self::MAJOR_SCALE[target.rem_euclid(7) as usize]
+                    - Self::MAJOR_SCALE[degree],
+            )
+        });
+        let semitones = diatonic.unwrap_or_else(|| {
+            let semitones = interval.chromatic().semitones() as i16;
+            if self.below {
+                -semitones
+            } else {
+                semitones
+            }
+        });
+
+        u8::try_from(key as i16 + semitones)
+            .ok()
+            .filter(|&key| key <= MidiValue::MAX.as_int())
+    }
+
+    /// Acrescenta a segunda voz às ações do canal 0.
+    pub fn apply(&self, actions: Vec<TimedAction>) -> Vec<TimedAction> {
+        if self.interval.is_none() {
+            return actions;
+        }
+
+        let mut harmonized = Vec::with_capacity(actions.len() * 2);
+        let mut accent = None;
+        for timed in actions {
+            if timed.channel != 0 {
+                harmonized.push(timed);
+                continue;
+            }
+
+            let voice: Vec<MidiAction> = match timed.action {
+                MidiAction::ChangeInstrument(_) | MidiAction::ChangeVolume(_) => {
+                    vec![timed.action.clone()]
+                }
+                MidiAction::Accent(velocity) => {
+                    accent = Some((timed.tick, velocity));
+                    Vec::new()
+                }
+                MidiAction::PlayNote(key) => {
+                    let accent = accent.take().filter(|&(tick, _)| tick == timed.tick);
+                    self.harmony(key)
+                        .map(|key| {
+                            let note = MidiAction::PlayNote(key);
+                            match accent {
+                                Some((_, velocity)) => vec![MidiAction::Accent(velocity), note],
+                                None => vec![note],
+                            }
+                        })
+                        .unwrap_or_default()
+                }
+                _ => Vec::new(),
+            };
+
+            let (tick, time) = (timed.tick, timed.time);
+            let source_range = timed.source_range.clone();
+            harmonized.push(timed);
+            harmonized.extend(voice.into_iter().map(|action| TimedAction {
+                tick,
+                time,
+                channel: Self::CHANNEL,
+                action,
+                source_range: source_range.clone(),
+            }));
+        }
+
+        harmonized
+    }
+}
+
+impl fmt::Display for VelocityCurve {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Linear => write!(f, "Linear"),
+            Self::Soft => write!(f, "Soft"),
+            Self::Hard => write!(f, "Hard"),
+            Self::Custom(_) => write!(f, "Custom"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn curves_keep_the_ends() {
+        for curve in VelocityCurve::all() {
+            assert_eq!(curve.map(0), 0, "{curve}");
+            assert_eq!(curve.map(127), 127, "{curve}");
+        }
+        assert!(VelocityCurve::Soft.map(50) > 50);
+        assert!(VelocityCurve::Hard.map(50) < 50);
+        assert_eq!(VelocityCurve::Linear.map(300), 127);
+    }
+
+    #[test]
+    fn custom_interpolates() {
+        let curve = VelocityCurve::Custom(vec![(100, 127), (20, 40)]);
+
+        assert_eq!(curve.map(0), 40);
+        assert_eq!(curve.map(20), 40);
+        assert_eq!(curve.map(60), 84);
+        assert_eq!(curve.map(110), 127);
+        assert_eq!(VelocityCurve::Custom(Vec::new()).map(60), 60);
+    }
+
+    #[test]
+    fn fade_ramps_the_written_volume() {
+        let actions = vec![
+            MidiAction::ChangeVolume(80),
+            MidiAction::PlayNote(60),
+            MidiAction::PlayNote(62),
+            MidiAction::ChangeVolume(40),
+            MidiAction::PlayNote(64),
+            MidiAction::Pause,
+            MidiAction::PlayNote(65),
+        ];
+
+        let faded = Fade {
+            fade_in: 2,
+            fade_out: 2,
+        }
+        .apply(actions);
+
+        let volumes: Vec<u16> = faded
+            .iter()
+            .filter_map(|action| match action {
+                &MidiAction::ChangeVolume(volume) => Some(volume),
+                _ => None,
+            })
+            .collect();
+        // Beats: 80 at 1/2, 80 at 1, 40 at 1, then the last two beats at 1 and 1/2
+        assert_eq!(volumes, [40, 80, 40, 20]);
+        assert_eq!(faded.len(), 5 + volumes.len());
+    }
+
+    #[test]
+    fn fade_counts_beats_of_the_note_length() {
+        let sixteenth = MidiAction::D_TPQN.as_int() as u32 / 4;
+        let mut actions = vec![
+            MidiAction::ChangeVolume(80),
+            MidiAction::NoteLength(sixteenth),
+        ];
+        actions.extend(vec![MidiAction::PlayNote(60); 32]);
+
+        let faded = Fade {
+            fade_in: 2,
+            fade_out: 0,
+        }
+        .apply(actions);
+
+        let volumes: Vec<u16> = faded
+            .iter()
+            .filter_map(|action| match action {
+                &MidiAction::ChangeVolume(volume) => Some(volume),
+                _ => None,
+            })
+            .collect();
+        // Two beats are eight sixteenths, each one an eighth louder
+        assert_eq!(volumes, [10, 20, 30, 40, 50, 60, 70, 80]);
+    }
+
+    #[test]
+    fn accents_restart_every_bar() {
+        let actions = vec![MidiAction::PlayNote(60); 8];
+
+        let accented = Accents::new(3).apply(actions);
+
+        let hits: Vec<bool> = accented
+            .windows(2)
+            .filter(|pair| matches!(pair[1], MidiAction::PlayNote(_)))
+            .map(|pair| matches!(pair[0], MidiAction::Accent(_)))
+            .collect();
+        // Beats 1 and 4 of each 4/4 bar
+        assert_eq!(hits, [true, false, false, true, true, false, false, true]);
+    }
+
+    #[test]
+    fn sources_survive_the_transforms() {
+        let actions = vec![
+            MidiAction::ChangeVolume(50),
+            MidiAction::PlayNote(60),
+            MidiAction::Pause,
+            MidiAction::PlayNote(62),
+        ];
+        let sources = [0..4, 4..5, 5..6, 6..7];
+
+        let transformed = Accents::new(1).apply(VelocityCurve::Hard.apply(actions.clone()));
+        let carried = carry_sources(&actions, &sources, &transformed);
+
+        // The changed volume and the accents point to the notes they affect
+        assert_eq!(carried, [4..5, 4..5, 4..5, 5..6, 6..7, 6..7]);
+
+        let echoed = Echo {
+            repeats: 2,
+            delay: 100,
+        }
+        .apply(TimedAction::from_sourced_actions(&transformed, &carried));
+        let sources: Vec<_> = echoed
+            .iter()
+            .filter(|timed| timed.action == MidiAction::PlayNote(62))
+            .map(|timed| timed.source_range.clone())
+            .collect();
+        assert_eq!(sources, [6..7, 6..7, 6..7]);
+    }
+
+    #[test]
+    fn notes_fold_by_octaves() {
+        let range = NoteRange { low: 48, high: 72 };
+        let actions = vec![
+            MidiAction::PlayNote(36),
+            MidiAction::PlayNote(60),
+            MidiAction::Pause,
+            MidiAction::PlayNote(86),
+        ];
+
+        let (folded, count) = range.fold_counting(actions);
+
+        assert_eq!(
+            folded,
+            [
+                MidiAction::PlayNote(48),
+                MidiAction::PlayNote(60),
+                MidiAction::Pause,
+                MidiAction::PlayNote(62),
+            ]
+        );
+        assert_eq!(count, 2);
+        // A range narrower than an octave clamps what cannot keep its name
+        assert_eq!(NoteRange { low: 60, high: 64 }.fold(55), 64);
+        assert_eq!(NoteRange::default().fold(127), 127);
+    }
+
+    #[test]
+    fn notes_snap_to_the_scale() {
+        let minor = ScaleQuantizer {
+            scale: Some(Scale::NaturalMinor),
+            tonic: Note::La,
+        };
+
+        // A minor: C# goes down to C, A# to A, and B stays
+        assert_eq!(minor.snap(61), 60);
+        assert_eq!(minor.snap(70), 69);
+        assert_eq!(minor.snap(71), 71);
+        // Between E and G in the minor pentatonic, F# is closer to G
+        let pentatonic = ScaleQuantizer {
+            scale: Some(Scale::MinorPentatonic),
+            tonic: Note::La,
+        };
+        assert_eq!(pentatonic.snap(66), 67);
+        assert_eq!(pentatonic.snap(126), 127);
+        assert_eq!(ScaleQuantizer::default().snap(61), 61);
+    }
+
+    #[test]
+    fn strums_spread_the_chord_notes() {
+        let actions = [
+            MidiAction::Chord(vec![48, 52, 55]),
+            MidiAction::PlayNote(60),
+            MidiAction::Chord(vec![50, 53]),
+            MidiAction::PlayNote(62),
+        ];
+        let strum = Strum {
+            direction: StrumDirection::Alternate,
+            spread: 10,
+            first_velocity: 90,
+            last_velocity: 70,
+        };
+
+        let strummed = strum.apply(TimedAction::from_actions(&actions));
+
+        let notes: Vec<(u64, u8, u8)> = strummed
+            .iter()
+            .filter_map(|timed| match timed.action {
+                MidiAction::ChordNote { key, velocity } => Some((timed.tick, key, velocity)),
+                _ => None,
+            })
+            .collect();
+        // The first chord goes down from the lowest note, the second comes back up
+        assert_eq!(
+            notes,
+            [
+                (0, 48, 90),
+                (10, 52, 80),
+                (20, 55, 70),
+                (480, 53, 90),
+                (490, 50, 70)
+            ]
+        );
+        assert_eq!(strummed[0].action, MidiAction::Chord(Vec::new()));
+        assert_eq!(
+            Strum::default()
+                .apply(TimedAction::from_actions(&actions))
+                .len(),
+            4
+        );
+    }
+
+    #[test]
+    fn echo_repeats_fade_out() {
+        let actions = [MidiAction::Accent(120), MidiAction::PlayNote(60)];
+
+        let echoed = Echo {
+            repeats: 3,
+            delay: 100,
+        }
+        .apply(TimedAction::from_actions(&actions));
+
+        let repeats: Vec<(u64, u8)> = echoed
+            .windows(2)
+            .filter_map(|pair| match (&pair[0].action, &pair[1].action) {
+                (&MidiAction::Accent(velocity), MidiAction::PlayNote(60)) => {
+                    Some((pair[1].tick, velocity))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(repeats, [(0, 120), (100, 90), (200, 60), (300, 30)]);
+        assert_eq!(
+            Echo::default().apply(TimedAction::from_actions(&actions)),
+            TimedAction::from_actions(&actions)
+        );
+    }
+
+    #[test]
+    fn harmony_follows_the_key() {
+        let mut harmonizer = Harmonizer {
+            interval: Some(HarmonyInterval::Third),
+            below: false,
+            key: None,
+        };
+        // C, D and C# (outside C major)
+        assert_eq!(
+            [60, 62, 61].map(|key| harmonizer.harmony(key)),
+            [Some(64), Some(66), Some(65)]
+        );
+
+        harmonizer.key = Some(Note::Do);
+        assert_eq!(
+            [60, 62, 61].map(|key| harmonizer.harmony(key)),
+            [Some(64), Some(65), Some(65)]
+        );
+
+        harmonizer.below = true;
+        harmonizer.interval = Some(HarmonyInterval::Fifth);
+        assert_eq!(
+            [60, 65].map(|key| harmonizer.harmony(key)),
+            [Some(53), Some(59)]
+        );
+        assert_eq!(harmonizer.harmony(3), None);
+    }
+
+    #[test]
+    fn harmony_goes_to_its_own_channel() {
+        let actions = TimedAction::from_actions(&[
+            MidiAction::ChangeInstrument(40),
+            MidiAction::Accent(100),
+            MidiAction::PlayNote(60),
+        ]);
+
+        let harmonized = Harmonizer {
+            interval: Some(HarmonyInterval::Octave),
+            ..Default::default()
+        }
+        .apply(actions);
+
+        let voice: Vec<&MidiAction> = harmonized
+            .iter()
+            .filter(|timed| timed.channel == Harmonizer::CHANNEL)
+            .map(|timed| &timed.action)
+            .collect();
+        assert_eq!(
+            voice,
+            [
+                &MidiAction::ChangeInstrument(40),
+                &MidiAction::Accent(100),
+                &MidiAction::PlayNote(72)
+            ]
+        );
+        assert_eq!(harmonized.len(), 6);
+    }
+
+    #[test]
+    fn module_reset_comes_first() {
+        let reset = ModuleReset {
+            gm_system_on: true,
+            bank: Some(129),
+        };
+
+        let actions = reset.apply(vec![MidiAction::ChangeInstrument(5)]);
+        let smf = MidiAction::as_track(&actions);
+
+        let kinds: Vec<_> = smf.tracks[0][4..8].iter().map(|event| event.kind).collect();
+        assert_eq!(
+            kinds[0],
+            midly::TrackEventKind::SysEx(&[0x7E, 0x7F, 0x09, 0x01, 0xF7])
+        );
+        let controller = |controller: u8, value: u8| midly::TrackEventKind::Midi {
+            channel: 0.into(),
+            message: midly::MidiMessage::Controller {
+                controller: controller.into(),
+                value: value.into(),
+            },
+        };
+        assert_eq!(kinds[1..3], [controller(0, 1), controller(32, 1)]);
+        assert!(matches!(
+            kinds[3],
+            midly::TrackEventKind::Midi {
+                message: midly::MidiMessage::ProgramChange { .. },
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn only_volumes_change() {
+        let actions = vec![MidiAction::ChangeVolume(50), MidiAction::PlayNote(50)];
+
+        let curved = VelocityCurve::Hard.apply(actions);
+
+        assert_eq!(
+            curved,
+            [MidiAction::ChangeVolume(20), MidiAction::PlayNote(50)]
+        );
+    }
+}
@@ -0,0 +1,160 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{midi_action::MidiAction, note::Note, transform::ActionTransform};
+
+/// Pitch bend sem desvio (centro da faixa de 14 bits).
+pub const BEND_CENTER: u16 = 8192;
+
+/// Faixa do pitch bend em cents, para cada lado. O padrão do General MIDI é de 2 semitons.
+const BEND_RANGE_CENTS: f64 = 200.0;
+
+/// Afinações disponíveis.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Tuning {
+    /// Temperamento igual de 12 notas, a afinação padrão do MIDI.
+    #[default]
+    TwelveTet,
+    /// Temperamento igual de 19 notas: cada nota é levada ao passo mais próximo.
+    NineteenTet,
+    /// Entonação justa a partir de dó, com razões de números inteiros pequenos.
+    JustIntonation,
+}
+
+impl Tuning {
+    /// Todas as afinações, para seleção na interface.
+    pub const ALL: [Self; 3] = [Self::TwelveTet, Self::NineteenTet, Self::JustIntonation];
+
+    /// O desvio, em cents, de cada uma das 12 notas da oitava em relação ao 12-TET.
+    pub fn table(self) -> [f64; Note::SEMITONES as usize] {
+        let mut table = [0.0; Note::SEMITONES as usize];
+
+        for (semitone, cents) in table.iter_mut().enumerate() {
+            let equal = semitone as f64 * 100.0;
+            *cents = match self {
+                Self::TwelveTet => 0.0,
+                Self::NineteenTet => {
+                    let step = (semitone as f64 * 19.0 / 12.0).round();
+                    step * 1200.0 / 19.0 - equal
+                }
+                Self::JustIntonation => {
+                    const RATIOS: [f64; 12] = [
+                        1.0,
+                        16.0 / 15.0,
+                        9.0 / 8.0,
+                        6.0 / 5.0,
+                        5.0 / 4.0,
+                        4.0 / 3.0,
+                        45.0 / 32.0,
+                        3.0 / 2.0,
+                        8.0 / 5.0,
+                        5.0 / 3.0,
+                        9.0 / 5.0,
+                        15.0 / 8.0,
+                    ];
+                    1200.0 * RATIOS[semitone].log2() - equal
+                }
+            };
+        }
+
+        table
+    }
+
+    /// O valor de pitch bend (14 bits) que afina a nota do MIDI dada.
+    pub fn pitch_bend(self, key: u8) -> u16 {
+        let cents = self.table()[(key % Note::SEMITONES) as usize];
+        let offset = (cents / BEND_RANGE_CENTS * BEND_CENTER as f64).round() as i32;
+
+        (BEND_CENTER as i32 + offset).clamp(0, 0x3FFF) as u16
+    }
+}
+
+impl ActionTransform for Tuning {
+    /// Insere um pitch bend antes de cada nota cuja afinação difere da anterior.
+    ///
+    /// Como a melodia é monofônica em um único canal, o bend de uma nota
+    /// não afeta nenhuma outra.
+    fn apply(&self, actions: Vec<MidiAction>) -> Vec<MidiAction> {
+        if *self == Self::TwelveTet {
+            return actions;
+        }
+
+        let mut current = BEND_CENTER;
+        let mut ret = Vec::with_capacity(actions.len());
+        for action in actions {
+            if let MidiAction::PlayNote(key) = action {
+                let bend = self.pitch_bend(key);
+                if bend != current {
+                    ret.push(MidiAction::PitchBend(bend));
+                    current = bend;
+                }
+            }
+            ret.push(action);
+        }
+
+        ret
+    }
+}
+
+impl fmt::Display for Tuning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TwelveTet => write!(f, "12-TET"),
+            Self::NineteenTet => write!(f, "19-TET"),
+            Self::JustIntonation => write!(f, "Just intonation"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn twelve_tet_is_centered() {
+        for key in 0..128 {
+            assert_eq!(Tuning::TwelveTet.pitch_bend(key), BEND_CENTER);
+        }
+    }
+
+    #[test]
+    fn just_major_third_is_flat() {
+        // 5/4 is about 13.7 cents below the equal tempered third
+        let cents = Tuning::JustIntonation.table()[4];
+        assert!((cents + 13.69).abs() < 0.01);
+        assert_eq!(Tuning::JustIntonation.pitch_bend(64), 8192 - 561);
+        // The fifth is 2 cents sharp
+        assert!(Tuning::JustIntonation.pitch_bend(67) > BEND_CENTER);
+    }
+
+    #[test]
+    fn nineteen_tet_keeps_octaves() {
+        let table = Tuning::NineteenTet.table();
+        assert_eq!(table[0], 0.0);
+        // The 19-TET fifth (11 steps) is about 5.3 cents flatter than the equal tempered one
+        assert!((table[7] + 5.26).abs() < 0.01);
+    }
+
+    #[test]
+    fn bends_only_when_needed() {
+        let actions = vec![
+            MidiAction::PlayNote(60),
+            MidiAction::PlayNote(72),
+            MidiAction::PlayNote(64),
+        ];
+
+        let tuned = Tuning::JustIntonation.apply(actions);
+
+        assert_eq!(
+            tuned,
+            [
+                MidiAction::PlayNote(60),
+                MidiAction::PlayNote(72),
+                MidiAction::PitchBend(8192 - 561),
+                MidiAction::PlayNote(64),
+            ]
+        );
+    }
+}
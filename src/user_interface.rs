@@ -1,115 +1,3155 @@
+mod accessibility;
+mod grid;
+mod lanes;
+mod palette;
+mod piano_roll;
+mod search;
+mod tab;
+
 use eframe::{
-    egui::{CentralPanel, Context, TextEdit},
+    egui::{
+        text::{CCursor, CCursorRange},
+        CentralPanel, Context, TextEdit,
+    },
     App, Frame,
 };
 use egui_file::FileDialog;
-use midly::Smf;
-use std::{fs, path::Path, path::PathBuf};
+use midly::{num::u4, Smf};
+use std::{
+    error::Error,
+    fs, mem,
+    ops::Range,
+    path::Path,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+#[cfg(feature = "native")]
+use crate::preferences::Preferences;
+#[cfg(target_arch = "wasm32")]
+use crate::web_midi;
+use crate::{
+    analysis::{self, DiffOp},
+    automation,
+    capture::Capture,
+    channel_map::ChannelMap,
+    clipboard,
+    clock::SystemClock,
+    compose::{Arrangement, Part},
+    conversion::{Converted, Job, Purpose},
+    diagnostics, examples,
+    export::{
+        self,
+        clips::{self, Clip, ClipLayout},
+        summary::{Summary, TimingName},
+    },
+    external_clock,
+    groove::Groove,
+    header::Header,
+    i18n::{Key, Language},
+    import, instruments,
+    launcher::{self, Launcher, Pads},
+    logging, metronome,
+    midi_action::{MidiAction, TimedAction},
+    midi_thru::{self, ThruChannel},
+    mixer::{self, Mixer},
+    note::{NameStyle, Note, Pitch},
+    patch_map::PatchMap,
+    play::{self, LiveControls, PlaybackOptions, Player, Playhead},
+    playback_guard::{Admission, Overlap, PlaybackGuard},
+    playlist::{Playlist, Repeat},
+    preset::Preset,
+    session::{Action, Recorder, Replay, Snapshot},
+    text_to_midi::{self, BaseLength, ConfigError, ParseError, State},
+    transform::{
+        ActionTransform, Echo, Fade, Harmonizer, HarmonyInterval, ModuleReset, NoteRange, Scale,
+        ScaleQuantizer, Strum, VelocityCurve,
+    },
+    tuning::Tuning,
+    validate,
+};
+
+use accessibility::{Named, Shortcut};
+use grid::StepGrid;
+use lanes::AutomationLanes;
+use piano_roll::PianoRoll;
+use search::{highlight, Search};
+use tab::Tab;
+
+/// Um arquivo convertido esperando a confirmação para ser salvo.
+struct ExportReview {
+    /// A aba de onde ele veio.
+    tab: usize,
+    /// Onde ele vai ser salvo.
+    path: PathBuf,
+    /// O arquivo como vai ser salvo, com o mixer e o aparelho aplicados.
+    bytes: Vec<u8>,
+    /// O arquivo como saiu da conversão, para a prévia, que aplica o mixer ao tocar.
+    converted: Vec<u8>,
+    summary: Summary,
+}
+
+/// Uma reprodução pedida, que espera no `PlaybackGuard` se outra estiver tocando.
+struct QueuedPlayback {
+    /// A aba que acompanha a reprodução.
+    tab: usize,
+    bytes: Vec<u8>,
+    /// As notas do texto, com os seus ticks, para destacar a que toca.
+    notes: Vec<(u64, Range<usize>)>,
+    /// O tick onde a reprodução termina, como na prévia da exportação.
+    end: Option<u64>,
+}
+
+pub struct UserInterface {
+    /// Os textos abertos, sempre pelo menos um.
+    tabs: Vec<Tab>,
+    /// O índice da aba sendo editada.
+    active: usize,
+    /// A aba com mudanças não salvas esperando confirmação para ser fechada.
+    closing: Option<usize>,
+    open_file_dialog: Option<FileDialog>,
+    text_file_dialog: Option<FileDialog>,
+    saved_file_dialog: Option<FileDialog>,
+    events_file_dialog: Option<FileDialog>,
+    clips_file_dialog: Option<FileDialog>,
+    stems_dialog: Option<FileDialog>,
+    capture_file_dialog: Option<FileDialog>,
+    import_file_dialog: Option<FileDialog>,
+    language: Language,
+    note_names: NameStyle,
+    tuning: Tuning,
+    velocity_curve: VelocityCurve,
+    fade: Fade,
+    scale: ScaleQuantizer,
+    note_range: NoteRange,
+    /// Quantas notas a última conversão dobrou para dentro da `note_range`.
+    folded_notes: Arc<AtomicUsize>,
+    echo: Echo,
+    harmonizer: Harmonizer,
+    strum: Strum,
+    groove: Groove,
+    /// Os grooves importados, com o nome do arquivo de onde vieram.
+    grooves: Vec<(String, Groove)>,
+    groove_dialog: Option<FileDialog>,
+    module_reset: ModuleReset,
+    /// Os presets abertos ou salvos, com o nome do arquivo de onde vieram.
+    presets: Vec<(String, Preset)>,
+    preset_dialog: Option<FileDialog>,
+    preset_save_dialog: Option<FileDialog>,
+    /// O arquivo a salvar, mostrado com o seu resumo antes.
+    export_review: Option<ExportReview>,
+    latency_ms: u64,
+    playback_rate: f64,
+    send_clock: bool,
+    audio_click: bool,
+    /// Se um som curto avisa quando a conversão falha ou o texto tem problemas.
+    error_sound: bool,
+    /// Se as próximas reproduções guardam o que foi enviado.
+    capture_sent: bool,
+    /// O que a última reprodução com captura enviou.
+    capture: Option<Arc<Capture>>,
+    count_in_bars: u8,
+    /// O tempo máximo de uma reprodução, em segundos, depois do qual ela para sozinha.
+    max_playback_secs: Option<u32>,
+    follow_clock: bool,
+    /// As preferências lembradas entre execuções, como a porta de saída.
+    #[cfg(feature = "native")]
+    preferences: Preferences,
+    /// As portas de saída encontradas na última busca.
+    #[cfg(feature = "native")]
+    output_ports: Vec<String>,
+    /// O thru ligado, da entrada MIDI para a saída, se houver.
+    #[cfg(feature = "native")]
+    thru: Option<midir::MidiInputConnection<play::OutputPort>>,
+    /// O canal para onde o thru manda as mensagens.
+    thru_channel: Arc<ThruChannel>,
+    /// Para onde vai cada canal ao tocar, salvar ou copiar.
+    channel_map: ChannelMap,
+    /// As trocas de programa do aparelho, com o nome do arquivo de onde vieram.
+    patch_map: Option<(String, PatchMap)>,
+    patch_map_dialog: Option<FileDialog>,
+    mixer: Arc<Mutex<Mixer>>,
+    channels: Vec<u4>,
+    track_names: Vec<String>,
+    show_mixer: bool,
+    show_settings: bool,
+    show_compare: bool,
+    show_search: bool,
+    show_grid: bool,
+    show_piano_roll: bool,
+    show_automation: bool,
+    show_log: bool,
+    search: Search,
+    grid: StepGrid,
+    piano_roll: PianoRoll,
+    automation: AutomationLanes,
+    compare_texts: [String; 2],
+    compare_dialog: Option<(usize, FileDialog)>,
+    show_compose: bool,
+    arrangement: Arrangement,
+    compose_add_dialog: Option<FileDialog>,
+    compose_save_dialog: Option<FileDialog>,
+    show_live: bool,
+    /// Os clipes da grade do modo ao vivo.
+    live_clips: Vec<Part>,
+    live_add_dialog: Option<FileDialog>,
+    /// Os clipes tocando ao vivo, enquanto a thread da reprodução existir.
+    launcher: Option<Arc<Launcher>>,
+    /// As notas da entrada que disparam os clipes.
+    pads: Arc<Pads>,
+    /// A entrada dos pads ligada, se houver.
+    #[cfg(feature = "native")]
+    pads_input: Option<midir::MidiInputConnection<()>>,
+    show_playlist: bool,
+    /// Os arquivos e textos da playlist, na ordem em que tocam.
+    playlist_items: Vec<Part>,
+    /// O silêncio entre os itens da playlist, em segundos.
+    playlist_gap: f32,
+    playlist_repeat: Repeat,
+    /// A semente do embaralhamento da playlist, se ele estiver ligado.
+    playlist_shuffle: Option<u64>,
+    playlist_add_dialog: Option<FileDialog>,
+    /// A playlist tocando, enquanto a thread da reprodução existir.
+    playlist: Option<Arc<Playlist>>,
+    /// Onde gravar o que é feito na interface, se a sessão estiver sendo gravada.
+    recorder: Option<Recorder>,
+    /// O diário sendo reproduzido, se houver.
+    replay: Option<Replay>,
+    high_contrast: bool,
+    /// Se o visual aplicado é o de alto contraste, para só trocá-lo quando a opção mudar
+    /// e manter o tema do sistema enquanto ela não for usada.
+    contrast_applied: bool,
+    /// Garante que só uma reprodução toque por vez, guardando as que esperam.
+    playback_guard: PlaybackGuard<QueuedPlayback>,
+    compare_diff: Vec<DiffOp>,
+    external_midi: Option<(String, Vec<u8>)>,
+    error: Option<String>,
+}
+
+impl UserInterface {
+    /// Por quantos segundos os trechos com problemas ficam destacados.
+    const FLASH_SECONDS: f64 = 1.5;
+
+    pub fn new_interface() -> Self {
+        let mut interface = UserInterface {
+            tabs: vec![Tab::default()],
+            active: 0,
+            closing: None,
+            open_file_dialog: None,
+            text_file_dialog: None,
+            saved_file_dialog: None,
+            events_file_dialog: None,
+            clips_file_dialog: None,
+            stems_dialog: None,
+            import_file_dialog: None,
+            language: Language::default(),
+            note_names: NameStyle::default(),
+            tuning: Tuning::default(),
+            velocity_curve: VelocityCurve::default(),
+            fade: Fade::default(),
+            scale: ScaleQuantizer::default(),
+            note_range: NoteRange::default(),
+            folded_notes: Arc::default(),
+            echo: Echo::default(),
+            harmonizer: Harmonizer::default(),
+            strum: Strum::default(),
+            groove: Groove::default(),
+            grooves: Vec::new(),
+            groove_dialog: None,
+            module_reset: ModuleReset::default(),
+            presets: Vec::new(),
+            preset_dialog: None,
+            preset_save_dialog: None,
+            export_review: None,
+            latency_ms: 0,
+            playback_rate: 1.0,
+            send_clock: false,
+            audio_click: false,
+            error_sound: false,
+            capture_sent: false,
+            capture: None,
+            capture_file_dialog: None,
+            count_in_bars: 0,
+            max_playback_secs: None,
+            follow_clock: false,
+            #[cfg(feature = "native")]
+            preferences: Preferences::load(),
+            #[cfg(feature = "native")]
+            output_ports: Vec::new(),
+            #[cfg(feature = "native")]
+            thru: None,
+            thru_channel: Arc::default(),
+            channel_map: ChannelMap::default(),
+            patch_map: None,
+            patch_map_dialog: None,
+            mixer: Arc::default(),
+            channels: Vec::new(),
+            track_names: Vec::new(),
+            show_mixer: false,
+            show_settings: false,
+            show_compare: false,
+            show_search: false,
+            show_grid: false,
+            show_piano_roll: false,
+            show_automation: false,
+            show_log: false,
+            search: Search::default(),
+            grid: StepGrid::default(),
+            piano_roll: PianoRoll::default(),
+            automation: AutomationLanes::default(),
+            compare_texts: Default::default(),
+            compare_dialog: None,
+            show_compose: false,
+            arrangement: Arrangement::default(),
+            compose_add_dialog: None,
+            compose_save_dialog: None,
+            show_live: false,
+            live_clips: Vec::new(),
+            live_add_dialog: None,
+            launcher: None,
+            pads: Arc::default(),
+            #[cfg(feature = "native")]
+            pads_input: None,
+            show_playlist: false,
+            playlist_items: Vec::new(),
+            playlist_gap: 2.0,
+            playlist_repeat: Repeat::default(),
+            playlist_shuffle: None,
+            playlist_add_dialog: None,
+            playlist: None,
+            recorder: None,
+            replay: None,
+            high_contrast: false,
+            contrast_applied: false,
+            playback_guard: PlaybackGuard::default(),
+            compare_diff: Vec::new(),
+            external_midi: None,
+            error: None,
+        };
+        #[cfg(feature = "native")]
+        {
+            interface.high_contrast = interface.preferences.high_contrast;
+            interface.playback_guard.overlap = interface.preferences.overlap;
+            interface.refresh_output_ports();
+        }
+
+        interface
+    }
+
+    /// Grava o que for feito no diário dado e reproduz as ações do outro.
+    pub fn with_session(mut self, recorder: Option<Recorder>, replay: Option<Replay>) -> Self {
+        self.recorder = recorder;
+        self.replay = replay;
+        self
+    }
+
+    /// Busca as portas de saída, avisando se a preferida não está entre elas.
+    #[cfg(feature = "native")]
+    fn refresh_output_ports(&mut self) {
+        match play::output_port_names() {
+            Ok(names) => self.output_ports = names,
+            Err(error) => self.error = Some(error.to_string()),
+        }
+        if let Some(preferred) = &self.preferences.output_port {
+            if !self.output_ports.contains(preferred) {
+                self.error = Some(format!(
+                    "Output port {preferred} not found, the first available one will be used"
+                ));
+            }
+        }
+    }
+
+    /// A aba sendo editada.
+    fn tab(&self) -> &Tab {
+        &self.tabs[self.active]
+    }
+
+    fn tab_mut(&mut self) -> &mut Tab {
+        &mut self.tabs[self.active]
+    }
+
+    /// Fecha a aba, abrindo uma vazia se ela era a última.
+    fn close_tab(&mut self, index: usize) {
+        self.record(Action::CloseTab { tab: index });
+        self.tabs.remove(index);
+        if self.tabs.is_empty() {
+            self.tabs.push(Tab::default());
+        }
+        if self.active > index || self.active == self.tabs.len() {
+            self.active -= 1;
+        }
+    }
+
+    /// O que o diário da sessão compara de um quadro para o próximo.
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            tabs: self.tabs.len(),
+            active: self.active,
+            text: self.tab().content.clone(),
+            settings: self.tab().settings(),
+        }
+    }
+
+    /// Grava a ação no diário da sessão, se ele estiver sendo gravado. Se o arquivo
+    /// der erro, a gravação para.
+    fn record(&mut self, action: Action) {
+        let Some(recorder) = &mut self.recorder else {
+            return;
+        };
+        if let Err(error) = recorder.record(action) {
+            self.error = Some(format!("Session recording stopped: {error}"));
+            self.recorder = None;
+        }
+    }
+
+    /// Faz as ações do diário sendo reproduzido que já chegaram na hora, pelos mesmos
+    /// métodos que as gravaram.
+    fn replay_session(&mut self, ctx: &Context, now: f64) {
+        let Some(replay) = &mut self.replay else {
+            return;
+        };
+        let actions = replay.due(now);
+        if let Some(wait) = replay.until_next(now) {
+            ctx.request_repaint_after(Duration::from_secs_f64(wait));
+        }
+        for action in actions {
+            match action {
+                Action::NewTab { text } => {
+                    self.tabs.push(Tab::unsaved(text));
+                    self.active = self.tabs.len() - 1;
+                }
+                Action::CloseTab { tab } if tab < self.tabs.len() => self.close_tab(tab),
+                Action::Select { tab } if tab < self.tabs.len() => self.active = tab,
+                Action::CloseTab { .. } | Action::Select { .. } => {}
+                Action::Edit { text } => {
+                    let tab = self.tab_mut();
+                    tab.content = text;
+                    tab.content_changed();
+                }
+                Action::Settings(settings) => self.tab_mut().set_settings(settings),
+                Action::Play => self.convert(Purpose::Play),
+                Action::Stop => self.stop(),
+                Action::Panic => self.panic(),
+            }
+        }
+    }
+
+    /// As ações de um texto qualquer, com as configurações da aba e opções de exportação atuais.
+    fn actions_for(&self, text: &str) -> Result<Vec<MidiAction>, ConfigError> {
+        Ok(self.transforms()(self.tab().sheet(text)?.process()))
+    }
+
+    /// As opções de exportação atuais, para serem aplicadas às ações em qualquer thread.
+    fn transforms(&self) -> impl Fn(Vec<MidiAction>) -> Vec<MidiAction> + Send + 'static {
+        let velocity_curve = self.velocity_curve.clone();
+        let fade = self.fade;
+        let scale = self.scale;
+        let note_range = self.note_range;
+        let folded_notes = Arc::clone(&self.folded_notes);
+        let tuning = self.tuning;
+        let module_reset = self.module_reset;
+        move |actions| {
+            let actions = scale.apply(fade.apply(velocity_curve.apply(actions)));
+            let (actions, folded) = note_range.fold_counting(actions);
+            folded_notes.store(folded, Ordering::Relaxed);
+            module_reset.apply(tuning.apply(actions))
+        }
+    }
+
+    /// Os efeitos que acrescentam vozes sobrepostas, posicionando as ações no tempo.
+    fn timed_transforms(&self) -> impl Fn(Vec<TimedAction>) -> Vec<TimedAction> + Send + 'static {
+        let echo = self.echo;
+        let harmonizer = self.harmonizer;
+        let strum = self.strum;
+        let groove = self.groove.clone();
+        move |timed| harmonizer.apply(strum.apply(echo.apply(groove.apply(timed))))
+    }
+
+    /// Os efeitos no tempo, seguidos das curvas de automação do cabeçalho do texto.
+    fn automated_transforms(
+        &self,
+        content: &str,
+    ) -> impl Fn(Vec<TimedAction>) -> Vec<TimedAction> + Send + 'static {
+        let lanes = Header::parse(content).0.lanes;
+        let resolution = self.automation.resolution;
+        let timed = self.timed_transforms();
+        move |actions| automation::apply(&lanes, timed(actions), resolution)
+    }
+
+    /// As configurações da aba atual e as transformações, como um preset.
+    fn preset(&self) -> Preset {
+        let tab = self.tab();
+        Preset {
+            version: Preset::VERSION,
+            instrument: tab.instrument,
+            bpm: tab.bpm,
+            volume: tab.volume,
+            base_length: tab.base_length,
+            velocity_curve: self.velocity_curve.clone(),
+            fade: self.fade,
+            scale: self.scale,
+            note_range: self.note_range,
+            tuning: self.tuning,
+            module_reset: self.module_reset,
+            echo: self.echo,
+            harmonizer: self.harmonizer,
+            strum: self.strum,
+            groove: self.groove.clone(),
+        }
+    }
+
+    /// Passa as configurações do preset para a aba atual e as transformações.
+    fn apply_preset(&mut self, preset: Preset) {
+        let tab = &mut self.tabs[self.active];
+        tab.instrument = preset.instrument;
+        tab.bpm = preset.bpm;
+        tab.volume = preset.volume;
+        tab.base_length = preset.base_length;
+        self.velocity_curve = preset.velocity_curve;
+        self.fade = preset.fade;
+        self.scale = preset.scale;
+        self.note_range = preset.note_range;
+        self.tuning = preset.tuning;
+        self.module_reset = preset.module_reset;
+        self.echo = preset.echo;
+        self.harmonizer = preset.harmonizer;
+        self.strum = preset.strum;
+        self.groove = preset.groove;
+    }
+
+    /// Mostra o erro do resultado, se houver, e retorna o valor em caso de sucesso.
+    fn report<T>(&mut self, result: Result<T, impl ToString>) -> Option<T> {
+        match result {
+            Ok(value) => {
+                self.error = None;
+                Some(value)
+            }
+            Err(error) => {
+                self.error = Some(error.to_string());
+                None
+            }
+        }
+    }
+
+    /// Converte o texto da aba atual em outra thread, sem travar a interface.
+    ///
+    /// O andamento aparece em uma barra de progresso, e quando a conversão
+    /// termina o resultado é usado conforme o `purpose`.
+    fn convert(&mut self, purpose: Purpose) {
+        if purpose == Purpose::Play {
+            self.record(Action::Play);
+        }
+        let tab = self.tab();
+        let Some(sheet) = self.report(tab.sheet(&tab.content)) else {
+            self.signal_error(self.active, Vec::new(), true);
+            return;
+        };
+        // Uma conversão anterior ainda em andamento é abandonada
+        let timed = self.automated_transforms(&self.tab().content);
+        let job = Job::spawn(sheet, self.transforms(), timed, purpose);
+        self.tab_mut().conversion = Some(job);
+    }
+
+    /// Usa o resultado das conversões terminadas, e mostra o andamento da aba atual.
+    fn poll_conversions(&mut self, ui: &mut egui::Ui) {
+        let language = self.language;
+        for index in 0..self.tabs.len() {
+            let Some(job) = &self.tabs[index].conversion else {
+                continue;
+            };
+
+            match job.try_finish() {
+                Some(Ok(converted)) => {
+                    self.tabs[index].conversion = None;
+                    self.finish_conversion(index, converted);
+                }
+                Some(Err(ParseError::Cancelled)) => self.tabs[index].conversion = None,
+                Some(Err(ParseError::Unrecognized(unrecognized))) => {
+                    self.tabs[index].conversion = None;
+                    let spans = unrecognized.iter().map(|u| u.span.clone()).collect();
+                    self.error = Some(ParseError::Unrecognized(unrecognized).to_string());
+                    self.signal_error(index, spans, true);
+                }
+                None if index == self.active => {
+                    ui.horizontal(|ui| {
+                        let fraction = job.progress().percent() as f32 / 100.0;
+                        ui.add(
+                            egui::ProgressBar::new(fraction).text(Key::Converting.text(language)),
+                        );
+                        if ui.button(Key::Cancel.text(language)).clicked() {
+                            job.progress().cancel();
+                        }
+                    });
+                    ui.ctx().request_repaint();
+                }
+                None => ui.ctx().request_repaint(),
+            }
+        }
+    }
+
+    /// Avisa de um problema na aba dada: destaca os trechos do texto por um momento e,
+    /// se pedido e ligado nas configurações, toca o som de erro.
+    fn signal_error(&mut self, index: usize, spans: Vec<Range<usize>>, sound: bool) {
+        if !spans.is_empty() {
+            self.tabs[index].flash = Some((spans, None));
+        }
+        // O som não espera nem interrompe outra reprodução
+        if sound && self.error_sound && !self.is_playing() {
+            let mut bytes = Vec::new();
+            MidiAction::as_track(&instruments::error_sound())
+                .write_std(&mut bytes)
+                .expect("writing to a vector never fails");
+            self.spawn_playback(QueuedPlayback {
+                tab: index,
+                bytes,
+                notes: Vec::new(),
+                end: None,
+            });
+        }
+    }
+
+    /// Usa o resultado de uma conversão terminada da aba dada.
+    fn finish_conversion(&mut self, index: usize, converted: Converted) {
+        let Converted {
+            purpose,
+            actions,
+            smf,
+            warnings,
+            mut diagnostics,
+            notes,
+        } = converted;
+        let mut file = Smf::parse(&smf).expect("the conversion always writes a valid file");
+
+        let tab = &mut self.tabs[index];
+        tab.warnings = warnings;
+        tab.warnings.extend(validate::validate_header(&tab.content));
+        diagnostics.extend(diagnostics::from_header(&tab.content));
+        self.inspect(&file);
+        if !diagnostics.is_empty() {
+            let spans = diagnostics.into_iter().map(|d| d.span).collect();
+            // A música tocando já mostra o problema, sem o som por cima dela
+            self.signal_error(index, spans, purpose != Purpose::Play);
+        }
+
+        match purpose {
+            Purpose::Play => self.request_playback(QueuedPlayback {
+                tab: index,
+                bytes: smf,
+                notes,
+                end: None,
+            }),
+            Purpose::Save(mut path) => {
+                let mixer = self.mixer.lock().unwrap();
+                mixer.remove_muted_tracks(&mut file);
+                for track in &mut file.tracks {
+                    mixer.apply(track);
+                }
+                self.to_device(&mut file);
+                drop(mixer);
+
+                path.set_extension("mid");
+                let mut bytes = Vec::new();
+                file.write_std(&mut bytes)
+                    .expect("writing to a vector never fails");
+                self.export_review = Some(ExportReview {
+                    tab: index,
+                    path,
+                    bytes,
+                    summary: Summary::of(&file),
+                    converted: smf,
+                });
+            }
+            Purpose::Copy => {
+                self.to_device(&mut file);
+                self.error = clipboard::copy(clipboard::encode(&file))
+                    .err()
+                    .map(|error| error.to_string());
+            }
+            Purpose::DumpEvents(path) => {
+                let actions = TimedAction::from_actions(&actions);
+                let _ = export::events::save(&actions, &path);
+            }
+            Purpose::Stems(directory) => {
+                if let Err(error) = export::stems::save(&file, &directory) {
+                    self.error = Some(format!("{}: {error}", directory.display()));
+                }
+            }
+        }
+    }
+
+    /// Mostra o resumo do arquivo a salvar, com a prévia do começo dele.
+    fn export_window(&mut self, ctx: &Context) {
+        let language = self.language;
+        let Some(review) = &self.export_review else {
+            return;
+        };
+        let summary = review.summary;
+        let (mut save, mut preview, mut cancel) = (false, false, false);
+        egui::Window::new(Key::ExportReview.text(language))
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.label(review.path.display().to_string());
+                egui::Grid::new("export_summary")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        let seconds = summary.duration.as_secs_f64();
+                        let rows = [
+                            (Key::FileFormat, summary.format_name().to_string()),
+                            (Key::Timing, TimingName(summary.timing).to_string()),
+                            (Key::Tracks, summary.tracks.to_string()),
+                            (Key::TempoEvents, summary.tempo_events.to_string()),
+                            (
+                                Key::EstimatedDuration,
+                                format!("{}:{:04.1}", (seconds / 60.0) as u64, seconds % 60.0),
+                            ),
+                        ];
+                        for (key, value) in rows {
+                            ui.label(key.text(language));
+                            ui.label(value);
+                            ui.end_row();
+                        }
+                    });
+                ui.horizontal(|ui| {
+                    save = ui.button(Key::Save.text(language)).clicked();
+                    preview = ui
+                        .button(Key::PreviewStart.fill(language, Summary::PREVIEW.as_secs()))
+                        .clicked();
+                    cancel = ui.button(Key::Cancel.text(language)).clicked();
+                });
+            });
+
+        if preview {
+            let converted = review.converted.clone();
+            let index = review.tab.min(self.tabs.len() - 1);
+            let end = Smf::parse(&converted)
+                .ok()
+                .and_then(|file| Summary::preview_end(&file));
+            self.request_playback(QueuedPlayback {
+                tab: index,
+                bytes: converted,
+                notes: Vec::new(),
+                end,
+            });
+        }
+        if save {
+            let review = self.export_review.take().expect("checked above");
+            if let Some(tab) = self.tabs.get_mut(review.tab) {
+                tab.saved_file = Some(review.path.clone());
+            }
+            let result = fs::write(&review.path, review.bytes);
+            self.report(result);
+        } else if cancel {
+            self.export_review = None;
+        }
+    }
+
+    /// Troca os programas e os canais do arquivo pelos que o aparelho espera.
+    fn to_device(&self, file: &mut Smf<'_>) {
+        for track in &mut file.tracks {
+            if let Some((_, patch_map)) = &self.patch_map {
+                patch_map.apply(track);
+            }
+            self.channel_map.apply(track);
+        }
+    }
+
+    /// Toca as ações em outra thread, pela porta de saída, acompanhando a posição na aba atual.
+    fn start_playback(&mut self, actions: Vec<MidiAction>) {
+        let mut bytes = Vec::new();
+        TimedAction::as_track(&self.automated_transforms(&self.tab().content)(
+            TimedAction::from_actions(&actions),
+        ))
+        .write_std(&mut bytes)
+        .expect("writing to a vector never fails");
+        self.start_playback_bytes(self.active, bytes);
+    }
+
+    /// Toca um arquivo MIDI já serializado, acompanhando a posição na aba dada, assim
+    /// que o `PlaybackGuard` deixar.
+    fn start_playback_bytes(&mut self, index: usize, bytes: Vec<u8>) {
+        self.request_playback(QueuedPlayback {
+            tab: index,
+            bytes,
+            notes: Vec::new(),
+            end: None,
+        });
+    }
+
+    /// Toca a reprodução já, espera a atual terminar ou a recusa, conforme o `Overlap`
+    /// escolhido.
+    fn request_playback(&mut self, playback: QueuedPlayback) {
+        match self.playback_guard.request(self.is_playing(), playback) {
+            Admission::Start(playback) => self.spawn_playback(playback),
+            Admission::StopCurrent => {
+                self.stop_playback();
+            }
+            Admission::Queued => {}
+            Admission::Rejected => {
+                self.error = Some(Key::AlreadyPlaying.text(self.language).to_string());
+            }
+        }
+    }
+
+    /// Se alguma reprodução está em andamento: de uma aba, do modo ao vivo ou da playlist.
+    fn is_playing(&self) -> bool {
+        self.tabs.iter().any(Tab::is_playing)
+            || self.running_launcher().is_some()
+            || self.running_playlist().is_some()
+    }
+
+    /// Toca um arquivo MIDI já serializado em outra thread, acompanhando a posição na aba dada.
+    fn spawn_playback(&mut self, playback: QueuedPlayback) {
+        let QueuedPlayback {
+            tab: index,
+            bytes,
+            notes,
+            end,
+        } = playback;
+        // A aba pode ter sido fechada enquanto a reprodução esperava
+        let index = index.min(self.tabs.len() - 1);
+        let playhead = Arc::new(Playhead::default());
+        let options = PlaybackOptions {
+            latency: Duration::from_millis(self.latency_ms),
+            send_clock: self.send_clock,
+            count_in_bars: self.count_in_bars,
+            max_duration: self
+                .max_playback_secs
+                .map(|secs| Duration::from_secs(secs.into())),
+        };
+        let mixer = Arc::clone(&self.mixer);
+        let follow_clock = self.follow_clock;
+        let audio_click = self.audio_click;
+        let channel_map = self.channel_map;
+        let patch_map = self.patch_map.as_ref().map(|(_, map)| map.clone());
+        let capture = self.capture_sent.then(|| {
+            let capture = Arc::new(Capture::default());
+            self.capture = Some(Arc::clone(&capture));
+            capture
+        });
+        #[cfg(feature = "native")]
+        let preferred = self.preferences.output_port.clone();
+        let controls = Arc::new(LiveControls::default());
+        controls.set_end(end);
+        let tab = &mut self.tabs[index];
+        controls.set_tempo_scale(self.playback_rate);
+        controls.set_volume(tab.volume.min(State::MAX_VOLUME) as u8);
+        tab.live = Some((Arc::clone(&controls), tab.bpm.max(1)));
+        tab.playhead = Some(Arc::clone(&playhead));
+        tab.playing_notes = notes;
+        tab.followed = None;
+        (tab.playing_ticks, tab.playing_timeline) = Smf::parse(&bytes)
+            .map(|file| {
+                let ticks = file
+                    .tracks
+                    .iter()
+                    .map(|track| track.iter().map(|e| e.delta.as_int() as u64).sum())
+                    .max()
+                    .unwrap_or(0);
+                (ticks, analysis::Timeline::from_smf(&file))
+            })
+            .unwrap_or_default();
+
+        #[cfg(feature = "native")]
+        thread::spawn(move || {
+            let play = || -> Result<(), Box<dyn Error>> {
+                let mut file = Smf::parse(&bytes)?;
+                for track in &mut file.tracks {
+                    mixer.lock().unwrap().apply(track);
+                    if let Some(patch_map) = &patch_map {
+                        patch_map.apply(track);
+                    }
+                }
+
+                let port = play::prepare_connection(preferred.as_deref())?;
+                if let Some(preferred) = preferred.filter(|name| name != port.name()) {
+                    playhead.report(format!(
+                        "Output port {preferred} not found, playing on {}",
+                        port.name()
+                    ));
+                }
+
+                let clock = SystemClock::new();
+                let mut player = Player::new(port, &clock)
+                    .with_options(options)
+                    .with_playhead(Arc::clone(&playhead))
+                    .with_controls(controls)
+                    .with_mixer(mixer)
+                    .with_channel_map(channel_map);
+                if let Some(capture) = capture {
+                    player = player.with_capture(capture);
+                }
+                if audio_click {
+                    match metronome::connect() {
+                        Ok(metronome) => player = player.with_metronome(metronome),
+                        Err(error) => playhead.report(format!("Audio metronome: {error}")),
+                    }
+                }
+                // A conexão de entrada precisa existir até o fim da reprodução
+                let _input = if follow_clock {
+                    let (input, external) = external_clock::connect()?;
+                    player = player.with_external_clock(external);
+                    Some(input)
+                } else {
+                    None
+                };
+                player.play(&file)?;
+                play::close_connection(&clock);
+
+                Ok(())
+            };
+            if let Err(error) = play() {
+                playhead.report(error);
+            }
+        });
+
+        #[cfg(target_arch = "wasm32")]
+        wasm_bindgen_futures::spawn_local(async move {
+            let reporter = Arc::clone(&playhead);
+            if let Err(error) = web_midi::play(&bytes, mixer, playhead, controls, options).await {
+                reporter.report(error);
+            }
+        });
+
+        #[cfg(not(any(feature = "native", target_arch = "wasm32")))]
+        {
+            self.error = Some("MIDI output is not available in this build".to_string());
+        }
+    }
+
+    /// Mostra os problemas avisados pelas reproduções, como a perda da porta de saída.
+    fn show_playback_problems(&mut self) {
+        for tab in &self.tabs {
+            if let Some(problem) = tab.playhead.as_ref().and_then(|p| p.take_problem()) {
+                self.error = Some(problem);
+            }
+        }
+        if let Some(problem) = self
+            .launcher
+            .as_ref()
+            .and_then(|l| l.playhead().take_problem())
+        {
+            self.error = Some(problem);
+        }
+        if let Some(problem) = self
+            .playlist
+            .as_ref()
+            .and_then(|p| p.playhead().take_problem())
+        {
+            self.error = Some(problem);
+        }
+    }
+
+    /// Leva a velocidade e os sliders de BPM e volume de cada aba para a sua reprodução
+    /// em andamento.
+    fn update_live_controls(&self) {
+        for tab in &self.tabs {
+            if let Some((controls, bpm)) = &tab.live {
+                controls.set_tempo_scale(self.playback_rate * tab.bpm as f64 / *bpm as f64);
+                controls.set_volume(tab.volume.min(State::MAX_VOLUME) as u8);
+            }
+        }
+    }
+
+    /// Lê um arquivo MIDI em base64 da área de transferência.
+    fn paste_midi(&mut self) {
+        let language = self.language;
+        match clipboard::paste().and_then(|text| Ok(clipboard::decode(&text)?)) {
+            Ok(bytes) => {
+                let file = Smf::parse(&bytes).expect("decode only accepts valid files");
+                self.check(&[], &file);
+                self.external_midi = Some((Key::PastedMidi.text(language).to_string(), bytes));
+                self.error = None;
+            }
+            Err(error) => self.error = Some(error.to_string()),
+        }
+    }
+
+    /// Carrega uma lista de eventos em JSON para ser tocada.
+    fn import_events(&mut self, file: &Path) {
+        let language = self.language;
+        let imported = fs::read_to_string(file)
+            .map_err(|error| error.to_string())
+            .and_then(|json| import::events::from_json(&json).map_err(|e| e.to_string()));
+
+        match imported {
+            Ok(actions) => {
+                let smf = TimedAction::as_track(&actions);
+                self.check(&[], &smf);
+                let mut bytes = Vec::new();
+                smf.write_std(&mut bytes)
+                    .expect("writing to a vector never fails");
+                let name = file.file_name().unwrap_or_default().to_string_lossy();
+                self.external_midi = Some((Key::Imported.fill(language, name), bytes));
+                self.error = None;
+            }
+            Err(error) => self.error = Some(error),
+        }
+    }
+
+    /// Salva as abas em um só arquivo MIDI, cada uma como um trecho com o seu nome.
+    ///
+    /// Diferente das outras conversões, esta é feita na hora, já que junta todas as abas.
+    fn export_clips(&mut self, file: &Path) {
+        let language = self.language;
+        let transforms = self.transforms();
+        let clips = self
+            .tabs
+            .iter()
+            .map(|tab| {
+                let timed = self.automated_transforms(&tab.content);
+                let actions = transforms(tab.sheet(&tab.content)?.process());
+                Ok(Clip {
+                    name: tab.name(language),
+                    actions: timed(TimedAction::from_actions(&actions)),
+                })
+            })
+            .collect::<Result<Vec<_>, ConfigError>>();
+        let Some(clips) = self.report(clips) else {
+            return;
+        };
+
+        let mut file = file.to_path_buf();
+        file.set_extension("mid");
+        let saved = clips::container(&clips, ClipLayout::Sequential).save(file);
+        self.report(saved);
+    }
+
+    /// Para as reproduções em andamento e cala as notas presas em todos os canais.
+    fn panic(&mut self) {
+        self.record(Action::Panic);
+        self.playback_guard.clear();
+        if !self.stop_playback() {
+            self.send_panic();
+        }
+    }
+
+    /// Para as reproduções em andamento e esquece as que esperam.
+    fn stop(&mut self) {
+        self.record(Action::Stop);
+        self.playback_guard.clear();
+        self.stop_playback();
+    }
+
+    /// Para as reproduções em andamento, que então enviam as mensagens de pânico,
+    /// retornando se alguma estava tocando.
+    fn stop_playback(&self) -> bool {
+        let mut stopped = false;
+        for tab in &self.tabs {
+            if let (Some((controls, _)), Some(playhead)) = (&tab.live, &tab.playhead) {
+                if playhead.is_playing() {
+                    controls.stop();
+                    stopped = true;
+                }
+            }
+        }
+        if let Some(launcher) = self.running_launcher() {
+            launcher.stop();
+            stopped = true;
+        }
+        if let Some(playlist) = self.running_playlist() {
+            playlist.stop();
+            stopped = true;
+        }
+
+        stopped
+    }
+
+    /// Envia as mensagens de pânico por uma conexão nova, já que as notas presas
+    /// podem ser de uma reprodução interrompida que já fechou a sua.
+    fn send_panic(&mut self) {
+        #[cfg(feature = "native")]
+        let preferred = self.preferences.output_port.clone();
+        #[cfg(feature = "native")]
+        thread::spawn(move || {
+            let panic = || -> Result<(), Box<dyn Error>> {
+                play::send_panic(&mut play::prepare_connection(preferred.as_deref())?)?;
+                play::close_connection(&SystemClock::new());
+                Ok(())
+            };
+            let _ = panic();
+        });
+
+        #[cfg(target_arch = "wasm32")]
+        wasm_bindgen_futures::spawn_local(async {
+            let _ = web_midi::send_panic().await;
+        });
+
+        #[cfg(not(any(feature = "native", target_arch = "wasm32")))]
+        {
+            self.error = Some("MIDI output is not available in this build".to_string());
+        }
+    }
+
+    /// Mostra uma faixa do mixer para cada canal usado pelo último arquivo gerado.
+    fn mixer_window(&mut self, ctx: &Context) {
+        let language = self.language;
+        let mut mixer = self.mixer.lock().unwrap();
+        egui::Window::new(Key::Mixer.text(language))
+            .open(&mut self.show_mixer)
+            .show(ctx, |ui| {
+                if self.channels.is_empty() {
+                    ui.label(Key::MixerEmpty.text(language));
+                }
+                if !self.track_names.is_empty() {
+                    ui.label(Key::Tracks.text(language));
+                    mixer
+                        .tracks
+                        .resize(self.track_names.len(), Default::default());
+                }
+                for (name, strip) in self.track_names.iter().zip(&mut mixer.tracks) {
+                    ui.horizontal(|ui| {
+                        ui.toggle_value(&mut strip.mute, Key::Mute.text(language));
+                        ui.toggle_value(&mut strip.solo, Key::Solo.text(language));
+                        ui.label(name);
+                    });
+                }
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    for channel in &self.channels {
+                        let strip = &mut mixer.strips[channel.as_int() as usize];
+                        ui.vertical(|ui| {
+                            ui.label(Key::Channel.fill(language, channel.as_int() + 1));
+                            ui.add(egui::Slider::new(&mut strip.volume, 0..=127).vertical());
+                            ui.add(
+                                egui::Slider::new(&mut strip.pan, 0..=127)
+                                    .text(Key::Pan.text(language)),
+                            );
+                            ui.horizontal(|ui| {
+                                ui.toggle_value(&mut strip.mute, Key::Mute.text(language));
+                                ui.toggle_value(&mut strip.solo, Key::Solo.text(language));
+                            });
+                        });
+                    }
+                });
+            });
+    }
+
+    /// Mostra as configurações de conversão.
+    fn settings_window(&mut self, ctx: &Context) {
+        let language = self.language;
+        #[cfg(feature = "native")]
+        let mut refresh_ports = false;
+        let current = self.preset();
+        let mut chosen = None;
+        egui::Window::new(Key::Settings.text(language))
+            .open(&mut self.show_settings)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    let selected = self
+                        .presets
+                        .iter()
+                        .find(|(_, preset)| *preset == current)
+                        .map_or("", |(name, _)| name.as_str());
+                    egui::ComboBox::from_label(Key::Preset.text(language))
+                        .selected_text(selected)
+                        .show_ui(ui, |ui| {
+                            for (name, preset) in &self.presets {
+                                if ui.selectable_label(*preset == current, name).clicked() {
+                                    chosen = Some(preset.clone());
+                                }
+                            }
+                        });
+                    if ui.button(Key::LoadPreset.text(language)).clicked() {
+                        let mut dialog = FileDialog::open_file(None);
+                        dialog.open();
+                        self.preset_dialog = Some(dialog);
+                    }
+                    if ui.button(Key::SavePreset.text(language)).clicked() {
+                        let mut dialog = FileDialog::save_file(None);
+                        dialog.open();
+                        self.preset_save_dialog = Some(dialog);
+                    }
+                })
+                .response
+                .on_hover_text(Key::PresetHelp.text(language));
+
+                egui::ComboBox::from_label(Key::VelocityCurve.text(language))
+                    .selected_text(self.velocity_curve.to_string())
+                    .show_ui(ui, |ui| {
+                        for curve in VelocityCurve::all() {
+                            let selected = mem::discriminant(&curve)
+                                == mem::discriminant(&self.velocity_curve);
+                            if ui.selectable_label(selected, curve.to_string()).clicked()
+                                && !selected
+                            {
+                                self.velocity_curve = curve;
+                            }
+                        }
+                    });
+
+                if let VelocityCurve::Custom(points) = &mut self.velocity_curve {
+                    let mut removed = None;
+                    for (index, (input, output)) in points.iter_mut().enumerate() {
+                        ui.horizontal(|ui| {
+                            ui.add(egui::DragValue::new(input).clamp_range(0..=127))
+                                .named(Key::VelocityCurve.text(language));
+                            ui.label("→");
+                            ui.add(egui::DragValue::new(output).clamp_range(0..=127))
+                                .named(Key::VelocityCurve.text(language));
+                            if ui.button("-").named(Key::Remove.text(language)).clicked() {
+                                removed = Some(index);
+                            }
+                        });
+                    }
+                    if let Some(index) = removed {
+                        points.remove(index);
+                    }
+                    if ui.button("+").named(Key::AddPoint.text(language)).clicked() {
+                        points.push((127, 127));
+                    }
+                }
+
+                ui.horizontal(|ui| {
+                    ui.label(Key::FadeIn.text(language));
+                    ui.add(egui::DragValue::new(&mut self.fade.fade_in).clamp_range(0..=64))
+                        .named(Key::FadeIn.text(language));
+                    ui.label(Key::FadeOut.text(language));
+                    ui.add(egui::DragValue::new(&mut self.fade.fade_out).clamp_range(0..=64))
+                        .named(Key::FadeOut.text(language));
+                })
+                .response
+                .on_hover_text(Key::FadeHelp.text(language));
+
+                ui.horizontal(|ui| {
+                    let scale_name = |scale: Option<Scale>| {
+                        match scale {
+                            None => Key::ScaleOff,
+                            Some(Scale::Major) => Key::Major,
+                            Some(Scale::NaturalMinor) => Key::NaturalMinor,
+                            Some(Scale::HarmonicMinor) => Key::HarmonicMinor,
+                            Some(Scale::MajorPentatonic) => Key::MajorPentatonic,
+                            Some(Scale::MinorPentatonic) => Key::MinorPentatonic,
+                            Some(Scale::Blues) => Key::Blues,
+                        }
+                        .text(language)
+                    };
+                    egui::ComboBox::from_label(Key::Scale.text(language))
+                        .selected_text(scale_name(self.scale.scale))
+                        .show_ui(ui, |ui| {
+                            for scale in [None].into_iter().chain(Scale::ALL.map(Some)) {
+                                ui.selectable_value(
+                                    &mut self.scale.scale,
+                                    scale,
+                                    scale_name(scale),
+                                );
+                            }
+                        });
+                    if self.scale.scale.is_some() {
+                        let note_names = self.note_names;
+                        egui::ComboBox::from_id_source("scale_tonic")
+                            .selected_text(self.scale.tonic.to_name(note_names))
+                            .show_ui(ui, |ui| {
+                                for tonic in Note::NATURALS {
+                                    let name = tonic.to_name(note_names);
+                                    ui.selectable_value(&mut self.scale.tonic, tonic, name);
+                                }
+                            });
+                    }
+                })
+                .response
+                .on_hover_text(Key::ScaleHelp.text(language));
+
+                ui.horizontal(|ui| {
+                    let style = self.note_names;
+                    let name = move |key: f64, _| {
+                        Pitch::from_midi(key as u8)
+                            .map_or(key.to_string(), |pitch| pitch.name(style))
+                    };
+                    ui.label(Key::NoteRange.text(language));
+                    for key in [&mut self.note_range.low, &mut self.note_range.high] {
+                        ui.add(
+                            egui::DragValue::new(key)
+                                .clamp_range(0..=127)
+                                .custom_formatter(name),
+                        )
+                        .named(Key::NoteRange.text(language));
+                    }
+                    if ui.button(Key::TwentyFiveKeys.text(language)).clicked() {
+                        self.note_range = NoteRange { low: 48, high: 72 };
+                    }
+                    if self.note_range != NoteRange::default() {
+                        if ui.button(Key::AllKeys.text(language)).clicked() {
+                            self.note_range = NoteRange::default();
+                        }
+                        let folded = self.folded_notes.load(Ordering::Relaxed);
+                        ui.label(Key::FoldedNotes.fill(language, folded));
+                    }
+                })
+                .response
+                .on_hover_text(Key::NoteRangeHelp.text(language));
+
+                ui.horizontal(|ui| {
+                    ui.label(Key::EchoRepeats.text(language));
+                    ui.add(egui::DragValue::new(&mut self.echo.repeats).clamp_range(0..=8))
+                        .named(Key::EchoRepeats.text(language));
+                    ui.label(Key::EchoDelay.text(language));
+                    ui.add(
+                        egui::DragValue::new(&mut self.echo.delay)
+                            .clamp_range(1..=1920)
+                            .suffix(" ticks"),
+                    )
+                    .named(Key::EchoDelay.text(language));
+                })
+                .response
+                .on_hover_text(Key::EchoHelp.text(language));
+
+                ui.horizontal(|ui| {
+                    let interval_name = |interval: Option<HarmonyInterval>| {
+                        match interval {
+                            None => Key::HarmonyOff,
+                            Some(HarmonyInterval::Third) => Key::Third,
+                            Some(HarmonyInterval::Fifth) => Key::Fifth,
+                            Some(HarmonyInterval::Octave) => Key::Octave,
+                        }
+                        .text(language)
+                    };
+                    egui::ComboBox::from_label(Key::Harmony.text(language))
+                        .selected_text(interval_name(self.harmonizer.interval))
+                        .show_ui(ui, |ui| {
+                            let intervals = HarmonyInterval::ALL.map(Some);
+                            for interval in [None].into_iter().chain(intervals) {
+                                ui.selectable_value(
+                                    &mut self.harmonizer.interval,
+                                    interval,
+                                    interval_name(interval),
+                                );
+                            }
+                        });
+                    ui.checkbox(&mut self.harmonizer.below, Key::Below.text(language));
+
+                    let note_names = self.note_names;
+                    let key_name = |key: Option<Note>| match key {
+                        Some(note) => note.to_name(note_names),
+                        None => Key::Chromatic.text(language),
+                    };
+                    egui::ComboBox::from_label(Key::Tonality.text(language))
+                        .selected_text(key_name(self.harmonizer.key))
+                        .show_ui(ui, |ui| {
+                            let keys = Note::NATURALS.map(Some);
+                            for key in [None].into_iter().chain(keys) {
+                                ui.selectable_value(&mut self.harmonizer.key, key, key_name(key));
+                            }
+                        });
+                })
+                .response
+                .on_hover_text(Key::HarmonyHelp.text(language));
+
+                ui.horizontal(|ui| {
+                    const PRESET_NAMES: [Key; 5] = [
+                        Key::StrumOff,
+                        Key::GuitarDown,
+                        Key::GuitarUp,
+                        Key::AlternatingStrum,
+                        Key::Harp,
+                    ];
+                    let selected = Strum::PRESETS
+                        .iter()
+                        .position(|preset| *preset == self.strum)
+                        .map_or(Key::CustomStrum, |index| PRESET_NAMES[index]);
+                    egui::ComboBox::from_label(Key::Strum.text(language))
+                        .selected_text(selected.text(language))
+                        .show_ui(ui, |ui| {
+                            for (preset, name) in Strum::PRESETS.into_iter().zip(PRESET_NAMES) {
+                                ui.selectable_value(&mut self.strum, preset, name.text(language));
+                            }
+                        });
+                    ui.add(
+                        egui::DragValue::new(&mut self.strum.spread)
+                            .clamp_range(0..=480)
+                            .suffix(" ticks"),
+                    )
+                    .named(Key::Strum.text(language));
+                    ui.label(Key::StrumVelocity.text(language));
+                    ui.add(
+                        egui::DragValue::new(&mut self.strum.first_velocity).clamp_range(1..=127),
+                    )
+                    .named(Key::StrumVelocity.text(language));
+                    ui.label("→");
+                    ui.add(
+                        egui::DragValue::new(&mut self.strum.last_velocity).clamp_range(1..=127),
+                    )
+                    .named(Key::StrumVelocity.text(language));
+                })
+                .response
+                .on_hover_text(Key::StrumHelp.text(language));
+
+                ui.horizontal(|ui| {
+                    let built_in_names = [
+                        Key::Straight.text(language).to_string(),
+                        Key::Swing.fill(language, 55),
+                        Key::Swing.fill(language, 60),
+                        Key::Swing.fill(language, 66),
+                        Key::LaidBack.text(language).to_string(),
+                    ];
+                    let grooves: Vec<(String, Groove)> = built_in_names
+                        .into_iter()
+                        .zip(Groove::built_in())
+                        .chain(self.grooves.iter().cloned())
+                        .collect();
+                    let selected = grooves
+                        .iter()
+                        .find(|(_, groove)| *groove == self.groove)
+                        .map_or("", |(name, _)| name.as_str());
+                    egui::ComboBox::from_label(Key::Groove.text(language))
+                        .selected_text(selected)
+                        .show_ui(ui, |ui| {
+                            for (name, groove) in &grooves {
+                                ui.selectable_value(&mut self.groove, groove.clone(), name);
+                            }
+                        });
+                    if ui.button(Key::ImportGroove.text(language)).clicked() {
+                        let mut dialog = FileDialog::open_file(None);
+                        dialog.open();
+                        self.groove_dialog = Some(dialog);
+                    }
+                })
+                .response
+                .on_hover_text(Key::GrooveHelp.text(language));
+
+                ui.horizontal(|ui| {
+                    ui.checkbox(
+                        &mut self.module_reset.gm_system_on,
+                        Key::GmReset.text(language),
+                    );
+                    let mut select_bank = self.module_reset.bank.is_some();
+                    ui.checkbox(&mut select_bank, Key::BankSelect.text(language));
+                    if select_bank {
+                        let bank = self.module_reset.bank.get_or_insert(0);
+                        ui.add(egui::DragValue::new(bank).clamp_range(0..=0x3FFF))
+                            .named(Key::BankSelect.text(language));
+                    } else {
+                        self.module_reset.bank = None;
+                    }
+                })
+                .response
+                .on_hover_text(Key::ModuleResetHelp.text(language));
+
+                #[cfg(feature = "native")]
+                ui.horizontal(|ui| {
+                    let automatic = Key::Automatic.text(language);
+                    let mut port = self.preferences.output_port.clone();
+                    egui::ComboBox::from_label(Key::OutputPort.text(language))
+                        .selected_text(port.as_deref().unwrap_or(automatic))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut port, None, automatic);
+                            for name in &self.output_ports {
+                                ui.selectable_value(&mut port, Some(name.clone()), name);
+                            }
+                        });
+                    if port != self.preferences.output_port {
+                        self.preferences.output_port = port;
+                        if let Err(error) = self.preferences.save() {
+                            self.error = Some(error.to_string());
+                        }
+                    }
+                    refresh_ports = ui.button(Key::Refresh.text(language)).clicked();
+                })
+                .response
+                .on_hover_text(Key::OutputPortHelp.text(language));
+
+                #[cfg(feature = "native")]
+                ui.horizontal(|ui| {
+                    let mut on = self.thru.is_some();
+                    if ui.checkbox(&mut on, Key::Thru.text(language)).changed() {
+                        self.thru = None;
+                        if on {
+                            let preferred = self.preferences.output_port.as_deref();
+                            let thru =
+                                midi_thru::connect(preferred, Arc::clone(&self.thru_channel));
+                            match thru {
+                                Ok(thru) => self.thru = Some(thru),
+                                Err(error) => self.error = Some(error.to_string()),
+                            }
+                        }
+                    }
+
+                    let channel_name = |channel: Option<u8>| match channel {
+                        Some(channel) => Key::Channel.fill(language, channel + 1),
+                        None => Key::KeepChannel.text(language).to_string(),
+                    };
+                    let mut channel = self.thru_channel.get();
+                    egui::ComboBox::from_id_source("thru_channel")
+                        .selected_text(channel_name(channel))
+                        .show_ui(ui, |ui| {
+                            for option in [None].into_iter().chain((0..16).map(Some)) {
+                                ui.selectable_value(&mut channel, option, channel_name(option));
+                            }
+                        });
+                    self.thru_channel.set(channel);
+                })
+                .response
+                .on_hover_text(Key::ThruHelp.text(language));
+
+                ui.horizontal(|ui| {
+                    ui.label(Key::PatchMap.text(language));
+                    match &self.patch_map {
+                        Some((name, map)) => {
+                            ui.label(name.as_str());
+                            ui.label(Key::PatchMapLoaded.fill(language, map.len()));
+                            if ui.button(Key::UnloadPatchMap.text(language)).clicked() {
+                                self.patch_map = None;
+                            }
+                        }
+                        None => {
+                            ui.label(Key::NoPatchMap.text(language));
+                        }
+                    }
+                    if ui.button(Key::LoadPatchMap.text(language)).clicked() {
+                        let mut dialog = FileDialog::open_file(None);
+                        dialog.open();
+                        self.patch_map_dialog = Some(dialog);
+                    }
+                })
+                .response
+                .on_hover_text(Key::PatchMapHelp.text(language));
+
+                egui::CollapsingHeader::new(Key::ChannelRemap.text(language))
+                    .show(ui, |ui| {
+                        let channel_name = |channel: u8| Key::Channel.fill(language, channel + 1);
+                        ui.horizontal(|ui| {
+                            ui.menu_button(Key::AllChannelsTo.text(language), |ui| {
+                                for channel in 0..16 {
+                                    if ui.button(channel_name(channel)).clicked() {
+                                        self.channel_map = ChannelMap::all_to(channel);
+                                        ui.close_menu();
+                                    }
+                                }
+                            });
+                            if ui.button(Key::ResetRemap.text(language)).clicked() {
+                                self.channel_map = ChannelMap::identity();
+                            }
+                        });
+                        egui::Grid::new("channel_map").show(ui, |ui| {
+                            for from in 0..16 {
+                                ui.label(format!("{} →", channel_name(from)));
+                                let mut to = self.channel_map.get(from);
+                                egui::ComboBox::from_id_source(("channel_map", from))
+                                    .selected_text(channel_name(to))
+                                    .show_ui(ui, |ui| {
+                                        for option in 0..16 {
+                                            ui.selectable_value(
+                                                &mut to,
+                                                option,
+                                                channel_name(option),
+                                            );
+                                        }
+                                    });
+                                self.channel_map.set(from, to);
+                                if from % 4 == 3 {
+                                    ui.end_row();
+                                }
+                            }
+                        });
+                    })
+                    .header_response
+                    .on_hover_text(Key::ChannelRemapHelp.text(language));
+            });
+
+        #[cfg(feature = "native")]
+        if refresh_ports {
+            self.refresh_output_ports();
+        }
+        if let Some(preset) = chosen {
+            self.apply_preset(preset);
+        }
+
+        let mut patch_file = None;
+        if let Some(dialog) = &mut self.patch_map_dialog {
+            if dialog.show(ctx).selected() {
+                patch_file = dialog.path().map(Path::to_path_buf);
+            }
+        }
+        let mut groove_file = None;
+        if let Some(dialog) = &mut self.groove_dialog {
+            if dialog.show(ctx).selected() {
+                groove_file = dialog.path().map(Path::to_path_buf);
+            }
+        }
+        let mut preset_file = None;
+        if let Some(dialog) = &mut self.preset_dialog {
+            if dialog.show(ctx).selected() {
+                preset_file = dialog.path().map(Path::to_path_buf);
+            }
+        }
+        if let Some(file) = preset_file {
+            match Preset::load(&file) {
+                Ok(preset) => {
+                    let name = file.file_stem().unwrap_or_default().to_string_lossy();
+                    self.presets.push((name.into_owned(), preset.clone()));
+                    self.apply_preset(preset);
+                }
+                Err(error) => self.error = Some(format!("{}: {error}", file.display())),
+            }
+        }
+        let mut saved_preset = None;
+        if let Some(dialog) = &mut self.preset_save_dialog {
+            if dialog.show(ctx).selected() {
+                saved_preset = dialog.path().map(Path::to_path_buf);
+            }
+        }
+        if let Some(file) = saved_preset {
+            let preset = self.preset();
+            match preset.save(&file) {
+                Ok(()) => {
+                    let name = file.file_stem().unwrap_or_default().to_string_lossy();
+                    self.presets.push((name.into_owned(), preset));
+                }
+                Err(error) => self.error = Some(format!("{}: {error}", file.display())),
+            }
+        }
+        if let Some(file) = groove_file {
+            match Groove::load(&file) {
+                Ok(groove) => {
+                    let name = file.file_stem().unwrap_or_default().to_string_lossy();
+                    self.grooves.push((name.into_owned(), groove.clone()));
+                    self.groove = groove;
+                }
+                Err(error) => self.error = Some(format!("{}: {error}", file.display())),
+            }
+        }
+        if let Some(file) = patch_file {
+            match PatchMap::load(&file) {
+                Ok(map) => {
+                    let name = file.file_name().unwrap_or_default().to_string_lossy();
+                    self.patch_map = Some((name.into_owned(), map));
+                }
+                Err(error) => self.error = Some(format!("{}: {error}", file.display())),
+            }
+        }
+    }
+
+    /// Mostra o registro do que o conversor e o player fizeram, para ser copiado.
+    fn log_window(&mut self, ctx: &Context) {
+        let language = self.language;
+        egui::Window::new(Key::Log.text(language))
+            .open(&mut self.show_log)
+            .show(ctx, |ui| {
+                let lines = logging::lines();
+                ui.horizontal(|ui| {
+                    if ui.button(Key::CopyLog.text(language)).clicked() {
+                        ui.output_mut(|output| output.copied_text = lines.join("\n"));
+                    }
+                    if ui.button(Key::ClearLog.text(language)).clicked() {
+                        logging::clear();
+                    }
+                });
+
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .stick_to_bottom(true)
+                    .show(ui, |ui| {
+                        for line in &lines {
+                            ui.monospace(line);
+                        }
+                    });
+            });
+    }
+
+    /// Mostra as notas do texto em uma grade de passos, que edita o texto ao ser clicada.
+    fn grid_window(&mut self, ctx: &Context) {
+        let language = self.language;
+        let note_names = self.note_names;
+        let mut open = self.show_grid;
+        egui::Window::new(Key::Grid.text(language))
+            .open(&mut open)
+            .show(ctx, |ui| {
+                let tab = &mut self.tabs[self.active];
+                let steps = self
+                    .grid
+                    .steps(&tab.content, &tab.text_actions, &tab.text_sources)
+                    .to_vec();
+                let first = self.grid.page * grid::STEPS;
+
+                ui.horizontal(|ui| {
+                    if ui
+                        .button("◀")
+                        .named(Key::PreviousPage.text(language))
+                        .clicked()
+                    {
+                        self.grid.page = self.grid.page.saturating_sub(1);
+                    }
+                    ui.label(
+                        Key::GridSteps
+                            .fill(language, format!("{}–{}", first + 1, first + grid::STEPS)),
+                    );
+                    if ui.button("▶").named(Key::NextPage.text(language)).clicked() {
+                        self.grid.page += 1;
+                    }
+                });
+
+                let mut toggled = None;
+                egui::Grid::new("step_grid").show(ui, |ui| {
+                    // As notas mais agudas ficam em cima, como em uma partitura
+                    for note in Note::NATURALS.into_iter().rev() {
+                        ui.label(note.to_name(note_names));
+                        for step in first..first + grid::STEPS {
+                            let on = steps.get(step).is_some_and(|s| s.note == Some(note));
+                            if ui
+                                .selectable_label(on, if on { "■" } else { "·" })
+                                .clicked()
+                            {
+                                toggled = Some((step, note));
+                            }
+                        }
+                        ui.end_row();
+                    }
+
+                    // O trecho do texto de cada passo, para ver o que a grade escreve
+                    ui.label("");
+                    for step in first..first + grid::STEPS {
+                        let token = steps
+                            .get(step)
+                            .map_or("", |s| &tab.content[s.source.clone()]);
+                        ui.monospace(token.replace(' ', "␣"));
+                    }
+                    ui.end_row();
+                });
+
+                if let Some((step, note)) = toggled {
+                    grid::toggle(&mut tab.content, &steps, step, note);
+                    tab.content_changed();
+                }
+            });
+        self.show_grid = open;
+    }
+
+    /// Mostra as notas do que está tocando, ou do texto da aba, em um piano roll.
+    fn piano_roll_window(&mut self, ctx: &Context) {
+        let language = self.language;
+        let note_names = self.note_names;
+        let tab = &self.tabs[self.active];
+        let playhead = tab
+            .playhead
+            .as_ref()
+            .filter(|playhead| playhead.is_playing())
+            .map(|playhead| playhead.tick());
+        egui::Window::new(Key::PianoRoll.text(language))
+            .open(&mut self.show_piano_roll)
+            .default_width(600.0)
+            .show(ctx, |ui| {
+                let seek =
+                    self.piano_roll
+                        .show(ui, &tab.playing_timeline, playhead, note_names, language);
+                // Como a lista embaixo da barra de progresso, só pula durante a reprodução
+                if let (Some(tick), Some(_), Some((controls, _))) = (seek, playhead, &tab.live) {
+                    controls.seek(tick);
+                }
+            });
+    }
+
+    /// Mostra as curvas de automação da aba atual, guardando as mudanças no cabeçalho.
+    fn automation_window(&mut self, ctx: &Context) {
+        let language = self.language;
+        let tab = &mut self.tabs[self.active];
+        let playhead = tab
+            .playhead
+            .as_ref()
+            .filter(|playhead| playhead.is_playing())
+            .map(|playhead| playhead.tick());
+        let mut lanes = Header::parse(&tab.content).0.lanes;
+        egui::Window::new(Key::Automation.text(language))
+            .open(&mut self.show_automation)
+            .default_width(600.0)
+            .show(ctx, |ui| {
+                let changed =
+                    self.automation
+                        .show(ui, &mut lanes, tab.playing_ticks, playhead, language);
+                let parameter = self.automation.parameter;
+                if let Some(lane) = lanes.iter().find(|lane| lane.parameter == parameter) {
+                    if changed {
+                        let points = (!lane.points().is_empty()).then(|| lane.to_string());
+                        tab.content = Header::with_entry(
+                            &tab.content,
+                            &format!("lane.{}", parameter.name()),
+                            points.as_deref(),
+                        );
+                        tab.content_changed();
+                    }
+                }
+            });
+    }
+
+    /// Mostra duas versões de um texto lado a lado, para tocá-las em sequência
+    /// ou comparar as ações geradas por cada uma.
+    fn compare_window(&mut self, ctx: &Context) {
+        let language = self.language;
+        let mut open = self.show_compare;
+        egui::Window::new(Key::CompareWindow.text(language))
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.columns(2, |columns| {
+                    for (index, ui) in columns.iter_mut().enumerate() {
+                        ui.horizontal(|ui| {
+                            ui.label(["A", "B"][index]);
+                            if ui.button(Key::Load.text(language)).clicked() {
+                                let mut dialog = FileDialog::open_file(self.tab().file.clone());
+                                dialog.open();
+                                self.compare_dialog = Some((index, dialog));
+                            }
+                            if ui.button(Key::UseCurrent.text(language)).clicked() {
+                                self.compare_texts[index] = self.tabs[self.active].content.clone();
+                            }
+                        });
+                        ui.add(
+                            TextEdit::multiline(&mut self.compare_texts[index])
+                                .desired_rows(8)
+                                .code_editor(),
+                        );
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    if ui.button(Key::PlayAThenB.text(language)).clicked() {
+                        if let Some((mut a, b)) = self.report(self.compare_versions()) {
+                            a.push(MidiAction::Pause);
+                            a.extend(b);
+                            self.start_playback(a);
+                        }
+                    }
+                    if ui.button(Key::Diff.text(language)).clicked() {
+                        if let Some((a, b)) = self.report(self.compare_versions()) {
+                            self.compare_diff = analysis::diff(&a, &b);
+                        }
+                    }
+                });
+
+                egui::ScrollArea::vertical()
+                    .max_height(200.0)
+                    .show(ui, |ui| {
+                        for op in &self.compare_diff {
+                            let text = op.to_string();
+                            match op {
+                                DiffOp::Insert(_) => ui.colored_label(egui::Color32::GREEN, text),
+                                DiffOp::Delete(_) => ui.colored_label(egui::Color32::RED, text),
+                                DiffOp::Equal(_) => ui.label(text),
+                            };
+                        }
+                    });
+            });
+        self.show_compare = open;
+
+        if let Some((index, dialog)) = &mut self.compare_dialog {
+            if dialog.show(ctx).selected() {
+                if let Some(content) = dialog.path().and_then(|p| fs::read_to_string(p).ok()) {
+                    self.compare_texts[*index] = content;
+                }
+            }
+        }
+    }
+
+    /// Mostra as partes da composição, com o compasso de início de cada uma.
+    fn compose_window(&mut self, ctx: &Context) {
+        let language = self.language;
+        let mut open = self.show_compose;
+        egui::Window::new(Key::Compose.text(language))
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button(Key::AddFile.text(language)).clicked() {
+                        let mut dialog = FileDialog::open_file(self.tab().file.clone());
+                        dialog.open();
+                        self.compose_add_dialog = Some(dialog);
+                    }
+                    if ui.button(Key::AddTab.text(language)).clicked() {
+                        if let Some(part) = self.tab_part() {
+                            self.arrangement.append(part);
+                        }
+                    }
+                    let parts = !self.arrangement.parts.is_empty();
+                    if ui
+                        .add_enabled(
+                            parts,
+                            egui::Button::new(Key::SaveComposition.text(language)),
+                        )
+                        .clicked()
+                    {
+                        let mut dialog = FileDialog::save_file(None);
+                        dialog.open();
+                        self.compose_save_dialog = Some(dialog);
+                    }
+                })
+                .response
+                .on_hover_text(Key::ComposeHelp.text(language));
+
+                let mut moved = None;
+                let mut removed = None;
+                egui::Grid::new("arrangement").striped(true).show(ui, |ui| {
+                    let count = self.arrangement.parts.len();
+                    for (index, part) in self.arrangement.parts.iter_mut().enumerate() {
+                        ui.label(part.name.as_str());
+                        ui.label(Key::StartBar.text(language));
+                        ui.add(egui::DragValue::new(&mut part.start_bar).clamp_range(1..=9999))
+                            .named(Key::StartBar.text(language));
+                        ui.label(Key::PartBars.fill(language, part.bars()));
+                        if ui
+                            .add_enabled(index > 0, egui::Button::new("⏶"))
+                            .named(Key::MoveUp.text(language))
+                            .clicked()
+                        {
+                            moved = Some((index, index - 1));
+                        }
+                        if ui
+                            .add_enabled(index + 1 < count, egui::Button::new("⏷"))
+                            .named(Key::MoveDown.text(language))
+                            .clicked()
+                        {
+                            moved = Some((index, index + 1));
+                        }
+                        if ui.button("🗑").named(Key::Remove.text(language)).clicked() {
+                            removed = Some(index);
+                        }
+                        ui.end_row();
+                    }
+                });
+                if let Some((from, to)) = moved {
+                    self.arrangement.parts.swap(from, to);
+                }
+                if let Some(index) = removed {
+                    self.arrangement.parts.remove(index);
+                }
+            });
+        self.show_compose = open;
+
+        let mut added = None;
+        if let Some(dialog) = &mut self.compose_add_dialog {
+            if dialog.show(ctx).selected() {
+                added = dialog.path().map(Path::to_path_buf);
+            }
+        }
+        if let Some(file) = added {
+            match Part::load(&file) {
+                Ok(part) => self.arrangement.append(part),
+                Err(error) => self.error = Some(format!("{}: {error}", file.display())),
+            }
+        }
+
+        let mut saved = None;
+        if let Some(dialog) = &mut self.compose_save_dialog {
+            if dialog.show(ctx).selected() {
+                saved = dialog.path().map(Path::to_path_buf);
+            }
+        }
+        if let Some(mut file) = saved {
+            file.set_extension("mid");
+            let mut smf = self.arrangement.to_smf();
+            self.to_device(&mut smf);
+            let result = smf.save(&file);
+            self.report(result);
+        }
+    }
+
+    /// A aba atual como uma parte, com as transformações e a automação.
+    fn tab_part(&mut self) -> Option<Part> {
+        let tab = self.tab();
+        let timed = self.automated_transforms(&tab.content);
+        let actions = tab.sheet(&tab.content).map(|sheet| {
+            timed(TimedAction::from_actions(&self.transforms()(
+                sheet.process(),
+            )))
+        });
+        let name = tab.name(self.language);
+
+        self.report(actions)
+            .map(|timed| Part::from_timed(name, &timed))
+    }
+
+    /// Os clipes tocando ao vivo, se a reprodução deles ainda não terminou.
+    fn running_launcher(&self) -> Option<&Arc<Launcher>> {
+        // A thread da reprodução guarda a sua cópia até terminar
+        self.launcher
+            .as_ref()
+            .filter(|launcher| Arc::strong_count(launcher) > 1)
+    }
+
+    /// Mostra a grade de clipes do modo ao vivo.
+    fn live_window(&mut self, ctx: &Context) {
+        let language = self.language;
+        for slot in self.pads.take_triggered() {
+            if slot < self.live_clips.len() {
+                self.launch(slot);
+            }
+        }
+        let mut open = self.show_live;
+        egui::Window::new(Key::Live.text(language))
+            .open(&mut open)
+            .show(ctx, |ui| {
+                let running = self.running_launcher().cloned();
+                ui.horizontal(|ui| {
+                    if ui.button(Key::AddFile.text(language)).clicked() {
+                        let mut dialog = FileDialog::open_file(self.tab().file.clone());
+                        dialog.open();
+                        self.live_add_dialog = Some(dialog);
+                    }
+                    if ui.button(Key::AddTab.text(language)).clicked() {
+                        if let Some(part) = self.tab_part() {
+                            self.live_clips.push(part);
+                        }
+                    }
+                    if ui
+                        .add_enabled(
+                            running.is_some(),
+                            egui::Button::new(Key::StopClips.text(language)),
+                        )
+                        .clicked()
+                    {
+                        if let Some(launcher) = &running {
+                            launcher.stop();
+                        }
+                    }
+                })
+                .response
+                .on_hover_text(Key::LiveHelp.text(language));
+
+                #[cfg(feature = "native")]
+                ui.horizontal(|ui| {
+                    let mut on = self.pads_input.is_some();
+                    if ui.checkbox(&mut on, Key::Pads.text(language)).changed() {
+                        self.pads_input = None;
+                        if on {
+                            let ctx = ctx.clone();
+                            let input = launcher::connect_pads(Arc::clone(&self.pads), move || {
+                                ctx.request_repaint()
+                            });
+                            match input {
+                                Ok(input) => self.pads_input = Some(input),
+                                Err(error) => self.error = Some(error.to_string()),
+                            }
+                        }
+                    }
+                    let style = self.note_names;
+                    let name = move |key: f64, _| {
+                        Pitch::from_midi(key as u8)
+                            .map_or(key.to_string(), |pitch| pitch.name(style))
+                    };
+                    let mut base = self.pads.base();
+                    ui.add(
+                        egui::DragValue::new(&mut base)
+                            .clamp_range(0..=127)
+                            .custom_formatter(name),
+                    )
+                    .named(Key::Pads.text(language));
+                    self.pads.set_base(base);
+                })
+                .response
+                .on_hover_text(Key::PadsHelp.text(language));
+
+                let playing = running.as_ref().and_then(|launcher| launcher.playing());
+                let queued = running.as_ref().and_then(|launcher| launcher.queued());
+                let mut launched = None;
+                egui::Grid::new("live clips").show(ui, |ui| {
+                    for (index, clip) in self.live_clips.iter().enumerate() {
+                        let name = if queued == Some(index) {
+                            format!("⏳ {}", clip.name)
+                        } else {
+                            clip.name.clone()
+                        };
+                        let cell = egui::SelectableLabel::new(playing == Some(index), name);
+                        if ui.add_sized([120.0, 48.0], cell).clicked() {
+                            launched = Some(index);
+                        }
+                        if index % 4 == 3 {
+                            ui.end_row();
+                        }
+                    }
+                });
+                if let Some(index) = launched {
+                    self.launch(index);
+                }
+                if running.is_some() {
+                    // A fila e o clipe tocando mudam sem nenhum clique
+                    ctx.request_repaint_after(Duration::from_millis(50));
+                }
+            });
+        self.show_live = open;
+
+        let mut added = None;
+        if let Some(dialog) = &mut self.live_add_dialog {
+            if dialog.show(ctx).selected() {
+                added = dialog.path().map(Path::to_path_buf);
+            }
+        }
+        if let Some(file) = added {
+            match Part::load(&file) {
+                Ok(part) => self.live_clips.push(part),
+                Err(error) => self.error = Some(format!("{}: {error}", file.display())),
+            }
+        }
+    }
+
+    /// Escolhe o clipe dado para tocar ao vivo, começando a reprodução se for o primeiro.
+    fn launch(&mut self, index: usize) {
+        let Some(bytes) = self.for_output(self.live_clips[index].bytes()) else {
+            return;
+        };
+
+        if let Some(launcher) = self.running_launcher() {
+            launcher.queue(index, bytes);
+            return;
+        }
+        let launcher = Arc::new(Launcher::default());
+        launcher.queue(index, bytes);
+        self.launcher = Some(Arc::clone(&launcher));
+
+        #[cfg(feature = "native")]
+        {
+            let options = PlaybackOptions {
+                latency: Duration::from_millis(self.latency_ms),
+                send_clock: self.send_clock,
+                count_in_bars: 0,
+                max_duration: self
+                    .max_playback_secs
+                    .map(|secs| Duration::from_secs(secs.into())),
+            };
+            let preferred = self.preferences.output_port.clone();
+            thread::spawn(move || {
+                let play = || -> Result<(), Box<dyn Error>> {
+                    let port = play::prepare_connection(preferred.as_deref())?;
+                    let clock = SystemClock::new();
+                    launcher.run(port, &clock, options)?;
+                    play::close_connection(&clock);
+                    Ok(())
+                };
+                if let Err(error) = play() {
+                    launcher.playhead().report(error);
+                }
+            });
+        }
 
-use crate::{
-    midi_action::MidiAction,
-    play::{self, play_file},
-    text_to_midi::{self, State},
-};
+        #[cfg(not(feature = "native"))]
+        {
+            drop(launcher);
+            self.error = Some("MIDI output is not available in this build".to_string());
+        }
+    }
 
-#[derive(Default)]
-pub struct UserInterface {
-    opened_file: Option<PathBuf>,
-    saved_file: Option<PathBuf>,
-    open_file_dialog: Option<FileDialog>,
-    saved_file_dialog: Option<FileDialog>,
-    file_content: String,
-    bpm: u16,
-    volume: u16,
-}
+    /// O arquivo dado com o mixer e as configurações do aparelho de saída, se ele for válido.
+    fn for_output(&self, bytes: &[u8]) -> Option<Vec<u8>> {
+        let mut file = Smf::parse(bytes).ok()?;
+        for track in &mut file.tracks {
+            self.mixer.lock().unwrap().apply(track);
+        }
+        self.to_device(&mut file);
+        let mut bytes = Vec::new();
+        file.write_std(&mut bytes)
+            .expect("writing to a vector never fails");
 
-impl UserInterface {
-    pub fn new_interface() -> Self {
-        UserInterface {
-            opened_file: None,
-            saved_file: None,
-            open_file_dialog: None,
-            saved_file_dialog: None,
-            file_content: String::new(),
-            bpm: State::D_BPM,
-            volume: State::D_VOLUME,
+        Some(bytes)
+    }
+
+    /// A playlist, se ela ainda estiver tocando.
+    fn running_playlist(&self) -> Option<&Arc<Playlist>> {
+        // A thread da reprodução guarda a sua cópia até terminar
+        self.playlist
+            .as_ref()
+            .filter(|playlist| Arc::strong_count(playlist) > 1)
+    }
+
+    /// Mostra a playlist: os itens em ordem, o que está tocando e os botões para
+    /// reordená-los.
+    fn playlist_window(&mut self, ctx: &Context) {
+        let language = self.language;
+        let mut open = self.show_playlist;
+        egui::Window::new(Key::Playlist.text(language))
+            .open(&mut open)
+            .show(ctx, |ui| {
+                let running = self.running_playlist().cloned();
+                ui.horizontal(|ui| {
+                    if ui.button(Key::AddFile.text(language)).clicked() {
+                        let mut dialog = FileDialog::open_file(self.tab().file.clone());
+                        dialog.open();
+                        self.playlist_add_dialog = Some(dialog);
+                    }
+                    if ui.button(Key::AddTab.text(language)).clicked() {
+                        if let Some(part) = self.tab_part() {
+                            self.playlist_items.push(part);
+                        }
+                    }
+                })
+                .response
+                .on_hover_text(Key::PlaylistHelp.text(language));
+
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::Slider::new(&mut self.playlist_gap, 0.0..=10.0)
+                            .suffix(" s")
+                            .text(Key::Gap.text(language)),
+                    );
+                    let repeat_name = |repeat| match repeat {
+                        Repeat::Off => Key::RepeatOff.text(language),
+                        Repeat::One => Key::RepeatOne.text(language),
+                        Repeat::All => Key::RepeatAll.text(language),
+                    };
+                    let before = self.playlist_repeat;
+                    egui::ComboBox::from_id_source("playlist repeat")
+                        .selected_text(repeat_name(before))
+                        .show_ui(ui, |ui| {
+                            for repeat in Repeat::ALL {
+                                ui.selectable_value(
+                                    &mut self.playlist_repeat,
+                                    repeat,
+                                    repeat_name(repeat),
+                                );
+                            }
+                        });
+                    if let Some(playlist) =
+                        running.as_ref().filter(|_| before != self.playlist_repeat)
+                    {
+                        playlist.set_repeat(self.playlist_repeat);
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    let mut shuffle = self.playlist_shuffle.is_some();
+                    ui.checkbox(&mut shuffle, Key::Shuffle.text(language));
+                    let mut seed = self.playlist_shuffle.unwrap_or_default();
+                    ui.add_enabled(shuffle, egui::DragValue::new(&mut seed))
+                        .named(Key::Shuffle.text(language));
+                    self.playlist_shuffle = shuffle.then_some(seed);
+                })
+                .response
+                .on_hover_text(Key::ShuffleHelp.text(language));
+
+                ui.horizontal(|ui| {
+                    let can_play = running.is_none() && !self.playlist_items.is_empty();
+                    if ui
+                        .add_enabled(can_play, egui::Button::new(Key::Play.text(language)))
+                        .clicked()
+                    {
+                        self.play_playlist();
+                    }
+                    if ui
+                        .add_enabled(
+                            running.is_some(),
+                            egui::Button::new(Key::StopClips.text(language)),
+                        )
+                        .clicked()
+                    {
+                        if let Some(playlist) = &running {
+                            playlist.stop();
+                        }
+                    }
+                    if let Some(playlist) = &running {
+                        if ui
+                            .button("⏮")
+                            .named(Key::PreviousItem.text(language))
+                            .on_hover_text(Key::PreviousItem.text(language))
+                            .clicked()
+                        {
+                            playlist.previous();
+                        }
+                        if ui
+                            .button("⏭")
+                            .named(Key::NextItem.text(language))
+                            .on_hover_text(Key::NextItem.text(language))
+                            .clicked()
+                        {
+                            playlist.next();
+                        }
+                    }
+                });
+
+                // A lista que toca é uma cópia, então ela só muda com a reprodução parada
+                let current = running.as_ref().and_then(|playlist| playlist.current());
+                let mut moved = None;
+                let mut removed = None;
+                let last = self.playlist_items.len().saturating_sub(1);
+                for (index, item) in self.playlist_items.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        let mark = if current == Some(index) { "▶" } else { " " };
+                        ui.label(format!("{mark} {}. {}", index + 1, item.name));
+                        ui.add_enabled_ui(running.is_none(), |ui| {
+                            if ui
+                                .add_enabled(index > 0, egui::Button::new("⬆"))
+                                .named(Key::MoveUp.text(language))
+                                .clicked()
+                            {
+                                moved = Some((index, index - 1));
+                            }
+                            if ui
+                                .add_enabled(index < last, egui::Button::new("⬇"))
+                                .named(Key::MoveDown.text(language))
+                                .clicked()
+                            {
+                                moved = Some((index, index + 1));
+                            }
+                            if ui.button("🗑").named(Key::Remove.text(language)).clicked() {
+                                removed = Some(index);
+                            }
+                        });
+                    });
+                }
+                if let Some((from, to)) = moved {
+                    self.playlist_items.swap(from, to);
+                }
+                if let Some(index) = removed {
+                    self.playlist_items.remove(index);
+                }
+                if running.is_some() {
+                    // O item tocando muda sem nenhum clique
+                    ctx.request_repaint_after(Duration::from_millis(50));
+                }
+            });
+        self.show_playlist = open;
+
+        let mut added = None;
+        if let Some(dialog) = &mut self.playlist_add_dialog {
+            if dialog.show(ctx).selected() {
+                added = dialog.path().map(Path::to_path_buf);
+            }
+        }
+        if let Some(file) = added {
+            match Part::load(&file) {
+                Ok(part) => self.playlist_items.push(part),
+                Err(error) => self.error = Some(format!("{}: {error}", file.display())),
+            }
+        }
+    }
+
+    /// Toca a playlist do começo, em outra thread.
+    fn play_playlist(&mut self) {
+        let items = self
+            .playlist_items
+            .iter()
+            .filter_map(|item| self.for_output(item.bytes()))
+            .collect();
+        let mut playlist = Playlist::new(items, Duration::from_secs_f32(self.playlist_gap))
+            .with_repeat(self.playlist_repeat);
+        if let Some(seed) = self.playlist_shuffle {
+            playlist = playlist.with_shuffle(seed);
+        }
+        let playlist = Arc::new(playlist);
+        self.playlist = Some(Arc::clone(&playlist));
+
+        #[cfg(feature = "native")]
+        {
+            let options = PlaybackOptions {
+                latency: Duration::from_millis(self.latency_ms),
+                send_clock: self.send_clock,
+                count_in_bars: 0,
+                max_duration: self
+                    .max_playback_secs
+                    .map(|secs| Duration::from_secs(secs.into())),
+            };
+            let preferred = self.preferences.output_port.clone();
+            thread::spawn(move || {
+                let play = || -> Result<(), Box<dyn Error>> {
+                    let port = play::prepare_connection(preferred.as_deref())?;
+                    let clock = SystemClock::new();
+                    playlist.run(port, &clock, options)?;
+                    play::close_connection(&clock);
+                    Ok(())
+                };
+                if let Err(error) = play() {
+                    playlist.playhead().report(error);
+                }
+            });
+        }
+
+        #[cfg(not(feature = "native"))]
+        {
+            drop(playlist);
+            self.error = Some("MIDI output is not available in this build".to_string());
+        }
+    }
+
+    /// As ações das versões A e B da comparação.
+    fn compare_versions(&self) -> Result<(Vec<MidiAction>, Vec<MidiAction>), ConfigError> {
+        Ok((
+            self.actions_for(&self.compare_texts[0])?,
+            self.actions_for(&self.compare_texts[1])?,
+        ))
+    }
+
+    /// Valida as ações e o arquivo gerado, guardando os avisos para exibição.
+    fn check(&mut self, actions: &[MidiAction], file: &Smf<'_>) {
+        let warnings = &mut self.tabs[self.active].warnings;
+        *warnings = validate::validate_actions(actions);
+        warnings.extend(validate::validate_smf(file));
+        self.inspect(file);
+    }
+
+    /// Salva o texto da aba atual no arquivo dado.
+    fn save_text(&mut self, file: PathBuf) {
+        match fs::write(&file, &self.tab().content) {
+            Ok(()) => {
+                self.tab_mut().mark_saved(file);
+                self.error = None;
+            }
+            Err(error) => self.error = Some(error.to_string()),
+        }
+    }
+
+    /// Os trechos com problemas ainda destacados na aba atual, marcando quando o
+    /// destaque acaba na primeira vez em que eles aparecem.
+    fn flashing(&mut self, ctx: &Context) -> Vec<Range<usize>> {
+        let now = ctx.input(|input| input.time);
+        let tab = &mut self.tabs[self.active];
+        let Some((spans, until)) = &mut tab.flash else {
+            return Vec::new();
+        };
+        let until = *until.get_or_insert(now + Self::FLASH_SECONDS);
+        if now >= until {
+            tab.flash = None;
+            return Vec::new();
+        }
+        ctx.request_repaint_after(Duration::from_secs_f64(until - now));
+
+        spans.clone()
+    }
+
+    /// O identificador do editor da aba atual, onde o egui guarda o cursor.
+    fn editor_id(&self) -> egui::Id {
+        egui::Id::new("editor").with(self.active)
+    }
+
+    /// Mostra os botões que inserem os tokens da linguagem no cursor do editor.
+    fn palette(&mut self, ui: &mut egui::Ui) {
+        let language = self.language;
+        let mut clicked = None;
+        ui.horizontal(|ui| {
+            for token in &palette::TOKENS {
+                if ui
+                    .button(language.pick(token.label))
+                    .on_hover_text(language.pick(token.help))
+                    .clicked()
+                {
+                    clicked = Some(token.text);
+                }
+            }
+        });
+        let Some(token) = clicked else {
+            return;
+        };
+
+        let id = self.editor_id();
+        let mut state = TextEdit::load_state(ui.ctx(), id).unwrap_or_default();
+        let tab = &mut self.tabs[self.active];
+        let cursor = state
+            .ccursor_range()
+            .map_or(tab.content.chars().count(), |range| range.primary.index);
+
+        let cursor = palette::insert(&mut tab.content, cursor, token);
+        tab.content_changed();
+
+        // Keeps typing right after the inserted token
+        state.set_ccursor_range(Some(CCursorRange::one(CCursor::new(cursor))));
+        TextEdit::store_state(ui.ctx(), id, state);
+        ui.memory_mut(|memory| memory.request_focus(id));
+    }
+
+    /// Mostra a barra de busca e substituição no texto da aba atual.
+    fn search_bar(&mut self, ui: &mut egui::Ui) {
+        let language = self.language;
+        let mut replace = None;
+        ui.horizontal(|ui| {
+            ui.add(
+                TextEdit::singleline(&mut self.search.query).hint_text(Key::Find.text(language)),
+            );
+            ui.add(
+                TextEdit::singleline(&mut self.search.replacement)
+                    .hint_text(Key::ReplaceWith.text(language)),
+            );
+            ui.checkbox(&mut self.search.regex, Key::Regex.text(language));
+            if ui.button(Key::Replace.text(language)).clicked() {
+                replace = Some(1);
+            }
+            if ui.button(Key::ReplaceAll.text(language)).clicked() {
+                replace = Some(0);
+            }
+        });
+
+        let tab = &mut self.tabs[self.active];
+        if let Some(limit) = replace {
+            if self.search.replace(&mut tab.content, limit).is_ok() {
+                tab.content_changed();
+            }
         }
+
+        match self.search.matches(&tab.content) {
+            Ok(_) if self.search.query.is_empty() => {}
+            Ok(matches) => {
+                ui.label(Key::Matches.fill(language, matches.len()));
+            }
+            Err(error) => {
+                ui.colored_label(egui::Color32::RED, error.to_string());
+            }
+        }
+    }
+
+    /// Mostra as abas, e a confirmação para fechar uma aba com mudanças não salvas.
+    fn tab_bar(&mut self, ui: &mut egui::Ui) {
+        let language = self.language;
+        let mut close = None;
+        ui.horizontal(|ui| {
+            for (index, tab) in self.tabs.iter().enumerate() {
+                ui.selectable_value(&mut self.active, index, tab.title(language));
+                if ui
+                    .small_button("x")
+                    .named(Key::Close.text(language))
+                    .on_hover_text(Key::Close.text(language))
+                    .clicked()
+                {
+                    close = Some(index);
+                }
+            }
+            if ui
+                .button("+")
+                .named(Key::NewTab.text(language))
+                .on_hover_text(Key::NewTab.text(language))
+                .clicked()
+            {
+                self.tabs.push(Tab::default());
+                self.active = self.tabs.len() - 1;
+            }
+        });
+
+        // While a confirmation is open the indices must not change
+        match close.filter(|_| self.closing.is_none()) {
+            Some(index) if self.tabs[index].is_dirty() => self.closing = Some(index),
+            Some(index) => self.close_tab(index),
+            None => {}
+        }
+
+        if let Some(index) = self.closing {
+            let title = self.tabs[index].title(language);
+            egui::Window::new(Key::UnsavedChanges.text(language))
+                .collapsible(false)
+                .resizable(false)
+                .show(ui.ctx(), |ui| {
+                    ui.label(Key::CloseAndDiscard.fill(language, title));
+                    ui.horizontal(|ui| {
+                        if ui.button(Key::Discard.text(language)).clicked() {
+                            self.closing = None;
+                            self.close_tab(index);
+                        }
+                        if ui.button(Key::Cancel.text(language)).clicked() {
+                            self.closing = None;
+                        }
+                    });
+                });
+        }
+    }
+
+    /// Guarda os canais e trilhas do arquivo, para o mixer.
+    fn inspect(&mut self, file: &Smf<'_>) {
+        let language = self.language;
+        self.channels = mixer::channels_in_use(&file.tracks);
+        self.track_names = file
+            .tracks
+            .iter()
+            .enumerate()
+            .map(|(index, track)| {
+                mixer::track_name(track).unwrap_or_else(|| Key::Track.fill(language, index + 1))
+            })
+            .collect();
     }
 }
 
 impl App for UserInterface {
     fn update(&mut self, ctx: &Context, _frame: &mut Frame) {
+        let language = self.language;
+        let now = ctx.input(|input| input.time);
+        if let Some(recorder) = &mut self.recorder {
+            recorder.set_time(now);
+        }
+        // As mudanças do quadro, inclusive as reproduzidas, são gravadas no fim dele
+        let before = self.recorder.is_some().then(|| self.snapshot());
+        self.replay_session(ctx, now);
+        if ctx.input(|input| input.key_pressed(egui::Key::Escape)) {
+            self.panic();
+        }
+        for shortcut in Shortcut::pressed(ctx) {
+            let count = self.tabs.len();
+            match shortcut {
+                Shortcut::Play => self.convert(Purpose::Play),
+                Shortcut::FocusEditor => {
+                    ctx.memory_mut(|memory| memory.request_focus(self.editor_id()));
+                }
+                Shortcut::NextTab => self.active = (self.active + 1) % count,
+                Shortcut::PreviousTab => self.active = (self.active + count - 1) % count,
+            }
+        }
+        if self.high_contrast != self.contrast_applied {
+            ctx.set_visuals(if self.high_contrast {
+                accessibility::high_contrast()
+            } else {
+                egui::Visuals::dark()
+            });
+            self.contrast_applied = self.high_contrast;
+        }
+        if let Some(playback) = self.playback_guard.next(self.is_playing()) {
+            self.spawn_playback(playback);
+        }
+        if self.playback_guard.waiting() > 0 {
+            ctx.request_repaint_after(Duration::from_millis(50));
+        }
+        if let Some(example) = self.tab().example {
+            egui::SidePanel::right("example").show(ctx, |ui| {
+                ui.heading(language.pick(example.name));
+                for (construct, explanation) in example.constructs {
+                    ui.separator();
+                    ui.code(format!("{construct:?}"));
+                    ui.label(language.pick(*explanation));
+                }
+            });
+        }
+
         CentralPanel::default().show(ctx, |ui| {
             ui.horizontal_top(|ui| {
-                if (ui.button("Open")).clicked() {
-                    let mut dialog = FileDialog::open_file(self.opened_file.clone());
+                if (ui.button(Key::Open.text(language))).clicked() {
+                    let mut dialog = FileDialog::open_file(self.tab().file.clone());
                     dialog.open();
                     self.open_file_dialog = Some(dialog);
                 }
 
-                if (ui.button("Play")).clicked() {
-                    let test = text_to_midi::Sheet::new(
-                        self.bpm,
-                        self.volume,
-                        self.file_content.to_string(),
-                    );
-                    let actions = test.process();
-                    let file = MidiAction::as_track(&actions);
-                    let _ = play_file(&file);
+                ui.menu_button(Key::Examples.text(language), |ui| {
+                    for example in &examples::ALL {
+                        if ui.button(language.pick(example.name)).clicked() {
+                            self.tabs.push(Tab::example(example));
+                            self.active = self.tabs.len() - 1;
+                            ui.close_menu();
+                        }
+                    }
+                });
+
+                if ui
+                    .button(Key::SaveText.text(language))
+                    .on_hover_text(Key::SaveTextHelp.text(language))
+                    .clicked()
+                {
+                    match self.tab().file.clone() {
+                        Some(file) => self.save_text(file),
+                        None => {
+                            let mut dialog = FileDialog::save_file(None);
+                            dialog.open();
+                            self.text_file_dialog = Some(dialog);
+                        }
+                    }
+                }
+
+                if self.tab().is_playing() {
+                    if ui.button(Key::Stop.text(language)).clicked() {
+                        self.stop();
+                    }
+                } else if ui.button(Key::Play.text(language)).clicked() {
+                    self.convert(Purpose::Play);
+                }
+                let waiting = self.playback_guard.waiting();
+                if waiting > 0 {
+                    ui.label(Key::Waiting.fill(language, waiting));
+                }
+
+                if ui
+                    .button(Key::Panic.text(language))
+                    .on_hover_text(Key::PanicHelp.text(language))
+                    .clicked()
+                {
+                    self.panic();
                 }
 
-                if (ui.button("Save")).clicked() {
-                    let mut dialog = FileDialog::save_file(self.saved_file.clone());
+                if (ui.button(Key::Save.text(language))).clicked() {
+                    let mut dialog = FileDialog::save_file(self.tab().saved_file.clone());
                     dialog.open();
                     self.saved_file_dialog = Some(dialog);
                 }
 
-                ui.add(egui::Slider::new(&mut self.bpm, 0..=State::MAX_BPM).text("BPM"));
+                if ui
+                    .button(Key::DumpEvents.text(language))
+                    .on_hover_text(Key::DumpEventsHelp.text(language))
+                    .clicked()
+                {
+                    let mut dialog = FileDialog::save_file(None);
+                    dialog.open();
+                    self.events_file_dialog = Some(dialog);
+                }
 
-                ui.add(egui::Slider::new(&mut self.volume, 0..=State::MAX_VOLUME).text("Volume"));
+                if ui
+                    .button(Key::ExportClips.text(language))
+                    .on_hover_text(Key::ExportClipsHelp.text(language))
+                    .clicked()
+                {
+                    let mut dialog = FileDialog::save_file(None);
+                    dialog.open();
+                    self.clips_file_dialog = Some(dialog);
+                }
 
-                if let Some(dialog) = &mut self.open_file_dialog {
-                    if dialog.show(ctx).selected() {
-                        if let Some(file) = dialog.path() {
-                            self.opened_file = Some(file.to_path_buf());
-                            // Read file content and store it
-                            if let Ok(content) = fs::read_to_string(&file) {
-                                self.file_content = content;
+                if ui
+                    .button(Key::ExportStems.text(language))
+                    .on_hover_text(Key::ExportStemsHelp.text(language))
+                    .clicked()
+                {
+                    let mut dialog = FileDialog::select_folder(None);
+                    dialog.open();
+                    self.stems_dialog = Some(dialog);
+                }
+
+                if ui
+                    .button(Key::ImportEvents.text(language))
+                    .on_hover_text(Key::ImportEventsHelp.text(language))
+                    .clicked()
+                {
+                    let mut dialog = FileDialog::open_file(None);
+                    dialog.open();
+                    self.import_file_dialog = Some(dialog);
+                }
+
+                if ui
+                    .button(Key::CopyMidi.text(language))
+                    .on_hover_text(Key::CopyMidiHelp.text(language))
+                    .clicked()
+                {
+                    self.convert(Purpose::Copy);
+                }
+
+                if ui
+                    .button(Key::PasteMidi.text(language))
+                    .on_hover_text(Key::PasteMidiHelp.text(language))
+                    .clicked()
+                {
+                    self.paste_midi();
+                }
+
+                let settings = self.tab().settings();
+                let tab = &mut self.tabs[self.active];
+                ui.add(
+                    egui::Slider::new(&mut tab.bpm, State::LIMITS.bpm)
+                        .text(Key::Bpm.text(language)),
+                );
+
+                ui.add(
+                    egui::Slider::new(&mut tab.volume, State::LIMITS.volume)
+                        .text(Key::Volume.text(language)),
+                );
+
+                egui::ComboBox::from_label(Key::Instrument.text(language))
+                    .selected_text(instruments::name(tab.instrument).unwrap_or_default())
+                    .show_ui(ui, |ui| {
+                        for (program, name) in instruments::GM_NAMES.iter().enumerate() {
+                            ui.selectable_value(&mut tab.instrument, program as u8, *name);
+                        }
+                    });
+
+                if ui
+                    .button("🔊")
+                    .named(Key::AuditionHelp.text(language))
+                    .on_hover_text(Key::AuditionHelp.text(language))
+                    .clicked()
+                {
+                    self.start_playback(instruments::audition(self.tab().instrument));
+                }
+
+                ui.checkbox(
+                    &mut self.tabs[self.active].lyrics,
+                    Key::Lyrics.text(language),
+                );
+                ui.checkbox(
+                    &mut self.tabs[self.active].strict,
+                    Key::Strict.text(language),
+                )
+                .on_hover_text(Key::StrictHelp.text(language));
+
+                let length_name = |length| match length {
+                    BaseLength::Quarter => Key::Quarter.text(language),
+                    BaseLength::Eighth => Key::Eighth.text(language),
+                    BaseLength::Sixteenth => Key::Sixteenth.text(language),
+                };
+                let tab = &mut self.tabs[self.active];
+                egui::ComboBox::from_label(Key::BaseLength.text(language))
+                    .selected_text(length_name(tab.base_length))
+                    .show_ui(ui, |ui| {
+                        for length in BaseLength::ALL {
+                            ui.selectable_value(&mut tab.base_length, length, length_name(length));
+                        }
+                    })
+                    .response
+                    .on_hover_text(Key::BaseLengthHelp.text(language));
+                if tab.settings() != settings {
+                    tab.process_text();
+                }
+
+                if ui
+                    .button(Key::ResetSettings.text(language))
+                    .on_hover_text(Key::ResetSettingsHelp.text(language))
+                    .clicked()
+                {
+                    self.tabs[self.active].reset_settings();
+                }
+
+                ui.toggle_value(&mut self.show_mixer, Key::Mixer.text(language));
+
+                ui.toggle_value(&mut self.show_settings, Key::Settings.text(language));
+
+                ui.toggle_value(&mut self.show_compare, Key::Compare.text(language));
+
+                ui.toggle_value(&mut self.show_compose, Key::Compose.text(language));
+
+                ui.toggle_value(&mut self.show_live, Key::Live.text(language));
+
+                ui.toggle_value(&mut self.show_playlist, Key::Playlist.text(language));
+
+                ui.toggle_value(&mut self.show_search, Key::Find.text(language));
+
+                ui.toggle_value(&mut self.show_grid, Key::Grid.text(language))
+                    .on_hover_text(Key::GridHelp.text(language));
+
+                ui.toggle_value(&mut self.show_piano_roll, Key::PianoRoll.text(language));
+                ui.toggle_value(&mut self.show_automation, Key::Automation.text(language));
+
+                ui.toggle_value(&mut self.show_log, Key::Log.text(language))
+                    .on_hover_text(Key::LogHelp.text(language));
+
+                egui::ComboBox::from_label(Key::NoteNames.text(language))
+                    .selected_text(self.note_names.to_string())
+                    .show_ui(ui, |ui| {
+                        for style in NameStyle::ALL {
+                            ui.selectable_value(&mut self.note_names, style, style.to_string());
+                        }
+                    });
+
+                egui::ComboBox::from_label(Key::Tuning.text(language))
+                    .selected_text(self.tuning.to_string())
+                    .show_ui(ui, |ui| {
+                        for tuning in Tuning::ALL {
+                            ui.selectable_value(&mut self.tuning, tuning, tuning.to_string());
+                        }
+                    });
+
+                egui::ComboBox::from_label(Key::Language.text(language))
+                    .selected_text(self.language.to_string())
+                    .show_ui(ui, |ui| {
+                        for option in Language::ALL {
+                            ui.selectable_value(&mut self.language, option, option.to_string());
+                        }
+                    });
+
+                if ui
+                    .checkbox(&mut self.high_contrast, Key::HighContrast.text(language))
+                    .on_hover_text(Key::HighContrastHelp.text(language))
+                    .changed()
+                {
+                    #[cfg(feature = "native")]
+                    {
+                        self.preferences.high_contrast = self.high_contrast;
+                        if let Err(error) = self.preferences.save() {
+                            self.error = Some(error.to_string());
+                        }
+                    }
+                }
+
+                ui.menu_button(Key::Keyboard.text(language), |ui| {
+                    ui.label(Key::KeyboardHelp.text(language));
+                });
+
+                ui.add(
+                    egui::Slider::new(&mut self.latency_ms, 0..=500)
+                        .text(Key::Latency.text(language))
+                        .suffix(" ms"),
+                );
+                ui.add(
+                    egui::Slider::new(&mut self.playback_rate, 0.25..=4.0)
+                        .logarithmic(true)
+                        .text(Key::PlaybackRate.text(language))
+                        .suffix("×"),
+                )
+                .on_hover_text(Key::PlaybackRateHelp.text(language));
+                ui.horizontal(|ui| {
+                    ui.label(Key::CountIn.text(language));
+                    for (bars, key) in [(0, Key::CountInOff), (1, Key::OneBar), (2, Key::TwoBars)] {
+                        ui.selectable_value(&mut self.count_in_bars, bars, key.text(language));
+                    }
+                });
+                ui.horizontal(|ui| {
+                    let mut limited = self.max_playback_secs.is_some();
+                    if ui
+                        .checkbox(&mut limited, Key::MaxPlayback.text(language))
+                        .on_hover_text(Key::MaxPlaybackHelp.text(language))
+                        .changed()
+                    {
+                        self.max_playback_secs = limited.then_some(600);
+                    }
+                    if let Some(secs) = &mut self.max_playback_secs {
+                        ui.add(
+                            egui::DragValue::new(secs)
+                                .clamp_range(1..=86_400)
+                                .suffix(" s"),
+                        )
+                        .named(Key::MaxPlayback.text(language));
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label(Key::WhilePlaying.text(language))
+                        .on_hover_text(Key::WhilePlayingHelp.text(language));
+                    let overlap = &mut self.playback_guard.overlap;
+                    let mut changed = false;
+                    for (value, key) in [
+                        (Overlap::Reject, Key::OverlapReject),
+                        (Overlap::Queue, Key::OverlapQueue),
+                        (Overlap::Replace, Key::OverlapReplace),
+                    ] {
+                        changed |= ui
+                            .selectable_value(overlap, value, key.text(language))
+                            .changed();
+                    }
+                    if changed {
+                        #[cfg(feature = "native")]
+                        {
+                            self.preferences.overlap = *overlap;
+                            if let Err(error) = self.preferences.save() {
+                                self.error = Some(error.to_string());
                             }
                         }
                     }
+                });
+                ui.checkbox(&mut self.send_clock, Key::SendClock.text(language))
+                    .on_hover_text(Key::SendClockHelp.text(language));
+                #[cfg(feature = "native")]
+                ui.checkbox(&mut self.audio_click, Key::AudioClick.text(language))
+                    .on_hover_text(Key::AudioClickHelp.text(language));
+                ui.checkbox(&mut self.error_sound, Key::ErrorSound.text(language))
+                    .on_hover_text(Key::ErrorSoundHelp.text(language));
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.capture_sent, Key::CaptureSent.text(language))
+                        .on_hover_text(Key::CaptureSentHelp.text(language));
+                    if let Some(capture) = self.capture.as_ref().filter(|c| c.len() > 0) {
+                        let label = Key::SaveCapture.fill(language, capture.len());
+                        if ui.button(label).clicked() {
+                            let mut dialog = FileDialog::save_file(None);
+                            dialog.open();
+                            self.capture_file_dialog = Some(dialog);
+                        }
+                    }
+                });
+                #[cfg(feature = "native")]
+                ui.checkbox(&mut self.follow_clock, Key::FollowClock.text(language))
+                    .on_hover_text(Key::FollowClockHelp.text(language));
+
+                let mut opened_file = None;
+                if let Some(dialog) = &mut self.open_file_dialog {
+                    if dialog.show(ctx).selected() {
+                        opened_file = dialog.path().map(Path::to_path_buf);
+                    }
+                }
+
+                // Read file content and open it in a new tab
+                if let Some((file, content)) = opened_file
+                    .and_then(|file| Some((file.clone(), fs::read_to_string(file).ok()?)))
+                {
+                    // An untouched empty tab is replaced instead of kept around
+                    let tab = self.tab();
+                    if tab.file.is_none() && tab.content.is_empty() {
+                        self.tabs.remove(self.active);
+                    }
+                    self.tabs.push(Tab::open(&file, content));
+                    self.active = self.tabs.len() - 1;
                 }
 
+                let mut text_file = None;
+                if let Some(dialog) = &mut self.text_file_dialog {
+                    if dialog.show(ctx).selected() {
+                        text_file = dialog.path().map(Path::to_path_buf);
+                    }
+                }
+
+                if let Some(file) = text_file {
+                    self.save_text(file);
+                }
+
+                let mut to_save = None;
                 if let Some(dialog) = &mut self.saved_file_dialog {
                     if dialog.show(ctx).selected() {
-                        if let Some(file) = dialog.path() {
-                            self.saved_file = Some(file.to_path_buf());
-                            let test = text_to_midi::Sheet::new(
-                                self.bpm,
-                                self.volume,
-                                self.file_content.to_string(),
-                            );
-                            let actions = test.process();
-                            let midi_file = MidiAction::as_track(&actions);
+                        to_save = dialog.path().map(Path::to_path_buf);
+                    }
+                }
 
-                            if let Some(saved_file) = &self.saved_file {
-                                let mut saved_file = saved_file.clone();
-                                saved_file.set_extension("mid");
-                                let _ = midi_file.save(saved_file);
-                            }
-                        }
+                let mut events_file = None;
+                if let Some(dialog) = &mut self.events_file_dialog {
+                    if dialog.show(ctx).selected() {
+                        events_file = dialog.path().map(Path::to_path_buf);
+                    }
+                }
+
+                if let Some(file) = events_file {
+                    self.convert(Purpose::DumpEvents(file));
+                }
+
+                let mut clips_file = None;
+                if let Some(dialog) = &mut self.clips_file_dialog {
+                    if dialog.show(ctx).selected() {
+                        clips_file = dialog.path().map(Path::to_path_buf);
+                    }
+                }
+
+                if let Some(file) = clips_file {
+                    self.export_clips(&file);
+                }
+
+                let mut stems_directory = None;
+                if let Some(dialog) = &mut self.stems_dialog {
+                    if dialog.show(ctx).selected() {
+                        stems_directory = dialog.path().map(Path::to_path_buf);
+                    }
+                }
+
+                if let Some(directory) = stems_directory {
+                    self.convert(Purpose::Stems(directory));
+                }
+
+                let mut capture_file = None;
+                if let Some(dialog) = &mut self.capture_file_dialog {
+                    if dialog.show(ctx).selected() {
+                        capture_file = dialog.path().map(Path::to_path_buf);
+                    }
+                }
+
+                if let (Some(mut file), Some(capture)) = (capture_file, self.capture.clone()) {
+                    file.set_extension("mid");
+                    self.report(fs::write(file, capture.to_bytes()));
+                }
+
+                let mut import_file = None;
+                if let Some(dialog) = &mut self.import_file_dialog {
+                    if dialog.show(ctx).selected() {
+                        import_file = dialog.path().map(Path::to_path_buf);
                     }
                 }
+
+                if let Some(file) = import_file {
+                    self.import_events(&file);
+                }
+
+                if let Some(file) = to_save {
+                    self.convert(Purpose::Save(file));
+                }
             });
 
+            self.mixer_window(ctx);
+            self.settings_window(ctx);
+            self.compare_window(ctx);
+            self.compose_window(ctx);
+            self.live_window(ctx);
+            self.playlist_window(ctx);
+            self.export_window(ctx);
+            self.log_window(ctx);
+            self.grid_window(ctx);
+            self.piano_roll_window(ctx);
+            self.automation_window(ctx);
+
+            self.tab_bar(ui);
+
+            self.poll_conversions(ui);
+
+            self.update_live_controls();
+            self.show_playback_problems();
+            let tab = self.tab();
+            if let Some(playhead) = tab.playhead.as_ref().filter(|p| p.is_playing()) {
+                let progress = playhead.tick() as f32 / tab.playing_ticks.max(1) as f32;
+                ui.add(egui::ProgressBar::new(progress.min(1.0)));
+                ctx.request_repaint();
+
+                let markers = &tab.playing_timeline.markers;
+                if let (false, Some((controls, _))) = (markers.is_empty(), &tab.live) {
+                    ui.horizontal_wrapped(|ui| {
+                        ui.label(Key::Markers.text(language))
+                            .on_hover_text(Key::MarkersHelp.text(language));
+                        for (tick, name) in markers {
+                            if ui.button(name).clicked() {
+                                controls.seek(*tick);
+                            }
+                        }
+                    });
+                }
+            }
+
+            if let Some((label, bytes)) = self.external_midi.clone() {
+                ui.horizontal(|ui| {
+                    ui.label(format!(
+                        "{label} ({})",
+                        Key::Bytes.fill(language, bytes.len())
+                    ));
+                    if ui.button(Key::Play.text(language)).clicked() {
+                        self.start_playback_bytes(self.active, bytes);
+                    }
+                    if ui.button(Key::Discard.text(language)).clicked() {
+                        self.external_midi = None;
+                    }
+                });
+            }
+
+            if let Some(error) = &self.error {
+                ui.colored_label(egui::Color32::RED, error);
+            }
+
+            for warning in &self.tab().warnings {
+                ui.colored_label(egui::Color32::YELLOW, Key::Warning.fill(language, warning));
+            }
+
+            if self.show_search {
+                self.search_bar(ui);
+            }
+
+            self.palette(ui);
+
+            let editor_id = self.editor_id();
             egui::ScrollArea::vertical()
                 .max_width(f32::INFINITY)
                 .show(ui, |ui| {
                     ui.centered_and_justified(|ui| {
                         //ui.label("File Content:");
-                        ui.text_edit_multiline(&mut self.file_content);
+                        let searching = self.show_search && !self.search.query.is_empty();
+                        let playing = self.tab().playing_source();
+                        let flashing = self.flashing(ui.ctx());
+                        let search = &self.search;
+                        let mut layouter = |ui: &egui::Ui, text: &str, wrap_width: f32| {
+                            let found = if searching {
+                                search.matches(text).unwrap_or_default()
+                            } else {
+                                Vec::new()
+                            };
+                            let mut job = highlight(ui, text, &found, playing.clone(), &flashing);
+                            job.wrap.max_width = wrap_width;
+                            ui.fonts(|fonts| fonts.layout_job(job))
+                        };
+                        let tab = &mut self.tabs[self.active];
+                        let name = Key::Editor.fill(language, tab.title(language));
+                        let mut editor = TextEdit::multiline(&mut tab.content).id(editor_id);
+                        if searching || playing.is_some() || !flashing.is_empty() {
+                            editor = editor.layouter(&mut layouter);
+                        }
+                        let output = editor.show(ui);
+                        output.response.clone().named(&name);
+                        if output.response.changed() {
+                            tab.content_changed();
+                        }
+
+                        // Rola até a nota tocando, mas só quando ela muda
+                        if let Some(range) =
+                            playing.filter(|range| tab.followed.as_ref() != Some(range))
+                        {
+                            let galley = &output.galley;
+                            let index = tab.content[..range.start].chars().count();
+                            let cursor = galley.from_ccursor(CCursor::new(index));
+                            let rect = galley.pos_from_cursor(&cursor);
+                            ui.scroll_to_rect(
+                                rect.translate(output.text_draw_pos.to_vec2()),
+                                Some(egui::Align::Center),
+                            );
+                            tab.followed = Some(range);
+                        }
                     });
                 });
         });
+
+        // As abas fechadas já foram gravadas
+        if let Some(before) = before.filter(|before| before.tabs <= self.tabs.len()) {
+            for action in before.changes(&self.snapshot()) {
+                self.record(action);
+            }
+        }
     }
 }
@@ -0,0 +1,134 @@
+//! Acessibilidade: os nomes lidos pelos leitores de tela, os atalhos de teclado e o
+//! modo de alto contraste.
+//!
+//! Os leitores de tela recebem a árvore dos widgets pelo AccessKit, ligado por padrão
+//! no eframe. Os widgets com texto já têm nome; os que só mostram um símbolo, como
+//! "🗑", ou um número, ganham um aqui.
+
+use eframe::egui::{self, Color32, Context, Modifiers, Response, Stroke, Visuals};
+
+/// Os atalhos de teclado, além do Tab e do Shift+Tab, que passam de um widget ao
+/// próximo, e do Espaço e do Enter, que os acionam.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shortcut {
+    /// F5: toca a aba atual.
+    Play,
+    /// Ctrl+E: volta para o editor.
+    FocusEditor,
+    /// Ctrl+Page Down: a próxima aba.
+    NextTab,
+    /// Ctrl+Page Up: a aba anterior.
+    PreviousTab,
+}
+
+impl Shortcut {
+    const ALL: [(Self, Modifiers, egui::Key); 4] = [
+        (Self::Play, Modifiers::NONE, egui::Key::F5),
+        (Self::FocusEditor, Modifiers::COMMAND, egui::Key::E),
+        (Self::NextTab, Modifiers::COMMAND, egui::Key::PageDown),
+        (Self::PreviousTab, Modifiers::COMMAND, egui::Key::PageUp),
+    ];
+
+    /// Os atalhos pressionados neste quadro, que não chegam aos widgets.
+    pub fn pressed(ctx: &Context) -> Vec<Self> {
+        ctx.input_mut(|input| {
+            Self::ALL
+                .into_iter()
+                .filter(|&(_, modifiers, key)| input.consume_key(modifiers, key))
+                .map(|(shortcut, _, _)| shortcut)
+                .collect()
+        })
+    }
+}
+
+/// Dá nome aos widgets para os leitores de tela.
+pub trait Named {
+    /// O widget, com o nome dado no lugar do texto que ele mostra.
+    fn named(self, name: &str) -> Self;
+}
+
+impl Named for Response {
+    fn named(self, name: &str) -> Self {
+        self.ctx
+            .accesskit_node_builder(self.id, |node| node.set_name(name));
+        self
+    }
+}
+
+/// O visual de alto contraste: texto branco sobre preto, bordas brancas em todos os
+/// widgets e amarelo no widget com o foco do teclado.
+pub fn high_contrast() -> Visuals {
+    let mut visuals = Visuals::dark();
+    visuals.override_text_color = Some(Color32::WHITE);
+    visuals.panel_fill = Color32::BLACK;
+    visuals.window_fill = Color32::BLACK;
+    visuals.extreme_bg_color = Color32::BLACK;
+    visuals.faint_bg_color = Color32::from_gray(24);
+    visuals.code_bg_color = Color32::from_gray(24);
+    visuals.window_stroke = Stroke::new(2.0, Color32::WHITE);
+    visuals.hyperlink_color = Color32::from_rgb(0x80, 0xC0, 0xFF);
+    visuals.selection.bg_fill = Color32::from_rgb(0, 0x40, 0x90);
+    visuals.selection.stroke = Stroke::new(2.0, Color32::YELLOW);
+
+    let widgets = &mut visuals.widgets;
+    let (black, lit) = (Color32::BLACK, Color32::from_gray(40));
+    // O egui mostra o widget com o foco como se ele estivesse sendo clicado
+    for (widget, fill, outline, width) in [
+        (&mut widgets.noninteractive, black, Color32::GRAY, 1.0),
+        (&mut widgets.inactive, black, Color32::WHITE, 1.0),
+        (&mut widgets.open, black, Color32::WHITE, 2.0),
+        (&mut widgets.hovered, lit, Color32::YELLOW, 2.0),
+        (&mut widgets.active, lit, Color32::YELLOW, 3.0),
+    ] {
+        widget.bg_fill = fill;
+        widget.weak_bg_fill = fill;
+        widget.bg_stroke = Stroke::new(width, outline);
+        widget.fg_stroke = Stroke::new(1.5, Color32::WHITE);
+    }
+
+    visuals
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A razão de contraste do WCAG entre duas cores opacas, de 1 a 21.
+    fn contrast(a: Color32, b: Color32) -> f32 {
+        let luminance = |color: Color32| {
+            let channel = |value: u8| {
+                let value = value as f32 / 255.0;
+                if value <= 0.03928 {
+                    value / 12.92
+                } else {
+                    ((value + 0.055) / 1.055).powf(2.4)
+                }
+            };
+            0.2126 * channel(color.r()) + 0.7152 * channel(color.g()) + 0.0722 * channel(color.b())
+        };
+        let (a, b) = (luminance(a), luminance(b));
+
+        (a.max(b) + 0.05) / (a.min(b) + 0.05)
+    }
+
+    #[test]
+    fn high_contrast_meets_wcag_aaa() {
+        let visuals = high_contrast();
+        let text = visuals.text_color();
+
+        // AAA asks for 7:1 for text, and 3:1 for focus indicators
+        for background in [
+            visuals.panel_fill,
+            visuals.window_fill,
+            visuals.faint_bg_color,
+            visuals.selection.bg_fill,
+            visuals.widgets.hovered.bg_fill,
+        ] {
+            assert!(contrast(text, background) >= 7.0, "{background:?}");
+        }
+        let focus = visuals.widgets.active.bg_stroke.color;
+        assert!(contrast(focus, visuals.panel_fill) >= 3.0);
+        // The focused widget stands out from the others by its thicker outline
+        assert!(visuals.widgets.active.bg_stroke.width > visuals.widgets.inactive.bg_stroke.width);
+    }
+}
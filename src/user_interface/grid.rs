@@ -0,0 +1,149 @@
+use std::ops::Range;
+
+use crate::{
+    midi_action::MidiAction,
+    note::{NameStyle, Note},
+};
+
+/// Quantos passos cabem em uma página da grade.
+pub const STEPS: usize = 16;
+
+/// Uma nota ou pausa do texto, vista como um passo da grade.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Step {
+    /// O trecho do texto, em bytes, que gerou o passo.
+    pub source: Range<usize>,
+    /// A nota escrita, `Note::Pause` para um espaço, ou `None` se o passo não veio de
+    /// uma letra, como o `?` e as vogais.
+    pub note: Option<Note>,
+}
+
+/// A grade de passos do texto de uma aba, refeita só quando o texto muda.
+#[derive(Clone, Debug, Default)]
+pub struct StepGrid {
+    /// O texto de onde vieram os passos.
+    text: String,
+    /// Os passos do texto.
+    steps: Vec<Step>,
+    /// A página mostrada, de `STEPS` em `STEPS` passos.
+    pub page: usize,
+}
+
+impl StepGrid {
+    /// Os passos do texto, a partir das suas ações já processadas, refazendo-os se o
+    /// texto mudou.
+    pub fn steps(
+        &mut self,
+        text: &str,
+        actions: &[MidiAction],
+        sources: &[Range<usize>],
+    ) -> &[Step] {
+        if self.text != text {
+            self.text = text.to_string();
+            self.steps = steps(text, actions, sources);
+        }
+        // Sempre dá para ir até a página do próximo passo, para escrever mais notas
+        self.page = self.page.min(self.steps.len() / STEPS);
+
+        &self.steps
+    }
+}
+
+/// Os passos do texto, na ordem em que aparecem nele, a partir das ações e dos seus trechos.
+///
+/// Cada trecho aparece uma vez só, mesmo que seja tocado de novo por um `[DC]` ou `[DS]`.
+pub fn steps(text: &str, actions: &[MidiAction], sources: &[Range<usize>]) -> Vec<Step> {
+    let mut ranges: Vec<Range<usize>> = actions
+        .iter()
+        .zip(sources)
+        .filter(|(action, source)| {
+            matches!(action, MidiAction::PlayNote(_) | MidiAction::Pause) && !source.is_empty()
+        })
+        .map(|(_, source)| source.clone())
+        .collect();
+    ranges.sort_by_key(|range| range.start);
+    ranges.dedup();
+
+    ranges
+        .into_iter()
+        .map(|source| Step {
+            note: text[source.clone()]
+                .chars()
+                .next()
+                .and_then(Note::from_char),
+            source,
+        })
+        .collect()
+}
+
+/// Liga ou desliga a nota no passo dado, editando o texto.
+///
+/// Ligar troca o caractere do passo pela letra da nota, minúscula se ele era minúsculo,
+/// e desligar troca por uma pausa. Um passo depois do último é escrito no fim do texto,
+/// com pausas até ele.
+pub fn toggle(text: &mut String, steps: &[Step], step: usize, note: Note) {
+    let letter = note.to_name(NameStyle::Letter);
+    match steps.get(step) {
+        Some(Step {
+            note: Some(current),
+            source,
+        }) if *current == note => text.replace_range(source.clone(), " "),
+        Some(Step { source, .. }) => {
+            let lowercase = text[source.clone()].starts_with(|c: char| c.is_ascii_lowercase());
+            let letter = if lowercase {
+                letter.to_ascii_lowercase()
+            } else {
+                letter.to_string()
+            };
+            text.replace_range(source.clone(), &letter);
+        }
+        None => {
+            text.push_str(&" ".repeat(step - steps.len()));
+            text.push_str(letter);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{progress::Progress, text_to_midi::Sheet};
+
+    fn grid(text: &str) -> Vec<Step> {
+        let (actions, sources) = Sheet::builder()
+            .text(text)
+            .build()
+            .unwrap()
+            .process_with_sources(&Progress::default())
+            .unwrap();
+        steps(text, &actions, &sources)
+    }
+
+    #[test]
+    fn steps_skip_commands_and_repeats() {
+        let steps = grid("@bpm=90\nCR+ d?[DC]");
+
+        let notes: Vec<Option<Note>> = steps.iter().map(|step| step.note).collect();
+        assert_eq!(
+            notes,
+            [Some(Note::Do), Some(Note::Pause), Some(Note::Re), None]
+        );
+        assert_eq!(steps[2].source, 12..13);
+    }
+
+    /// O texto depois de alternar a nota no passo dado.
+    fn toggled(text: &str, step: usize, note: Note) -> String {
+        let steps = grid(text);
+        let mut text = text.to_string();
+        toggle(&mut text, &steps, step, note);
+        text
+    }
+
+    #[test]
+    fn toggling_edits_the_text() {
+        assert_eq!(toggled("CR+ d", 2, Note::Mi), "CR+ e");
+        assert_eq!(toggled("CR+ d", 0, Note::Do), " R+ d");
+        assert_eq!(toggled("CR+ d", 4, Note::Sol), "CR+ d G");
+        assert_eq!(grid("CR+ d G").len(), 5);
+    }
+}
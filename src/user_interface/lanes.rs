@@ -0,0 +1,220 @@
+use std::ops::RangeInclusive;
+
+use eframe::egui::{self, Align, Color32, Pos2, Rect, Sense, Stroke, Ui, Vec2};
+
+use crate::{
+    automation::{Lane, Parameter},
+    i18n::{Key, Language},
+    midi_action::MidiAction,
+};
+
+/// O editor das curvas de automação, uma por vez, desenhadas com o mouse.
+#[derive(Clone, Debug)]
+pub struct AutomationLanes {
+    /// A curva sendo editada.
+    pub parameter: Parameter,
+    /// Pixels por semimínima.
+    pub zoom: f32,
+    /// Ticks entre os eventos gerados ao longo de cada reta, e a grade dos pontos.
+    pub resolution: u64,
+    /// O ponto sendo arrastado, pela posição na curva.
+    dragging: Option<usize>,
+}
+
+impl Default for AutomationLanes {
+    fn default() -> Self {
+        Self {
+            parameter: Parameter::Volume,
+            zoom: 20.0,
+            resolution: Lane::D_RESOLUTION,
+            dragging: None,
+        }
+    }
+}
+
+impl AutomationLanes {
+    /// Os limites do zoom, em pixels por semimínima.
+    const ZOOM: RangeInclusive<f32> = 2.0..=200.0;
+    /// A altura da área das curvas.
+    const HEIGHT: f32 = 120.0;
+    /// A distância, em pixels, em que um ponto pode ser pego.
+    const GRAB: f32 = 6.0;
+    /// Quantas semínimas sobram depois da música e dos pontos, para continuar a curva.
+    const MARGIN_BEATS: u64 = 8;
+
+    /// O nome do parâmetro na interface.
+    pub fn parameter_name(parameter: Parameter, language: Language) -> &'static str {
+        match parameter {
+            Parameter::Volume => Key::Volume,
+            Parameter::Pan => Key::Pan,
+            Parameter::Tempo => Key::Bpm,
+        }
+        .text(language)
+    }
+
+    /// Mostra os controles e a curva do parâmetro escolhido, criando-a se faltar.
+    ///
+    /// Um clique acrescenta um ponto, arrastar move o ponto e o botão direito o tira.
+    /// Retorna se a curva mudou.
+    pub fn show(
+        &mut self,
+        ui: &mut Ui,
+        lanes: &mut Vec<Lane>,
+        ticks: u64,
+        playhead: Option<u64>,
+        language: Language,
+    ) -> bool {
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_source("automation parameter")
+                .selected_text(Self::parameter_name(self.parameter, language))
+                .show_ui(ui, |ui| {
+                    for parameter in Parameter::ALL {
+                        let name = Self::parameter_name(parameter, language);
+                        if ui
+                            .selectable_value(&mut self.parameter, parameter, name)
+                            .changed()
+                        {
+                            self.dragging = None;
+                        }
+                    }
+                });
+            ui.add(
+                egui::Slider::new(&mut self.zoom, Self::ZOOM)
+                    .logarithmic(true)
+                    .show_value(false)
+                    .text(Key::Zoom.text(language)),
+            );
+            ui.label(Key::Resolution.text(language));
+            ui.add(
+                egui::DragValue::new(&mut self.resolution)
+                    .clamp_range(1..=MidiAction::D_TPQN.as_int() as u64)
+                    .suffix(" ticks"),
+            );
+            if ui.button(Key::ClearLane.text(language)).clicked() {
+                if let Some(lane) = lanes
+                    .iter_mut()
+                    .find(|lane| lane.parameter == self.parameter)
+                {
+                    lane.clear();
+                    changed = true;
+                }
+            }
+        });
+
+        let index = match lanes
+            .iter()
+            .position(|lane| lane.parameter == self.parameter)
+        {
+            Some(index) => index,
+            None => {
+                lanes.push(Lane::new(self.parameter));
+                lanes.len() - 1
+            }
+        };
+        let lane = &mut lanes[index];
+        let (min, max) = lane.parameter.range().into_inner();
+        let tpqn = MidiAction::D_TPQN.as_int() as f32;
+        let last_point = lane.points().last().map_or(0, |point| point.tick);
+        let end = ticks.max(last_point) + Self::MARGIN_BEATS * tpqn as u64;
+
+        egui::ScrollArea::horizontal().show(ui, |ui| {
+            let size = Vec2::new(end as f32 / tpqn * self.zoom, Self::HEIGHT);
+            let (response, painter) = ui.allocate_painter(size, Sense::click_and_drag());
+            let rect = response.rect;
+            let x = |tick: u64| rect.left() + tick as f32 / tpqn * self.zoom;
+            let y = |value: u16| {
+                rect.bottom() - (value - min) as f32 / (max - min) as f32 * (rect.height() - 2.0)
+            };
+            let resolution = self.resolution.max(1);
+            let at = |pos: Pos2| {
+                let tick = ((pos.x - rect.left()).max(0.0) / self.zoom * tpqn) as u64;
+                let tick = (tick + resolution / 2) / resolution * resolution;
+                let level = ((rect.bottom() - pos.y) / (rect.height() - 2.0)).clamp(0.0, 1.0);
+                (tick, min + (level * (max - min) as f32).round() as u16)
+            };
+            let visuals = ui.visuals();
+
+            // Uma linha por tempo, mais forte a cada compasso de 4/4
+            let beat = tpqn as u64;
+            for tick in (0..=end).step_by(beat as usize) {
+                let stroke = if tick % (beat * 4) == 0 {
+                    visuals.widgets.noninteractive.fg_stroke
+                } else {
+                    visuals.widgets.noninteractive.bg_stroke
+                };
+                painter.line_segment(
+                    [
+                        Pos2::new(x(tick), rect.top()),
+                        Pos2::new(x(tick), rect.bottom()),
+                    ],
+                    stroke,
+                );
+            }
+
+            let color = visuals.selection.stroke.color;
+            let points: Vec<Pos2> = lane
+                .points()
+                .iter()
+                .map(|point| Pos2::new(x(point.tick), y(point.value)))
+                .collect();
+            painter.add(egui::Shape::line(points.clone(), Stroke::new(2.0, color)));
+            if let Some(&last) = points.last() {
+                painter.line_segment(
+                    [last, Pos2::new(rect.right(), last.y)],
+                    Stroke::new(1.0, color.gamma_multiply(0.5)),
+                );
+            }
+            for &point in &points {
+                painter.circle_filled(point, 3.5, color);
+            }
+
+            let pointer = response.interact_pointer_pos().or(response.hover_pos());
+            let nearest = pointer.and_then(|pointer| {
+                points
+                    .iter()
+                    .position(|point| point.distance(pointer) <= Self::GRAB)
+            });
+            if let Some(pointer) = pointer {
+                if response.drag_started() {
+                    self.dragging = Some(nearest.unwrap_or_else(|| {
+                        let (tick, value) = at(pointer);
+                        lane.insert(tick, value)
+                    }));
+                    changed = true;
+                } else if response.dragged() {
+                    if let Some(dragged) = self.dragging {
+                        let (tick, value) = at(pointer);
+                        lane.remove(dragged);
+                        self.dragging = Some(lane.insert(tick, value));
+                        changed = true;
+                    }
+                } else if response.clicked() && nearest.is_none() {
+                    let (tick, value) = at(pointer);
+                    lane.insert(tick, value);
+                    changed = true;
+                } else if response.secondary_clicked() {
+                    if let Some(nearest) = nearest {
+                        lane.remove(nearest);
+                        changed = true;
+                    }
+                }
+            }
+            if response.drag_released() {
+                self.dragging = None;
+            }
+
+            if let Some(tick) = playhead {
+                let line = Rect::from_x_y_ranges(x(tick)..=x(tick), rect.y_range());
+                painter.line_segment(
+                    [line.center_top(), line.center_bottom()],
+                    Stroke::new(1.5, Color32::RED),
+                );
+                ui.scroll_to_rect(line, Some(Align::Center));
+            }
+            response.on_hover_text(Key::AutomationHelp.text(language));
+        });
+
+        changed
+    }
+}
@@ -0,0 +1,172 @@
+use crate::i18n::Translated;
+
+/// Um botão da paleta de inserção.
+pub struct Token {
+    /// O que é inserido no texto.
+    pub text: &'static str,
+    /// O que aparece no botão.
+    pub label: Translated,
+    /// O que o token faz, mostrado ao passar o mouse.
+    pub help: Translated,
+}
+
+impl Token {
+    const fn new(text: &'static str, label: Translated, help: Translated) -> Self {
+        Self { text, label, help }
+    }
+}
+
+/// Os tokens da linguagem, na ordem em que aparecem na paleta.
+pub const TOKENS: [Token; 24] = [
+    Token::new("C", ["C", "C"], ["Play C (Do)", "Toca Dó"]),
+    Token::new("D", ["D", "D"], ["Play D (Re)", "Toca Ré"]),
+    Token::new("E", ["E", "E"], ["Play E (Mi)", "Toca Mi"]),
+    Token::new("F", ["F", "F"], ["Play F (Fa)", "Toca Fá"]),
+    Token::new("G", ["G", "G"], ["Play G (Sol)", "Toca Sol"]),
+    Token::new("A", ["A", "A"], ["Play A (La)", "Toca Lá"]),
+    Token::new("B", ["B", "B"], ["Play B (Si)", "Toca Si"]),
+    Token::new(
+        " ",
+        ["Rest", "Pausa"],
+        ["Silence for one note", "Silêncio por uma nota"],
+    ),
+    Token::new(
+        "L:1/8",
+        ["L:1/8", "L:1/8"],
+        [
+            "Make the following notes and rests eighths (1/16 for sixteenths, 1/4 back to quarters)",
+            "Faz das notas e pausas seguintes colcheias (1/16 para semicolcheias, 1/4 volta às semínimas)",
+        ],
+    ),
+    Token::new(
+        "t(CDE)",
+        ["t( )", "t( )"],
+        [
+            "Triplet: three notes in the space of two",
+            "Quiáltera: três notas no espaço de duas",
+        ],
+    ),
+    Token::new("R+", ["R+", "R+"], ["Go up one octave", "Sobe uma oitava"]),
+    Token::new(
+        "R-",
+        ["R-", "R-"],
+        ["Go down one octave", "Desce uma oitava"],
+    ),
+    Token::new(
+        "BPM+",
+        ["BPM+", "BPM+"],
+        ["Increase the BPM by 80", "Aumenta o BPM em 80"],
+    ),
+    Token::new(
+        "?",
+        ["?", "?"],
+        ["Play a random note", "Toca uma nota aleatória"],
+    ),
+    Token::new(
+        "DRONE+",
+        ["DRONE+", "DRONE+"],
+        [
+            "Hold the last note under the melody, on channel 3",
+            "Segura a última nota por baixo da melodia, no canal 3",
+        ],
+    ),
+    Token::new(
+        "DRONE-",
+        ["DRONE-", "DRONE-"],
+        ["Release the held note", "Solta a nota segurada"],
+    ),
+    Token::new(
+        "P+",
+        ["P+", "P+"],
+        [
+            "Press the keys harder (aftertouch), for swells",
+            "Aperta mais as teclas (aftertouch), para crescendos",
+        ],
+    ),
+    Token::new(
+        "P-",
+        ["P-", "P-"],
+        ["Release the key pressure", "Solta a pressão das teclas"],
+    ),
+    Token::new(
+        "X{F0 7E 7F 09 01 F7}",
+        ["SysEx", "SysEx"],
+        [
+            "Send raw SysEx bytes, written in hex from F0 to F7",
+            "Envia bytes SysEx crus, escritos em hexadecimal de F0 a F7",
+        ],
+    ),
+    Token::new(
+        "==Chorus==",
+        ["==Marker==", "==Marcador=="],
+        [
+            "Mark the start of a section, to jump to it during playback",
+            "Marca o começo de uma seção, para pular até ela durante a reprodução",
+        ],
+    ),
+    Token::new(
+        "[Segno]",
+        ["Segno", "Segno"],
+        ["Where [DS] goes back to", "Para onde o [DS] volta"],
+    ),
+    Token::new(
+        "[DC]",
+        ["D.C.", "D.C."],
+        [
+            "Da capo: play again from the beginning, up to [Fine]",
+            "Da capo: toca de novo desde o começo, até o [Fine]",
+        ],
+    ),
+    Token::new(
+        "[DS]",
+        ["D.S.", "D.S."],
+        [
+            "Dal segno: play again from [Segno], up to [Fine]",
+            "Dal segno: toca de novo desde o [Segno], até o [Fine]",
+        ],
+    ),
+    Token::new(
+        "[Fine]",
+        ["Fine", "Fine"],
+        [
+            "Where the music ends after [DC] or [DS]",
+            "Onde a música termina depois do [DC] ou do [DS]",
+        ],
+    ),
+];
+
+/// Insere o token na posição dada, em caracteres, e retorna a posição logo depois dele.
+///
+/// Uma posição depois do fim do texto insere no fim.
+pub fn insert(text: &mut String, cursor: usize, token: &str) -> usize {
+    let index = text
+        .char_indices()
+        .nth(cursor)
+        .map_or(text.len(), |(index, _)| index);
+    text.insert_str(index, token);
+
+    text[..index].chars().count() + token.chars().count()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn inserts_at_char_position() {
+        let mut text = "ÇÃO CD".to_string();
+
+        let cursor = insert(&mut text, 4, "R+");
+
+        assert_eq!(text, "ÇÃO R+CD");
+        assert_eq!(cursor, 6);
+    }
+
+    #[test]
+    fn cursor_past_end_appends() {
+        let mut text = "CD".to_string();
+
+        assert_eq!(insert(&mut text, 10, "BPM+"), 6);
+        assert_eq!(text, "CDBPM+");
+    }
+}
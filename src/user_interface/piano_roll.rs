@@ -0,0 +1,357 @@
+use std::ops::RangeInclusive;
+
+use eframe::egui::{self, Align, Align2, Color32, FontId, Pos2, Rect, Sense, Stroke, Ui, Vec2};
+
+use crate::{
+    analysis::Timeline,
+    i18n::{Key, Language},
+    note::{NameStyle, Pitch},
+};
+
+/// As notas tocadas ao longo do tempo, com zoom e uma régua de compassos, tempos e
+/// marcadores.
+#[derive(Clone, Debug)]
+pub struct PianoRoll {
+    /// Pixels por semimínima.
+    pub zoom: f32,
+    /// A tecla mais grave e a mais aguda mostradas. Sem elas, só as usadas pelas notas.
+    pub keys: Option<(u8, u8)>,
+    /// Se a visualização rola sozinha para acompanhar a reprodução.
+    pub follow: bool,
+    /// O canal mostrado na faixa de dinâmica, embaixo das notas. Sem ele, todos.
+    pub lane_channel: Option<u8>,
+}
+
+impl Default for PianoRoll {
+    fn default() -> Self {
+        Self {
+            zoom: 40.0,
+            keys: None,
+            follow: true,
+            lane_channel: None,
+        }
+    }
+}
+
+impl PianoRoll {
+    /// Os limites do zoom, em pixels por semimínima.
+    const ZOOM: RangeInclusive<f32> = 2.0..=400.0;
+    /// A altura de cada tecla.
+    const ROW_HEIGHT: f32 = 8.0;
+    /// A altura da régua, com os números dos compassos em cima e os marcadores embaixo.
+    const RULER_HEIGHT: f32 = 28.0;
+    /// A altura da faixa de dinâmica, com as velocidades das notas e os volumes.
+    const LANE_HEIGHT: f32 = 48.0;
+    /// Quantas teclas sobram acima e abaixo das notas quando a faixa é automática.
+    const MARGIN_KEYS: u8 = 2;
+
+    /// As teclas mostradas, da mais grave à mais aguda.
+    pub fn key_range(&self, timeline: &Timeline) -> RangeInclusive<u8> {
+        if let Some((low, high)) = self.keys {
+            return low..=high;
+        }
+        let low = timeline
+            .notes
+            .iter()
+            .map(|note| note.key)
+            .min()
+            .unwrap_or(60);
+        let high = timeline
+            .notes
+            .iter()
+            .map(|note| note.key)
+            .max()
+            .unwrap_or(72);
+
+        low.saturating_sub(Self::MARGIN_KEYS)..=high.saturating_add(Self::MARGIN_KEYS).min(127)
+    }
+
+    /// Mostra os controles e as notas, com a posição da reprodução, se houver.
+    ///
+    /// Ctrl+rolagem sobre as notas muda o zoom. Retorna o tick do marcador clicado na
+    /// régua, se houver.
+    pub fn show(
+        &mut self,
+        ui: &mut Ui,
+        timeline: &Timeline,
+        playhead: Option<u64>,
+        style: NameStyle,
+        language: Language,
+    ) -> Option<u64> {
+        let range = self.key_range(timeline);
+        let (mut low, mut high) = (*range.start(), *range.end());
+        let name = move |key: f64, _| {
+            Pitch::from_midi(key as u8).map_or(key.to_string(), |pitch| pitch.name(style))
+        };
+
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.follow, Key::FollowPlayhead.text(language));
+            ui.add(
+                egui::Slider::new(&mut self.zoom, Self::ZOOM)
+                    .logarithmic(true)
+                    .show_value(false)
+                    .text(Key::Zoom.text(language)),
+            )
+            .on_hover_text(Key::ZoomHelp.text(language));
+
+            ui.label(Key::Keys.text(language));
+            let changed = ui
+                .add(
+                    egui::DragValue::new(&mut low)
+                        .clamp_range(0..=127)
+                        .custom_formatter(name),
+                )
+                .changed()
+                | ui.add(
+                    egui::DragValue::new(&mut high)
+                        .clamp_range(0..=127)
+                        .custom_formatter(name),
+                )
+                .changed();
+            if changed {
+                self.keys = Some((low.min(high), high.max(low)));
+            }
+            if ui.button(Key::Fit.text(language)).clicked() {
+                self.keys = None;
+            }
+
+            let channel_name = |channel: Option<u8>| match channel {
+                Some(channel) => Key::Channel.fill(language, channel + 1),
+                None => Key::AllChannels.text(language).to_string(),
+            };
+            egui::ComboBox::from_label(Key::DynamicsLane.text(language))
+                .selected_text(channel_name(self.lane_channel))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.lane_channel, None, channel_name(None));
+                    for channel in timeline.channels() {
+                        let channel = Some(channel);
+                        ui.selectable_value(&mut self.lane_channel, channel, channel_name(channel));
+                    }
+                })
+                .response
+                .on_hover_text(Key::DynamicsLaneHelp.text(language));
+        });
+
+        if timeline.notes.is_empty() {
+            ui.label(Key::PianoRollEmpty.text(language));
+            return None;
+        }
+
+        let tpqn = timeline.tpqn.max(1) as f32;
+        let rows = (high - low + 1) as f32;
+        let output = egui::ScrollArea::both().show(ui, |ui| {
+            let size = Vec2::new(
+                timeline.ticks as f32 / tpqn * self.zoom + Self::RULER_HEIGHT,
+                Self::RULER_HEIGHT + rows * Self::ROW_HEIGHT + Self::LANE_HEIGHT,
+            );
+            let (response, painter) = ui.allocate_painter(size, Sense::hover());
+            let origin = response.rect.min;
+            let x = |tick: u64| origin.x + tick as f32 / tpqn * self.zoom;
+            let y =
+                |key: u8| origin.y + Self::RULER_HEIGHT + (high - key) as f32 * Self::ROW_HEIGHT;
+            let visuals = ui.visuals();
+            let clip = painter.clip_rect();
+
+            // As teclas pretas ficam mais escuras, como em um teclado
+            for key in low..=high {
+                if matches!(key % 12, 1 | 3 | 6 | 8 | 10) {
+                    let row = Rect::from_min_size(
+                        Pos2::new(clip.left(), y(key)),
+                        Vec2::new(clip.width(), Self::ROW_HEIGHT),
+                    );
+                    painter.rect_filled(row, 0.0, visuals.faint_bg_color);
+                }
+            }
+
+            // A régua, com os números dos compassos e as marcas dos tempos
+            let bar = timeline.bar_ticks().max(1);
+            let beat = timeline.beat_ticks().max(1);
+            let beat_width = beat as f32 / tpqn * self.zoom;
+            let bar_width = bar as f32 / tpqn * self.zoom;
+            let label_every = (40.0 / bar_width).ceil().max(1.0) as u64;
+            let first = ((clip.left() - origin.x) / self.zoom * tpqn).max(0.0) as u64;
+            let last = ((clip.right() - origin.x) / self.zoom * tpqn) as u64;
+            let mut tick = first / beat * beat;
+            while tick <= last.min(timeline.ticks) {
+                let is_bar = tick.is_multiple_of(bar);
+                if is_bar || beat_width >= 6.0 {
+                    let (top, stroke) = if is_bar {
+                        (origin.y, visuals.widgets.noninteractive.fg_stroke)
+                    } else {
+                        (
+                            origin.y + Self::RULER_HEIGHT / 2.0,
+                            visuals.widgets.noninteractive.bg_stroke,
+                        )
+                    };
+                    painter.line_segment(
+                        [
+                            Pos2::new(x(tick), top),
+                            Pos2::new(x(tick), response.rect.bottom()),
+                        ],
+                        stroke,
+                    );
+                }
+                if is_bar && (tick / bar).is_multiple_of(label_every) {
+                    painter.text(
+                        Pos2::new(x(tick) + 2.0, origin.y),
+                        Align2::LEFT_TOP,
+                        tick / bar + 1,
+                        FontId::monospace(10.0),
+                        visuals.text_color(),
+                    );
+                }
+                tick += beat;
+            }
+
+            // Os marcadores, como etiquetas que pulam para o seu tick quando clicadas
+            let mut seek = None;
+            for (tick, name) in &timeline.markers {
+                if *tick > last {
+                    continue;
+                }
+                let galley = painter.layout_no_wrap(
+                    name.clone(),
+                    FontId::proportional(10.0),
+                    visuals.strong_text_color(),
+                );
+                let top = origin.y + Self::RULER_HEIGHT / 2.0;
+                let rect =
+                    Rect::from_min_size(Pos2::new(x(*tick), top), galley.size() + Vec2::X * 4.0);
+                if rect.right() < clip.left() {
+                    continue;
+                }
+                let marker = ui
+                    .interact(rect, response.id.with(tick), Sense::click())
+                    .on_hover_text(Key::MarkersHelp.text(language));
+                let fill = if marker.hovered() {
+                    visuals.selection.bg_fill
+                } else {
+                    visuals.widgets.inactive.bg_fill
+                };
+                painter.rect_filled(rect, 2.0, fill);
+                painter.galley(rect.min + Vec2::X * 2.0, galley);
+                if marker.clicked() {
+                    seek = Some(*tick);
+                }
+            }
+
+            for note in &timeline.notes {
+                if !range.contains(&note.key) || note.end < first || note.start > last {
+                    continue;
+                }
+                let rect = Rect::from_min_max(
+                    Pos2::new(x(note.start), y(note.key)),
+                    Pos2::new(
+                        x(note.end).max(x(note.start) + 1.0),
+                        y(note.key) + Self::ROW_HEIGHT,
+                    ),
+                );
+                let color = Self::channel_color(note.channel)
+                    .gamma_multiply(0.4 + note.velocity as f32 / 127.0 * 0.6);
+                painter.rect_filled(rect.shrink(0.5), 1.0, color);
+            }
+
+            // A faixa de dinâmica: uma haste por nota, da altura da velocidade,
+            // e o volume do canal como uma escada
+            let lane = Rect::from_min_max(
+                Pos2::new(origin.x, response.rect.bottom() - Self::LANE_HEIGHT),
+                response.rect.right_bottom(),
+            );
+            let level = |value: u8| lane.bottom() - value as f32 / 127.0 * (lane.height() - 2.0);
+            painter.line_segment(
+                [lane.left_top(), lane.right_top()],
+                visuals.widgets.noninteractive.fg_stroke,
+            );
+            let shown = |channel: u8| self.lane_channel.is_none_or(|lane| lane == channel);
+            for note in timeline.notes.iter().filter(|note| shown(note.channel)) {
+                if note.start < first || note.start > last {
+                    continue;
+                }
+                let top = Pos2::new(x(note.start), level(note.velocity));
+                let color = Self::channel_color(note.channel);
+                painter.line_segment(
+                    [top, Pos2::new(top.x, lane.bottom())],
+                    Stroke::new(1.0, color),
+                );
+                painter.circle_filled(top, 2.0, color);
+            }
+            for channel in timeline.channels().into_iter().filter(|&c| shown(c)) {
+                let changes: Vec<_> = timeline
+                    .volumes
+                    .iter()
+                    .filter(|change| change.channel == channel)
+                    .collect();
+                let stroke = Stroke::new(1.5, Self::channel_color(channel).gamma_multiply(0.6));
+                for (index, change) in changes.iter().enumerate() {
+                    let end = changes
+                        .get(index + 1)
+                        .map_or(timeline.ticks, |next| next.tick);
+                    let y = level(change.volume);
+                    painter
+                        .line_segment([Pos2::new(x(change.tick), y), Pos2::new(x(end), y)], stroke);
+                    if let Some(next) = changes.get(index + 1) {
+                        painter.line_segment(
+                            [Pos2::new(x(end), y), Pos2::new(x(end), level(next.volume))],
+                            stroke,
+                        );
+                    }
+                }
+            }
+
+            if let Some(tick) = playhead {
+                let line = Rect::from_x_y_ranges(x(tick)..=x(tick), response.rect.y_range());
+                painter.line_segment(
+                    [line.center_top(), line.center_bottom()],
+                    Stroke::new(1.5, Color32::RED),
+                );
+                if self.follow {
+                    ui.scroll_to_rect(line, Some(Align::Center));
+                }
+            }
+
+            if response.hovered() {
+                let zoom = ui.input(|input| input.zoom_delta());
+                if zoom != 1.0 {
+                    self.zoom = (self.zoom * zoom).clamp(*Self::ZOOM.start(), *Self::ZOOM.end());
+                }
+            }
+
+            seek
+        });
+
+        output.inner
+    }
+
+    /// Uma cor para cada canal, espalhadas pelo círculo de cores.
+    fn channel_color(channel: u8) -> Color32 {
+        let hue = (channel as f32 * 0.382).fract();
+        egui::ecolor::Hsva::new(hue, 0.7, 0.9, 1.0).into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::analysis::NoteSpan;
+
+    #[test]
+    fn key_range_fits_the_notes() {
+        let note = |key| NoteSpan {
+            channel: 0,
+            key,
+            velocity: 100,
+            start: 0,
+            end: 480,
+        };
+        let timeline = Timeline {
+            notes: vec![note(60), note(67), note(126)],
+            ..Timeline::default()
+        };
+        let mut roll = PianoRoll::default();
+
+        assert_eq!(roll.key_range(&timeline), 58..=127);
+
+        roll.keys = Some((48, 72));
+        assert_eq!(roll.key_range(&timeline), 48..=72);
+    }
+}
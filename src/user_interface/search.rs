@@ -0,0 +1,199 @@
+use std::ops::Range;
+
+use eframe::egui::{
+    text::{LayoutJob, TextFormat},
+    Color32, TextStyle, Ui,
+};
+use regex::Regex;
+
+/// Busca e substituição no texto do editor.
+#[derive(Clone, Debug, Default)]
+pub struct Search {
+    /// O que procurar.
+    pub query: String,
+    /// Pelo que substituir.
+    pub replacement: String,
+    /// Se `query` é uma expressão regular, e `replacement` pode usar `$1`, `$nome`, etc.
+    pub regex: bool,
+}
+
+impl Search {
+    /// A expressão que encontra `query`.
+    fn pattern(&self) -> Result<Regex, regex::Error> {
+        if self.regex {
+            Regex::new(&self.query)
+        } else {
+            Regex::new(&regex::escape(&self.query))
+        }
+    }
+
+    /// As posições, em bytes, de cada ocorrência no texto.
+    ///
+    /// Ocorrências vazias são ignoradas, já que não há o que destacar nelas.
+    pub fn matches(&self, text: &str) -> Result<Vec<Range<usize>>, regex::Error> {
+        if self.query.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        Ok(self
+            .pattern()?
+            .find_iter(text)
+            .map(|found| found.range())
+            .filter(|range| !range.is_empty())
+            .collect())
+    }
+
+    /// Substitui as primeiras `limit` ocorrências no texto, ou todas se `limit` for 0.
+    ///
+    /// Só as ocorrências do `matches` são substituídas: as vazias, que uma expressão
+    /// como `C*` acha entre cada caractere, ficam como estão. Retorna quantas foram
+    /// substituídas.
+    pub fn replace(&self, text: &mut String, limit: usize) -> Result<usize, regex::Error> {
+        if self.query.is_empty() {
+            return Ok(0);
+        }
+
+        let limit = if limit == 0 { usize::MAX } else { limit };
+        let mut replaced = String::with_capacity(text.len());
+        let mut last = 0;
+        let mut count = 0;
+        for captures in self
+            .pattern()?
+            .captures_iter(text)
+            .filter(|captures| !captures[0].is_empty())
+            .take(limit)
+        {
+            let found = captures.get(0).expect("the whole match is always captured");
+            replaced.push_str(&text[last..found.start()]);
+            if self.regex {
+                captures.expand(&self.replacement, &mut replaced);
+            } else {
+                replaced.push_str(&self.replacement);
+            }
+            last = found.end();
+            count += 1;
+        }
+        if count > 0 {
+            replaced.push_str(&text[last..]);
+            *text = replaced;
+        }
+
+        Ok(count)
+    }
+}
+
+/// O texto com as ocorrências da busca, a nota tocando e os trechos com problemas
+/// destacados, para ser mostrado pelo editor.
+///
+/// `found` são as ocorrências, em ordem e sem se sobrepor, como as de `Search::matches`.
+/// Os problemas, em `flashing`, ficam por cima dos outros destaques.
+pub fn highlight(
+    ui: &Ui,
+    text: &str,
+    found: &[Range<usize>],
+    playing: Option<Range<usize>>,
+    flashing: &[Range<usize>],
+) -> LayoutJob {
+    let normal = TextFormat {
+        font_id: TextStyle::Body.resolve(ui.style()),
+        color: ui.visuals().text_color(),
+        ..TextFormat::default()
+    };
+    let match_format = TextFormat {
+        background: Color32::from_rgb(120, 100, 0),
+        ..normal.clone()
+    };
+    let playing_format = TextFormat {
+        background: Color32::from_rgb(0, 90, 130),
+        ..normal.clone()
+    };
+    let flashing_format = TextFormat {
+        background: Color32::from_rgb(150, 20, 20),
+        ..normal.clone()
+    };
+
+    // Os pontos onde o destaque pode mudar
+    let mut cuts: Vec<usize> = found
+        .iter()
+        .chain(&playing)
+        .chain(flashing)
+        .flat_map(|range| [range.start.min(text.len()), range.end.min(text.len())])
+        .chain([0, text.len()])
+        .collect();
+    cuts.sort_unstable();
+    cuts.dedup();
+
+    let mut job = LayoutJob::default();
+    let mut matches = found.iter().peekable();
+    for piece in cuts.windows(2) {
+        let (start, end) = (piece[0], piece[1]);
+        while matches.next_if(|range| range.end <= start).is_some() {}
+
+        let format = if flashing.iter().any(|range| range.contains(&start)) {
+            &flashing_format
+        } else if playing.as_ref().is_some_and(|range| range.contains(&start)) {
+            &playing_format
+        } else if matches.peek().is_some_and(|range| range.start <= start) {
+            &match_format
+        } else {
+            &normal
+        };
+        job.append(&text[start..end], 0.0, format.clone());
+    }
+    if text.is_empty() {
+        job.append("", 0.0, normal);
+    }
+
+    job
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn search(query: &str, replacement: &str, regex: bool) -> Search {
+        Search {
+            query: query.to_string(),
+            replacement: replacement.to_string(),
+            regex,
+        }
+    }
+
+    #[test]
+    fn literal_search_escapes_the_query() {
+        let search = search("C+", "D$1", false);
+        let mut text = "C+ C C+".to_string();
+
+        assert_eq!(search.matches(&text).unwrap(), [0..2, 5..7]);
+        assert_eq!(search.replace(&mut text, 0).unwrap(), 2);
+        assert_eq!(text, "D$1 C D$1");
+    }
+
+    #[test]
+    fn regex_replacement_uses_groups() {
+        let search = search("([A-G])([A-G])", "$2$1", true);
+        let mut text = "CD EF".to_string();
+
+        assert_eq!(search.replace(&mut text, 1).unwrap(), 1);
+        assert_eq!(text, "DC EF");
+    }
+
+    #[test]
+    fn empty_matches_are_not_replaced() {
+        let search = search("C*", "x", true);
+        let mut text = "CCDEC".to_string();
+
+        assert_eq!(search.matches(&text).unwrap(), [0..2, 4..5]);
+        assert_eq!(search.replace(&mut text, 0).unwrap(), 2);
+        assert_eq!(text, "xDEx");
+        assert_eq!(search.replace(&mut text, 0).unwrap(), 0);
+        assert_eq!(text, "xDEx");
+    }
+
+    #[test]
+    fn invalid_regex_is_an_error() {
+        assert!(search("(", "", true).matches("C").is_err());
+        assert!(search("(", "", false).matches("C(").is_ok());
+        assert!(search("", "", false).matches("C").unwrap().is_empty());
+    }
+}
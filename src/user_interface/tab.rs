@@ -0,0 +1,341 @@
+use std::{
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    analysis::Timeline,
+    conversion::Job,
+    examples::Example,
+    header::Header,
+    i18n::{Key, Language},
+    midi_action::MidiAction,
+    play::{LiveControls, Playhead},
+    session::Settings,
+    text_to_midi::{
+        BaseLength, ConfigError, IncrementalSheet, ParseMode, Sheet, SheetBuilder, State,
+    },
+    validate::Warning,
+};
+
+/// Um texto aberto no editor, com as suas próprias configurações e reprodução.
+pub struct Tab {
+    /// O arquivo de onde o texto veio, ou onde ele foi salvo por último.
+    pub file: Option<PathBuf>,
+    /// O último arquivo MIDI salvo a partir do texto.
+    pub saved_file: Option<PathBuf>,
+    /// O texto sendo editado.
+    pub content: String,
+    /// O texto como está no disco, para saber se há mudanças não salvas.
+    saved_content: String,
+    pub bpm: u16,
+    pub volume: u16,
+    pub instrument: u8,
+    pub lyrics: bool,
+    /// Se os caracteres que não significam nada são um erro, em vez de ignorados.
+    pub strict: bool,
+    /// A figura das notas antes da primeira diretiva `L:` do texto.
+    pub base_length: BaseLength,
+    /// Os avisos da última conversão.
+    pub warnings: Vec<Warning>,
+    /// A partitura do texto, reprocessada a cada edição, com as configurações de quando
+    /// foi criada. `None` se as configurações não são válidas.
+    incremental: Option<(Settings, IncrementalSheet)>,
+    /// As ações do texto, atualizadas a cada edição, para a grade de passos.
+    pub text_actions: Vec<MidiAction>,
+    /// O trecho do texto de cada uma das `text_actions`.
+    pub text_sources: Vec<Range<usize>>,
+    /// A posição da reprodução em andamento, se houver.
+    pub playhead: Option<Arc<Playhead>>,
+    /// A duração, em ticks, do que está sendo tocado.
+    pub playing_ticks: u64,
+    /// As notas e os marcadores do que está sendo tocado, para o piano roll e para
+    /// pular até os marcadores. Sem reprodução, são os do texto, atualizados a cada edição.
+    pub playing_timeline: Timeline,
+    /// O tick em que começa cada nota tocada, junto do trecho do texto que a gerou,
+    /// para o editor acompanhar a reprodução. Vazio quando o que toca não veio do texto.
+    pub playing_notes: Vec<(u64, Range<usize>)>,
+    /// O último trecho para onde o editor rolou, para só rolar de novo quando a nota mudar
+    /// e deixar o usuário rolar à vontade enquanto ela toca.
+    pub followed: Option<Range<usize>>,
+    /// Os ajustes ao vivo da reprodução em andamento, junto do BPM com que ela começou.
+    pub live: Option<(Arc<LiveControls>, u16)>,
+    /// A conversão em andamento, se houver.
+    pub conversion: Option<Job>,
+    /// Os trechos do texto com problemas, destacados no editor até o instante dado,
+    /// em segundos do relógio da interface, que só é marcado quando eles aparecem.
+    pub flash: Option<(Vec<Range<usize>>, Option<f64>)>,
+    /// O exemplo de onde o texto veio, cuja explicação é mostrada ao lado do editor.
+    pub example: Option<&'static Example>,
+}
+
+impl Default for Tab {
+    fn default() -> Self {
+        Self {
+            file: None,
+            saved_file: None,
+            content: String::new(),
+            saved_content: String::new(),
+            bpm: State::D_BPM,
+            volume: State::D_VOLUME,
+            instrument: 0,
+            lyrics: false,
+            strict: false,
+            base_length: BaseLength::default(),
+            warnings: Vec::new(),
+            incremental: None,
+            text_actions: Vec::new(),
+            text_sources: Vec::new(),
+            playhead: None,
+            playing_ticks: 0,
+            playing_timeline: Timeline::default(),
+            playing_notes: Vec::new(),
+            followed: None,
+            live: None,
+            conversion: None,
+            flash: None,
+            example: None,
+        }
+    }
+}
+
+impl Tab {
+    /// Uma aba com o texto lido do arquivo dado.
+    pub fn open(file: &Path, content: String) -> Self {
+        let mut tab = Self {
+            file: Some(file.to_path_buf()),
+            saved_content: content.clone(),
+            content,
+            ..Self::default()
+        };
+        tab.content_changed();
+        tab
+    }
+
+    /// Uma aba com o texto dado, ainda não salvo em nenhum arquivo.
+    pub fn unsaved(content: String) -> Self {
+        let mut tab = Self {
+            content,
+            ..Self::default()
+        };
+        tab.content_changed();
+        tab
+    }
+
+    /// Uma aba com o texto de um exemplo.
+    pub fn example(example: &'static Example) -> Self {
+        let mut tab = Self {
+            content: example.text.to_string(),
+            saved_content: example.text.to_string(),
+            example: Some(example),
+            ..Self::default()
+        };
+        tab.content_changed();
+        tab
+    }
+
+    /// O nome do arquivo da aba, ou do exemplo de onde ela veio.
+    pub fn name(&self, language: Language) -> String {
+        let untitled = match self.example {
+            Some(example) => language.pick(example.name),
+            None => Key::Untitled.text(language),
+        };
+        self.file
+            .as_deref()
+            .and_then(Path::file_name)
+            .map_or(untitled.into(), |name| name.to_string_lossy())
+            .into_owned()
+    }
+
+    /// O nome mostrado na aba, marcado com `*` se houver mudanças não salvas.
+    pub fn title(&self, language: Language) -> String {
+        let name = self.name(language);
+        if self.is_dirty() {
+            format!("{name}*")
+        } else {
+            name
+        }
+    }
+
+    /// Se o texto mudou desde que foi aberto ou salvo.
+    pub fn is_dirty(&self) -> bool {
+        self.content != self.saved_content
+    }
+
+    /// Registra que o texto atual foi salvo no arquivo dado.
+    pub fn mark_saved(&mut self, file: PathBuf) {
+        self.file = Some(file);
+        self.saved_content = self.content.clone();
+    }
+
+    /// Atualiza o que depende do texto depois que ele foi editado.
+    pub fn content_changed(&mut self) {
+        self.load_header();
+        // Os trechos mudaram de lugar
+        self.flash = None;
+        self.process_text();
+    }
+
+    /// Atualiza as ações do texto, reprocessando só a partir da primeira mudança, e o
+    /// piano roll se nada estiver tocando. Com outras configurações, reprocessa tudo.
+    pub fn process_text(&mut self) {
+        let settings = self.settings();
+        if self.incremental.as_ref().map(|&(old, _)| old) != Some(settings) {
+            self.incremental = IncrementalSheet::new(self.builder())
+                .ok()
+                .map(|incremental| (settings, incremental));
+        }
+        (self.text_actions, self.text_sources) = match &mut self.incremental {
+            Some((_, incremental)) => incremental.update(&self.content),
+            None => Default::default(),
+        };
+
+        if !self.is_playing() {
+            self.playing_timeline = Timeline::from_smf(&MidiAction::as_track(&self.text_actions));
+        }
+    }
+
+    /// Preenche as configurações da aba com os valores do cabeçalho do texto, se houver.
+    pub fn load_header(&mut self) {
+        let (header, _) = Header::parse(&self.content);
+        if let Some(bpm) = header.bpm {
+            self.bpm = bpm;
+        }
+        if let Some(volume) = header.volume {
+            self.volume = volume;
+        }
+        if let Some(instrument) = header.instrument {
+            self.instrument = instrument;
+        }
+    }
+
+    /// Volta as configurações da aba para as padrão, sem mexer no texto.
+    pub fn reset_settings(&mut self) {
+        let defaults = Self::default();
+        self.bpm = defaults.bpm;
+        self.volume = defaults.volume;
+        self.instrument = defaults.instrument;
+        self.lyrics = defaults.lyrics;
+        self.strict = defaults.strict;
+        self.base_length = defaults.base_length;
+        self.process_text();
+    }
+
+    /// As configurações da aba, como gravadas no diário da sessão.
+    pub fn settings(&self) -> Settings {
+        Settings {
+            bpm: self.bpm,
+            volume: self.volume,
+            instrument: self.instrument,
+            lyrics: self.lyrics,
+            strict: self.strict,
+            base_length: self.base_length,
+        }
+    }
+
+    /// Troca as configurações da aba pelas dadas.
+    pub fn set_settings(&mut self, settings: Settings) {
+        self.bpm = settings.bpm;
+        self.volume = settings.volume;
+        self.instrument = settings.instrument;
+        self.lyrics = settings.lyrics;
+        self.strict = settings.strict;
+        self.base_length = settings.base_length;
+        self.process_text();
+    }
+
+    /// Se a reprodução da aba ainda não terminou, contando a espera para fechar a porta.
+    pub fn is_playing(&self) -> bool {
+        // A thread da reprodução guarda a sua cópia até terminar
+        self.playhead
+            .as_ref()
+            .is_some_and(|playhead| Arc::strong_count(playhead) > 1)
+    }
+
+    /// O trecho do texto da nota tocando agora, se houver.
+    pub fn playing_source(&self) -> Option<Range<usize>> {
+        let tick = self.playhead.as_ref().filter(|p| p.is_playing())?.tick();
+        let index = self
+            .playing_notes
+            .partition_point(|(start, _)| *start <= tick)
+            .checked_sub(1)?;
+        let range = self.playing_notes[index].1.clone();
+
+        // O texto pode ter sido editado depois que a reprodução começou
+        self.content.get(range.clone()).map(|_| range)
+    }
+
+    /// Cria a partitura com o texto dado e as configurações da aba.
+    pub fn sheet(&self, text: &str) -> Result<Sheet, ConfigError> {
+        self.builder().text(text).build()
+    }
+
+    /// As configurações da aba para uma partitura, ainda sem o texto.
+    fn builder(&self) -> SheetBuilder {
+        Sheet::builder()
+            .bpm(self.bpm)
+            .volume(self.volume)
+            .instrument(self.instrument)
+            .lyrics(self.lyrics)
+            .mode(if self.strict {
+                ParseMode::Strict
+            } else {
+                ParseMode::Lenient
+            })
+            .base_length(self.base_length)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::examples;
+
+    #[test]
+    fn editing_marks_dirty() {
+        let mut tab = Tab::open(Path::new("/songs/theme.txt"), "CDE".to_string());
+        assert_eq!(tab.title(Language::English), "theme.txt");
+
+        tab.content.push('F');
+        assert!(tab.is_dirty());
+        assert_eq!(tab.title(Language::English), "theme.txt*");
+
+        tab.mark_saved(PathBuf::from("/songs/theme2.txt"));
+        assert!(!tab.is_dirty());
+        assert_eq!(tab.title(Language::English), "theme2.txt");
+    }
+
+    #[test]
+    fn opening_reads_the_header() {
+        let mut tab = Tab::open(Path::new("song.txt"), "@bpm=90 @volume=70\nCDE".to_string());
+
+        assert_eq!((tab.bpm, tab.volume), (90, 70));
+        tab.reset_settings();
+        assert_eq!((tab.bpm, tab.volume), (State::D_BPM, State::D_VOLUME));
+        assert!(tab.content.ends_with("CDE"));
+        assert_eq!(Tab::default().title(Language::Portuguese), "Sem título");
+        assert_eq!(
+            Tab::example(&examples::ALL[0]).title(Language::English),
+            "Scales"
+        );
+    }
+
+    #[test]
+    fn edits_reach_the_piano_roll() {
+        let mut tab = Tab::unsaved("CDE".to_string());
+        assert_eq!(tab.playing_timeline.notes.len(), 3);
+
+        tab.content.push_str(" FG");
+        tab.content_changed();
+        assert_eq!(tab.playing_timeline.notes.len(), 5);
+        let full = tab.sheet(&tab.content).unwrap().process();
+        assert_eq!(tab.text_actions, full);
+
+        tab.strict = true;
+        tab.base_length = BaseLength::Eighth;
+        tab.process_text();
+        let full = tab.sheet(&tab.content).unwrap().process();
+        assert_eq!(tab.text_actions, full);
+    }
+}
@@ -0,0 +1,333 @@
+use std::fmt;
+
+use midly::{MetaMessage, MidiMessage, Smf, TrackEventKind};
+
+use crate::{
+    header::{Header, HeaderError},
+    midi_action::MidiAction,
+    time_state::{TempoError, TimeSignature, TimeState},
+};
+
+/// O maior valor representável em um byte de dados do MIDI.
+const MAX_DATA_VALUE: u16 = 127;
+
+/// O maior valor de pitch bend, que tem 14 bits.
+const MAX_PITCH_BEND: u16 = 0x3FFF;
+
+/// O maior banco de timbres, que também tem 14 bits.
+const MAX_BANK: u16 = 0x3FFF;
+
+/// Um problema encontrado ao validar ações ou arquivos MIDI.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Warning {
+    /// O arquivo não possui nenhuma trilha.
+    NoTracks,
+    /// A trilha não tem duração, ou seja, nenhum evento depois do tick 0.
+    ZeroLengthTrack { track: usize },
+    /// A trilha não termina com um `EndOfTrack`.
+    MissingEndOfTrack { track: usize },
+    /// Uma nota foi ligada novamente antes de ser desligada no mesmo canal.
+    OverlappingNote {
+        track: usize,
+        tick: u64,
+        channel: u8,
+        key: u8,
+    },
+    /// Uma ação carrega um valor que não cabe na faixa do MIDI.
+    ValueOutOfRange {
+        index: usize,
+        what: &'static str,
+        value: u16,
+        max: u16,
+    },
+    /// Uma SysEx que não começa com `F0`, não termina com `F7` ou tem bytes de dados acima de 127.
+    MalformedSysEx { index: usize },
+    /// Uma mudança de BPM que não pode ser representada como tempo do MIDI.
+    InvalidTempo { index: usize, error: TempoError },
+    /// Uma entrada do cabeçalho do texto que foi ignorada.
+    InvalidHeader(HeaderError),
+}
+
+impl Warning {
+    /// A posição da ação com o problema, se ele for de uma ação.
+    pub const fn action(&self) -> Option<usize> {
+        match self {
+            Self::ValueOutOfRange { index, .. }
+            | Self::MalformedSysEx { index }
+            | Self::InvalidTempo { index, .. } => Some(*index),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoTracks => write!(f, "the file has no tracks"),
+            Self::ZeroLengthTrack { track } => write!(f, "track {track} has zero length"),
+            Self::MissingEndOfTrack { track } => {
+                write!(f, "track {track} does not end with EndOfTrack")
+            }
+            Self::OverlappingNote {
+                track,
+                tick,
+                channel,
+                key,
+            } => write!(
+                f,
+                "track {track}, tick {tick}: note {key} on channel {channel} started again before being released"
+            ),
+            Self::ValueOutOfRange {
+                index,
+                what,
+                value,
+                max,
+            } => write!(
+                f,
+                "action {index}: {what} {value} is above {max} and will be clamped"
+            ),
+            Self::MalformedSysEx { index } => write!(
+                f,
+                "action {index}: SysEx must go from F0 to F7 with data bytes up to 7F, and may be rejected"
+            ),
+            Self::InvalidTempo { index, error } => {
+                write!(f, "action {index}: {error}, using the slowest tempo instead")
+            }
+            Self::InvalidHeader(error) => write!(f, "{error}, ignoring it"),
+        }
+    }
+}
+
+/// Verifica as entradas do cabeçalho do texto.
+pub fn validate_header(text: &str) -> Vec<Warning> {
+    Header::parse(text)
+        .0
+        .errors
+        .into_iter()
+        .map(Warning::InvalidHeader)
+        .collect()
+}
+
+/// Se a SysEx vai de `F0` a `F7`, só com bytes de dados entre eles.
+fn is_valid_sysex(bytes: &[u8]) -> bool {
+    match bytes {
+        [0xF0, data @ .., 0xF7] => data.iter().all(|&byte| byte <= MAX_DATA_VALUE as u8),
+        _ => false,
+    }
+}
+
+/// Verifica se as ações possuem valores fora da faixa aceita pelo MIDI.
+pub fn validate_actions(actions: &[MidiAction]) -> Vec<Warning> {
+    actions
+        .iter()
+        .enumerate()
+        .filter_map(|(index, action)| {
+            let (what, value) = match *action {
+                MidiAction::PlayNote(note) => ("note", note as u16),
+                MidiAction::ChangeInstrument(instrument) => ("instrument", instrument as u16),
+                MidiAction::ChangeVolume(volume) => ("volume", volume),
+                MidiAction::Accent(velocity) => ("accent", velocity as u16),
+                MidiAction::DroneStart(key) | MidiAction::DroneStop(key) => ("drone", key as u16),
+                MidiAction::Chord(ref keys) => ("chord", keys.iter().copied().max()? as u16),
+                MidiAction::ChordNote { key, .. } => ("chord", key as u16),
+                MidiAction::ChannelPressure(pressure) => ("pressure", pressure as u16),
+                MidiAction::Pan(pan) => ("pan", pan as u16),
+                MidiAction::ChangeBPM(bpm) => {
+                    return TimeState::mspqn_from_bpm(bpm, TimeSignature::COMMON_TIME)
+                        .err()
+                        .map(|error| Warning::InvalidTempo { index, error })
+                }
+                MidiAction::SysEx(ref bytes) => {
+                    return (!is_valid_sysex(bytes)).then_some(Warning::MalformedSysEx { index })
+                }
+                MidiAction::BankSelect(bank) => {
+                    return (bank > MAX_BANK).then_some(Warning::ValueOutOfRange {
+                        index,
+                        what: "bank",
+                        value: bank,
+                        max: MAX_BANK,
+                    })
+                }
+                MidiAction::PitchBend(bend) => {
+                    return (bend > MAX_PITCH_BEND).then_some(Warning::ValueOutOfRange {
+                        index,
+                        what: "pitch bend",
+                        value: bend,
+                        max: MAX_PITCH_BEND,
+                    })
+                }
+                _ => return None,
+            };
+
+            (value > MAX_DATA_VALUE).then_some(Warning::ValueOutOfRange {
+                index,
+                what,
+                value,
+                max: MAX_DATA_VALUE,
+            })
+        })
+        .collect()
+}
+
+/// Verifica a estrutura de um arquivo MIDI, gerado ou importado.
+pub fn validate_smf(smf: &Smf<'_>) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+
+    if smf.tracks.is_empty() {
+        warnings.push(Warning::NoTracks);
+    }
+
+    for (track_index, track) in smf.tracks.iter().enumerate() {
+        let mut tick = 0_u64;
+        let mut sounding = [[false; 128]; 16];
+
+        for event in track {
+            tick += event.delta.as_int() as u64;
+
+            if let TrackEventKind::Midi { channel, message } = event.kind {
+                let channel = channel.as_int();
+                match message {
+                    MidiMessage::NoteOn { key, vel } if vel > 0 => {
+                        let playing = &mut sounding[channel as usize][key.as_int() as usize];
+                        if *playing {
+                            warnings.push(Warning::OverlappingNote {
+                                track: track_index,
+                                tick,
+                                channel,
+                                key: key.as_int(),
+                            });
+                        }
+                        *playing = true;
+                    }
+                    MidiMessage::NoteOn { key, .. } | MidiMessage::NoteOff { key, .. } => {
+                        sounding[channel as usize][key.as_int() as usize] = false;
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        if tick == 0 {
+            warnings.push(Warning::ZeroLengthTrack { track: track_index });
+        }
+
+        if !matches!(
+            track.last().map(|event| event.kind),
+            Some(TrackEventKind::Meta(MetaMessage::EndOfTrack))
+        ) {
+            warnings.push(Warning::MissingEndOfTrack { track: track_index });
+        }
+    }
+
+    warnings
+}
+
+#[cfg(test)]
+mod test {
+    use midly::{num::*, TrackEvent};
+
+    use super::*;
+    use crate::text_to_midi::{Sheet, State};
+
+    fn note_on(delta: u32, key: u8) -> TrackEvent<'static> {
+        TrackEvent {
+            delta: u28::from(delta),
+            kind: TrackEventKind::Midi {
+                channel: u4::from(0),
+                message: MidiMessage::NoteOn {
+                    key: u7::from(key),
+                    vel: u7::from(64),
+                },
+            },
+        }
+    }
+
+    #[test]
+    fn generated_file_is_valid() {
+        let actions = Sheet::builder().text("CDEFGAB").build().unwrap().process();
+        let smf = MidiAction::as_track(&actions);
+
+        assert!(validate_actions(&actions).is_empty());
+        assert!(validate_smf(&smf).is_empty());
+    }
+
+    #[test]
+    fn high_octave_is_out_of_range() {
+        let warnings =
+            validate_actions(&[MidiAction::PlayNote(167), MidiAction::ChangeVolume(127)]);
+
+        assert_eq!(
+            warnings,
+            [Warning::ValueOutOfRange {
+                index: 0,
+                what: "note",
+                value: 167,
+                max: 127,
+            }]
+        );
+    }
+
+    #[test]
+    fn zero_bpm() {
+        assert_eq!(
+            validate_actions(&[MidiAction::ChangeBPM(0)]),
+            [Warning::InvalidTempo {
+                index: 0,
+                error: TempoError::ZeroBpm
+            }]
+        );
+    }
+
+    #[test]
+    fn malformed_sysex() {
+        assert_eq!(
+            validate_actions(&[
+                MidiAction::SysEx(vec![0xF0, 0x7E, 0x7F, 0x09, 0x01, 0xF7]),
+                MidiAction::SysEx(vec![0x41, 0x10]),
+                MidiAction::SysEx(vec![0xF0, 0x80, 0xF7]),
+            ]),
+            [
+                Warning::MalformedSysEx { index: 1 },
+                Warning::MalformedSysEx { index: 2 }
+            ]
+        );
+    }
+
+    #[test]
+    fn detects_broken_track() {
+        // Arrange
+        let mut smf = Smf::new(midly::Header {
+            format: midly::Format::SingleTrack,
+            timing: midly::Timing::Metrical(MidiAction::D_TPQN),
+        });
+        smf.tracks.push(vec![note_on(0, 60), note_on(0, 60)]);
+
+        // Act
+        let warnings = validate_smf(&smf);
+
+        // Assert
+        assert_eq!(
+            warnings,
+            [
+                Warning::OverlappingNote {
+                    track: 0,
+                    tick: 0,
+                    channel: 0,
+                    key: 60
+                },
+                Warning::ZeroLengthTrack { track: 0 },
+                Warning::MissingEndOfTrack { track: 0 },
+            ]
+        );
+    }
+
+    #[test]
+    fn no_tracks() {
+        let smf = Smf::new(midly::Header {
+            format: midly::Format::SingleTrack,
+            timing: midly::Timing::Metrical(MidiAction::D_TPQN),
+        });
+
+        assert_eq!(validate_smf(&smf), [Warning::NoTracks]);
+    }
+}
@@ -0,0 +1,154 @@
+//! Saída MIDI no navegador, pela Web MIDI API.
+
+use std::{
+    error::Error,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use gloo_timers::future::sleep;
+
+use js_sys::Uint8Array;
+use midly::Smf;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{MidiAccess, MidiOutput};
+
+use crate::{
+    clock::{Clock, ScheduledClock},
+    mixer::Mixer,
+    play::{self, LiveControls, MidiSink, PlaybackOptions, Player, Playhead},
+    tempo_map::TempoMap,
+};
+
+/// De quanto em quanto tempo a posição exibida é atualizada.
+const PLAYHEAD_INTERVAL: Duration = Duration::from_millis(30);
+
+/// Envia as mensagens para uma saída da Web MIDI API.
+///
+/// O navegador não pode dormir, então cada mensagem é enviada com o seu instante,
+/// e o próprio navegador a toca na hora certa.
+pub struct WebMidiSink<'a> {
+    output: MidiOutput,
+    /// O instante do início da música, em milissegundos de `performance.now()`.
+    start: f64,
+    /// O relógio do player, que diz o instante do evento sendo enviado.
+    clock: &'a ScheduledClock,
+}
+
+/// O instante atual do navegador, em milissegundos.
+fn now_ms() -> Result<f64, Box<dyn Error>> {
+    Ok(web_sys::window()
+        .and_then(|window| window.performance())
+        .ok_or("no performance timer in this browser")?
+        .now())
+}
+
+impl<'a> WebMidiSink<'a> {
+    /// Começa a tocar agora na saída dada.
+    pub fn new(output: MidiOutput, clock: &'a ScheduledClock) -> Result<Self, Box<dyn Error>> {
+        let start = now_ms()?;
+
+        Ok(Self {
+            output,
+            start,
+            clock,
+        })
+    }
+}
+
+impl MidiSink for WebMidiSink<'_> {
+    fn send(&mut self, message: &[u8]) -> Result<(), Box<dyn Error>> {
+        let timestamp = self.start + self.clock.now().as_secs_f64() * 1000.0;
+        self.output
+            .send_with_timestamp(&Uint8Array::from(message), timestamp)
+            .map_err(|error| format!("{error:?}").into())
+    }
+}
+
+/// Pede acesso ao MIDI do navegador e retorna a primeira saída disponível.
+pub async fn first_output() -> Result<MidiOutput, Box<dyn Error>> {
+    let js_error = |error: JsValue| format!("{error:?}");
+
+    let navigator = web_sys::window().ok_or("no window")?.navigator();
+    let access = JsFuture::from(navigator.request_midi_access().map_err(js_error)?)
+        .await
+        .map_err(js_error)?;
+    let access: MidiAccess = access.dyn_into().map_err(js_error)?;
+
+    let next = access.outputs().values().next().map_err(js_error)?;
+    if next.done() {
+        return Err("No output port found.".into());
+    }
+    Ok(next.value().dyn_into().map_err(js_error)?)
+}
+
+/// Toca o arquivo na primeira saída MIDI do navegador.
+///
+/// Todos os eventos são agendados de uma vez; depois disso, a posição é atualizada
+/// com os temporizadores do navegador até o fim da música.
+pub async fn play(
+    bytes: &[u8],
+    mixer: Arc<Mutex<Mixer>>,
+    playhead: Arc<Playhead>,
+    controls: Arc<LiveControls>,
+    options: PlaybackOptions,
+) -> Result<(), Box<dyn Error>> {
+    let mut file = Smf::parse(bytes)?;
+    for track in &mut file.tracks {
+        mixer.lock().unwrap().apply(track);
+    }
+
+    let midly::Timing::Metrical(tpqn) = file.header.timing else {
+        return Err("The timing of the received file is not coded with metrical.".into());
+    };
+    let tempo_map = TempoMap::from_tracks(&file.tracks, tpqn);
+
+    let clock = ScheduledClock::default();
+    let output = first_output().await?;
+    let sink = WebMidiSink::new(output.clone(), &clock)?;
+    let start = now_ms()?;
+    // Os eventos já foram agendados, então só a velocidade do início vale
+    let tempo_scale = controls.tempo_scale();
+    Player::new(sink, &clock)
+        .with_mixer(mixer)
+        .with_controls(Arc::clone(&controls))
+        .play(&file)?;
+
+    let end = clock.now() + options.latency;
+    playhead.set_playing(true);
+    loop {
+        if controls.is_stopped() {
+            // Descarta o que ainda estava agendado antes de calar as notas
+            output.clear();
+            send_panic_to(&output)?;
+            break;
+        }
+        let elapsed = Duration::from_secs_f64((now_ms()? - start).max(0.0) / 1000.0);
+        if elapsed >= end {
+            break;
+        }
+        let written = elapsed.saturating_sub(options.latency).mul_f64(tempo_scale);
+        playhead.set_tick(tempo_map.tick_at(written));
+        sleep(PLAYHEAD_INTERVAL).await;
+    }
+    playhead.set_playing(false);
+
+    Ok(())
+}
+
+/// Envia as mensagens de pânico na primeira saída MIDI do navegador.
+pub async fn send_panic() -> Result<(), Box<dyn Error>> {
+    send_panic_to(&first_output().await?)
+}
+
+/// Envia as mensagens de pânico agora, na saída dada.
+fn send_panic_to(output: &MidiOutput) -> Result<(), Box<dyn Error>> {
+    for message in play::panic_messages() {
+        output
+            .send(&Uint8Array::from(&message[..]))
+            .map_err(|error| format!("{error:?}"))?;
+    }
+
+    Ok(())
+}